@@ -7,5 +7,8 @@ import kotlinx.parcelize.Parcelize
 enum class SelectedObfuscation : Parcelable {
     Auto,
     Off,
-    Udp2Tcp
+    Udp2Tcp,
+    Shadowsocks,
+    Custom,
+    Quic
 }
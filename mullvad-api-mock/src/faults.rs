@@ -0,0 +1,49 @@
+//! Faults which make the mock misbehave, so that error handling in clients can be tested.
+
+use serde::{Deserialize, Serialize};
+
+/// Makes requests to paths which start with `path_prefix` fail in some way.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fault {
+    /// Requests to paths starting with this are affected. An empty prefix affects all requests
+    /// except those to the control API.
+    #[serde(default)]
+    pub path_prefix: String,
+    pub action: FaultAction,
+    /// Number of requests that are affected before the fault is removed. If this is `None`, the
+    /// fault stays until it is cleared.
+    #[serde(default)]
+    pub count: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FaultAction {
+    /// Respond with an error status and, optionally, an API error code.
+    Error { status: u16, code: Option<String> },
+    /// Wait before handling the request normally.
+    Delay { millis: u64 },
+    /// Close the connection without responding.
+    Drop,
+}
+
+impl Fault {
+    fn matches(&self, path: &str) -> bool {
+        path.starts_with(&self.path_prefix)
+    }
+}
+
+/// Returns the action of the first fault which affects `path`, and removes that fault if it has
+/// been used up.
+pub(crate) fn take_action(faults: &mut Vec<Fault>, path: &str) -> Option<FaultAction> {
+    let index = faults.iter().position(|fault| fault.matches(path))?;
+    let fault = &mut faults[index];
+    let action = fault.action.clone();
+    if let Some(count) = fault.count.as_mut() {
+        *count = count.saturating_sub(1);
+        if *count == 0 {
+            faults.remove(index);
+        }
+    }
+    Some(action)
+}
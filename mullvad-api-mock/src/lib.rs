@@ -0,0 +1,152 @@
+//! An in-memory stand-in for the Mullvad API. It implements the endpoints used by the daemon, so
+//! that the daemon can be run end to end without network access, by building it with the
+//! `api-override` feature and pointing it at the mock.
+//!
+//! The mock is scripted through its [`State`], and can be made to misbehave by injecting
+//! [`Fault`]s. Both can also be changed over HTTP, through the control API under `/mock/v1/`:
+//!
+//! * `GET /mock/v1/state` and `PUT /mock/v1/state` read and replace the state. Replacing the
+//!   state also revokes all access tokens.
+//! * `GET /mock/v1/faults` lists active faults, `POST /mock/v1/faults` adds one and
+//!   `DELETE /mock/v1/faults` removes all of them.
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+mod faults;
+mod routes;
+mod state;
+
+pub use faults::{Fault, FaultAction};
+pub use state::{Account, AppVersion, Device, ProblemReport, State, Voucher};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Failed to read a request body
+    #[error("Failed to read request body")]
+    ReadBody(#[source] hyper::Error),
+
+    /// A fault closed the connection
+    #[error("Connection dropped by injected fault")]
+    Dropped,
+
+    /// Failed to set up the listening socket
+    #[error("Failed to listen on socket")]
+    Listen(#[source] std::io::Error),
+
+    /// The HTTP server failed
+    #[error("Mock API server failed")]
+    Server(#[source] hyper::Error),
+}
+
+/// Handle to a mock API. Clones share state, so a handle can be kept to script the mock while it
+/// is being served.
+#[derive(Clone, Default)]
+pub struct MockApi {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    state: State,
+    faults: Vec<Fault>,
+}
+
+impl MockApi {
+    pub fn new(state: State) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                state,
+                faults: vec![],
+            })),
+        }
+    }
+
+    /// Read or modify the state of the mock.
+    pub fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        f(&mut self.lock().state)
+    }
+
+    /// Make requests misbehave until the fault is used up or cleared. Faults are checked in the
+    /// order they were added, and only the first matching fault is applied to a request.
+    pub fn inject_fault(&self, fault: Fault) {
+        self.lock().faults.push(fault);
+    }
+
+    pub fn clear_faults(&self) {
+        self.lock().faults.clear();
+    }
+
+    /// Serve the mock over plain HTTP on `listener`. This only returns if the server fails.
+    pub async fn serve(self, listener: std::net::TcpListener) -> Result<(), Error> {
+        listener.set_nonblocking(true).map_err(Error::Listen)?;
+        let make_service = make_service_fn(move |_| {
+            let api = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let api = api.clone();
+                    async move { api.handle(request).await }
+                }))
+            }
+        });
+        Server::from_tcp(listener)
+            .map_err(Error::Server)?
+            .serve(make_service)
+            .await
+            .map_err(Error::Server)
+    }
+
+    async fn handle(&self, request: Request<Body>) -> Result<Response<Body>, Error> {
+        let path = request.uri().path().to_owned();
+        log::debug!("{} {}", request.method(), path);
+
+        let fault = if path.starts_with(routes::CONTROL_PREFIX) {
+            None
+        } else {
+            let mut inner = self.lock();
+            faults::take_action(&mut inner.faults, &path)
+        };
+        match fault {
+            Some(FaultAction::Error { status, code }) => {
+                let status = hyper::StatusCode::from_u16(status)
+                    .unwrap_or(hyper::StatusCode::INTERNAL_SERVER_ERROR);
+                return Ok(match code {
+                    Some(code) => routes::error(status, &code),
+                    None => routes::empty(status),
+                });
+            }
+            Some(FaultAction::Delay { millis }) => {
+                tokio::time::sleep(Duration::from_millis(millis)).await;
+            }
+            Some(FaultAction::Drop) => return Err(Error::Dropped),
+            None => (),
+        }
+
+        let (parts, body) = request.into_parts();
+        let body = hyper::body::to_bytes(body).await.map_err(Error::ReadBody)?;
+
+        let mut inner = self.lock();
+        let Inner { state, faults } = &mut *inner;
+        Ok(routes::handle(
+            state,
+            faults,
+            routes::Request {
+                method: &parts.method,
+                path: &path,
+                headers: &parts.headers,
+                body: &body,
+            },
+        ))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().expect("mock API state lock is poisoned")
+    }
+}
@@ -0,0 +1,60 @@
+use clap::Parser;
+use mullvad_api_mock::{MockApi, State};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+/// Serves an in-memory mock of the Mullvad API over plain HTTP
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Cli {
+    /// Address to accept connections on
+    #[arg(long = "listen", default_value = "127.0.0.1:8080")]
+    listen_addr: SocketAddr,
+    /// JSON file with the initial state of the mock. Without this, the mock starts without any
+    /// accounts
+    #[arg(long)]
+    state: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let cli = Cli::parse();
+
+    let state = match &cli.state {
+        Some(path) => match read_state(path) {
+            Ok(state) => state,
+            Err(error) => {
+                eprintln!("Failed to read state from {}: {error}", path.display());
+                exit(1);
+            }
+        },
+        None => State::default(),
+    };
+
+    let listener = match std::net::TcpListener::bind(cli.listen_addr) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("Failed to listen on {}: {error}", cli.listen_addr);
+            exit(1);
+        }
+    };
+
+    log::info!(
+        "Serving mock API on {addr}. Run a daemon built with the `api-override` feature with \
+        MULLVAD_API_HOST=localhost MULLVAD_API_ADDR={addr} MULLVAD_API_DISABLE_TLS=1 to use it",
+        addr = cli.listen_addr,
+    );
+    if let Err(error) = MockApi::new(state).serve(listener).await {
+        eprintln!("{error}");
+        exit(1);
+    }
+}
+
+fn read_state(path: &Path) -> Result<State, Box<dyn std::error::Error>> {
+    let contents = std::fs::read(path)?;
+    Ok(serde_json::from_slice(&contents)?)
+}
@@ -0,0 +1,369 @@
+//! Request handlers for the endpoints of the Mullvad API, and for the control API which is used to
+//! script the mock.
+
+use crate::{
+    faults::Fault,
+    state::{Device, ProblemReport, State},
+};
+use chrono::{Duration, Utc};
+use hyper::{
+    header::{self, HeaderMap, HeaderValue},
+    Body, Method, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+/// Paths starting with this belong to the control API, and are never affected by faults.
+pub(crate) const CONTROL_PREFIX: &str = "/mock/v1/";
+
+const INVALID_ACCESS_TOKEN: &str = "INVALID_ACCESS_TOKEN";
+const INVALID_ACCOUNT: &str = "INVALID_ACCOUNT";
+const INVALID_VOUCHER: &str = "INVALID_VOUCHER";
+const VOUCHER_USED: &str = "VOUCHER_USED";
+const DEVICE_NOT_FOUND: &str = "DEVICE_NOT_FOUND";
+const MAX_DEVICES_REACHED: &str = "MAX_DEVICES_REACHED";
+const PUBKEY_IN_USE: &str = "PUBKEY_IN_USE";
+const INVALID_REQUEST: &str = "INVALID_REQUEST";
+const NOT_FOUND: &str = "NOT_FOUND";
+
+pub(crate) struct Request<'a> {
+    pub method: &'a Method,
+    pub path: &'a str,
+    pub headers: &'a HeaderMap,
+    pub body: &'a [u8],
+}
+
+/// Handle `request`. `faults` is only used by the control API.
+pub(crate) fn handle(
+    state: &mut State,
+    faults: &mut Vec<Fault>,
+    request: Request<'_>,
+) -> Response<Body> {
+    let segments: Vec<&str> = request.path.trim_start_matches('/').split('/').collect();
+    let result = match (request.method, segments.as_slice()) {
+        (&Method::POST, ["auth", "v1", "token"]) => create_access_token(state, &request),
+
+        (&Method::POST, ["accounts", "v1", "accounts"]) => Ok(create_account(state)),
+        (&Method::GET, ["accounts", "v1", "accounts", "me"]) => get_account(state, &request),
+        (&Method::DELETE, ["accounts", "v1", "accounts", "me"]) => delete_account(state, &request),
+        (&Method::POST, ["accounts", "v1", "devices"]) => create_device(state, &request),
+        (&Method::GET, ["accounts", "v1", "devices"]) => list_devices(state, &request),
+        (&Method::GET, ["accounts", "v1", "devices", id]) => get_device(state, &request, id),
+        (&Method::DELETE, ["accounts", "v1", "devices", id]) => remove_device(state, &request, id),
+        (&Method::PUT, ["accounts", "v1", "devices", id, "pubkey"]) => {
+            replace_pubkey(state, &request, id)
+        }
+
+        (&Method::POST, ["app", "v1", "submit-voucher"]) => submit_voucher(state, &request),
+        (&Method::POST, ["app", "v1", "www-auth-token"]) => www_auth_token(state, &request),
+        (&Method::GET, ["app", "v1", "relays"]) => Ok(relay_list(state, &request)),
+        (&Method::GET, ["app", "v1", "releases", _platform, _version]) => {
+            Ok(json(StatusCode::OK, &state.app_version))
+        }
+        (&Method::GET, ["app", "v1", "api-addrs"]) => Ok(json(StatusCode::OK, &state.api_addrs)),
+        (&Method::HEAD, ["app", "v1", "api-addrs"]) => Ok(empty(StatusCode::OK)),
+        (&Method::POST, ["app", "v1", "problem-report"]) => problem_report(state, &request),
+
+        (&Method::GET, ["mock", "v1", "state"]) => Ok(json(StatusCode::OK, &*state)),
+        (&Method::PUT, ["mock", "v1", "state"]) => parse_body(&request).map(|new_state| {
+            *state = new_state;
+            empty(StatusCode::NO_CONTENT)
+        }),
+        (&Method::GET, ["mock", "v1", "faults"]) => Ok(json(StatusCode::OK, &*faults)),
+        (&Method::POST, ["mock", "v1", "faults"]) => parse_body(&request).map(|fault| {
+            faults.push(fault);
+            empty(StatusCode::NO_CONTENT)
+        }),
+        (&Method::DELETE, ["mock", "v1", "faults"]) => {
+            faults.clear();
+            Ok(empty(StatusCode::NO_CONTENT))
+        }
+
+        _ => Err(error(StatusCode::NOT_FOUND, NOT_FOUND)),
+    };
+    result.unwrap_or_else(|error_response| error_response)
+}
+
+/// Either a successful response, or an error response. Both are returned to the client.
+type Result<T> = std::result::Result<T, Response<Body>>;
+
+fn create_access_token(state: &mut State, request: &Request<'_>) -> Result<Response<Body>> {
+    #[derive(Deserialize)]
+    struct AccessTokenRequest {
+        account_number: String,
+    }
+    #[derive(Serialize)]
+    struct AccessTokenResponse {
+        access_token: String,
+        expiry: chrono::DateTime<Utc>,
+    }
+
+    let AccessTokenRequest { account_number } = parse_body(request)?;
+    let (access_token, expiry) = state
+        .create_access_token(&account_number)
+        .ok_or_else(|| error(StatusCode::BAD_REQUEST, INVALID_ACCOUNT))?;
+    Ok(json(
+        StatusCode::OK,
+        &AccessTokenResponse {
+            access_token,
+            expiry,
+        },
+    ))
+}
+
+fn create_account(state: &mut State) -> Response<Body> {
+    #[derive(Serialize)]
+    struct AccountCreationResponse {
+        number: String,
+    }
+
+    let number = state.create_account(Utc::now());
+    json(StatusCode::CREATED, &AccountCreationResponse { number })
+}
+
+fn get_account(state: &mut State, request: &Request<'_>) -> Result<Response<Body>> {
+    #[derive(Serialize)]
+    struct AccountResponse<'a> {
+        id: &'a str,
+        expiry: chrono::DateTime<Utc>,
+    }
+
+    let account_number = authenticate(state, request)?;
+    let account = &state.accounts[&account_number];
+    Ok(json(
+        StatusCode::OK,
+        &AccountResponse {
+            id: &account.id,
+            expiry: account.expiry,
+        },
+    ))
+}
+
+fn delete_account(state: &mut State, request: &Request<'_>) -> Result<Response<Body>> {
+    let account_number = authenticate(state, request)?;
+    state.accounts.remove(&account_number);
+    Ok(empty(StatusCode::NO_CONTENT))
+}
+
+fn create_device(state: &mut State, request: &Request<'_>) -> Result<Response<Body>> {
+    #[derive(Deserialize)]
+    struct DeviceSubmission {
+        pubkey: String,
+        #[serde(default)]
+        hijack_dns: bool,
+    }
+
+    let account_number = authenticate(state, request)?;
+    let DeviceSubmission { pubkey, hijack_dns } = parse_body(request)?;
+
+    if state.accounts[&account_number].devices.len() >= state.max_devices {
+        return Err(error(StatusCode::BAD_REQUEST, MAX_DEVICES_REACHED));
+    }
+    if state.pubkey_in_use(&pubkey) {
+        return Err(error(StatusCode::BAD_REQUEST, PUBKEY_IN_USE));
+    }
+
+    let device = state.new_device(pubkey, hijack_dns);
+    let response = json(StatusCode::CREATED, &device);
+    state
+        .accounts
+        .get_mut(&account_number)
+        .expect("authenticated account must exist")
+        .devices
+        .push(device);
+    Ok(response)
+}
+
+fn list_devices(state: &mut State, request: &Request<'_>) -> Result<Response<Body>> {
+    let account_number = authenticate(state, request)?;
+    Ok(json(
+        StatusCode::OK,
+        &state.accounts[&account_number].devices,
+    ))
+}
+
+fn get_device(state: &mut State, request: &Request<'_>, id: &str) -> Result<Response<Body>> {
+    let account_number = authenticate(state, request)?;
+    let device = find_device(state, &account_number, id)?;
+    Ok(json(StatusCode::OK, device))
+}
+
+fn remove_device(state: &mut State, request: &Request<'_>, id: &str) -> Result<Response<Body>> {
+    let account_number = authenticate(state, request)?;
+    let devices = &mut state
+        .accounts
+        .get_mut(&account_number)
+        .expect("authenticated account must exist")
+        .devices;
+    let index = devices
+        .iter()
+        .position(|device| device.id == id)
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, DEVICE_NOT_FOUND))?;
+    devices.remove(index);
+    Ok(empty(StatusCode::NO_CONTENT))
+}
+
+fn replace_pubkey(state: &mut State, request: &Request<'_>, id: &str) -> Result<Response<Body>> {
+    #[derive(Deserialize)]
+    struct PubkeySubmission {
+        pubkey: String,
+    }
+
+    let account_number = authenticate(state, request)?;
+    let PubkeySubmission { pubkey } = parse_body(request)?;
+
+    if find_device(state, &account_number, id)?.pubkey != pubkey && state.pubkey_in_use(&pubkey) {
+        return Err(error(StatusCode::BAD_REQUEST, PUBKEY_IN_USE));
+    }
+
+    let device = state
+        .accounts
+        .get_mut(&account_number)
+        .expect("authenticated account must exist")
+        .devices
+        .iter_mut()
+        .find(|device| device.id == id)
+        .expect("device was found above");
+    device.pubkey = pubkey;
+    Ok(json(StatusCode::OK, device))
+}
+
+fn submit_voucher(state: &mut State, request: &Request<'_>) -> Result<Response<Body>> {
+    #[derive(Deserialize)]
+    struct VoucherSubmission {
+        voucher_code: String,
+    }
+    #[derive(Serialize)]
+    struct VoucherResponse {
+        time_added: u64,
+        new_expiry: chrono::DateTime<Utc>,
+    }
+
+    let account_number = authenticate(state, request)?;
+    let VoucherSubmission { voucher_code } = parse_body(request)?;
+
+    let voucher = state
+        .vouchers
+        .get_mut(&voucher_code)
+        .ok_or_else(|| error(StatusCode::BAD_REQUEST, INVALID_VOUCHER))?;
+    if voucher.used {
+        return Err(error(StatusCode::BAD_REQUEST, VOUCHER_USED));
+    }
+    voucher.used = true;
+    let time_added = voucher.seconds;
+
+    let account = state
+        .accounts
+        .get_mut(&account_number)
+        .expect("authenticated account must exist");
+    let seconds = i64::try_from(time_added).unwrap_or(i64::MAX);
+    account.expiry = account.expiry.max(Utc::now()) + Duration::seconds(seconds);
+
+    Ok(json(
+        StatusCode::OK,
+        &VoucherResponse {
+            time_added,
+            new_expiry: account.expiry,
+        },
+    ))
+}
+
+fn www_auth_token(state: &mut State, request: &Request<'_>) -> Result<Response<Body>> {
+    #[derive(Serialize)]
+    struct AuthTokenResponse {
+        auth_token: String,
+    }
+
+    authenticate(state, request)?;
+    let auth_token = uuid::Uuid::new_v4().simple().to_string();
+    Ok(json(StatusCode::OK, &AuthTokenResponse { auth_token }))
+}
+
+/// Serve the relay list, or `304 Not Modified` if the client already has the current one.
+fn relay_list(state: &State, request: &Request<'_>) -> Response<Body> {
+    let body = serde_json::to_vec(&state.relay_list).expect("JSON values can be serialized");
+
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    let etag = format!("\"{:016x}\"", hasher.finish());
+
+    let client_etag = request
+        .headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|tag| tag.to_str().ok())
+        .map(|tag| tag.trim_start_matches("W/"));
+
+    let mut response = if client_etag == Some(etag.as_str()) {
+        empty(StatusCode::NOT_MODIFIED)
+    } else {
+        raw_json(StatusCode::OK, body)
+    };
+    response.headers_mut().insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).expect("ETag is a valid header value"),
+    );
+    response
+}
+
+fn problem_report(state: &mut State, request: &Request<'_>) -> Result<Response<Body>> {
+    let report: ProblemReport = parse_body(request)?;
+    log::info!("Received problem report from {:?}", report.address);
+    state.problem_reports.push(report);
+    Ok(empty(StatusCode::NO_CONTENT))
+}
+
+/// Return the number of the account that the request is authenticated as.
+fn authenticate(state: &State, request: &Request<'_>) -> Result<String> {
+    request
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| state.authenticate(token))
+        .ok_or_else(|| error(StatusCode::UNAUTHORIZED, INVALID_ACCESS_TOKEN))
+}
+
+fn find_device<'a>(state: &'a State, account_number: &str, id: &str) -> Result<&'a Device> {
+    state.accounts[account_number]
+        .devices
+        .iter()
+        .find(|device| device.id == id)
+        .ok_or_else(|| error(StatusCode::NOT_FOUND, DEVICE_NOT_FOUND))
+}
+
+fn parse_body<T: DeserializeOwned>(request: &Request<'_>) -> Result<T> {
+    serde_json::from_slice(request.body).map_err(|error| {
+        log::debug!("Invalid request body for {}: {error}", request.path);
+        self::error(StatusCode::BAD_REQUEST, INVALID_REQUEST)
+    })
+}
+
+fn json<T: Serialize + ?Sized>(status: StatusCode, body: &T) -> Response<Body> {
+    raw_json(
+        status,
+        serde_json::to_vec(body).expect("response types can be serialized"),
+    )
+}
+
+fn raw_json(status: StatusCode, body: Vec<u8>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .expect("response is valid")
+}
+
+/// An error response in the format used by the API.
+pub(crate) fn error(status: StatusCode, code: &str) -> Response<Body> {
+    json(status, &serde_json::json!({ "code": code }))
+}
+
+pub(crate) fn empty(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .expect("response is valid")
+}
@@ -0,0 +1,240 @@
+//! The data served by the mock API. All of it can be loaded from and dumped to JSON, which is
+//! how tests set up and inspect the mock.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+};
+
+/// Number of devices an account can have unless [`State::max_devices`] is set.
+const DEFAULT_MAX_DEVICES: usize = 5;
+/// How long access tokens handed out by the mock are valid.
+const ACCESS_TOKEN_VALIDITY: Duration = Duration::hours(1);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    /// Accounts by account number.
+    pub accounts: BTreeMap<String, Account>,
+    /// Vouchers by voucher code.
+    pub vouchers: BTreeMap<String, Voucher>,
+    /// Maximum number of devices per account.
+    pub max_devices: usize,
+    /// The relay list, in the format served by `app/v1/relays`.
+    pub relay_list: serde_json::Value,
+    /// Served by `app/v1/releases/<platform>/<version>`, for all platforms and versions.
+    pub app_version: AppVersion,
+    /// Served by `app/v1/api-addrs`.
+    pub api_addrs: Vec<SocketAddr>,
+    /// Problem reports which have been sent to the mock, oldest first.
+    pub problem_reports: Vec<ProblemReport>,
+
+    #[serde(skip)]
+    access_tokens: HashMap<String, AccessToken>,
+    /// Used to give new accounts and devices unique numbers and addresses.
+    #[serde(skip)]
+    counter: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Account {
+    #[serde(default = "new_id")]
+    pub id: String,
+    pub expiry: DateTime<Utc>,
+    #[serde(default)]
+    pub devices: Vec<Device>,
+}
+
+/// A device, as it is represented by the API.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+    /// Base64 encoded WireGuard public key.
+    pub pubkey: String,
+    pub ipv4_address: String,
+    pub ipv6_address: String,
+    pub hijack_dns: bool,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Voucher {
+    /// Number of seconds that the voucher adds to an account.
+    pub seconds: u64,
+    #[serde(default)]
+    pub used: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AppVersion {
+    pub supported: bool,
+    pub latest: String,
+    pub latest_stable: Option<String>,
+    pub latest_beta: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProblemReport {
+    pub address: String,
+    pub message: String,
+    pub log: String,
+    pub metadata: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug)]
+struct AccessToken {
+    account_number: String,
+    expiry: DateTime<Utc>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            accounts: BTreeMap::new(),
+            vouchers: BTreeMap::new(),
+            max_devices: DEFAULT_MAX_DEVICES,
+            relay_list: default_relay_list(),
+            app_version: AppVersion {
+                supported: true,
+                latest: env!("CARGO_PKG_VERSION").to_owned(),
+                latest_stable: Some(env!("CARGO_PKG_VERSION").to_owned()),
+                latest_beta: env!("CARGO_PKG_VERSION").to_owned(),
+            },
+            api_addrs: vec![],
+            problem_reports: vec![],
+            access_tokens: HashMap::new(),
+            counter: 0,
+        }
+    }
+}
+
+impl State {
+    /// Add an account which expires at `expiry`, and return its account number.
+    pub fn create_account(&mut self, expiry: DateTime<Utc>) -> String {
+        let account_number = loop {
+            let candidate = format!("{:016}", 1_000_000_000_000_000 + self.next_number());
+            if !self.accounts.contains_key(&candidate) {
+                break candidate;
+            }
+        };
+        self.accounts.insert(
+            account_number.clone(),
+            Account {
+                id: new_id(),
+                expiry,
+                devices: vec![],
+            },
+        );
+        account_number
+    }
+
+    /// Hand out a new access token for `account_number`, or `None` if there is no such account.
+    pub(crate) fn create_access_token(
+        &mut self,
+        account_number: &str,
+    ) -> Option<(String, DateTime<Utc>)> {
+        if !self.accounts.contains_key(account_number) {
+            return None;
+        }
+        let token = format!("mock_at_{}", uuid::Uuid::new_v4().simple());
+        let expiry = Utc::now() + ACCESS_TOKEN_VALIDITY;
+        self.access_tokens.insert(
+            token.clone(),
+            AccessToken {
+                account_number: account_number.to_owned(),
+                expiry,
+            },
+        );
+        Some((token, expiry))
+    }
+
+    /// Return the number of the account that `token` was handed out for, if it is still valid.
+    pub(crate) fn authenticate(&self, token: &str) -> Option<String> {
+        self.access_tokens
+            .get(token)
+            .filter(|token| token.expiry > Utc::now())
+            .filter(|token| self.accounts.contains_key(&token.account_number))
+            .map(|token| token.account_number.clone())
+    }
+
+    /// Forget all access tokens, so that clients have to request new ones.
+    pub fn revoke_access_tokens(&mut self) {
+        self.access_tokens.clear();
+    }
+
+    /// Create a new device, with unique addresses, for `pubkey`.
+    pub(crate) fn new_device(&mut self, pubkey: String, hijack_dns: bool) -> Device {
+        let number = self.next_number();
+        Device {
+            id: new_id(),
+            name: format!("mock device {number}"),
+            pubkey,
+            ipv4_address: format!("10.64.{}.{}/32", (number >> 8) & 0xff, number & 0xff),
+            ipv6_address: format!("fc00:bbbb:bbbb:bb01::{:x}/128", number & 0xffff),
+            hijack_dns,
+            created: Utc::now(),
+        }
+    }
+
+    /// Return whether any device on any account uses `pubkey`.
+    pub(crate) fn pubkey_in_use(&self, pubkey: &str) -> bool {
+        self.accounts
+            .values()
+            .flat_map(|account| &account.devices)
+            .any(|device| device.pubkey == pubkey)
+    }
+
+    fn next_number(&mut self) -> u64 {
+        self.counter += 1;
+        self.counter
+    }
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// A relay list with a single WireGuard relay, which is enough for the daemon to be able to
+/// select a relay.
+fn default_relay_list() -> serde_json::Value {
+    serde_json::json!({
+        "locations": {
+            "se-got": {
+                "city": "Gothenburg",
+                "country": "Sweden",
+                "latitude": 57.70887,
+                "longitude": 11.97456
+            }
+        },
+        "openvpn": {
+            "ports": [],
+            "relays": []
+        },
+        "wireguard": {
+            "port_ranges": [[53, 53], [4000, 33433], [33565, 51820], [52000, 60000]],
+            "ipv4_gateway": "10.64.0.1",
+            "ipv6_gateway": "fc00:bbbb:bbbb:bb01::1",
+            "relays": [
+                {
+                    "hostname": "se-got-wg-001",
+                    "active": true,
+                    "owned": true,
+                    "location": "se-got",
+                    "provider": "mock",
+                    "ipv4_addr_in": "192.0.2.10",
+                    "ipv6_addr_in": null,
+                    "weight": 100,
+                    "include_in_country": true,
+                    "public_key": "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="
+                }
+            ]
+        },
+        "bridge": {
+            "shadowsocks": [],
+            "relays": []
+        }
+    })
+}
@@ -0,0 +1,251 @@
+//! Talks to the mock API over HTTP, the same way the daemon does.
+
+use hyper::{
+    header::{self, HeaderValue},
+    Body, Client, Method, Request, StatusCode,
+};
+use mullvad_api_mock::{Fault, FaultAction, MockApi, State, Voucher};
+use serde_json::{json, Value};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+
+struct TestClient {
+    addr: SocketAddr,
+    access_token: Option<String>,
+}
+
+impl TestClient {
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+        headers: &[(header::HeaderName, &str)],
+    ) -> (StatusCode, hyper::HeaderMap, Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("http://{}/{path}", self.addr));
+        if let Some(token) = &self.access_token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        for (name, value) in headers {
+            request = request.header(name, HeaderValue::from_str(value).unwrap());
+        }
+        let body = body.map(|body| Body::from(body.to_string()));
+        let request = request.body(body.unwrap_or_default()).unwrap();
+
+        let response = Client::new().request(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = if body.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&body).unwrap()
+        };
+        (status, headers, body)
+    }
+
+    async fn log_in(&mut self, account_number: &str) {
+        let (status, _, body) = self
+            .request(
+                Method::POST,
+                "auth/v1/token",
+                Some(json!({ "account_number": account_number })),
+                &[],
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        self.access_token = Some(body["access_token"].as_str().unwrap().to_owned());
+    }
+}
+
+fn spawn_mock(state: State) -> (MockApi, TestClient) {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let api = MockApi::new(state);
+    tokio::spawn(api.clone().serve(listener));
+    let client = TestClient {
+        addr,
+        access_token: None,
+    };
+    (api, client)
+}
+
+#[tokio::test]
+async fn test_account_and_devices() {
+    let mut state = State::default();
+    state.max_devices = 1;
+    let (api, mut client) = spawn_mock(state);
+
+    let (status, _, body) = client
+        .request(Method::POST, "accounts/v1/accounts", None, &[])
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let account_number = body["number"].as_str().unwrap().to_owned();
+    assert_eq!(account_number.len(), 16);
+
+    let (status, _, body) = client
+        .request(Method::GET, "accounts/v1/accounts/me", None, &[])
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "INVALID_ACCESS_TOKEN");
+
+    client.log_in(&account_number).await;
+    let (status, _, _) = client
+        .request(Method::GET, "accounts/v1/accounts/me", None, &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, device) = client
+        .request(
+            Method::POST,
+            "accounts/v1/devices",
+            Some(json!({ "pubkey": "key1", "hijack_dns": false })),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let device_id = device["id"].as_str().unwrap();
+
+    let (status, _, body) = client
+        .request(
+            Method::POST,
+            "accounts/v1/devices",
+            Some(json!({ "pubkey": "key2", "hijack_dns": false })),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "MAX_DEVICES_REACHED");
+
+    let (status, _, body) = client
+        .request(
+            Method::PUT,
+            &format!("accounts/v1/devices/{device_id}/pubkey"),
+            Some(json!({ "pubkey": "key2" })),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["pubkey"], "key2");
+
+    let (status, _, _) = client
+        .request(
+            Method::DELETE,
+            &format!("accounts/v1/devices/{device_id}"),
+            None,
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(api.with_state(|state| state.accounts[&account_number].devices.is_empty()));
+}
+
+#[tokio::test]
+async fn test_voucher() {
+    let mut state = State::default();
+    let account_number = state.create_account(chrono::Utc::now());
+    state.vouchers.insert(
+        "VOUCHER".to_owned(),
+        Voucher {
+            seconds: 3600,
+            used: false,
+        },
+    );
+    let (_api, mut client) = spawn_mock(state);
+    client.log_in(&account_number).await;
+
+    let voucher = json!({ "voucher_code": "VOUCHER" });
+    let (status, _, body) = client
+        .request(
+            Method::POST,
+            "app/v1/submit-voucher",
+            Some(voucher.clone()),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["time_added"], 3600);
+
+    let (status, _, body) = client
+        .request(Method::POST, "app/v1/submit-voucher", Some(voucher), &[])
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "VOUCHER_USED");
+}
+
+#[tokio::test]
+async fn test_relay_list_etag() {
+    let (api, client) = spawn_mock(State::default());
+
+    let (status, headers, _) = client
+        .request(Method::GET, "app/v1/relays", None, &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    // The daemon stores the tag as a weak tag
+    let etag = format!("W/{}", headers[header::ETAG].to_str().unwrap());
+
+    let (status, _, _) = client
+        .request(
+            Method::GET,
+            "app/v1/relays",
+            None,
+            &[(header::IF_NONE_MATCH, etag.as_str())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+    api.with_state(|state| state.relay_list["wireguard"]["relays"] = json!([]));
+    let (status, _, _) = client
+        .request(
+            Method::GET,
+            "app/v1/relays",
+            None,
+            &[(header::IF_NONE_MATCH, etag.as_str())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_fault_injection() {
+    let (api, client) = spawn_mock(State::default());
+    api.inject_fault(Fault {
+        path_prefix: "/app/v1/api-addrs".to_owned(),
+        action: FaultAction::Error {
+            status: 503,
+            code: None,
+        },
+        count: Some(1),
+    });
+
+    let (status, _, _) = client
+        .request(Method::GET, "app/v1/api-addrs", None, &[])
+        .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let (status, _, body) = client
+        .request(Method::GET, "app/v1/api-addrs", None, &[])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!([]));
+
+    // Faults can also be injected through the control API
+    let (status, _, _) = client
+        .request(
+            Method::POST,
+            "mock/v1/faults",
+            Some(json!({ "action": { "type": "drop" } })),
+            &[],
+        )
+        .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(Client::new()
+        .get(
+            format!("http://{}/app/v1/api-addrs", client.addr)
+                .parse()
+                .unwrap()
+        )
+        .await
+        .is_err());
+}
@@ -1,13 +1,19 @@
-//! This module keeps track of the last known good API IP address and reads and stores it on disk.
+//! This module keeps track of the known API IP addresses and how reachable they have been, and
+//! reads and stores them on disk in order of preference.
 
 use super::API;
 use std::{io, net::SocketAddr, path::Path, sync::Arc};
 use tokio::{
     fs,
     io::{AsyncReadExt, AsyncWriteExt},
-    sync::Mutex,
+    sync::{watch, Mutex},
 };
 
+/// Number of consecutive connection attempts in which no address could be reached before the
+/// preferred address is blamed anyway. In blocking states, the firewall only allows the preferred
+/// address, so no other address can succeed until it has been replaced.
+const MAX_UNREACHABLE_ATTEMPTS: u32 = 3;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Failed to open the address cache file")]
@@ -27,60 +33,141 @@ pub enum Error {
 pub struct AddressCache {
     inner: Arc<Mutex<AddressCacheInner>>,
     write_path: Option<Arc<Path>>,
+    preferred_tx: Arc<watch::Sender<SocketAddr>>,
 }
 
 impl AddressCache {
     /// Initialize cache using the hardcoded address, and write changes to `write_path`.
     pub fn new(write_path: Option<Box<Path>>) -> Result<Self, Error> {
-        Self::new_inner(API.address(), write_path)
+        Self::new_inner(vec![API.address()], write_path)
     }
 
     pub fn with_static_addr(address: SocketAddr) -> Self {
-        Self::new_inner(address, None)
+        Self::new_inner(vec![address], None)
             .expect("Failed to construct an address cache from a static address")
     }
 
-    /// Initialize cache using `read_path`, and write changes to `write_path`.
+    /// Initialize cache using `read_path`, and write changes to `write_path`. The hardcoded
+    /// address is kept as a last resort.
     pub async fn from_file(read_path: &Path, write_path: Option<Box<Path>>) -> Result<Self, Error> {
         log::debug!("Loading API addresses from {}", read_path.display());
-        Self::new_inner(read_address_file(read_path).await?, write_path)
+        let addresses = with_hardcoded_address(read_address_file(read_path).await?);
+        Self::new_inner(addresses, write_path)
     }
 
-    fn new_inner(address: SocketAddr, write_path: Option<Box<Path>>) -> Result<Self, Error> {
-        let cache = AddressCacheInner::from_address(address);
-        log::debug!("Using API address: {}", cache.address);
+    fn new_inner(addresses: Vec<SocketAddr>, write_path: Option<Box<Path>>) -> Result<Self, Error> {
+        let cache = AddressCacheInner::from_addresses(addresses);
+        log::debug!("Using API address: {}", cache.preferred());
 
+        let (preferred_tx, _) = watch::channel(cache.preferred());
         let address_cache = Self {
             inner: Arc::new(Mutex::new(cache)),
             write_path: write_path.map(Arc::from),
+            preferred_tx: Arc::new(preferred_tx),
         };
         Ok(address_cache)
     }
 
-    /// Returns the address if the hostname equals `API.host`. Otherwise, returns `None`.
-    pub async fn resolve_hostname(&self, hostname: &str) -> Option<SocketAddr> {
+    /// Returns all addresses, in order of preference, if the hostname equals `API.host`.
+    /// Otherwise, returns `None`.
+    pub async fn resolve_hostname(&self, hostname: &str) -> Option<Vec<SocketAddr>> {
         if hostname.eq_ignore_ascii_case(API.host()) {
-            Some(self.get_address().await)
+            Some(self.get_addresses().await)
         } else {
             None
         }
     }
 
-    /// Returns the currently selected address.
+    /// Returns the currently preferred address.
     pub async fn get_address(&self) -> SocketAddr {
-        self.inner.lock().await.address
+        self.inner.lock().await.preferred()
+    }
+
+    /// Returns all addresses, in order of preference.
+    pub async fn get_addresses(&self) -> Vec<SocketAddr> {
+        self.inner.lock().await.addresses()
+    }
+
+    /// Returns a receiver which is notified whenever the preferred address changes, e.g.
+    /// because it could not be reached. The firewall only allows the preferred address, so it
+    /// has to be updated when this happens.
+    pub fn watch_preferred(&self) -> watch::Receiver<SocketAddr> {
+        self.preferred_tx.subscribe()
+    }
+
+    /// Replace the known addresses with `addresses`. The reachability of addresses which were
+    /// already known is remembered, and addresses that have been unreachable are placed last.
+    pub async fn set_addresses(&self, addresses: Vec<SocketAddr>) -> Result<(), Error> {
+        if addresses.is_empty() {
+            return Ok(());
+        }
+        let mut inner = self.inner.lock().await;
+        let mut new_inner = inner.clone();
+        new_inner.replace(with_hardcoded_address(addresses));
+        self.update(&mut inner, new_inner).await
     }
 
-    pub async fn set_address(&self, address: SocketAddr) -> Result<(), Error> {
+    /// Record that a connection to `address` was established.
+    pub async fn record_success(&self, address: SocketAddr) -> Result<(), Error> {
+        self.record(address, AddressEntry::record_success).await
+    }
+
+    /// Record that a connection to `address` failed.
+    pub async fn record_failure(&self, address: SocketAddr) -> Result<(), Error> {
+        self.record(address, AddressEntry::record_failure).await
+    }
+
+    /// Record that no address could be reached, starting with `address`. After
+    /// [`MAX_UNREACHABLE_ATTEMPTS`] such attempts in a row, a failure is recorded for the
+    /// preferred address, so that another address is tried. Nothing is recorded unless `address`
+    /// is the preferred address.
+    pub async fn record_unreachable(&self, address: SocketAddr) -> Result<(), Error> {
         let mut inner = self.inner.lock().await;
-        if address != inner.address {
-            self.save_to_disk(&address).await?;
-            inner.address = address;
+        if inner.preferred() != address {
+            return Ok(());
+        }
+        let mut new_inner = inner.clone();
+        new_inner.record_unreachable();
+        self.update(&mut inner, new_inner).await
+    }
+
+    async fn record(
+        &self,
+        address: SocketAddr,
+        record: impl FnOnce(&mut AddressEntry),
+    ) -> Result<(), Error> {
+        let mut inner = self.inner.lock().await;
+        let mut new_inner = inner.clone();
+        match new_inner.entry_mut(address) {
+            Some(entry) => record(entry),
+            None => return Ok(()),
+        }
+        new_inner.unreachable_attempts = 0;
+        new_inner.sort();
+        self.update(&mut inner, new_inner).await
+    }
+
+    /// Replace `inner` with `new_inner`, and save the addresses to disk if their order changed.
+    async fn update(
+        &self,
+        inner: &mut AddressCacheInner,
+        new_inner: AddressCacheInner,
+    ) -> Result<(), Error> {
+        let new_addresses = new_inner.addresses();
+        let changed = new_addresses != inner.addresses();
+        *inner = new_inner;
+        if changed {
+            log::debug!("Using API address: {}", inner.preferred());
+            self.preferred_tx.send_if_modified(|preferred| {
+                let old_preferred = std::mem::replace(preferred, inner.preferred());
+                old_preferred != *preferred
+            });
+            self.save_to_disk(&new_addresses).await?;
         }
         Ok(())
     }
 
-    async fn save_to_disk(&self, address: &SocketAddr) -> Result<(), Error> {
+    async fn save_to_disk(&self, addresses: &[SocketAddr]) -> Result<(), Error> {
         let write_path = match self.write_path.as_ref() {
             Some(write_path) => write_path,
             None => return Ok(()),
@@ -89,8 +176,11 @@ impl AddressCache {
         let mut file = mullvad_fs::AtomicFile::new(&**write_path)
             .await
             .map_err(Error::Open)?;
-        let mut contents = address.to_string();
-        contents += "\n";
+        let mut contents = String::new();
+        for address in addresses {
+            contents += &address.to_string();
+            contents += "\n";
+        }
         file.write_all(contents.as_bytes())
             .await
             .map_err(Error::Write)?;
@@ -98,22 +188,234 @@ impl AddressCache {
     }
 }
 
-#[derive(Clone, PartialEq, Eq)]
+/// Known addresses in order of preference. Never empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct AddressCacheInner {
+    addresses: Vec<AddressEntry>,
+    /// Number of connection attempts in a row in which no address could be reached.
+    unreachable_attempts: u32,
+}
+
+/// An address and how reachable it has been.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct AddressEntry {
     address: SocketAddr,
+    successes: u32,
+    failures: u32,
+    /// Number of failed connection attempts since the last successful one.
+    consecutive_failures: u32,
 }
 
 impl AddressCacheInner {
-    fn from_address(address: SocketAddr) -> Self {
-        Self { address }
+    fn from_addresses(addresses: Vec<SocketAddr>) -> Self {
+        assert!(!addresses.is_empty(), "The address cache must not be empty");
+        Self {
+            addresses: addresses.into_iter().map(AddressEntry::new).collect(),
+            unreachable_attempts: 0,
+        }
+    }
+
+    fn preferred(&self) -> SocketAddr {
+        self.addresses[0].address
     }
+
+    fn addresses(&self) -> Vec<SocketAddr> {
+        self.addresses.iter().map(|entry| entry.address).collect()
+    }
+
+    fn entry_mut(&mut self, address: SocketAddr) -> Option<&mut AddressEntry> {
+        self.addresses
+            .iter_mut()
+            .find(|entry| entry.address == address)
+    }
+
+    /// Replace the addresses with `addresses`, but keep the history of the ones that remain.
+    fn replace(&mut self, addresses: Vec<SocketAddr>) {
+        let mut new_entries = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            if new_entries
+                .iter()
+                .any(|entry: &AddressEntry| entry.address == address)
+            {
+                continue;
+            }
+            let entry = self
+                .entry_mut(address)
+                .cloned()
+                .unwrap_or_else(|| AddressEntry::new(address));
+            new_entries.push(entry);
+        }
+        self.addresses = new_entries;
+        self.sort();
+    }
+
+    /// Blames the preferred address once no address has been reachable for
+    /// [`MAX_UNREACHABLE_ATTEMPTS`] attempts in a row.
+    fn record_unreachable(&mut self) {
+        self.unreachable_attempts = self.unreachable_attempts.saturating_add(1);
+        if self.unreachable_attempts >= MAX_UNREACHABLE_ATTEMPTS {
+            self.unreachable_attempts = 0;
+            self.addresses[0].record_failure();
+            self.sort();
+        }
+    }
+
+    /// Places addresses that have failed since they last worked last. Otherwise, the order is
+    /// kept.
+    fn sort(&mut self) {
+        self.addresses
+            .sort_by_key(|entry| entry.consecutive_failures);
+    }
+}
+
+impl AddressEntry {
+    fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            successes: 0,
+            failures: 0,
+            consecutive_failures: 0,
+        }
+    }
+
+    fn record_success(&mut self) {
+        self.successes = self.successes.saturating_add(1);
+        self.consecutive_failures = 0;
+    }
+
+    fn record_failure(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        log::debug!(
+            "Failed to connect to API address {}. {} of {} attempts have failed",
+            self.address,
+            self.failures,
+            u64::from(self.failures) + u64::from(self.successes),
+        );
+    }
+}
+
+/// Appends the hardcoded address to `addresses` unless it is already included.
+fn with_hardcoded_address(mut addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let hardcoded = API.address();
+    if !addresses.contains(&hardcoded) {
+        addresses.push(hardcoded);
+    }
+    addresses
 }
 
-async fn read_address_file(path: &Path) -> Result<SocketAddr, Error> {
+/// Reads addresses from `path`, one per line, in order of preference. Files with a single address
+/// written by older versions are also accepted.
+async fn read_address_file(path: &Path) -> Result<Vec<SocketAddr>, Error> {
     let mut file = fs::File::open(path).await.map_err(Error::Open)?;
-    let mut address = String::new();
-    file.read_to_string(&mut address)
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
         .await
         .map_err(Error::Read)?;
-    address.trim().parse().map_err(|_| Error::Parse)
+    let addresses = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.parse().map_err(|_| Error::Parse))
+        .collect::<Result<Vec<_>, _>>()?;
+    if addresses.is_empty() {
+        return Err(Error::Parse);
+    }
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn addr(address: &str) -> SocketAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn test_failing_addresses_are_placed_last() {
+        let mut cache = AddressCacheInner::from_addresses(vec![
+            addr("192.0.2.1:443"),
+            addr("[2001:db8::1]:443"),
+            addr("192.0.2.2:443"),
+        ]);
+
+        cache
+            .entry_mut(addr("192.0.2.1:443"))
+            .unwrap()
+            .record_failure();
+        cache.sort();
+        assert_eq!(
+            cache.addresses(),
+            vec![
+                addr("[2001:db8::1]:443"),
+                addr("192.0.2.2:443"),
+                addr("192.0.2.1:443")
+            ]
+        );
+
+        cache
+            .entry_mut(addr("192.0.2.1:443"))
+            .unwrap()
+            .record_success();
+        cache.sort();
+        assert_eq!(cache.preferred(), addr("[2001:db8::1]:443"));
+    }
+
+    #[tokio::test]
+    async fn test_watch_preferred() {
+        let cache =
+            AddressCache::new_inner(vec![addr("192.0.2.1:443"), addr("192.0.2.2:443")], None)
+                .unwrap();
+        let mut preferred_rx = cache.watch_preferred();
+
+        cache.record_success(addr("192.0.2.1:443")).await.unwrap();
+        assert!(!preferred_rx.has_changed().unwrap());
+
+        cache.record_failure(addr("192.0.2.1:443")).await.unwrap();
+        assert!(preferred_rx.has_changed().unwrap());
+        assert_eq!(*preferred_rx.borrow_and_update(), addr("192.0.2.2:443"));
+    }
+
+    #[test]
+    fn test_unreachable_preferred_address_is_replaced() {
+        let mut cache =
+            AddressCacheInner::from_addresses(vec![addr("192.0.2.1:443"), addr("192.0.2.2:443")]);
+
+        for _ in 1..MAX_UNREACHABLE_ATTEMPTS {
+            cache.record_unreachable();
+        }
+        assert_eq!(cache.preferred(), addr("192.0.2.1:443"));
+
+        cache.record_unreachable();
+        assert_eq!(cache.preferred(), addr("192.0.2.2:443"));
+        assert_eq!(cache.unreachable_attempts, 0);
+    }
+
+    #[test]
+    fn test_replace_keeps_history() {
+        let mut cache =
+            AddressCacheInner::from_addresses(vec![addr("192.0.2.1:443"), addr("192.0.2.2:443")]);
+        cache
+            .entry_mut(addr("192.0.2.1:443"))
+            .unwrap()
+            .record_failure();
+
+        cache.replace(vec![
+            addr("192.0.2.1:443"),
+            addr("192.0.2.3:443"),
+            addr("192.0.2.3:443"),
+        ]);
+        assert_eq!(
+            cache.addresses(),
+            vec![addr("192.0.2.3:443"), addr("192.0.2.1:443")]
+        );
+        assert_eq!(
+            cache
+                .entry_mut(addr("192.0.2.1:443"))
+                .unwrap()
+                .consecutive_failures,
+            1
+        );
+    }
 }
@@ -0,0 +1,220 @@
+//! Connects to one of several addresses by racing connection attempts, like "Happy Eyeballs"
+//! (RFC 8305) does. Attempts are started in order of preference, alternating between IPv4 and
+//! IPv6, and a new attempt is started whenever the previous one fails or takes too long.
+//! Addresses can also be tried one at a time, when racing is too expensive.
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::{collections::VecDeque, future::Future, io, net::SocketAddr, time::Duration};
+
+/// How long to wait for an attempt before starting the next one.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+pub struct Outcome<T> {
+    /// The first successful connection and its address, or the error of the last failed attempt.
+    pub result: io::Result<(SocketAddr, T)>,
+    /// Addresses whose connection attempts failed. Attempts which were cancelled because another
+    /// one succeeded are not included.
+    pub failed: Vec<SocketAddr>,
+}
+
+/// Calls `connect` for the addresses in `addrs` until one of the returned futures succeeds.
+pub async fn connect<T, F, Fut>(addrs: &[SocketAddr], mut connect: F) -> Outcome<T>
+where
+    F: FnMut(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+{
+    let mut pending = interleave_families(addrs);
+    let mut attempts = FuturesUnordered::new();
+    let mut failed = vec![];
+    let mut last_error = None;
+
+    loop {
+        if attempts.is_empty() {
+            match pending.pop_front() {
+                Some(addr) => attempts.push(attempt(addr, connect(addr))),
+                None => break,
+            }
+        }
+
+        tokio::select! {
+            Some((addr, result)) = attempts.next() => match result {
+                Ok(connection) => {
+                    return Outcome {
+                        result: Ok((addr, connection)),
+                        failed,
+                    };
+                }
+                Err(error) => {
+                    log::debug!("Failed to connect to {addr}: {error}");
+                    failed.push(addr);
+                    last_error = Some(error);
+                    if let Some(addr) = pending.pop_front() {
+                        attempts.push(attempt(addr, connect(addr)));
+                    }
+                }
+            },
+            _ = tokio::time::sleep(CONNECTION_ATTEMPT_DELAY), if !pending.is_empty() => {
+                if let Some(addr) = pending.pop_front() {
+                    attempts.push(attempt(addr, connect(addr)));
+                }
+            }
+        }
+    }
+
+    Outcome {
+        result: Err(last_error.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "No addresses to connect to")
+        })),
+        failed,
+    }
+}
+
+/// Calls `connect` for the addresses in `addrs`, one at a time and in order, until one of the
+/// returned futures succeeds. This is used when attempts are expensive, such as when every attempt
+/// goes through a proxy.
+pub async fn connect_in_order<T, F, Fut>(addrs: &[SocketAddr], mut connect: F) -> Outcome<T>
+where
+    F: FnMut(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+{
+    let mut failed = vec![];
+    let mut last_error = None;
+    for &addr in addrs {
+        match connect(addr).await {
+            Ok(connection) => {
+                return Outcome {
+                    result: Ok((addr, connection)),
+                    failed,
+                };
+            }
+            Err(error) => {
+                log::debug!("Failed to connect to {addr}: {error}");
+                failed.push(addr);
+                last_error = Some(error);
+            }
+        }
+    }
+
+    Outcome {
+        result: Err(last_error.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "No addresses to connect to")
+        })),
+        failed,
+    }
+}
+
+async fn attempt<Fut: Future>(addr: SocketAddr, connect: Fut) -> (SocketAddr, Fut::Output) {
+    (addr, connect.await)
+}
+
+/// Reorders `addrs` so that IPv4 and IPv6 addresses alternate, starting with the family of the
+/// first address. Otherwise, the order is kept.
+fn interleave_families(addrs: &[SocketAddr]) -> VecDeque<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return VecDeque::new();
+    };
+    let (mut preferred, mut other): (VecDeque<_>, VecDeque<_>) = addrs
+        .iter()
+        .copied()
+        .partition(|addr| addr.is_ipv4() == first.is_ipv4());
+
+    let mut interleaved = VecDeque::with_capacity(addrs.len());
+    while !preferred.is_empty() || !other.is_empty() {
+        interleaved.extend(preferred.pop_front());
+        interleaved.extend(other.pop_front());
+    }
+    interleaved
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn addr(address: &str) -> SocketAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn test_interleave_families() {
+        let addrs = [
+            addr("[2001:db8::1]:443"),
+            addr("[2001:db8::2]:443"),
+            addr("[2001:db8::3]:443"),
+            addr("192.0.2.1:443"),
+        ];
+        assert_eq!(
+            interleave_families(&addrs),
+            [
+                addr("[2001:db8::1]:443"),
+                addr("192.0.2.1:443"),
+                addr("[2001:db8::2]:443"),
+                addr("[2001:db8::3]:443"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_attempt_is_raced() {
+        let slow = addr("192.0.2.1:443");
+        let fast = addr("[2001:db8::1]:443");
+
+        let outcome = connect(&[slow, fast], |addr| async move {
+            if addr == slow {
+                std::future::pending::<()>().await;
+            }
+            Ok(addr)
+        })
+        .await;
+
+        assert_eq!(outcome.result.unwrap(), (fast, fast));
+        assert!(outcome.failed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_attempts() {
+        let broken = addr("192.0.2.1:443");
+        let working = addr("192.0.2.2:443");
+
+        let outcome = connect(&[broken, working], |addr| async move {
+            if addr == broken {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
+            }
+            Ok(())
+        })
+        .await;
+        assert_eq!(outcome.result.unwrap().0, working);
+        assert_eq!(outcome.failed, [broken]);
+
+        let outcome = connect(&[broken], |_| async {
+            Err::<(), _>(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"))
+        })
+        .await;
+        assert_eq!(
+            outcome.result.unwrap_err().kind(),
+            io::ErrorKind::ConnectionRefused
+        );
+        assert_eq!(outcome.failed, [broken]);
+    }
+
+    #[tokio::test]
+    async fn test_connect_in_order() {
+        let broken = addr("192.0.2.1:443");
+        let working = addr("[2001:db8::1]:443");
+        let unused = addr("192.0.2.2:443");
+
+        let mut attempted = vec![];
+        let outcome = connect_in_order(&[broken, working, unused], |addr| {
+            attempted.push(addr);
+            async move {
+                if addr == broken {
+                    return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
+                }
+                Ok(())
+            }
+        })
+        .await;
+        assert_eq!(outcome.result.unwrap().0, working);
+        assert_eq!(outcome.failed, [broken]);
+        assert_eq!(attempted, [broken, working]);
+    }
+}
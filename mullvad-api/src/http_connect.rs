@@ -0,0 +1,119 @@
+//! Client side of the HTTP `CONNECT` method, used to tunnel API connections through HTTP proxies.
+
+use std::{io, net::SocketAddr};
+use talpid_types::net::proxy::HttpAuth;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Upper bound on the size of the response headers sent by the proxy.
+const MAX_RESPONSE_SIZE: usize = 16 * 1024;
+
+/// Asks the HTTP proxy at the other end of `stream` to open a tunnel to `target`. Once this
+/// returns, `stream` is connected to `target`.
+pub async fn connect<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    target: SocketAddr,
+    auth: Option<&HttpAuth>,
+) -> io::Result<S> {
+    stream
+        .write_all(connect_request(target, auth).as_bytes())
+        .await?;
+    let response = read_response_head(&mut stream).await?;
+    check_response(&response)?;
+    Ok(stream)
+}
+
+fn connect_request(target: SocketAddr, auth: Option<&HttpAuth>) -> String {
+    let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+    if let Some(auth) = auth {
+        let credentials = base64::encode(format!("{}:{}", auth.username(), auth.password()));
+        request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+    }
+    request.push_str("\r\n");
+    request
+}
+
+/// Reads the status line and headers of the response. This reads one byte at a time, so that no
+/// data sent by `target` after the response is consumed.
+async fn read_response_head<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<String> {
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_RESPONSE_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "HTTP proxy response is too large",
+            ));
+        }
+        response.push(stream.read_u8().await?);
+    }
+    String::from_utf8(response)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid HTTP proxy response"))
+}
+
+fn check_response(response: &str) -> io::Result<()> {
+    let status_line = response.lines().next().unwrap_or_default();
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or_default();
+    let status = parts.next().unwrap_or_default();
+    if !version.starts_with("HTTP/1.") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unexpected HTTP proxy response: {status_line}"),
+        ));
+    }
+    match status {
+        "200" => Ok(()),
+        "407" => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "HTTP proxy requires valid credentials",
+        )),
+        _ => Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("HTTP proxy refused to connect: {status_line}"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn target() -> SocketAddr {
+        "192.0.2.1:443".parse().unwrap()
+    }
+
+    async fn run_proxy(response: &'static [u8]) -> (io::Result<Vec<u8>>, String) {
+        let (client, mut server) = tokio::io::duplex(1024);
+
+        let server_task = tokio::spawn(async move {
+            let request = read_response_head(&mut server).await.unwrap();
+            server.write_all(response).await.unwrap();
+            request
+        });
+
+        let auth = HttpAuth::new("user".to_owned(), "pass".to_owned()).unwrap();
+        let result = match connect(client, target(), Some(&auth)).await {
+            Ok(mut stream) => {
+                let mut remaining = vec![];
+                stream.read_to_end(&mut remaining).await.map(|_| remaining)
+            }
+            Err(error) => Err(error),
+        };
+        (result, server_task.await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_connect() {
+        let (result, request) =
+            run_proxy(b"HTTP/1.1 200 Connection established\r\n\r\ntunneled data").await;
+        assert_eq!(result.unwrap(), b"tunneled data");
+        assert!(request.starts_with("CONNECT 192.0.2.1:443 HTTP/1.1\r\n"));
+        // "user:pass" in base64
+        assert!(request.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_authentication_required() {
+        let (result, _) = run_proxy(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     abortable_stream::{AbortableStream, AbortableStreamHandle},
+    happy_eyeballs, http_connect,
     proxy::{ApiConnection, ApiConnectionMode, ProxyConfig},
     tls_stream::TlsStream,
     AddressCache,
@@ -77,8 +78,18 @@ enum InnerConnectionMode {
     Shadowsocks(ShadowsocksConfig),
     /// Connect to the destination via a Socks proxy.
     Socks5(SocksConfig),
+    /// Connect to the destination via an HTTP proxy.
+    HttpConnect(HttpConnectConfig),
+    /// Connect to the destination via a sequence of proxies, each reached through the previous
+    /// one.
+    Chain(Vec<InnerConnectionMode>),
 }
 
+/// A stream which a proxy protocol can be run on top of.
+trait ProxyStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ProxyStream for T {}
+
 impl InnerConnectionMode {
     async fn connect(
         self,
@@ -86,72 +97,105 @@ impl InnerConnectionMode {
         addr: &SocketAddr,
         #[cfg(target_os = "android")] socket_bypass_tx: Option<mpsc::Sender<SocketBypassRequest>>,
     ) -> Result<ApiConnection, std::io::Error> {
+        let first_hop = self.peer().unwrap_or(*addr);
+        let make_proxy_stream = |tcp_stream| self.handshake(tcp_stream, *addr);
+        Self::connect_proxied(
+            first_hop,
+            hostname,
+            make_proxy_stream,
+            #[cfg(target_os = "android")]
+            socket_bypass_tx,
+        )
+        .await
+    }
+
+    /// Returns the address that has to be connected to first, or `None` if the target is
+    /// connected to directly.
+    fn peer(&self) -> Option<SocketAddr> {
         match self {
-            // Set up a TCP-socket connection.
-            InnerConnectionMode::Direct => {
-                let first_hop = *addr;
-                let make_proxy_stream = |tcp_stream| async { Ok(tcp_stream) };
-                Self::connect_proxied(
-                    first_hop,
-                    hostname,
-                    make_proxy_stream,
-                    #[cfg(target_os = "android")]
-                    socket_bypass_tx,
-                )
-                .await
-            }
+            InnerConnectionMode::Direct => None,
+            InnerConnectionMode::Shadowsocks(shadowsocks) => Some(shadowsocks.params.peer),
+            InnerConnectionMode::Socks5(socks) => Some(socks.peer),
+            InnerConnectionMode::HttpConnect(http) => Some(http.peer),
+            InnerConnectionMode::Chain(hops) => hops.first().and_then(Self::peer),
+        }
+    }
+
+    /// Set up a connection to `target` over `stream`, which must be connected to the first hop.
+    /// For chains, each hop is asked to connect to the next one, and the last hop connects to
+    /// `target`.
+    async fn handshake(
+        self,
+        stream: TcpStream,
+        target: SocketAddr,
+    ) -> io::Result<Box<dyn ProxyStream>> {
+        let hops = match self {
+            InnerConnectionMode::Chain(hops) => hops,
+            single_hop => return single_hop.handshake_hop(stream, target).await,
+        };
+        let targets: Vec<_> = hops
+            .iter()
+            .skip(1)
+            .map(|hop| hop.peer().unwrap_or(target))
+            .chain(std::iter::once(target))
+            .collect();
+        let mut stream: Box<dyn ProxyStream> = Box::new(stream);
+        for (hop, target) in hops.into_iter().zip(targets) {
+            stream = hop.handshake_hop(stream, target).await?;
+        }
+        Ok(stream)
+    }
+
+    /// Set up a connection to `target` through a single proxy, over `stream`.
+    async fn handshake_hop<S: ProxyStream + 'static>(
+        self,
+        stream: S,
+        target: SocketAddr,
+    ) -> io::Result<Box<dyn ProxyStream>> {
+        match self {
+            // Use the stream as is.
+            InnerConnectionMode::Direct => Ok(Box::new(stream)),
             // Set up a Shadowsocks-connection.
             InnerConnectionMode::Shadowsocks(shadowsocks) => {
-                let first_hop = shadowsocks.params.peer;
-                let make_proxy_stream = |tcp_stream| async {
-                    Ok(ProxyClientStream::from_stream(
-                        shadowsocks.proxy_context,
-                        tcp_stream,
-                        &ServerConfig::from(shadowsocks.params),
-                        *addr,
-                    ))
-                };
-                Self::connect_proxied(
-                    first_hop,
-                    hostname,
-                    make_proxy_stream,
-                    #[cfg(target_os = "android")]
-                    socket_bypass_tx,
-                )
-                .await
+                Ok(Box::new(ProxyClientStream::from_stream(
+                    shadowsocks.proxy_context,
+                    stream,
+                    &ServerConfig::from(shadowsocks.params),
+                    target,
+                )))
             }
             // Set up a SOCKS5-connection.
             InnerConnectionMode::Socks5(socks) => {
-                let first_hop = socks.peer;
-                let make_proxy_stream = |tcp_stream| async {
-                    match socks.authentication {
-                        None => {
-                            tokio_socks::tcp::Socks5Stream::connect_with_socket(tcp_stream, addr)
-                                .await
-                        }
-                        Some(credentials) => {
-                            tokio_socks::tcp::Socks5Stream::connect_with_password_and_socket(
-                                tcp_stream,
-                                addr,
-                                credentials.username(),
-                                credentials.password(),
-                            )
-                            .await
-                        }
+                let socks_stream = match socks.authentication {
+                    None => {
+                        tokio_socks::tcp::Socks5Stream::connect_with_socket(stream, target).await
                     }
-                    .map_err(|error| {
-                        io::Error::new(io::ErrorKind::Other, format!("SOCKS error: {error}"))
-                    })
-                };
-                Self::connect_proxied(
-                    first_hop,
-                    hostname,
-                    make_proxy_stream,
-                    #[cfg(target_os = "android")]
-                    socket_bypass_tx,
-                )
-                .await
+                    Some(credentials) => {
+                        tokio_socks::tcp::Socks5Stream::connect_with_password_and_socket(
+                            stream,
+                            target,
+                            credentials.username(),
+                            credentials.password(),
+                        )
+                        .await
+                    }
+                }
+                .map_err(|error| {
+                    io::Error::new(io::ErrorKind::Other, format!("SOCKS error: {error}"))
+                })?;
+                Ok(Box::new(socks_stream))
             }
+            // Set up a tunnel through an HTTP proxy.
+            InnerConnectionMode::HttpConnect(http) => {
+                let http_stream =
+                    http_connect::connect(stream, target, http.authentication.as_ref()).await?;
+                Ok(Box::new(http_stream))
+            }
+            // Chains are flattened when they are created.
+            InnerConnectionMode::Chain(_) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Nested proxy chains are not supported",
+            )),
         }
     }
 
@@ -222,6 +266,12 @@ struct SocksConfig {
     authentication: Option<proxy::SocksAuth>,
 }
 
+#[derive(Clone)]
+struct HttpConnectConfig {
+    peer: SocketAddr,
+    authentication: Option<proxy::HttpAuth>,
+}
+
 #[derive(thiserror::Error, Debug)]
 enum ProxyConfigError {
     #[error("Unrecognized cipher selected: {0}")]
@@ -255,6 +305,18 @@ impl TryFrom<ApiConnectionMode> for InnerConnectionMode {
                     peer: config.endpoint,
                     authentication: config.auth,
                 }),
+                ProxyConfig::HttpConnect(config) => {
+                    InnerConnectionMode::HttpConnect(HttpConnectConfig {
+                        peer: config.endpoint,
+                        authentication: config.auth,
+                    })
+                }
+                ProxyConfig::Chain(chain) => InnerConnectionMode::Chain(
+                    chain
+                        .into_iter()
+                        .map(|hop| InnerConnectionMode::try_from(ApiConnectionMode::Proxied(hop)))
+                        .collect::<Result<_, _>>()?,
+                ),
             },
         })
     }
@@ -364,7 +426,8 @@ impl HttpsConnectorWithSni {
             .map_err(|err| io::Error::new(io::ErrorKind::TimedOut, err))?
     }
 
-    async fn resolve_address(address_cache: AddressCache, uri: Uri) -> io::Result<SocketAddr> {
+    /// Returns the addresses of the host in `uri`, in order of preference.
+    async fn resolve_address(address_cache: AddressCache, uri: Uri) -> io::Result<Vec<SocketAddr>> {
         const DEFAULT_PORT: u16 = 443;
 
         let hostname = uri.host().ok_or_else(|| {
@@ -372,31 +435,97 @@ impl HttpsConnectorWithSni {
         })?;
         let port = uri.port_u16();
         if let Ok(addr) = hostname.parse::<IpAddr>() {
-            return Ok(SocketAddr::new(addr, port.unwrap_or(DEFAULT_PORT)));
+            return Ok(vec![SocketAddr::new(addr, port.unwrap_or(DEFAULT_PORT))]);
         }
 
-        // Preferentially, use cached address.
+        // Preferentially, use cached addresses.
         //
-        if let Some(addr) = address_cache.resolve_hostname(hostname).await {
-            return Ok(SocketAddr::new(
-                addr.ip(),
-                port.unwrap_or_else(|| addr.port()),
-            ));
+        if let Some(addrs) = address_cache.resolve_hostname(hostname).await {
+            return Ok(addrs
+                .into_iter()
+                .map(|addr| SocketAddr::new(addr.ip(), port.unwrap_or_else(|| addr.port())))
+                .collect());
         }
 
         // Use getaddrinfo as a fallback
         //
-        let mut addrs = GaiResolver::new()
+        let addrs: Vec<_> = GaiResolver::new()
             .call(
                 Name::from_str(hostname)
                     .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?,
             )
             .await
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
-        let addr = addrs
-            .next()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Empty DNS response"))?;
-        Ok(SocketAddr::new(addr.ip(), port.unwrap_or(DEFAULT_PORT)))
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+            .map(|addr| SocketAddr::new(addr.ip(), port.unwrap_or(DEFAULT_PORT)))
+            .collect();
+        if addrs.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::Other, "Empty DNS response"));
+        }
+        Ok(addrs)
+    }
+
+    /// Connects to one of `addrs` using `connection_mode`, and records the outcomes in
+    /// `address_cache` so that working addresses are preferred.
+    ///
+    /// Direct connections race the addresses against each other. Proxied connections try them
+    /// one at a time, since every attempt goes through the proxy. Note that only the preferred
+    /// address is allowed by the firewall in blocking states, so the alternatives of direct
+    /// connections only help when the firewall is open, or once the firewall has been updated to
+    /// allow the new preferred address. For that reason, the preferred address is eventually
+    /// blamed when direct connections repeatedly fail to reach any address.
+    async fn connect_any(
+        connection_mode: InnerConnectionMode,
+        hostname: &str,
+        addrs: &[SocketAddr],
+        address_cache: &AddressCache,
+        #[cfg(target_os = "android")] socket_bypass_tx: Option<mpsc::Sender<SocketBypassRequest>>,
+    ) -> io::Result<ApiConnection> {
+        let is_direct = matches!(connection_mode, InnerConnectionMode::Direct);
+        let connect = |addr: SocketAddr| {
+            let connection_mode = connection_mode.clone();
+            #[cfg(target_os = "android")]
+            let socket_bypass_tx = socket_bypass_tx.clone();
+            async move {
+                connection_mode
+                    .connect(
+                        hostname,
+                        &addr,
+                        #[cfg(target_os = "android")]
+                        socket_bypass_tx,
+                    )
+                    .await
+            }
+        };
+        let outcome = if is_direct {
+            happy_eyeballs::connect(addrs, connect).await
+        } else {
+            happy_eyeballs::connect_in_order(addrs, connect).await
+        };
+
+        // Only blame the addresses if some address works. Otherwise, the problem is likely
+        // elsewhere, unless it keeps happening for direct connections.
+        let mut results = vec![];
+        match &outcome.result {
+            Ok((addr, _)) => {
+                results.push(address_cache.record_success(*addr).await);
+                for addr in outcome.failed {
+                    results.push(address_cache.record_failure(addr).await);
+                }
+            }
+            Err(_) if is_direct => {
+                if let Some(addr) = addrs.first() {
+                    results.push(address_cache.record_unreachable(*addr).await);
+                }
+            }
+            Err(_) => (),
+        }
+        for error in results.into_iter().filter_map(Result::err) {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to update API address cache")
+            );
+        }
+        outcome.result.map(|(_, connection)| connection)
     }
 }
 
@@ -441,16 +570,18 @@ impl Service<Uri> for HttpsConnectorWithSni {
             }
 
             let hostname = sni_hostname?;
-            let addr = Self::resolve_address(address_cache, uri).await?;
+            let addrs = Self::resolve_address(address_cache.clone(), uri).await?;
 
             // Loop until we have established a connection. This starts over if a new endpoint
             // is selected while connecting.
             let stream = loop {
                 let notify = abort_notify.notified();
                 let proxy_config = { inner.lock().unwrap().proxy_config.clone() };
-                let stream_fut = proxy_config.connect(
+                let stream_fut = Self::connect_any(
+                    proxy_config,
                     &hostname,
-                    &addr,
+                    &addrs,
+                    &address_cache,
                     #[cfg(target_os = "android")]
                     socket_bypass_tx.clone(),
                 );
@@ -478,3 +609,120 @@ impl Service<Uri> for HttpsConnectorWithSni {
         Box::pin(fut)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    async fn listen() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        (listener, addr)
+    }
+
+    fn forward<S>(mut client: S, mut server: TcpStream)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        tokio::spawn(async move {
+            let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+        });
+    }
+
+    /// Accepts a single connection as a SOCKS5 proxy without authentication, and returns the
+    /// address it was asked to connect to.
+    async fn run_socks_proxy(listener: TcpListener) -> SocketAddr {
+        let (mut client, _) = listener.accept().await.unwrap();
+
+        let mut greeting = [0u8; 2];
+        client.read_exact(&mut greeting).await.unwrap();
+        let mut methods = vec![0u8; usize::from(greeting[1])];
+        client.read_exact(&mut methods).await.unwrap();
+        client.write_all(&[5, 0]).await.unwrap();
+
+        // Version, command, reserved, address type, IPv4 address and port
+        let mut request = [0u8; 10];
+        client.read_exact(&mut request).await.unwrap();
+        assert_eq!(request[..4], [5, 1, 0, 1]);
+        let target = SocketAddr::from((
+            [request[4], request[5], request[6], request[7]],
+            u16::from_be_bytes([request[8], request[9]]),
+        ));
+
+        let server = TcpStream::connect(target).await.unwrap();
+        client
+            .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+            .await
+            .unwrap();
+        forward(client, server);
+        target
+    }
+
+    /// Accepts a single connection as an HTTP CONNECT proxy, and returns the address it was
+    /// asked to connect to.
+    async fn run_http_proxy(listener: TcpListener) -> SocketAddr {
+        let (client, _) = listener.accept().await.unwrap();
+        let mut client = BufReader::new(client);
+
+        let mut request_line = String::new();
+        client.read_line(&mut request_line).await.unwrap();
+        let target: SocketAddr = request_line.split(' ').nth(1).unwrap().parse().unwrap();
+        let mut header = String::new();
+        loop {
+            header.clear();
+            let read = client.read_line(&mut header).await.unwrap();
+            if read == 0 || header == "\r\n" {
+                break;
+            }
+        }
+
+        let server = TcpStream::connect(target).await.unwrap();
+        client
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await
+            .unwrap();
+        forward(client, server);
+        target
+    }
+
+    #[tokio::test]
+    async fn test_chain_handshake() {
+        let (target, target_addr) = listen().await;
+        tokio::spawn(async move {
+            let (mut stream, _) = target.accept().await.unwrap();
+            let (mut reader, mut writer) = stream.split();
+            let _ = tokio::io::copy(&mut reader, &mut writer).await;
+        });
+
+        let (socks, socks_addr) = listen().await;
+        let (http, http_addr) = listen().await;
+        let socks_proxy = tokio::spawn(run_socks_proxy(socks));
+        let http_proxy = tokio::spawn(run_http_proxy(http));
+
+        let chain = InnerConnectionMode::Chain(vec![
+            InnerConnectionMode::Socks5(SocksConfig {
+                peer: socks_addr,
+                authentication: None,
+            }),
+            InnerConnectionMode::HttpConnect(HttpConnectConfig {
+                peer: http_addr,
+                authentication: None,
+            }),
+        ]);
+        assert_eq!(chain.peer(), Some(socks_addr));
+
+        let stream = TcpStream::connect(socks_addr).await.unwrap();
+        let mut stream = chain.handshake(stream, target_addr).await.unwrap();
+        assert_eq!(socks_proxy.await.unwrap(), http_addr);
+        assert_eq!(http_proxy.await.unwrap(), target_addr);
+
+        stream.write_all(b"ping").await.unwrap();
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"ping");
+    }
+}
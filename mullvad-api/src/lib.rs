@@ -16,6 +16,7 @@ use std::{
     ops::Deref,
     path::Path,
     sync::OnceLock,
+    time::Duration,
 };
 use talpid_types::ErrorExt;
 
@@ -24,6 +25,8 @@ use availability::{ApiAvailability, ApiAvailabilityHandle};
 pub mod rest;
 
 mod abortable_stream;
+mod happy_eyeballs;
+mod http_connect;
 mod https_client_with_sni;
 pub mod proxy;
 mod tls_stream;
@@ -108,6 +111,8 @@ pub mod env {
     pub const API_ADDR_VAR: &str = "MULLVAD_API_ADDR";
     pub const API_FORCE_DIRECT_VAR: &str = "MULLVAD_API_FORCE_DIRECT";
     pub const DISABLE_TLS_VAR: &str = "MULLVAD_API_DISABLE_TLS";
+    pub const ROOT_CERT_VAR: &str = "MULLVAD_API_ROOT_CERT";
+    pub const PINNED_KEYS_VAR: &str = "MULLVAD_API_PINNED_KEYS";
 }
 
 /// A hostname and socketaddr to reach the Mullvad REST API over.
@@ -134,6 +139,16 @@ pub struct ApiEndpoint {
     pub disable_address_cache: bool,
     #[cfg(feature = "api-override")]
     pub disable_tls: bool,
+    /// DER-encoded certificates which are trusted in addition to the bundled root certificate.
+    /// Initialized from the PEM file at the path in the environment variable
+    /// `MULLVAD_API_ROOT_CERT` if it has been set.
+    #[cfg(feature = "api-override")]
+    pub root_certificates: Vec<Vec<u8>>,
+    /// SHA-256 digests of DER-encoded public keys (SubjectPublicKeyInfo), of which one must be
+    /// the key of the server certificate sent by the API. No keys are pinned if this is empty.
+    /// Initialized with the comma-separated, base64-encoded digests in the environment variable
+    /// `MULLVAD_API_PINNED_KEYS` if it has been set.
+    pub pinned_keys: Vec<[u8; 32]>,
     #[cfg(feature = "api-override")]
     /// Whether bridges/proxies can be used to access the API or not. This is
     /// useful primarily for testing purposes.
@@ -162,8 +177,8 @@ impl ApiEndpoint {
     ///
     /// # Panics
     ///
-    /// Panics if `MULLVAD_API_ADDR`, `MULLVAD_API_HOST` or
-    /// `MULLVAD_API_DISABLE_TLS` has invalid contents.
+    /// Panics if `MULLVAD_API_ADDR`, `MULLVAD_API_HOST`, `MULLVAD_API_DISABLE_TLS`,
+    /// `MULLVAD_API_ROOT_CERT` or `MULLVAD_API_PINNED_KEYS` has invalid contents.
     #[cfg(feature = "api-override")]
     pub fn from_env_vars() -> ApiEndpoint {
         let host_var = Self::read_var(env::API_HOST_VAR);
@@ -176,6 +191,8 @@ impl ApiEndpoint {
             address: None,
             disable_address_cache: host_var.is_some() || address_var.is_some(),
             disable_tls: false,
+            root_certificates: Self::read_root_certificates(),
+            pinned_keys: Self::read_pinned_keys(),
             force_direct: force_direct
                 .map(|force_direct| force_direct != "0")
                 .unwrap_or_else(|| host_var.is_some() || address_var.is_some()),
@@ -246,8 +263,7 @@ impl ApiEndpoint {
     ///
     /// # Panics
     ///
-    /// Panics if `MULLVAD_API_ADDR`, `MULLVAD_API_HOST` or
-    /// `MULLVAD_API_DISABLE_TLS` has invalid contents.
+    /// Panics if `MULLVAD_API_PINNED_KEYS` has invalid contents.
     #[cfg(not(feature = "api-override"))]
     pub fn from_env_vars() -> ApiEndpoint {
         let env_vars = [
@@ -255,6 +271,7 @@ impl ApiEndpoint {
             env::API_ADDR_VAR,
             env::DISABLE_TLS_VAR,
             env::API_FORCE_DIRECT_VAR,
+            env::ROOT_CERT_VAR,
         ];
 
         if env_vars.map(Self::read_var).iter().any(Option::is_some) {
@@ -267,6 +284,7 @@ impl ApiEndpoint {
         ApiEndpoint {
             host: None,
             address: None,
+            pinned_keys: Self::read_pinned_keys(),
         }
     }
 
@@ -288,6 +306,71 @@ impl ApiEndpoint {
         ))
     }
 
+    /// Read the certificates in the PEM file at `MULLVAD_API_ROOT_CERT`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file cannot be read or does not contain any certificates.
+    #[cfg(feature = "api-override")]
+    fn read_root_certificates() -> Vec<Vec<u8>> {
+        let Some(path) = Self::read_var(env::ROOT_CERT_VAR) else {
+            return vec![];
+        };
+        let file = std::fs::File::open(&path).unwrap_or_else(|error| {
+            panic!(
+                "{root_cert}={path} cannot be opened: {error}",
+                root_cert = env::ROOT_CERT_VAR
+            )
+        });
+        let certificates = rustls_pemfile::certs(&mut std::io::BufReader::new(file))
+            .unwrap_or_else(|error| {
+                panic!(
+                    "{root_cert}={path} is not a valid PEM file: {error}",
+                    root_cert = env::ROOT_CERT_VAR
+                )
+            });
+        if certificates.is_empty() {
+            panic!(
+                "{root_cert}={path} does not contain any certificates",
+                root_cert = env::ROOT_CERT_VAR
+            );
+        }
+        log::debug!(
+            "Trusting {} additional API root certificates",
+            certificates.len()
+        );
+        certificates
+    }
+
+    /// Read the public key digests in `MULLVAD_API_PINNED_KEYS`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the digests is not a base64-encoded SHA-256 digest.
+    fn read_pinned_keys() -> Vec<[u8; 32]> {
+        let Some(pins) = Self::read_var(env::PINNED_KEYS_VAR) else {
+            return vec![];
+        };
+        let pinned_keys: Vec<[u8; 32]> = pins
+            .split(',')
+            .map(str::trim)
+            .filter(|pin| !pin.is_empty())
+            .map(|pin| {
+                base64::decode(pin)
+                    .ok()
+                    .and_then(|digest| <[u8; 32]>::try_from(digest).ok())
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "{pinned_keys}: {pin} is not a base64-encoded SHA-256 digest",
+                            pinned_keys = env::PINNED_KEYS_VAR
+                        )
+                    })
+            })
+            .collect();
+        log::debug!("Pinning {} API public keys", pinned_keys.len());
+        pinned_keys
+    }
+
     /// Try to read the value of an environment variable. Returns `None` if the
     /// environment variable has not been set.
     ///
@@ -681,6 +764,11 @@ impl ProblemReportProxy {
     }
 }
 
+/// Version checks are retried a few times, since the daemon otherwise waits hours for the next one.
+/// The API is not allowed to delay them by more than a minute.
+const VERSION_CHECK_RETRY_POLICY: rest::RetryPolicy =
+    rest::RetryPolicy::new(Some(4)).max_delay(Duration::from_secs(60));
+
 #[derive(Clone)]
 pub struct AppVersionProxy {
     handle: rest::MullvadRestHandle,
@@ -713,6 +801,7 @@ impl AppVersionProxy {
         async move {
             let request = request?
                 .expected_status(&[StatusCode::OK])
+                .retry(VERSION_CHECK_RETRY_POLICY)
                 .header("M-Platform-Version", &platform_version)?;
             let response = service.request(request).await?;
             response.deserialize().await
@@ -1,10 +1,12 @@
 use hyper::client::connect::Connected;
+use mullvad_types::access_method;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt, io,
     path::Path,
     pin::Pin,
     task::{self, Poll},
+    time::Duration,
 };
 use talpid_types::{
     net::{proxy, Endpoint, TransportProtocol},
@@ -26,6 +28,24 @@ pub trait ConnectionModeProvider: Send {
 
     /// Receive changes to the connection mode, announced by the provider
     fn receive(&mut self) -> impl std::future::Future<Output = Option<ApiConnectionMode>> + Send;
+
+    /// The access method which the current connection mode was created from, if any. This
+    /// changes when a new connection mode is received.
+    fn access_method(&self) -> Option<access_method::Id> {
+        None
+    }
+
+    /// Report the outcome of a request made using `access_method`
+    fn report(&self, _access_method: access_method::Id, _outcome: RequestOutcome) {}
+}
+
+/// Outcome of an API request, as reported to a [`ConnectionModeProvider`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RequestOutcome {
+    /// The API responded after `latency`.
+    Success { latency: Duration },
+    /// The API could not be reached.
+    Failure,
 }
 
 pub struct StaticConnectionModeProvider {
@@ -74,6 +94,78 @@ pub enum ProxyConfig {
     Shadowsocks(proxy::Shadowsocks),
     Socks5Local(proxy::Socks5Local),
     Socks5Remote(proxy::Socks5Remote),
+    HttpConnect(proxy::HttpConnect),
+    Chain(ProxyChain),
+}
+
+/// A non-empty list of proxies. The first proxy is connected to directly, and every other proxy
+/// is reached through the one before it.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "Vec<ProxyConfig>", into = "Vec<ProxyConfig>")]
+pub struct ProxyChain {
+    first: Box<ProxyConfig>,
+    rest: Vec<ProxyConfig>,
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyChainError {
+    #[error("A proxy chain must contain at least one proxy")]
+    Empty,
+    /// A local proxy listens on this machine, so it cannot be reached through another proxy.
+    #[error("A local SOCKS5 proxy can only be the first proxy in a chain")]
+    LocalProxyNotFirst,
+}
+
+impl ProxyChain {
+    /// Nested chains are flattened. Fails if `proxies` is empty, or if a local SOCKS5 proxy
+    /// is not the first proxy.
+    pub fn new(proxies: impl IntoIterator<Item = ProxyConfig>) -> Result<Self, ProxyChainError> {
+        let mut proxies = proxies.into_iter().flat_map(|proxy| match proxy {
+            ProxyConfig::Chain(chain) => chain.into_iter().collect(),
+            proxy => vec![proxy],
+        });
+        let first = Box::new(proxies.next().ok_or(ProxyChainError::Empty)?);
+        let rest: Vec<_> = proxies.collect();
+        if rest
+            .iter()
+            .any(|proxy| matches!(proxy, ProxyConfig::Socks5Local(_)))
+        {
+            return Err(ProxyChainError::LocalProxyNotFirst);
+        }
+        Ok(Self { first, rest })
+    }
+
+    /// The proxy which is connected to directly.
+    pub fn first(&self) -> &ProxyConfig {
+        &self.first
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ProxyConfig> {
+        std::iter::once(&*self.first).chain(&self.rest)
+    }
+}
+
+impl TryFrom<Vec<ProxyConfig>> for ProxyChain {
+    type Error = ProxyChainError;
+
+    fn try_from(proxies: Vec<ProxyConfig>) -> Result<Self, Self::Error> {
+        Self::new(proxies)
+    }
+}
+
+impl From<ProxyChain> for Vec<ProxyConfig> {
+    fn from(chain: ProxyChain) -> Self {
+        chain.into_iter().collect()
+    }
+}
+
+impl IntoIterator for ProxyChain {
+    type Item = ProxyConfig;
+    type IntoIter = std::iter::Chain<std::iter::Once<ProxyConfig>, std::vec::IntoIter<ProxyConfig>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        std::iter::once(*self.first).chain(self.rest)
+    }
 }
 
 impl ProxyConfig {
@@ -87,6 +179,19 @@ impl ProxyConfig {
             ProxyConfig::Socks5Remote(remote) => {
                 Endpoint::from_socket_address(remote.endpoint, TransportProtocol::Tcp)
             }
+            ProxyConfig::HttpConnect(http) => {
+                Endpoint::from_socket_address(http.endpoint, TransportProtocol::Tcp)
+            }
+            ProxyConfig::Chain(chain) => chain.first().get_endpoint(),
+        }
+    }
+
+    /// Returns the first proxy that has to be reached, i.e. the proxy itself unless it is a
+    /// chain.
+    pub fn first_hop(&self) -> &ProxyConfig {
+        match self {
+            ProxyConfig::Chain(chain) => chain.first(),
+            proxy => proxy,
         }
     }
 }
@@ -97,9 +202,19 @@ impl fmt::Display for ProxyConfig {
         match self {
             ProxyConfig::Shadowsocks(_) => write!(f, "Shadowsocks {}", endpoint),
             ProxyConfig::Socks5Remote(_) => write!(f, "Socks5 {}", endpoint),
+            ProxyConfig::HttpConnect(_) => write!(f, "HTTP {}", endpoint),
             ProxyConfig::Socks5Local(local) => {
                 write!(f, "Socks5 {} via localhost:{}", endpoint, local.local_port)
             }
+            ProxyConfig::Chain(chain) => {
+                for (i, proxy) in chain.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " -> ")?;
+                    }
+                    proxy.fmt(f)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -110,6 +225,7 @@ impl From<proxy::CustomProxy> for ProxyConfig {
             proxy::CustomProxy::Shadowsocks(shadowsocks) => ProxyConfig::Shadowsocks(shadowsocks),
             proxy::CustomProxy::Socks5Local(socks) => ProxyConfig::Socks5Local(socks),
             proxy::CustomProxy::Socks5Remote(socks) => ProxyConfig::Socks5Remote(socks),
+            proxy::CustomProxy::HttpConnect(http) => ProxyConfig::HttpConnect(http),
         }
     }
 }
@@ -282,3 +398,54 @@ impl hyper::client::connect::Connection for ApiConnection {
         self.0.connected()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn socks(port: u16) -> ProxyConfig {
+        ProxyConfig::Socks5Local(proxy::Socks5Local::new(([192, 0, 2, 1], 1080), port))
+    }
+
+    fn remote(port: u16) -> ProxyConfig {
+        ProxyConfig::Socks5Remote(proxy::Socks5Remote::new(([192, 0, 2, 2], port)))
+    }
+
+    #[test]
+    fn test_proxy_chain() {
+        assert_eq!(ProxyChain::new(vec![]), Err(ProxyChainError::Empty));
+
+        let inner = ProxyChain::new(vec![remote(2), remote(3)]).unwrap();
+        let chain = ProxyChain::new(vec![socks(1), ProxyConfig::Chain(inner)]).unwrap();
+        assert_eq!(chain.first(), &socks(1));
+        assert_eq!(
+            chain.into_iter().collect::<Vec<_>>(),
+            vec![socks(1), remote(2), remote(3)]
+        );
+    }
+
+    #[test]
+    fn test_proxy_chain_local_proxy_not_first() {
+        assert_eq!(
+            ProxyChain::new(vec![remote(1), socks(2)]),
+            Err(ProxyChainError::LocalProxyNotFirst)
+        );
+
+        let inner = ProxyChain::new(vec![socks(2), remote(3)]).unwrap();
+        assert_eq!(
+            ProxyChain::new(vec![remote(1), ProxyConfig::Chain(inner)]),
+            Err(ProxyChainError::LocalProxyNotFirst)
+        );
+    }
+
+    #[test]
+    fn test_proxy_chain_deserialization() {
+        let chain = ProxyChain::new(vec![socks(1), remote(2)]).unwrap();
+        let json = serde_json::to_string(&chain).unwrap();
+        assert_eq!(serde_json::from_str::<ProxyChain>(&json).unwrap(), chain);
+
+        assert!(serde_json::from_str::<ProxyChain>("[]").is_err());
+        let json = serde_json::to_string(&vec![remote(1), socks(2)]).unwrap();
+        assert!(serde_json::from_str::<ProxyChain>(&json).is_err());
+    }
+}
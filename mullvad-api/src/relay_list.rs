@@ -244,6 +244,8 @@ struct Wireguard {
     port_ranges: Vec<(u16, u16)>,
     ipv4_gateway: Ipv4Addr,
     ipv6_gateway: Ipv6Addr,
+    #[serde(default)]
+    shadowsocks_port_ranges: Vec<(u16, u16)>,
     relays: Vec<WireGuardRelay>,
 }
 
@@ -254,6 +256,7 @@ impl From<&Wireguard> for relay_list::WireguardEndpointData {
             ipv4_gateway: wg.ipv4_gateway,
             ipv6_gateway: wg.ipv6_gateway,
             udp2tcp_ports: vec![],
+            shadowsocks_port_ranges: wg.shadowsocks_port_ranges.clone(),
         }
     }
 }
@@ -5,26 +5,34 @@ use crate::{
     address_cache::AddressCache,
     availability::ApiAvailabilityHandle,
     https_client_with_sni::{HttpsConnectorWithSni, HttpsConnectorWithSniHandle},
-    proxy::ConnectionModeProvider,
+    proxy::{ConnectionModeProvider, RequestOutcome},
+    tls_stream::PinnedKeyMismatch,
 };
 use futures::{
     channel::{mpsc, oneshot},
     stream::StreamExt,
 };
 use hyper::{
+    body::Bytes,
     client::{connect::Connect, Client},
     header::{self, HeaderValue},
     Method, Uri,
 };
-use mullvad_types::account::AccountToken;
+use mullvad_types::{access_method, account::AccountToken};
 use std::{
     borrow::Cow,
     error::Error as StdError,
+    fmt,
     str::FromStr,
-    sync::{Arc, Weak},
-    time::Duration,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant, SystemTime},
 };
+use talpid_future::retry::{ExponentialBackoff, Jittered};
 use talpid_types::ErrorExt;
+use tokio_rustls::rustls;
 
 pub use hyper::StatusCode;
 
@@ -67,11 +75,27 @@ pub enum Error {
 
     #[error("Set account token on factory with no access token store")]
     NoAccessTokenStore,
+
+    /// The certificate of the API could not be verified using the trusted root certificates.
+    #[error("Failed to verify API certificate")]
+    UntrustedCertificate(#[source] Arc<rustls::Error>),
+
+    /// The certificate of the API does not have any of the pinned public keys.
+    #[error("API certificate does not have a pinned public key")]
+    PinnedKeyMismatch,
 }
 
 impl Error {
+    /// Return true if the API could not be reached. Certificate verification failures are
+    /// included, since they are likely caused by something between us and the API.
     pub fn is_network_error(&self) -> bool {
-        matches!(self, Error::HyperError(_) | Error::TimeoutError)
+        matches!(
+            self,
+            Error::HyperError(_)
+                | Error::TimeoutError
+                | Error::UntrustedCertificate(_)
+                | Error::PinnedKeyMismatch
+        )
     }
 
     /// Return true if there was no route to the destination
@@ -93,24 +117,41 @@ impl Error {
         matches!(self, Error::Aborted)
     }
 
-    /// Returns a new instance for which `abortable_stream::Aborted` is mapped to `Self::Aborted`.
-    fn map_aborted(self) -> Self {
+    /// Returns a new instance for which `abortable_stream::Aborted` is mapped to `Self::Aborted`,
+    /// and certificate verification errors are mapped to `Self::UntrustedCertificate` or
+    /// `Self::PinnedKeyMismatch`.
+    fn map_io_error(self) -> Self {
         if let Error::HyperError(error) = &self {
             let mut source = error.source();
             while let Some(error) = source {
                 let io_error: Option<&std::io::Error> = error.downcast_ref();
-                if let Some(io_error) = io_error {
-                    let abort_error: Option<&crate::abortable_stream::Aborted> =
-                        io_error.get_ref().and_then(|inner| inner.downcast_ref());
-                    if abort_error.is_some() {
+                if let Some(inner) = io_error.and_then(|io_error| io_error.get_ref()) {
+                    if inner.is::<crate::abortable_stream::Aborted>() {
                         return Self::Aborted;
                     }
+                    if let Some(tls_error) = inner.downcast_ref::<rustls::Error>() {
+                        return Self::from_tls_error(tls_error).unwrap_or(self);
+                    }
                 }
                 source = error.source();
             }
         }
         self
     }
+
+    fn from_tls_error(error: &rustls::Error) -> Option<Self> {
+        match error {
+            rustls::Error::InvalidCertificate(rustls::CertificateError::Other(other))
+                if other.is::<PinnedKeyMismatch>() =>
+            {
+                Some(Self::PinnedKeyMismatch)
+            }
+            rustls::Error::InvalidCertificate(_) => {
+                Some(Self::UntrustedCertificate(Arc::new(error.clone())))
+            }
+            _ => None,
+        }
+    }
 }
 
 /// A service that executes HTTP requests, allowing for on-demand termination of all in-flight
@@ -155,9 +196,13 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
             client,
             connection_mode_provider,
             connection_mode_generation: 0,
+            api_availability: api_availability.clone(),
+        };
+        let handle = RequestServiceHandle {
+            tx: command_tx,
             api_availability,
+            retry_counters: Arc::default(),
         };
-        let handle = RequestServiceHandle { tx: command_tx };
         tokio::spawn(service.into_future());
         handle
     }
@@ -198,13 +243,16 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
                     self.connection_mode_provider.rotate().await;
                 }
             }
+            RequestCommand::ReportOutcome(access_method, outcome) => {
+                self.connection_mode_provider.report(access_method, outcome);
+            }
         }
     }
 
     fn handle_new_request(
         &mut self,
         request: Request,
-        completion_tx: oneshot::Sender<Result<Response>>,
+        completion_tx: oneshot::Sender<AttemptResult>,
     ) {
         let tx = self.command_tx.upgrade();
 
@@ -212,12 +260,37 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
         let request_future = request.into_future(self.client.clone(), api_availability.clone());
 
         let connection_mode_generation = self.connection_mode_generation;
+        let access_method = self.connection_mode_provider.access_method();
+        let started = Instant::now();
 
         tokio::spawn(async move {
-            let response = request_future.await.map_err(|error| error.map_aborted());
+            let response = request_future.await.map_err(|mut attempt_error| {
+                attempt_error.error = attempt_error.error.map_io_error();
+                attempt_error
+            });
+
+            let outcome = match &response {
+                // Any response from the API means that the connection mode works, even if its
+                // body could not be parsed
+                Ok(_)
+                | Err(AttemptError {
+                    status: Some(_), ..
+                }) => Some(RequestOutcome::Success {
+                    latency: started.elapsed(),
+                }),
+                Err(AttemptError { error: err, .. })
+                    if err.is_network_error() && !api_availability.get_state().is_offline() =>
+                {
+                    Some(RequestOutcome::Failure)
+                }
+                Err(_) => None,
+            };
+            if let (Some(access_method), Some(outcome), Some(tx)) = (access_method, outcome, &tx) {
+                let _ = tx.unbounded_send(RequestCommand::ReportOutcome(access_method, outcome));
+            }
 
             // Switch API endpoint if the request failed due to a network error
-            if let Err(err) = &response {
+            if let Err(AttemptError { error: err, .. }) = &response {
                 if err.is_network_error() && !api_availability.get_state().is_offline() {
                     log::error!("{}", err.display_chain_with_msg("HTTP request failed"));
                     if let Some(tx) = tx {
@@ -237,6 +310,8 @@ impl<T: ConnectionModeProvider + 'static> RequestService<T> {
 /// A handle to interact with a spawned `RequestService`.
 pub struct RequestServiceHandle {
     tx: Arc<mpsc::UnboundedSender<RequestCommand>>,
+    api_availability: ApiAvailabilityHandle,
+    retry_counters: Arc<RetryCounters>,
 }
 
 impl RequestServiceHandle {
@@ -245,34 +320,361 @@ impl RequestServiceHandle {
         let _ = self.tx.unbounded_send(RequestCommand::Reset);
     }
 
-    /// Submits a `RestRequest` for execution to the request service.
+    /// Submits a `RestRequest` for execution to the request service. If the request fails, it is
+    /// sent again as allowed by its [`RetryPolicy`]. No more attempts are made once the returned
+    /// future is dropped.
     pub async fn request(&self, request: Request) -> Result<Response> {
+        let policy = request.retry_policy;
+        let mut delays = policy.delays();
+        let mut attempt = 1;
+
+        loop {
+            match policy.wait_for {
+                WaitFor::Nothing => (),
+                WaitFor::Online => {
+                    let _ = self.api_availability.wait_online().await;
+                }
+                WaitFor::Background => {
+                    let _ = self.api_availability.wait_background().await;
+                }
+            }
+
+            let attempt_error = match self.send_attempt(request.clone()).await {
+                Ok(response) => {
+                    if attempt > 1 {
+                        self.retry_counters
+                            .recovered
+                            .fetch_add(1, Ordering::Relaxed);
+                        log::debug!(
+                            "Request to {} succeeded after {attempt} attempts. Retry metrics: {}",
+                            request.uri(),
+                            self.retry_metrics()
+                        );
+                    }
+                    return Ok(response);
+                }
+                Err(attempt_error) => attempt_error,
+            };
+
+            // Unless the policy waits for the API to come back online, give up immediately
+            let offline = policy.wait_for == WaitFor::Nothing
+                && attempt_error.error.is_network_error()
+                && self.api_availability.get_state().is_offline();
+            let delay = if offline {
+                None
+            } else {
+                policy.next_delay(attempt, &attempt_error, &mut delays)
+            };
+            let Some(delay) = delay else {
+                if attempt > 1 {
+                    self.retry_counters
+                        .exhausted
+                        .fetch_add(1, Ordering::Relaxed);
+                    log::debug!(
+                        "Request to {} failed after {attempt} attempts. Retry metrics: {}",
+                        request.uri(),
+                        self.retry_metrics()
+                    );
+                }
+                return Err(attempt_error.error);
+            };
+
+            log::debug!(
+                "{}",
+                attempt_error.error.display_chain_with_msg(&format!(
+                    "Request to {} failed. Retrying in {} ms",
+                    request.uri(),
+                    delay.as_millis()
+                ))
+            );
+            self.retry_counters.retries.fetch_add(1, Ordering::Relaxed);
+            talpid_time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send_attempt(&self, request: Request) -> AttemptResult {
         let (completion_tx, completion_rx) = oneshot::channel();
         self.tx
             .unbounded_send(RequestCommand::NewRequest(request, completion_tx))
             .map_err(|_| Error::RestServiceDown)?;
         completion_rx.await.map_err(|_| Error::RestServiceDown)?
     }
+
+    /// Returns how many requests submitted through this service have been retried.
+    pub fn retry_metrics(&self) -> RetryMetrics {
+        RetryMetrics {
+            retries: self.retry_counters.retries.load(Ordering::Relaxed),
+            recovered: self.retry_counters.recovered.load(Ordering::Relaxed),
+            exhausted: self.retry_counters.exhausted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counts of retried requests, see [`RequestServiceHandle::retry_metrics`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetryMetrics {
+    /// Number of times a failed request was sent again.
+    pub retries: u64,
+    /// Number of requests that succeeded after being retried.
+    pub recovered: u64,
+    /// Number of requests that were retried but failed anyway.
+    pub exhausted: u64,
+}
+
+impl fmt::Display for RetryMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} sent again, {} recovered, {} exhausted",
+            self.retries, self.recovered, self.exhausted
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct RetryCounters {
+    retries: AtomicU64,
+    recovered: AtomicU64,
+    exhausted: AtomicU64,
+}
+
+/// Determines whether a failed request is sent again, and how long to wait before doing so.
+///
+/// Delays grow exponentially up to a maximum delay, and are jittered. If the API responds with
+/// `429 Too Many Requests` or `503 Service Unavailable` and a `Retry-After` header, that delay is
+/// used instead. Network errors are not retried while the API is considered to be offline, unless
+/// the policy waits for it to come back online.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: Option<usize>,
+    initial_delay: Duration,
+    factor: u32,
+    max_delay: Duration,
+    retry_network_errors: bool,
+    retry_status: RetryStatus,
+    wait_for: WaitFor,
+}
+
+/// Which error responses to retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RetryStatus {
+    /// Retry responses with any of these status codes.
+    Only(&'static [StatusCode]),
+    /// Retry all responses except those with any of these status codes or error codes.
+    AllExcept {
+        status: &'static [StatusCode],
+        codes: &'static [&'static str],
+    },
+}
+
+/// What to wait for before each attempt to send a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WaitFor {
+    Nothing,
+    Online,
+    Background,
+}
+
+impl RetryPolicy {
+    /// Only send the request once. This is the default for all requests.
+    pub const NEVER: RetryPolicy = RetryPolicy::new(Some(1));
+
+    /// Response status codes which mean that the API is temporarily unable to handle a request.
+    pub const SERVER_ERRORS: &'static [StatusCode] = &[
+        StatusCode::TOO_MANY_REQUESTS,
+        StatusCode::INTERNAL_SERVER_ERROR,
+        StatusCode::BAD_GATEWAY,
+        StatusCode::SERVICE_UNAVAILABLE,
+        StatusCode::GATEWAY_TIMEOUT,
+    ];
+
+    /// The default maximum delay between two attempts.
+    pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+
+    /// Creates a policy which sends a request at most `max_attempts` times, or until it succeeds
+    /// if `max_attempts` is `None`.
+    ///
+    /// By default, network errors and `429 Too Many Requests` and `503 Service Unavailable`
+    /// responses are retried. The first delay is one second, and it doubles after each attempt
+    /// up to [`Self::DEFAULT_MAX_DELAY`].
+    pub const fn new(max_attempts: Option<usize>) -> Self {
+        Self {
+            max_attempts,
+            initial_delay: Duration::from_secs(1),
+            factor: 2,
+            max_delay: Self::DEFAULT_MAX_DELAY,
+            retry_network_errors: true,
+            retry_status: RetryStatus::Only(&[
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::SERVICE_UNAVAILABLE,
+            ]),
+            wait_for: WaitFor::Nothing,
+        }
+    }
+
+    /// Set the delay before the first retry, and the factor to multiply it by for each following
+    /// retry. See [`ExponentialBackoff::new`].
+    pub const fn backoff(mut self, initial_delay: Duration, factor: u32) -> Self {
+        self.initial_delay = initial_delay;
+        self.factor = factor;
+        self
+    }
+
+    /// Set the maximum delay between two attempts. A request is not retried if the API asks us
+    /// to wait longer than this.
+    pub const fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Set whether requests which failed due to a network error or a timeout should be retried.
+    pub const fn retry_network_errors(mut self, retry: bool) -> Self {
+        self.retry_network_errors = retry;
+        self
+    }
+
+    /// Set the unexpected response status codes for which a request should be retried.
+    pub const fn retry_status(mut self, status: &'static [StatusCode]) -> Self {
+        self.retry_status = RetryStatus::Only(status);
+        self
+    }
+
+    /// Retry all unexpected responses, except those with any of the given status codes or API
+    /// error codes.
+    pub const fn retry_all_status_except(
+        mut self,
+        status: &'static [StatusCode],
+        codes: &'static [&'static str],
+    ) -> Self {
+        self.retry_status = RetryStatus::AllExcept { status, codes };
+        self
+    }
+
+    /// Wait until the API is online before each attempt. Network errors are then retried while
+    /// the API is offline, instead of failing the request.
+    pub const fn when_online(mut self) -> Self {
+        self.wait_for = WaitFor::Online;
+        self
+    }
+
+    /// Wait until background requests are allowed before each attempt.
+    /// See [`ApiAvailabilityHandle::when_bg_resumes`].
+    pub const fn when_bg_resumes(mut self) -> Self {
+        self.wait_for = WaitFor::Background;
+        self
+    }
+
+    fn delays(&self) -> Jittered<ExponentialBackoff> {
+        Jittered::jitter(
+            ExponentialBackoff::new(self.initial_delay, self.factor)
+                .max_delay(Some(self.max_delay)),
+        )
+    }
+
+    /// Responses are classified by their status code, so that an error response is retried
+    /// even if its body could not be parsed.
+    fn is_retryable(&self, attempt_error: &AttemptError) -> bool {
+        let status = match attempt_error.status {
+            Some(status) => status,
+            None => return self.retry_network_errors && attempt_error.error.is_network_error(),
+        };
+        match self.retry_status {
+            RetryStatus::Only(retry_status) => retry_status.contains(&status),
+            RetryStatus::AllExcept {
+                status: excluded_status,
+                codes: excluded_codes,
+            } => {
+                let code = match &attempt_error.error {
+                    Error::ApiError(_, code) => Some(code.as_str()),
+                    _ => None,
+                };
+                !excluded_status.contains(&status)
+                    && !code.is_some_and(|code| excluded_codes.contains(&code))
+            }
+        }
+    }
+
+    /// Returns how long to wait before sending a request again, after attempt number `attempt`
+    /// failed. Returns `None` if the request should not be retried.
+    fn next_delay(
+        &self,
+        attempt: usize,
+        attempt_error: &AttemptError,
+        delays: &mut impl Iterator<Item = Duration>,
+    ) -> Option<Duration> {
+        let attempts_left = self
+            .max_attempts
+            .map_or(true, |max_attempts| attempt < max_attempts);
+        if !attempts_left || !self.is_retryable(attempt_error) {
+            return None;
+        }
+        let delay = delays.next()?;
+        match attempt_error.retry_after {
+            Some(retry_after) if retry_after > self.max_delay => {
+                log::debug!(
+                    "Not retrying request since the API asked us to wait {} s",
+                    retry_after.as_secs()
+                );
+                None
+            }
+            Some(retry_after) => Some(retry_after),
+            None => Some(delay),
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::NEVER
+    }
+}
+
+/// The result of sending a request once.
+type AttemptResult = std::result::Result<Response, AttemptError>;
+
+/// Describes why a single attempt to send a request failed.
+#[derive(Debug)]
+pub(crate) struct AttemptError {
+    error: Error,
+    /// How long the API asked us to wait before trying again, if it did.
+    retry_after: Option<Duration>,
+    /// The status of the response, if the API responded.
+    status: Option<StatusCode>,
+}
+
+impl From<Error> for AttemptError {
+    fn from(error: Error) -> Self {
+        let status = match &error {
+            Error::ApiError(status, _) => Some(*status),
+            _ => None,
+        };
+        Self {
+            error,
+            retry_after: None,
+            status,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub(crate) enum RequestCommand {
-    NewRequest(
-        Request,
-        oneshot::Sender<std::result::Result<Response, Error>>,
-    ),
+    NewRequest(Request, oneshot::Sender<AttemptResult>),
     Reset,
     NextApiConfig(usize),
+    ReportOutcome(access_method::Id, RequestOutcome),
 }
 
 /// A REST request that is sent to the RequestService to be executed.
 #[derive(Debug)]
 pub struct Request {
-    request: hyper::Request<hyper::Body>,
+    /// The body is kept in memory so that the request can be sent again.
+    request: hyper::Request<Bytes>,
     timeout: Duration,
     access_token_store: Option<AccessTokenStore>,
     account: Option<AccountToken>,
     expected_status: &'static [hyper::StatusCode],
+    retry_policy: RetryPolicy,
 }
 
 impl Request {
@@ -291,20 +693,18 @@ impl Request {
             );
         };
 
-        let request = builder.uri(uri).body(hyper::Body::empty())?;
+        let request = builder.uri(uri).body(Bytes::new())?;
         Ok(Self::new(request, None))
     }
 
-    fn new(
-        request: hyper::Request<hyper::Body>,
-        access_token_store: Option<AccessTokenStore>,
-    ) -> Self {
+    fn new(request: hyper::Request<Bytes>, access_token_store: Option<AccessTokenStore>) -> Self {
         Self {
             request,
             timeout: DEFAULT_TIMEOUT,
             access_token_store,
             account: None,
             expected_status: &[],
+            retry_policy: RetryPolicy::NEVER,
         }
     }
 
@@ -329,6 +729,12 @@ impl Request {
         self
     }
 
+    /// Sets the policy for retrying the request if it fails. By default, it is only sent once.
+    pub fn retry(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
     pub fn header<T: header::IntoHeaderName>(mut self, key: T, value: &str) -> Result<Self> {
         let header_value =
             http::HeaderValue::from_str(value).map_err(|_| Error::InvalidHeaderError)?;
@@ -340,7 +746,7 @@ impl Request {
         self,
         hyper_client: hyper::Client<C>,
         api_availability: ApiAvailabilityHandle,
-    ) -> Result<Response> {
+    ) -> AttemptResult {
         let timeout = self.timeout;
         let inner_fut = self.into_future_without_timeout(hyper_client, api_availability);
         tokio::time::timeout(timeout, inner_fut)
@@ -349,27 +755,24 @@ impl Request {
     }
 
     async fn into_future_without_timeout<C: Connect + Clone + Send + Sync + 'static>(
-        mut self,
+        self,
         hyper_client: hyper::Client<C>,
         api_availability: ApiAvailabilityHandle,
-    ) -> Result<Response> {
+    ) -> AttemptResult {
         let _ = api_availability.wait_for_unsuspend().await;
 
+        let mut request = self.request.map(hyper::Body::from);
+
         // Obtain access token first
         if let (Some(account), Some(store)) = (&self.account, &self.access_token_store) {
             let access_token = store.get_token(account).await?;
             let auth = HeaderValue::from_str(&format!("Bearer {access_token}"))
                 .map_err(|_| Error::InvalidHeaderError)?;
-            self.request
-                .headers_mut()
-                .insert(header::AUTHORIZATION, auth);
+            request.headers_mut().insert(header::AUTHORIZATION, auth);
         }
 
         // Make request to hyper client
-        let response = hyper_client
-            .request(self.request)
-            .await
-            .map_err(Error::from);
+        let response = hyper_client.request(request).await.map_err(Error::from);
 
         // Notify access token store of expired tokens
         if let (Some(account), Some(store)) = (&self.account, &self.access_token_store) {
@@ -393,7 +796,15 @@ impl Request {
                 );
             }
             if !response.status().is_success() {
-                return handle_error_response(response).await;
+                let status = Some(response.status());
+                let retry_after = get_retry_after(&response);
+                return handle_error_response(response)
+                    .await
+                    .map_err(|error| AttemptError {
+                        error,
+                        retry_after,
+                        status,
+                    });
             }
         }
 
@@ -406,6 +817,25 @@ impl Request {
     }
 }
 
+impl Clone for Request {
+    fn clone(&self) -> Self {
+        let mut request = hyper::Request::new(self.request.body().clone());
+        *request.method_mut() = self.request.method().clone();
+        *request.uri_mut() = self.request.uri().clone();
+        *request.version_mut() = self.request.version();
+        *request.headers_mut() = self.request.headers().clone();
+
+        Self {
+            request,
+            timeout: self.timeout,
+            access_token_store: self.access_token_store.clone(),
+            account: self.account.clone(),
+            expected_status: self.expected_status,
+            retry_policy: self.retry_policy,
+        }
+    }
+}
+
 /// Successful result of a REST request
 #[derive(Debug)]
 pub struct Response {
@@ -448,6 +878,7 @@ pub struct RequestFactory {
     hostname: Cow<'static, str>,
     token_store: Option<AccessTokenStore>,
     default_timeout: Duration,
+    default_retry_policy: RetryPolicy,
 }
 
 impl RequestFactory {
@@ -459,13 +890,15 @@ impl RequestFactory {
             hostname: hostname.into(),
             token_store,
             default_timeout: DEFAULT_TIMEOUT,
+            default_retry_policy: RetryPolicy::NEVER,
         }
     }
 
     pub fn request(&self, path: &str, method: Method) -> Result<Request> {
         Ok(
             Request::new(self.hyper_request(path, method)?, self.token_store.clone())
-                .timeout(self.default_timeout),
+                .timeout(self.default_timeout)
+                .retry(self.default_retry_policy),
         )
     }
 
@@ -502,6 +935,12 @@ impl RequestFactory {
         self
     }
 
+    /// Sets the [`RetryPolicy`] of all requests created by this factory.
+    pub fn default_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.default_retry_policy = retry_policy;
+        self
+    }
+
     fn json_request<S: serde::Serialize>(
         &self,
         method: Method,
@@ -512,7 +951,7 @@ impl RequestFactory {
 
         let json_body = serde_json::to_string(&body)?;
         let body_length = json_body.as_bytes().len();
-        *request.body_mut() = json_body.into_bytes().into();
+        *request.body_mut() = Bytes::from(json_body);
 
         let headers = request.headers_mut();
         headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body_length));
@@ -521,10 +960,12 @@ impl RequestFactory {
             HeaderValue::from_static("application/json"),
         );
 
-        Ok(Request::new(request, self.token_store.clone()).timeout(self.default_timeout))
+        Ok(Request::new(request, self.token_store.clone())
+            .timeout(self.default_timeout)
+            .retry(self.default_retry_policy))
     }
 
-    fn hyper_request(&self, path: &str, method: Method) -> Result<hyper::Request<hyper::Body>> {
+    fn hyper_request(&self, path: &str, method: Method) -> Result<hyper::Request<Bytes>> {
         let uri = self.get_uri(path)?;
         let request = http::request::Builder::new()
             .method(method)
@@ -536,7 +977,7 @@ impl RequestFactory {
                 HeaderValue::from_str(&self.hostname).map_err(|_| Error::InvalidHeaderError)?,
             );
 
-        let result = request.body(hyper::Body::empty())?;
+        let result = request.body(Bytes::new())?;
         Ok(result)
     }
 
@@ -555,6 +996,29 @@ fn get_body_length(response: &hyper::Response<hyper::Body>) -> usize {
         .unwrap_or(0)
 }
 
+/// Returns the delay requested by the `Retry-After` header of a `429 Too Many Requests` or
+/// `503 Service Unavailable` response. The header may contain either a number of seconds or a date.
+fn get_retry_after(response: &hyper::Response<hyper::Body>) -> Option<Duration> {
+    if !matches!(
+        response.status(),
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    ) {
+        return None;
+    }
+    let value = response.headers().get(header::RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value, SystemTime::now())
+}
+
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let date = SystemTime::from(date);
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
 async fn handle_error_response<T>(response: hyper::Response<hyper::Body>) -> Result<T> {
     let status = response.status();
     let error_message = match status {
@@ -624,6 +1088,14 @@ impl MullvadRestHandle {
     pub fn service(&self) -> RequestServiceHandle {
         self.service.clone()
     }
+
+    /// Returns a handle whose requests are retried according to `retry_policy`, unless a request
+    /// sets its own policy.
+    pub fn with_retry_policy(&self, retry_policy: RetryPolicy) -> Self {
+        let mut handle = self.clone();
+        handle.factory = handle.factory.default_retry_policy(retry_policy);
+        handle
+    }
 }
 
 macro_rules! impl_into_arc_err {
@@ -639,3 +1111,123 @@ macro_rules! impl_into_arc_err {
 impl_into_arc_err!(hyper::Error);
 impl_into_arc_err!(serde_json::Error);
 impl_into_arc_err!(http::Error);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_retry_after() {
+        // Sun, 06 Nov 1994 08:49:37 GMT
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::new(Some(3)).max_delay(Duration::from_secs(60));
+        let mut delays = std::iter::repeat(Duration::from_secs(1));
+        let network_error = AttemptError::from(Error::TimeoutError);
+        let unavailable = |retry_after| AttemptError {
+            error: Error::ApiError(StatusCode::SERVICE_UNAVAILABLE, String::new()),
+            retry_after,
+            status: Some(StatusCode::SERVICE_UNAVAILABLE),
+        };
+
+        assert_eq!(
+            policy.next_delay(1, &network_error, &mut delays),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(policy.next_delay(3, &network_error, &mut delays), None);
+        assert_eq!(
+            policy.next_delay(1, &unavailable(Some(Duration::from_secs(30))), &mut delays),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            policy.next_delay(1, &unavailable(Some(Duration::from_secs(120))), &mut delays),
+            None
+        );
+
+        let not_found = AttemptError::from(Error::ApiError(StatusCode::NOT_FOUND, String::new()));
+        assert_eq!(policy.next_delay(1, &not_found, &mut delays), None);
+
+        // An error response is retried even if its body is not valid JSON
+        let unparsable = AttemptError {
+            error: Error::DeserializeError(Arc::new(
+                serde_json::from_str::<()>("<html>").unwrap_err(),
+            )),
+            retry_after: None,
+            status: Some(StatusCode::SERVICE_UNAVAILABLE),
+        };
+        assert_eq!(
+            policy.next_delay(1, &unparsable, &mut delays),
+            Some(Duration::from_secs(1))
+        );
+        let unparsable_not_found = AttemptError {
+            status: Some(StatusCode::NOT_FOUND),
+            ..unparsable
+        };
+        assert_eq!(
+            policy.next_delay(1, &unparsable_not_found, &mut delays),
+            None
+        );
+        assert_eq!(
+            RetryPolicy::NEVER.next_delay(1, &network_error, &mut delays),
+            None
+        );
+    }
+
+    #[test]
+    fn test_retry_all_status_except() {
+        let policy = RetryPolicy::new(None)
+            .retry_all_status_except(&[StatusCode::NOT_FOUND], &[crate::INVALID_ACCOUNT]);
+        let mut delays = std::iter::repeat(Duration::from_secs(1));
+        let api_error =
+            |status, code: &str| AttemptError::from(Error::ApiError(status, code.to_owned()));
+
+        assert_eq!(
+            policy.next_delay(
+                1,
+                &api_error(StatusCode::UNAUTHORIZED, crate::INVALID_ACCESS_TOKEN),
+                &mut delays
+            ),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            policy.next_delay(
+                1,
+                &api_error(StatusCode::NOT_FOUND, crate::DEVICE_NOT_FOUND),
+                &mut delays
+            ),
+            None
+        );
+        assert_eq!(
+            policy.next_delay(
+                1,
+                &api_error(StatusCode::BAD_REQUEST, crate::INVALID_ACCOUNT),
+                &mut delays
+            ),
+            None
+        );
+
+        // The API may not delay requests indefinitely
+        let unavailable = AttemptError {
+            error: Error::ApiError(StatusCode::SERVICE_UNAVAILABLE, String::new()),
+            retry_after: Some(RetryPolicy::DEFAULT_MAX_DELAY * 2),
+            status: Some(StatusCode::SERVICE_UNAVAILABLE),
+        };
+        assert_eq!(policy.next_delay(1, &unavailable, &mut delays), None);
+    }
+}
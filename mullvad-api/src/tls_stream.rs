@@ -1,18 +1,27 @@
-//! Provides a TLS 1.3 stream with SNI and LE root cert only.
+//! Provides a TLS 1.3 stream with SNI and LE root cert only, unless other trust anchors or
+//! pinned public keys are configured in [`API`].
 use std::{
     io::{self, ErrorKind},
     pin::Pin,
     sync::Arc,
     task::{self, Poll},
+    time::SystemTime,
 };
 
 use hyper::client::connect::{Connected, Connection};
 use once_cell::sync::Lazy;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio_rustls::{
-    rustls::{self, ClientConfig, ServerName},
+    rustls::{
+        self,
+        client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+        Certificate, CertificateError, ClientConfig, ServerName,
+    },
     TlsConnector,
 };
+use x509_cert::der::{Decode, Encode};
+
+use crate::API;
 
 const LE_ROOT_CERT: &[u8] = include_bytes!("../le_root_cert.pem");
 
@@ -30,9 +39,20 @@ where
                 .with_safe_default_cipher_suites()
                 .with_safe_default_kx_groups()
                 .with_protocol_versions(&[&rustls::version::TLS13])
-                .unwrap()
-                .with_root_certificates(read_cert_store())
-                .with_no_client_auth();
+                .unwrap();
+            let root_store = read_cert_store();
+            let config = if API.pinned_keys.is_empty() {
+                config
+                    .with_root_certificates(root_store)
+                    .with_no_client_auth()
+            } else {
+                config
+                    .with_custom_certificate_verifier(Arc::new(PinnedKeyVerifier {
+                        inner: WebPkiVerifier::new(root_store, None),
+                        pinned_keys: API.pinned_keys.clone(),
+                    }))
+                    .with_no_client_auth()
+            };
             Arc::new(config)
         });
 
@@ -64,9 +84,75 @@ fn read_cert_store() -> rustls::RootCertStore {
         panic!("Failed to add root cert");
     }
 
+    #[cfg(feature = "api-override")]
+    {
+        let (_, num_failures) = cert_store.add_parsable_certificates(&API.root_certificates);
+        if num_failures > 0 {
+            panic!("Failed to add configured root certs");
+        }
+    }
+
     cert_store
 }
 
+/// Returned by [`PinnedKeyVerifier`] if the server certificate does not have a pinned key.
+#[derive(thiserror::Error, Debug)]
+#[error("Server certificate does not have a pinned public key")]
+pub struct PinnedKeyMismatch;
+
+/// Verifies certificates like [`WebPkiVerifier`] does, but also requires the end-entity
+/// certificate to have one of the pinned public keys. Intermediates are not considered, since
+/// the server can send any certificate as an intermediate.
+struct PinnedKeyVerifier {
+    inner: WebPkiVerifier,
+    /// SHA-256 digests of DER-encoded SubjectPublicKeyInfo structures.
+    pinned_keys: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedKeyVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let has_pinned_key = subject_public_key_info(&end_entity.0).is_some_and(|key| {
+            let digest = ring::digest::digest(&ring::digest::SHA256, &key);
+            self.pinned_keys
+                .iter()
+                .any(|pinned_key| pinned_key[..] == *digest.as_ref())
+        });
+        if !has_pinned_key {
+            return Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                Arc::new(PinnedKeyMismatch),
+            )));
+        }
+
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )
+    }
+}
+
+/// Returns the DER-encoded SubjectPublicKeyInfo of an X.509 certificate, or `None` if the
+/// certificate is not valid DER.
+fn subject_public_key_info(certificate: &[u8]) -> Option<Vec<u8>> {
+    let certificate = x509_cert::Certificate::from_der(certificate).ok()?;
+    certificate
+        .tbs_certificate
+        .subject_public_key_info
+        .to_vec()
+        .ok()
+}
+
 impl<S> AsyncRead for TlsStream<S>
 where
     S: AsyncRead + AsyncWrite + Unpin,
@@ -118,4 +204,87 @@ mod test {
     fn test_cert_loading() {
         let _certs = read_cert_store();
     }
+
+    #[test]
+    fn test_subject_public_key_info() {
+        let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(LE_ROOT_CERT)).unwrap();
+        let key = subject_public_key_info(&certs[0]).unwrap();
+        let digest = ring::digest::digest(&ring::digest::SHA256, &key);
+        // Well-known pin of ISRG Root X1
+        assert_eq!(
+            base64::encode(digest),
+            "C5+lpZ7tcVwmwQIMcRtPbsQtWLABXhQzejna0wHFr8M="
+        );
+
+        assert!(subject_public_key_info(&certs[0][..100]).is_none());
+    }
+
+    #[test]
+    fn test_subject_public_key_info_rejects_non_minimal_length() {
+        let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(LE_ROOT_CERT)).unwrap();
+        let cert = &certs[0];
+        // The certificate is a SEQUENCE with a two-byte length
+        assert_eq!(cert[..2], [0x30, 0x82]);
+
+        // Encode the same length with a redundant leading zero byte
+        let mut non_minimal = vec![0x30, 0x83, 0x00];
+        non_minimal.extend_from_slice(&cert[2..]);
+        assert!(subject_public_key_info(&non_minimal).is_none());
+    }
+
+    fn verify(
+        end_entity: Vec<u8>,
+        intermediates: Vec<Vec<u8>>,
+        pinned_keys: Vec<[u8; 32]>,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verifier = PinnedKeyVerifier {
+            inner: WebPkiVerifier::new(read_cert_store(), None),
+            pinned_keys,
+        };
+        let intermediates: Vec<_> = intermediates.into_iter().map(Certificate).collect();
+        verifier.verify_server_cert(
+            &Certificate(end_entity),
+            &intermediates,
+            &ServerName::try_from("api.mullvad.net").unwrap(),
+            &mut std::iter::empty(),
+            &[],
+            SystemTime::now(),
+        )
+    }
+
+    fn is_pinned_key_mismatch(error: &rustls::Error) -> bool {
+        matches!(
+            error,
+            rustls::Error::InvalidCertificate(CertificateError::Other(other))
+                if other.is::<PinnedKeyMismatch>()
+        )
+    }
+
+    #[test]
+    fn test_pinned_key_in_intermediates() {
+        let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(LE_ROOT_CERT)).unwrap();
+        let pinned_cert = certs[0].clone();
+        let key = subject_public_key_info(&pinned_cert).unwrap();
+        let pinned_key: [u8; 32] = ring::digest::digest(&ring::digest::SHA256, &key)
+            .as_ref()
+            .try_into()
+            .unwrap();
+
+        // Change the last byte of the public key to get a certificate with an unpinned key
+        let key_start = pinned_cert
+            .windows(key.len())
+            .position(|window| window == key)
+            .unwrap();
+        let key_end = key_start + key.len();
+        let mut unpinned_cert = pinned_cert.clone();
+        unpinned_cert[key_end - 1] ^= 1;
+
+        let error = verify(unpinned_cert, vec![pinned_cert.clone()], vec![pinned_key]).unwrap_err();
+        assert!(is_pinned_key_mismatch(&error), "{error}");
+
+        // The pinned key is accepted in the end-entity certificate, which then fails to verify
+        // since it is a CA certificate
+        let error = verify(pinned_cert, vec![], vec![pinned_key]).unwrap_err();
+        assert!(!is_pinned_key_mismatch(&error), "{error}");
+    }
 }
@@ -5,7 +5,9 @@ use talpid_types::net::proxy::CustomProxy;
 
 use clap::{Args, Subcommand};
 
-use super::proxies::{ProxyEditParams, ShadowsocksAdd, Socks5LocalAdd, Socks5RemoteAdd};
+use super::proxies::{
+    HttpConnectAdd, ProxyEditParams, ShadowsocksAdd, Socks5LocalAdd, Socks5RemoteAdd,
+};
 
 #[derive(Subcommand, Debug, Clone)]
 pub enum ApiAccess {
@@ -36,6 +38,11 @@ pub enum ApiAccess {
     Use(SelectItem),
     /// Try to reach the Mullvad API using a specific access method
     Test(SelectItem),
+    /// Forget how well each API access method has worked
+    ///
+    /// Access methods which have recently reached the Mullvad API are tried first. This makes
+    /// all access methods be tried in the order they are listed in again.
+    ResetStats,
 }
 
 impl ApiAccess {
@@ -64,6 +71,9 @@ impl ApiAccess {
             ApiAccess::Get => {
                 Self::get().await?;
             }
+            ApiAccess::ResetStats => {
+                Self::reset_stats().await?;
+            }
         };
         Ok(())
     }
@@ -71,22 +81,34 @@ impl ApiAccess {
     /// Show all API access methods.
     async fn list() -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
+        let statistics = rpc.get_api_access_method_statistics().await?;
         for (index, api_access_method) in rpc.get_api_access_methods().await?.iter().enumerate() {
-            println!(
-                "{}. {}",
-                index + 1,
-                pp::ApiAccessMethodFormatter::new(api_access_method)
-            );
+            let mut formatter = pp::ApiAccessMethodFormatter::new(api_access_method);
+            formatter.statistics = statistics.get(&api_access_method.get_id());
+            println!("{}. {}", index + 1, formatter);
         }
         Ok(())
     }
 
+    /// Forget the outcomes of previous requests made using each API access method.
+    async fn reset_stats() -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.reset_api_access_method_statistics().await?;
+        println!("Reset API access method statistics");
+        Ok(())
+    }
+
     /// Add a custom API access method.
     async fn add(cmd: AddCustomCommands) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
         let name = cmd.name().to_string();
         let enabled = cmd.enabled();
-        let access_method = AccessMethod::try_from(cmd)?;
+        let access_method = match cmd {
+            AddCustomCommands::Chain { methods, .. } => {
+                Self::get_proxy_chain(&mut rpc, methods).await?
+            }
+            cmd => AccessMethod::try_from(cmd)?,
+        };
         rpc.add_access_method(name, enabled, access_method).await?;
         Ok(())
     }
@@ -107,9 +129,12 @@ impl ApiAccess {
         let mut api_access_method = Self::get_access_method(&mut rpc, &cmd.item).await?;
 
         // Create a new access method combining the new params with the previous values
-        let access_method = match api_access_method.as_custom() {
-            None => return Err(anyhow!("Can not edit built-in access method")),
-            Some(x) => match x.clone() {
+        let access_method = match &api_access_method.access_method {
+            AccessMethod::BuiltIn(_) => return Err(anyhow!("Can not edit built-in access method")),
+            AccessMethod::Chain(proxies) => {
+                Self::edit_chain(&mut rpc, proxies.clone(), cmd.chain, cmd.params).await?
+            }
+            AccessMethod::Custom(x) => match x.clone() {
                 CustomProxy::Shadowsocks(shadowsocks) => {
                     let ip = cmd.params.ip.unwrap_or(shadowsocks.endpoint.ip());
                     let port = cmd.params.port.unwrap_or(shadowsocks.endpoint.port());
@@ -153,6 +178,9 @@ impl ApiAccess {
                         }
                     })
                 }
+                CustomProxy::HttpConnect(http) => {
+                    AccessMethod::from(cmd.params.merge_http_connect(&http)?)
+                }
             },
         };
 
@@ -166,6 +194,35 @@ impl ApiAccess {
         Ok(())
     }
 
+    /// Edit a proxy chain, either by replacing all of its proxies or by editing a single hop.
+    async fn edit_chain(
+        rpc: &mut MullvadProxyClient,
+        mut proxies: Vec<CustomProxy>,
+        chain: ChainEditParams,
+        params: ProxyEditParams,
+    ) -> Result<AccessMethod> {
+        if let Some(methods) = chain.methods {
+            return Self::get_proxy_chain(rpc, methods).await;
+        }
+        let Some(hop) = chain.hop else {
+            if !params.is_empty() {
+                return Err(anyhow!("Select which hop of the chain to edit with --hop"));
+            }
+            return Ok(AccessMethod::Chain(proxies));
+        };
+        let proxy = hop
+            .checked_sub(1)
+            .and_then(|index| proxies.get_mut(index))
+            .ok_or(anyhow!("Hop {hop} does not exist"))?;
+        match proxy {
+            CustomProxy::Shadowsocks(ss) => *ss = params.merge_shadowsocks(ss),
+            CustomProxy::Socks5Local(local) => *local = params.merge_socks_local(local),
+            CustomProxy::Socks5Remote(remote) => *remote = params.merge_socks_remote(remote)?,
+            CustomProxy::HttpConnect(http) => *http = params.merge_http_connect(http)?,
+        };
+        Ok(AccessMethod::Chain(proxies))
+    }
+
     /// Enable a custom API access method.
     async fn enable(item: SelectItem) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
@@ -248,6 +305,33 @@ impl ApiAccess {
             .cloned()
             .ok_or(anyhow!(format!("Access method {} does not exist", item)))
     }
+
+    /// Create a chain out of the custom proxies of the access methods at the (1-based)
+    /// `indices`, in order. Chained access methods are expanded into their proxies.
+    async fn get_proxy_chain(
+        rpc: &mut MullvadProxyClient,
+        indices: Vec<usize>,
+    ) -> Result<AccessMethod> {
+        let access_methods = rpc.get_api_access_methods().await?;
+        let mut proxies = vec![];
+        for index in indices {
+            let item = SelectItem { index };
+            let access_method = access_methods
+                .get(item.as_array_index()?)
+                .ok_or(anyhow!(format!("Access method {} does not exist", item)))?;
+            match &access_method.access_method {
+                AccessMethod::Custom(proxy) => proxies.push(proxy.clone()),
+                AccessMethod::Chain(chain) => proxies.extend(chain.iter().cloned()),
+                AccessMethod::BuiltIn(_) => {
+                    return Err(anyhow!(
+                        "Can not chain built-in access method \"{}\"",
+                        access_method.get_name()
+                    ))
+                }
+            }
+        }
+        Ok(AccessMethod::Chain(proxies))
+    }
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -266,6 +350,35 @@ pub enum AddCustomCommands {
         #[clap(flatten)]
         add: ShadowsocksAdd,
     },
+    /// Configure an HTTP proxy which supports the CONNECT method
+    ///
+    /// The connection to the proxy itself is not encrypted, so HTTPS proxies are not supported.
+    Http {
+        /// An easy to remember name for this custom proxy
+        name: String,
+        /// Disable the use of this custom access method. It has to be manually
+        /// enabled at a later stage to be used when accessing the Mullvad API.
+        #[arg(default_value_t = false, short, long)]
+        disabled: bool,
+        #[clap(flatten)]
+        add: HttpConnectAdd,
+    },
+    /// Chain existing custom access methods, so that each proxy is reached through the one
+    /// before it
+    ///
+    /// Only the first proxy in the chain is connected to directly, so it is the only one which
+    /// is allowed through the firewall.
+    Chain {
+        /// An easy to remember name for this custom proxy chain
+        name: String,
+        /// Disable the use of this custom access method. It has to be manually
+        /// enabled at a later stage to be used when accessing the Mullvad API.
+        #[arg(default_value_t = false, short, long)]
+        disabled: bool,
+        /// The access methods to chain, in the order that they should be connected through
+        #[arg(required = true, num_args = 2..)]
+        methods: Vec<usize>,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -298,6 +411,8 @@ impl AddCustomCommands {
     fn name(&self) -> &str {
         match self {
             AddCustomCommands::Shadowsocks { name, .. }
+            | AddCustomCommands::Http { name, .. }
+            | AddCustomCommands::Chain { name, .. }
             | AddCustomCommands::Socks5(AddSocks5Commands::Remote { name, .. })
             | AddCustomCommands::Socks5(AddSocks5Commands::Local { name, .. }) => name,
         }
@@ -306,6 +421,8 @@ impl AddCustomCommands {
     fn enabled(&self) -> bool {
         match self {
             AddCustomCommands::Shadowsocks { disabled, .. }
+            | AddCustomCommands::Http { disabled, .. }
+            | AddCustomCommands::Chain { disabled, .. }
             | AddCustomCommands::Socks5(AddSocks5Commands::Remote { disabled, .. })
             | AddCustomCommands::Socks5(AddSocks5Commands::Local { disabled, .. }) => !disabled,
         }
@@ -346,6 +463,20 @@ pub struct EditCustomCommands {
     /// Editing parameters
     #[clap(flatten)]
     params: ProxyEditParams,
+    /// Editing parameters for proxy chains
+    #[clap(flatten)]
+    chain: ChainEditParams,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ChainEditParams {
+    /// Which hop of the chain the editing parameters apply to, starting at 1 [Chain]
+    #[arg(long)]
+    hop: Option<usize>,
+    /// Replace the proxies in the chain with those of these access methods, in the order
+    /// that they should be connected through [Chain]
+    #[arg(long, num_args = 2.., conflicts_with = "hop")]
+    methods: Option<Vec<usize>>,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -363,7 +494,7 @@ pub struct EditParams {
 /// we define them in a hidden-away module.
 mod conversions {
     use super::{AddCustomCommands, AddSocks5Commands};
-    use crate::cmds::proxies::{Error, SocksAuthentication};
+    use crate::cmds::proxies::{Error, ProxyAuthentication};
     use mullvad_types::access_method as daemon_types;
     use talpid_types::net::proxy as talpid_types;
 
@@ -381,7 +512,7 @@ mod conversions {
                     )),
                     AddSocks5Commands::Remote { add, .. } => {
                         Ok(daemon_types::AccessMethod::from(match add.authentication {
-                            Some(SocksAuthentication { username, password }) => {
+                            Some(ProxyAuthentication { username, password }) => {
                                 let auth = talpid_types::SocksAuth::new(username, password)?;
                                 talpid_types::Socks5Remote::new_with_authentication(
                                     (add.remote_ip, add.remote_port),
@@ -401,6 +532,12 @@ mod conversions {
                         add.password,
                     ),
                 )),
+                AddCustomCommands::Http { add, .. } => Ok(daemon_types::AccessMethod::from(
+                    talpid_types::HttpConnect::try_from(add)?,
+                )),
+                AddCustomCommands::Chain { .. } => {
+                    unreachable!("Chains refer to existing access methods and are resolved via RPC")
+                }
             }
         }
     }
@@ -408,11 +545,13 @@ mod conversions {
 
 /// Pretty printing of [`ApiAccessMethod`]s
 mod pp {
-    use crate::cmds::proxies::pp::CustomProxyFormatter;
-    use mullvad_types::access_method::{AccessMethod, AccessMethodSetting};
+    use crate::{cmds::proxies::pp::CustomProxyFormatter, print_option};
+    use mullvad_types::access_method::{AccessMethod, AccessMethodSetting, AccessMethodStatistics};
 
     pub struct ApiAccessMethodFormatter<'a> {
         api_access_method: &'a AccessMethodSetting,
+        /// Outcomes of previous requests made using the access method, if any.
+        pub statistics: Option<&'a AccessMethodStatistics>,
         pub settings: FormatterSettings,
     }
 
@@ -434,9 +573,33 @@ mod pp {
         pub fn new(api_access_method: &'a AccessMethodSetting) -> ApiAccessMethodFormatter<'a> {
             ApiAccessMethodFormatter {
                 api_access_method,
+                statistics: None,
                 settings: Default::default(),
             }
         }
+
+        fn print_statistics(&self) {
+            let Some(statistics) = self.statistics else {
+                return;
+            };
+            if let Some(success_rate) = statistics.success_rate() {
+                print_option!(
+                    "Success rate",
+                    format!(
+                        "{:.0}% ({} of {} requests)",
+                        success_rate * 100.0,
+                        statistics.successes,
+                        u64::from(statistics.successes) + u64::from(statistics.failures)
+                    )
+                );
+            }
+            if let Some(latency) = statistics.latency {
+                print_option!("Latency", format!("{} ms", latency.as_millis()));
+            }
+            if let Some(last_success) = statistics.last_success {
+                print_option!("Last success", last_success.with_timezone(&chrono::Local));
+            }
+        }
     }
 
     impl std::fmt::Display for ApiAccessMethodFormatter<'_> {
@@ -455,6 +618,10 @@ mod pp {
                     if self.settings.write_enabled {
                         write_status(f, self.api_access_method.enabled())?;
                     }
+                    if self.statistics.is_some() {
+                        writeln!(f)?;
+                        self.print_statistics();
+                    }
                     Ok(())
                 }
                 AccessMethod::Custom(method) => {
@@ -463,12 +630,29 @@ mod pp {
                         write_status(f, self.api_access_method.enabled())?;
                     }
                     writeln!(f)?;
+                    self.print_statistics();
                     let formatter = CustomProxyFormatter {
                         custom_proxy: method,
                     };
                     write!(f, "{}", formatter)?;
                     Ok(())
                 }
+                AccessMethod::Chain(proxies) => {
+                    write!(f, "{}", self.api_access_method.get_name())?;
+                    if self.settings.write_enabled {
+                        write_status(f, self.api_access_method.enabled())?;
+                    }
+                    writeln!(f)?;
+                    self.print_statistics();
+                    for (hop, proxy) in proxies.iter().enumerate() {
+                        print_option!("Hop", hop + 1);
+                        let formatter = CustomProxyFormatter {
+                            custom_proxy: proxy,
+                        };
+                        write!(f, "{}", formatter)?;
+                    }
+                    Ok(())
+                }
             }
         }
     }
@@ -305,6 +305,7 @@ impl Bridge {
             CustomProxy::Shadowsocks(ss) => *ss = edit.merge_shadowsocks(ss),
             CustomProxy::Socks5Local(local) => *local = edit.merge_socks_local(local),
             CustomProxy::Socks5Remote(remote) => *remote = edit.merge_socks_remote(remote)?,
+            CustomProxy::HttpConnect(http) => *http = edit.merge_http_connect(http)?,
         };
 
         rpc.set_bridge_settings(settings.bridge_settings)
@@ -1,8 +1,16 @@
 use anyhow::Result;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use anyhow::{anyhow, Context};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use mullvad_types::settings::DnsBlocklistOptions;
 use mullvad_types::settings::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
 use std::net::IpAddr;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::path::PathBuf;
+#[cfg(target_os = "linux")]
+use talpid_types::net::dns::DnsManager;
 
 #[derive(Subcommand, Debug)]
 pub enum Dns {
@@ -14,6 +22,38 @@ pub enum Dns {
         #[clap(subcommand)]
         cmd: DnsSet,
     },
+
+    /// Manage files listing domains to block, in hosts format or with one domain per line.
+    /// Subdomains of listed domains are also blocked. Files are reloaded when they change.
+    /// On Linux, lists are only enforced when the DNS manager is "local-resolver".
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[clap(subcommand)]
+    Blocklist(DomainList),
+
+    /// Manage files listing domains that are never blocked, even if they appear in a blocklist
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[clap(subcommand)]
+    Allowlist(DomainList),
+
+    /// Set the system component used to configure DNS
+    #[cfg(target_os = "linux")]
+    Manager {
+        /// One of "auto", "systemd-resolved", "network-manager", "resolvconf", "static-file" or
+        /// "local-resolver".
+        /// "auto" uses the first one that is detected to be in use.
+        manager: DnsManager,
+    },
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[derive(Subcommand, Debug, Clone)]
+pub enum DomainList {
+    /// Add a file
+    Add { path: PathBuf },
+    /// Remove a file
+    Remove { path: PathBuf },
+    /// Remove all files
+    Clear,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -82,6 +122,16 @@ impl Dns {
             Dns::Set {
                 cmd: DnsSet::Custom { servers },
             } => Self::set_custom(servers).await,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            Dns::Blocklist(cmd) => {
+                Self::update_domain_lists(cmd, |options| &mut options.blocklists).await
+            }
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            Dns::Allowlist(cmd) => {
+                Self::update_domain_lists(cmd, |options| &mut options.allowlists).await
+            }
+            #[cfg(target_os = "linux")]
+            Dns::Manager { manager } => Self::set_manager(manager).await,
         }
     }
 
@@ -113,6 +163,105 @@ impl Dns {
             }
         }
 
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        Self::print_domain_lists(&mut rpc).await?;
+
+        #[cfg(target_os = "linux")]
+        Self::print_manager(&mut rpc, options.manager).await;
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn print_manager(rpc: &mut MullvadProxyClient, preferred: DnsManager) {
+        match rpc.get_active_dns_manager().await {
+            Ok(active) if preferred == DnsManager::Auto => {
+                println!("DNS manager: {preferred} (using {active})")
+            }
+            Ok(active) => println!("DNS manager: {active}"),
+            Err(_) => println!("DNS manager: {preferred} (not in use)"),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn set_manager(manager: DnsManager) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        rpc.set_dns_manager(manager).await?;
+        println!("Updated DNS manager");
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    async fn print_domain_lists(rpc: &mut MullvadProxyClient) -> Result<()> {
+        let stats = rpc.get_dns_blocklist_stats().await?;
+        if stats.lists.is_empty() {
+            return Ok(());
+        }
+
+        println!(
+            "Domain lists (blocked queries: {}, allowed queries: {}):",
+            stats.blocked_queries(),
+            stats.allowed_queries()
+        );
+        for list in &stats.lists {
+            print!(
+                "{:<11}{} ({} entries, {} hits)",
+                list.kind,
+                list.path.display(),
+                list.entries,
+                list.hits
+            );
+            match &list.error {
+                Some(error) => println!(" - error: {error}"),
+                None => println!(),
+            }
+        }
+        #[cfg(target_os = "linux")]
+        if !matches!(
+            rpc.get_active_dns_manager().await,
+            Ok(DnsManager::LocalResolver)
+        ) {
+            println!("Domain lists are only enforced when the DNS manager is local-resolver");
+        }
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    async fn update_domain_lists(
+        cmd: DomainList,
+        select_lists: impl FnOnce(&mut DnsBlocklistOptions) -> &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        let mut rpc = MullvadProxyClient::new().await?;
+        let mut options = rpc
+            .get_settings()
+            .await?
+            .tunnel_options
+            .dns_options
+            .blocklists;
+        let lists = select_lists(&mut options);
+
+        match cmd {
+            DomainList::Add { path } => {
+                let path = std::fs::canonicalize(&path)
+                    .with_context(|| format!("Cannot read {}", path.display()))?;
+                if lists.contains(&path) {
+                    return Err(anyhow!("{} has already been added", path.display()));
+                }
+                lists.push(path);
+            }
+            DomainList::Remove { path } => {
+                let path = std::fs::canonicalize(&path).unwrap_or(path);
+                let len_before = lists.len();
+                lists.retain(|list| list != &path);
+                if lists.len() == len_before {
+                    return Err(anyhow!("{} has not been added", path.display()));
+                }
+            }
+            DomainList::Clear => lists.clear(),
+        }
+
+        rpc.set_dns_blocklists(options).await?;
+        println!("Updated DNS settings");
         Ok(())
     }
 
@@ -3,8 +3,13 @@ use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::Constraint,
-    relay_constraints::{ObfuscationSettings, SelectedObfuscation, Udp2TcpObfuscationSettings},
+    relay_constraints::{
+        CustomObfuscationEndpoint, CustomObfuscationProtocol, ObfuscationSettings,
+        SelectedObfuscation, ShadowsocksObfuscationSettings, Udp2TcpObfuscationSettings,
+    },
 };
+use std::net::{IpAddr, SocketAddr};
+use talpid_types::net::proxy::SHADOWSOCKS_CIPHERS;
 
 #[derive(Subcommand, Debug)]
 pub enum Obfuscation {
@@ -28,6 +33,74 @@ pub enum SetCommands {
         #[arg(long, short = 'p')]
         port: Constraint<u16>,
     },
+
+    /// Specifies the config for the Shadowsocks obfuscator.
+    Shadowsocks {
+        /// Port to use, or 'any'
+        #[arg(long, short = 'p')]
+        port: Constraint<u16>,
+    },
+
+    /// Specifies an obfuscation server of your own, which is used instead of the relay
+    /// when the obfuscation mode is 'custom'.
+    #[clap(subcommand)]
+    Custom(CustomCommands),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CustomCommands {
+    /// Use a udp2tcp server. It must be set up to forward the traffic to the WireGuard
+    /// endpoint of the given relay, which is then always connected to.
+    Udp2tcp {
+        /// The IP of the udp2tcp server
+        remote_ip: IpAddr,
+        /// Port on which the udp2tcp server listens for traffic
+        remote_port: u16,
+        /// Hostname of the relay that the udp2tcp server forwards the traffic to
+        #[arg(long)]
+        relay: String,
+    },
+
+    /// Use a Shadowsocks server. It forwards the traffic to the relay that you connect to.
+    Shadowsocks {
+        /// The IP of the Shadowsocks server
+        remote_ip: IpAddr,
+        /// Port on which the Shadowsocks server listens for traffic
+        remote_port: u16,
+        /// Password for authentication
+        password: String,
+        /// Cipher to use
+        #[arg(long, value_parser = SHADOWSOCKS_CIPHERS)]
+        cipher: String,
+    },
+
+    /// Remove the custom obfuscation server
+    Unset,
+}
+
+impl CustomCommands {
+    fn into_endpoint(self) -> Option<CustomObfuscationEndpoint> {
+        match self {
+            CustomCommands::Udp2tcp {
+                remote_ip,
+                remote_port,
+                relay,
+            } => Some(CustomObfuscationEndpoint {
+                address: SocketAddr::new(remote_ip, remote_port),
+                protocol: CustomObfuscationProtocol::Udp2Tcp { relay },
+            }),
+            CustomCommands::Shadowsocks {
+                remote_ip,
+                remote_port,
+                password,
+                cipher,
+            } => Some(CustomObfuscationEndpoint {
+                address: SocketAddr::new(remote_ip, remote_port),
+                protocol: CustomObfuscationProtocol::Shadowsocks { password, cipher },
+            }),
+            CustomCommands::Unset => None,
+        }
+    }
 }
 
 impl Obfuscation {
@@ -41,6 +114,11 @@ impl Obfuscation {
                     obfuscation_settings.selected_obfuscation
                 );
                 println!("udp2tcp settings: {}", obfuscation_settings.udp2tcp);
+                println!("Shadowsocks settings: {}", obfuscation_settings.shadowsocks);
+                match &obfuscation_settings.custom {
+                    Some(custom) => println!("Custom server: {custom}"),
+                    None => println!("Custom server: none"),
+                }
                 Ok(())
             }
             Obfuscation::Set(subcmd) => Self::set(subcmd).await,
@@ -66,6 +144,20 @@ impl Obfuscation {
                 })
                 .await?;
             }
+            SetCommands::Shadowsocks { port } => {
+                rpc.set_obfuscation_settings(ObfuscationSettings {
+                    shadowsocks: ShadowsocksObfuscationSettings { port },
+                    ..current_settings
+                })
+                .await?;
+            }
+            SetCommands::Custom(custom) => {
+                rpc.set_obfuscation_settings(ObfuscationSettings {
+                    custom: custom.into_endpoint(),
+                    ..current_settings
+                })
+                .await?;
+            }
         }
 
         println!("Updated obfuscation settings");
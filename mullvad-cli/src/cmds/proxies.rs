@@ -1,7 +1,10 @@
 use clap::Args;
 use std::net::{IpAddr, SocketAddr};
 use talpid_types::net::{
-    proxy::{Shadowsocks, Socks5Local, Socks5Remote, SocksAuth, SHADOWSOCKS_CIPHERS},
+    proxy::{
+        HttpAuth, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote, SocksAuth,
+        SHADOWSOCKS_CIPHERS,
+    },
     Endpoint, TransportProtocol,
 };
 
@@ -50,7 +53,7 @@ pub struct Socks5RemoteAdd {
     pub remote_port: u16,
 
     #[clap(flatten)]
-    pub authentication: Option<SocksAuthentication>,
+    pub authentication: Option<ProxyAuthentication>,
 }
 
 impl TryFrom<Socks5RemoteAdd> for Socks5Remote {
@@ -66,6 +69,30 @@ impl TryFrom<Socks5RemoteAdd> for Socks5Remote {
     }
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct HttpConnectAdd {
+    /// The IP of the HTTP proxy server
+    pub remote_ip: IpAddr,
+    /// The port of the HTTP proxy server
+    pub remote_port: u16,
+
+    #[clap(flatten)]
+    pub authentication: Option<ProxyAuthentication>,
+}
+
+impl TryFrom<HttpConnectAdd> for HttpConnect {
+    type Error = Error;
+    fn try_from(add: HttpConnectAdd) -> Result<Self, Self::Error> {
+        Ok(Self {
+            endpoint: SocketAddr::new(add.remote_ip, add.remote_port),
+            auth: add
+                .authentication
+                .map(|auth| HttpAuth::new(auth.username, auth.password))
+                .transpose()?,
+        })
+    }
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct ShadowsocksAdd {
     /// The IP of the remote Shadowsocks-proxy
@@ -91,30 +118,30 @@ impl From<ShadowsocksAdd> for Shadowsocks {
 
 #[derive(Args, Debug, Clone)]
 #[group(requires_all = ["username", "password"])] // https://github.com/clap-rs/clap/issues/5092
-pub struct SocksAuthentication {
-    /// Username for authentication against a remote SOCKS5 proxy
+pub struct ProxyAuthentication {
+    /// Username for authentication against a remote SOCKS5 or HTTP proxy
     #[arg(short, long, required = false)]
     pub username: String,
-    /// Password for authentication against a remote SOCKS5 proxy
+    /// Password for authentication against a remote SOCKS5 or HTTP proxy
     #[arg(short, long, required = false)]
     pub password: String,
 }
 
 #[derive(Args, Debug, Clone)]
 pub struct ProxyEditParams {
-    /// Username for authentication [Socks5 (Remote proxy)]
+    /// Username for authentication [Socks5 (Remote proxy), HTTP]
     #[arg(long)]
     pub username: Option<String>,
-    /// Password for authentication [Socks5 (Remote proxy), Shadowsocks]
+    /// Password for authentication [Socks5 (Remote proxy), Shadowsocks, HTTP]
     #[arg(long)]
     pub password: Option<String>,
     /// Cipher to use [Shadowsocks]
     #[arg(value_parser = SHADOWSOCKS_CIPHERS, long)]
     pub cipher: Option<String>,
-    /// The IP of the remote proxy server [Socks5 (Local & Remote proxy), Shadowsocks]
+    /// The IP of the remote proxy server [Socks5 (Local & Remote proxy), Shadowsocks, HTTP]
     #[arg(long)]
     pub ip: Option<IpAddr>,
-    /// The port of the remote proxy server [Socks5 (Local & Remote proxy), Shadowsocks]
+    /// The port of the remote proxy server [Socks5 (Local & Remote proxy), Shadowsocks, HTTP]
     #[arg(long)]
     pub port: Option<u16>,
     /// The port that the server on localhost is listening on [Socks5 (Local proxy)]
@@ -126,6 +153,17 @@ pub struct ProxyEditParams {
 }
 
 impl ProxyEditParams {
+    /// Returns whether no parameters are set.
+    pub fn is_empty(&self) -> bool {
+        self.username.is_none()
+            && self.password.is_none()
+            && self.cipher.is_none()
+            && self.ip.is_none()
+            && self.port.is_none()
+            && self.local_port.is_none()
+            && self.transport_protocol.is_none()
+    }
+
     pub fn merge_socks_local(self, local: &Socks5Local) -> Socks5Local {
         let remote_ip = self.ip.unwrap_or(local.remote_endpoint.address.ip());
         let remote_port = self.port.unwrap_or(local.remote_endpoint.address.port());
@@ -165,6 +203,31 @@ impl ProxyEditParams {
         Ok(config)
     }
 
+    pub fn merge_http_connect(self, http: &HttpConnect) -> Result<HttpConnect, Error> {
+        let ip = self.ip.unwrap_or(http.endpoint.ip());
+        let port = self.port.unwrap_or(http.endpoint.port());
+        let config = match &http.auth {
+            None => match (self.username, self.password) {
+                (Some(username), Some(password)) => {
+                    let auth = HttpAuth::new(username, password)?;
+                    HttpConnect::new_with_authentication((ip, port), auth)
+                }
+                (None, None) => HttpConnect::new((ip, port)),
+                _ => {
+                    println!("HTTP proxy does not have a username and password set already, so you must provide both or neither when you edit.");
+                    HttpConnect::new((ip, port))
+                }
+            },
+            Some(credentials) => {
+                let username = self.username.unwrap_or(credentials.username().to_string());
+                let password = self.password.unwrap_or(credentials.password().to_string());
+                let auth = HttpAuth::new(username, password)?;
+                HttpConnect::new_with_authentication((ip, port), auth)
+            }
+        };
+        Ok(config)
+    }
+
     pub fn merge_shadowsocks(self, shadowsocks: &Shadowsocks) -> Shadowsocks {
         let ip = self.ip.unwrap_or(shadowsocks.endpoint.ip());
         let port = self.port.unwrap_or(shadowsocks.endpoint.port());
@@ -203,6 +266,15 @@ pub mod pp {
                     }
                     Ok(())
                 }
+                CustomProxy::HttpConnect(http) => {
+                    print_option!("Protocol", "HTTP");
+                    print_option!("Peer", http.endpoint);
+                    if let Some(credentials) = &http.auth {
+                        print_option!("Username", credentials.username());
+                        print_option!("Password", credentials.password());
+                    }
+                    Ok(())
+                }
                 CustomProxy::Socks5Local(local) => {
                     print_option!("Protocol", "Socks5 (local)");
                     print_option!(
@@ -11,12 +11,13 @@ use mullvad_types::{
         RelaySettings, TransportPort, WireguardConstraints,
     },
     relay_list::{RelayEndpointData, RelayListCountry},
-    ConnectionConfig, CustomTunnelEndpoint,
+    wg_quick, ConnectionConfig, CustomTunnelEndpoint,
 };
 use std::{
     collections::HashMap,
     io::BufRead,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
 };
 use talpid_types::net::{
     all_of_the_internet, openvpn, wireguard, Endpoint, IpVersion, TransportProtocol, TunnelType,
@@ -212,6 +213,28 @@ pub enum SetCustomCommands {
         #[arg(long)]
         v6_gateway: Option<Ipv6Addr>,
     },
+
+    /// Use a custom OpenVPN relay from an .ovpn configuration file. Certificates and keys must be
+    /// inlined. Directives that run commands, such as up and script-security, are not supported.
+    #[clap(arg_required_else_help = true)]
+    Ovpn {
+        /// Path to the configuration file
+        path: PathBuf,
+        /// Username for authentication, if the configuration contains auth-user-pass
+        #[arg(long, requires = "password")]
+        username: Option<String>,
+        /// Password for authentication, if the configuration contains auth-user-pass
+        #[arg(long, requires = "username")]
+        password: Option<String>,
+    },
+
+    /// Use a custom WireGuard relay from a wg-quick configuration file. Directives that only
+    /// affect how wg-quick sets up the interface, such as PostUp, are not supported.
+    #[clap(arg_required_else_help = true)]
+    WgQuick {
+        /// Path to the configuration file
+        path: PathBuf,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -449,6 +472,7 @@ impl Relay {
     }
 
     async fn set_custom(subcmd: SetCustomCommands) -> Result<()> {
+        let mut dns_servers = vec![];
         let custom_endpoint = match subcmd {
             SetCustomCommands::Openvpn {
                 host,
@@ -477,11 +501,29 @@ impl Relay {
                 )
                 .await?
             }
+            SetCustomCommands::Ovpn {
+                path,
+                username,
+                password,
+            } => Self::read_custom_ovpn_relay(path, username, password)?,
+            SetCustomCommands::WgQuick { path } => {
+                let config = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let config = wg_quick::parse(&config)
+                    .with_context(|| format!("Failed to import {}", path.display()))?;
+                dns_servers = config.dns_servers;
+                config.endpoint
+            }
         };
         let mut rpc = MullvadProxyClient::new().await?;
         rpc.set_relay_settings(RelaySettings::CustomTunnelEndpoint(custom_endpoint))
             .await?;
         println!("Relay constraints updated");
+        if !dns_servers.is_empty() {
+            let servers = dns_servers.iter().join(" ");
+            println!("The configuration uses the DNS servers {servers}. To use them, run:");
+            println!("  mullvad dns set custom {servers}");
+        }
         Ok(())
     }
 
@@ -501,8 +543,39 @@ impl Relay {
                 ),
                 username,
                 password,
+                custom_server: None,
             }),
+            wireguard_options: Default::default(),
+        }
+    }
+
+    fn read_custom_ovpn_relay(
+        path: PathBuf,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> Result<CustomTunnelEndpoint> {
+        let config = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut endpoint = CustomTunnelEndpoint::from_ovpn(&config)
+            .with_context(|| format!("Failed to import {}", path.display()))?;
+
+        if let ConnectionConfig::OpenVpn(config) = &mut endpoint.config {
+            let auth_user_pass = config
+                .custom_server
+                .as_ref()
+                .is_some_and(|server| server.auth_user_pass);
+            match (username, password) {
+                (Some(username), Some(password)) => {
+                    config.username = username;
+                    config.password = password;
+                }
+                _ if auth_user_pass => {
+                    bail!("The server requires --username and --password")
+                }
+                _ => (),
+            }
         }
+        Ok(endpoint)
     }
 
     async fn read_custom_wireguard_relay(
@@ -550,6 +623,7 @@ impl Relay {
                 #[cfg(target_os = "linux")]
                 fwmark: None,
             }),
+            wireguard_options: Default::default(),
         })
     }
 
@@ -92,6 +92,18 @@ impl Status {
                         println!("New access method: {access_method:#?}");
                     }
                 }
+                DaemonEvent::DnsConfigurationDrift(drift) => {
+                    if args.debug {
+                        println!("DNS configuration drift: {drift:#?}");
+                    } else {
+                        format::print_dns_configuration_drift(&drift);
+                    }
+                }
+                DaemonEvent::PskRenegotiationFailed => {
+                    println!(
+                        "Failed to renegotiate the quantum-resistant PSK. Keeping the current one"
+                    );
+                }
             }
         }
         Ok(())
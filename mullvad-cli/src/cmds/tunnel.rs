@@ -1,12 +1,17 @@
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 use clap::Subcommand;
 use mullvad_management_interface::MullvadProxyClient;
 use mullvad_types::{
     constraints::Constraint,
+    relay_constraints::LocationConstraint,
+    relay_list::RelayEndpointData,
     wireguard::{QuantumResistantState, RotationInterval, DEFAULT_ROTATION_INTERVAL},
 };
+use std::{net::Ipv4Addr, path::PathBuf, time::Duration};
+use talpid_types::net::wireguard::ConnectivityCheckOptions;
 
-use super::BooleanOption;
+use super::{receive_confirmation, BooleanOption};
+use super::{relay::resolve_location_constraint, relay_constraints::LocationArgs};
 use crate::print_option;
 
 #[derive(Subcommand, Debug)]
@@ -17,6 +22,34 @@ pub enum Tunnel {
     /// Set tunnel options
     #[clap(subcommand)]
     Set(TunnelOptions),
+
+    /// Manage WireGuard tunnels
+    #[clap(subcommand)]
+    Wireguard(WireguardCommand),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum WireguardCommand {
+    /// Export WireGuard relays and the device key as wg-quick configuration files. Multihop
+    /// results in one file for the entry relay and one for the exit relay.
+    Export {
+        /// Location or hostname of the relay to export, which is otherwise selected according
+        /// to the current relay settings. The last used relays are exported if omitted.
+        #[clap(flatten)]
+        location: Option<LocationArgs>,
+
+        /// Include preshared keys, if any are used
+        #[arg(long)]
+        include_psk: bool,
+
+        /// Write each configuration to `<relay>.conf` in this directory, instead of to stdout
+        #[arg(long, short = 'o')]
+        output_dir: Option<PathBuf>,
+
+        /// Export the private key without asking for confirmation
+        #[arg(long, short = 'y', default_value_t = false)]
+        confirm: bool,
+    },
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -41,6 +74,15 @@ pub enum TunnelOptions {
         /// The key rotation interval. Number of hours, or 'any'
         #[arg(long)]
         rotation_interval: Option<Constraint<RotationInterval>>,
+        /// Interval in seconds between keepalive packets sent to the relay, or 0 to disable
+        #[arg(long)]
+        keepalive: Option<u16>,
+        /// Interval in hours between negotiating new quantum-resistant PSKs while connected, or 0
+        /// to disable
+        #[arg(long)]
+        psk_rekey_interval: Option<u16>,
+        #[clap(flatten)]
+        connectivity_check: ConnectivityCheckArgs,
         /// Rotate WireGuard key
         #[clap(subcommand)]
         rotate_key: Option<RotateKey>,
@@ -51,6 +93,59 @@ pub enum TunnelOptions {
     Ipv6 { state: BooleanOption },
 }
 
+/// Advanced options for detecting a broken WireGuard tunnel
+#[derive(clap::Args, Debug, Clone)]
+pub struct ConnectivityCheckArgs {
+    /// Seconds to wait for incoming traffic after sending traffic before starting to ping, or
+    /// 'any' for the default
+    #[arg(long)]
+    rx_timeout: Option<Constraint<u16>>,
+    /// Seconds to wait for a response after the first ping before reconnecting, or 'any' for the
+    /// default
+    #[arg(long)]
+    ping_timeout: Option<Constraint<u16>>,
+    /// Seconds to wait for traffic when first establishing the tunnel, or 'any' for the default
+    #[arg(long)]
+    establish_timeout: Option<Constraint<u16>>,
+    /// IPv4 address inside the tunnel to ping, or 'any' to ping the relay gateway
+    #[arg(long)]
+    ping_target: Option<Constraint<Ipv4Addr>>,
+}
+
+impl ConnectivityCheckArgs {
+    fn is_empty(&self) -> bool {
+        self.rx_timeout.is_none()
+            && self.ping_timeout.is_none()
+            && self.establish_timeout.is_none()
+            && self.ping_target.is_none()
+    }
+
+    /// Updates `options` with the arguments that were given
+    fn apply(self, options: &mut ConnectivityCheckOptions) -> Result<()> {
+        let to_timeout = |secs: Constraint<u16>| -> Result<Option<Duration>> {
+            match secs {
+                Constraint::Only(0) => Err(anyhow!("Timeouts must be greater than zero")),
+                secs => Ok(secs
+                    .option()
+                    .map(|secs| Duration::from_secs(u64::from(secs)))),
+            }
+        };
+        if let Some(timeout) = self.rx_timeout {
+            options.rx_timeout = to_timeout(timeout)?;
+        }
+        if let Some(timeout) = self.ping_timeout {
+            options.ping_timeout = to_timeout(timeout)?;
+        }
+        if let Some(timeout) = self.establish_timeout {
+            options.establish_timeout = to_timeout(timeout)?;
+        }
+        if let Some(target) = self.ping_target {
+            options.ping_target = target.option();
+        }
+        Ok(())
+    }
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum RotateKey {
     /// Replace the WireGuard key with a new one
@@ -62,7 +157,63 @@ impl Tunnel {
         match self {
             Tunnel::Get => Self::get().await,
             Tunnel::Set(options) => Self::set(options).await,
+            Tunnel::Wireguard(WireguardCommand::Export {
+                location,
+                include_psk,
+                output_dir,
+                confirm,
+            }) => Self::export_wireguard(location, include_psk, output_dir, confirm).await,
+        }
+    }
+
+    async fn export_wireguard(
+        location: Option<LocationArgs>,
+        include_psk: bool,
+        output_dir: Option<PathBuf>,
+        confirm: bool,
+    ) -> Result<()> {
+        if !confirm
+            && !receive_confirmation(
+                "The exported configuration contains the private key of this device. Anyone who has it can use your account. Continue?",
+                false,
+            )
+            .await
+        {
+            return Ok(());
+        }
+
+        let mut rpc = MullvadProxyClient::new().await?;
+        let location = match location {
+            Some(location) => Some(
+                resolve_location_constraint(&mut rpc, location, |relay| {
+                    relay.active && matches!(relay.endpoint_data, RelayEndpointData::Wireguard(_))
+                })
+                .await?
+                .map(LocationConstraint::from),
+            ),
+            None => None,
+        };
+        let configs = rpc
+            .export_wireguard_config(true, include_psk, location)
+            .await?;
+
+        match output_dir {
+            Some(dir) => {
+                for config in configs {
+                    let path = dir.join(format!("{}.conf", config.name));
+                    write_private_file(&path, &config.contents)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    println!("Wrote {}", path.display());
+                }
+            }
+            None => {
+                for config in configs {
+                    println!("# {}.conf", config.name);
+                    println!("{}", config.contents);
+                }
+            }
         }
+        Ok(())
     }
 
     async fn get() -> Result<()> {
@@ -94,6 +245,44 @@ impl Tunnel {
             "Quantum resistance",
             tunnel_options.wireguard.quantum_resistant,
         );
+        print_option!(
+            "Persistent keepalive",
+            tunnel_options
+                .wireguard
+                .persistent_keepalive
+                .map(|interval| format!("{interval} s"))
+                .unwrap_or("off".to_string()),
+        );
+        print_option!(
+            "PSK rekey interval",
+            tunnel_options
+                .wireguard
+                .psk_rekey_interval
+                .map(|interval| format!("{} h", interval.as_duration().as_secs() / 60 / 60))
+                .unwrap_or("off".to_string()),
+        );
+        let connectivity_check = &tunnel_options.wireguard.connectivity_check;
+        let format_timeout = |timeout: Option<Duration>| {
+            timeout
+                .map(|timeout| format!("{} s", timeout.as_secs()))
+                .unwrap_or("default".to_string())
+        };
+        print_option!("RX timeout", format_timeout(connectivity_check.rx_timeout));
+        print_option!(
+            "Ping timeout",
+            format_timeout(connectivity_check.ping_timeout)
+        );
+        print_option!(
+            "Establish timeout",
+            format_timeout(connectivity_check.establish_timeout)
+        );
+        print_option!(
+            "Ping target",
+            connectivity_check
+                .ping_target
+                .map(|addr| addr.to_string())
+                .unwrap_or("gateway".to_string()),
+        );
 
         let key = rpc.get_wireguard_key().await?;
         print_option!("Public key", key.key,);
@@ -130,9 +319,21 @@ impl Tunnel {
                 mtu,
                 quantum_resistant,
                 rotation_interval,
+                keepalive,
+                psk_rekey_interval,
+                connectivity_check,
                 rotate_key,
             } => {
-                Self::handle_wireguard(mtu, quantum_resistant, rotation_interval, rotate_key).await
+                Self::handle_wireguard(
+                    mtu,
+                    quantum_resistant,
+                    rotation_interval,
+                    keepalive,
+                    psk_rekey_interval,
+                    connectivity_check,
+                    rotate_key,
+                )
+                .await
             }
             TunnelOptions::Ipv6 { state } => Self::handle_ipv6(state).await,
         }
@@ -160,6 +361,9 @@ impl Tunnel {
         mtu: Option<Constraint<u16>>,
         quantum_resistant: Option<QuantumResistantState>,
         rotation_interval: Option<Constraint<RotationInterval>>,
+        keepalive: Option<u16>,
+        psk_rekey_interval: Option<u16>,
+        connectivity_check: ConnectivityCheckArgs,
         rotate_key: Option<RotateKey>,
     ) -> Result<()> {
         let mut rpc = MullvadProxyClient::new().await?;
@@ -190,6 +394,35 @@ impl Tunnel {
             }
         }
 
+        if let Some(keepalive) = keepalive {
+            rpc.set_wireguard_persistent_keepalive(
+                Some(keepalive).filter(|&interval| interval != 0),
+            )
+            .await?;
+            println!("Persistent keepalive setting has been updated");
+        }
+
+        if let Some(hours) = psk_rekey_interval {
+            rpc.set_wireguard_psk_rekey_interval(
+                Some(Duration::from_secs(u64::from(hours) * 60 * 60))
+                    .filter(|interval| !interval.is_zero()),
+            )
+            .await?;
+            println!("PSK rekey interval has been updated");
+        }
+
+        if !connectivity_check.is_empty() {
+            let mut options = rpc
+                .get_settings()
+                .await?
+                .tunnel_options
+                .wireguard
+                .connectivity_check;
+            connectivity_check.apply(&mut options)?;
+            rpc.set_wireguard_connectivity_check(&options).await?;
+            println!("Connectivity check settings have been updated");
+        }
+
         if matches!(rotate_key, Some(RotateKey::RotateKey)) {
             rpc.rotate_wireguard_key().await?;
             println!("Rotated WireGuard key");
@@ -198,3 +431,14 @@ impl Tunnel {
         Ok(())
     }
 }
+
+/// Creates a file at `path` that is only readable by the current user.
+fn write_private_file(path: &std::path::Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
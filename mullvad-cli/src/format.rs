@@ -1,6 +1,6 @@
 use mullvad_types::{auth_failed::AuthFailed, location::GeoIpLocation, states::TunnelState};
 use talpid_types::{
-    net::{Endpoint, TunnelEndpoint},
+    net::{dns::DnsConfigurationDrift, openvpn::SessionInfo, Endpoint, TunnelEndpoint},
     tunnel::ErrorState,
 };
 
@@ -28,6 +28,9 @@ pub fn print_state(state: &TunnelState, verbose: bool) {
                 if let Some(tunnel_interface) = &endpoint.tunnel_interface {
                     println!("Tunnel interface: {tunnel_interface}")
                 }
+                if let Some(session) = &endpoint.openvpn_session {
+                    print_openvpn_session(session);
+                }
             }
         }
         Connecting { endpoint, location } => {
@@ -36,6 +39,11 @@ pub fn print_state(state: &TunnelState, verbose: bool) {
                 "Connecting to {}{ellipsis}",
                 format_relay_connection(endpoint, location.as_ref(), verbose)
             );
+            if verbose {
+                if let Some(reason) = &endpoint.reconnect_reason {
+                    println!("Reconnecting because: {reason}");
+                }
+            }
         }
         Disconnected {
             location: _,
@@ -51,6 +59,21 @@ pub fn print_state(state: &TunnelState, verbose: bool) {
     }
 }
 
+fn print_openvpn_session(session: &SessionInfo) {
+    if let Some(cipher) = &session.cipher {
+        println!("Data channel cipher: {cipher}");
+    }
+    if let Some(server_certificate) = &session.server_certificate {
+        println!("Server certificate: {server_certificate}");
+    }
+    if !session.pushed_options.is_empty() {
+        println!("Pushed options:");
+        for option in &session.pushed_options {
+            print_option!(option);
+        }
+    }
+}
+
 pub fn print_location(state: &TunnelState) {
     let location = match state {
         TunnelState::Disconnected {
@@ -75,6 +98,28 @@ pub fn print_location(state: &TunnelState) {
     }
 }
 
+pub fn print_dns_configuration_drift(drift: &DnsConfigurationDrift) {
+    let format_servers = |servers: &[std::net::IpAddr]| {
+        servers
+            .iter()
+            .map(|server| server.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    println!(
+        "DNS settings managed by {} were changed by another program",
+        drift.manager
+    );
+    print_option!("Expected servers", format_servers(&drift.expected_servers));
+    print_option!("Found servers", format_servers(&drift.actual_servers));
+    if drift.restored {
+        print_option!("The expected DNS settings have been restored");
+    } else {
+        print_option!("Failed to restore the expected DNS settings");
+    }
+}
+
 fn format_relay_connection(
     endpoint: &TunnelEndpoint,
     location: Option<&GeoIpLocation>,
@@ -12,18 +12,38 @@ use futures::{
 };
 use mullvad_api::{
     availability::ApiAvailabilityHandle,
-    proxy::{ApiConnectionMode, ConnectionModeProvider, ProxyConfig},
+    proxy::{
+        ApiConnectionMode, ConnectionModeProvider, ProxyChain, ProxyChainError, ProxyConfig,
+        RequestOutcome,
+    },
     AddressCache,
 };
 use mullvad_relay_selector::RelaySelector;
 use mullvad_types::access_method::{
-    AccessMethod, AccessMethodSetting, BuiltInAccessMethod, Id, Settings,
+    AccessMethod, AccessMethodSetting, AccessMethodStatistics, BuiltInAccessMethod, Id, Settings,
+};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
 };
-use std::{net::SocketAddr, path::PathBuf};
 use talpid_core::mpsc::Sender;
-use talpid_types::net::{
-    AllowedClients, AllowedEndpoint, Connectivity, Endpoint, TransportProtocol,
+use talpid_types::{
+    net::{AllowedClients, AllowedEndpoint, Connectivity, Endpoint, TransportProtocol},
+    ErrorExt,
 };
+use tokio::{fs, io::AsyncWriteExt, sync::watch};
+
+/// File in the cache directory where [`AccessMethodStatistics`] are stored.
+const STATISTICS_FILENAME: &str = "api-access-statistics.json";
+/// Statistics change after every API request, so changes are collected for this long before
+/// they are written to disk.
+const STATISTICS_SAVE_DELAY: Duration = Duration::from_secs(5);
+
+pub type Statistics = HashMap<Id, AccessMethodStatistics>;
 
 pub enum Message {
     Get(ResponseTx<ResolvedConnectionMode>),
@@ -31,6 +51,11 @@ pub enum Message {
     Rotate(ResponseTx<ApiConnectionMode>),
     Update(ResponseTx<()>, Settings),
     Resolve(ResponseTx<ResolvedConnectionMode>, AccessMethodSetting),
+    Report(Id, RequestOutcome),
+    GetStatistics(ResponseTx<Statistics>),
+    ResetStatistics(ResponseTx<()>),
+    /// The preferred API address has changed.
+    PreferredAddressChanged,
 }
 
 /// Calling [`AccessMethodEvent::send`] will cause a
@@ -107,6 +132,8 @@ pub enum Error {
     OneshotSendFailed,
     #[error("AccessModeSelector is not responding.")]
     NotRunning(#[from] oneshot::Canceled),
+    #[error("Invalid access method")]
+    InvalidAccessMethod(#[from] ProxyChainError),
 }
 
 impl std::fmt::Display for Message {
@@ -117,6 +144,10 @@ impl std::fmt::Display for Message {
             Message::Rotate(_) => f.write_str("Rotate"),
             Message::Update(..) => f.write_str("Update"),
             Message::Resolve(..) => f.write_str("Resolve"),
+            Message::Report(..) => f.write_str("Report"),
+            Message::GetStatistics(_) => f.write_str("GetStatistics"),
+            Message::ResetStatistics(_) => f.write_str("ResetStatistics"),
+            Message::PreferredAddressChanged => f.write_str("PreferredAddressChanged"),
         }
     }
 }
@@ -192,22 +223,50 @@ impl AccessModeSelectorHandle {
             err
         })
     }
+
+    /// Record the outcome of a request made using `access_method`.
+    pub fn report(&self, access_method: Id, outcome: RequestOutcome) -> Result<()> {
+        self.cmd_tx
+            .unbounded_send(Message::Report(access_method, outcome))?;
+        Ok(())
+    }
+
+    pub async fn get_statistics(&self) -> Result<Statistics> {
+        self.send_command(Message::GetStatistics)
+            .await
+            .map_err(|err| {
+                log::debug!("Failed to get access method statistics");
+                err
+            })
+    }
+
+    pub async fn reset_statistics(&self) -> Result<()> {
+        self.send_command(Message::ResetStatistics)
+            .await
+            .map_err(|err| {
+                log::debug!("Failed to reset access method statistics");
+                err
+            })
+    }
 }
 
 pub struct AccessModeConnectionModeProvider {
     initial: ApiConnectionMode,
+    /// The access method which the connection mode in use was created from.
+    access_method: Id,
     handle: AccessModeSelectorHandle,
-    change_rx: mpsc::UnboundedReceiver<ApiConnectionMode>,
+    change_rx: mpsc::UnboundedReceiver<(Id, ApiConnectionMode)>,
 }
 
 impl AccessModeConnectionModeProvider {
     fn new(
         handle: AccessModeSelectorHandle,
-        initial_connection_mode: ApiConnectionMode,
-        change_rx: mpsc::UnboundedReceiver<ApiConnectionMode>,
+        initial_connection_mode: ResolvedConnectionMode,
+        change_rx: mpsc::UnboundedReceiver<(Id, ApiConnectionMode)>,
     ) -> Result<Self> {
         Ok(Self {
-            initial: initial_connection_mode,
+            initial: initial_connection_mode.connection_mode,
+            access_method: initial_connection_mode.setting.get_id(),
             handle,
             change_rx,
         })
@@ -220,7 +279,15 @@ impl ConnectionModeProvider for AccessModeConnectionModeProvider {
     }
 
     fn receive(&mut self) -> impl std::future::Future<Output = Option<ApiConnectionMode>> + Send {
-        self.change_rx.next()
+        async move {
+            let (access_method, connection_mode) = self.change_rx.next().await?;
+            self.access_method = access_method;
+            Some(connection_mode)
+        }
+    }
+
+    fn access_method(&self) -> Option<Id> {
+        Some(self.access_method.clone())
     }
 
     fn rotate(&self) -> impl std::future::Future<Output = ()> + Send {
@@ -229,6 +296,10 @@ impl ConnectionModeProvider for AccessModeConnectionModeProvider {
             handle.rotate().await.ok();
         }
     }
+
+    fn report(&self, access_method: Id, outcome: RequestOutcome) {
+        let _ = self.handle.report(access_method, outcome);
+    }
 }
 
 /// A small actor which takes care of handling the logic around rotating
@@ -247,10 +318,12 @@ pub struct AccessModeSelector {
     access_method_settings: Settings,
     address_cache: AddressCache,
     access_method_event_sender: DaemonEventSender<(AccessMethodEvent, oneshot::Sender<()>)>,
-    connection_mode_provider_sender: mpsc::UnboundedSender<ApiConnectionMode>,
+    connection_mode_provider_sender: mpsc::UnboundedSender<(Id, ApiConnectionMode)>,
     current: ResolvedConnectionMode,
-    /// `index` is used to keep track of the [`AccessMethodSetting`] to use.
-    index: usize,
+    /// Outcomes of requests made using each access method, used to pick the next one to try.
+    statistics: Statistics,
+    /// Hands [`Statistics`] to the task which writes them to disk.
+    statistics_tx: watch::Sender<Statistics>,
 }
 
 impl AccessModeSelector {
@@ -272,14 +345,42 @@ impl AccessModeSelector {
             }
         }
 
-        // Always start looking from the position of `Direct`.
-        let (index, next) = Self::find_next_active(0, &access_method_settings);
+        for setting in access_method_settings.iter() {
+            if let Err(error) = validate_access_method(&setting.access_method) {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg(&format!(
+                        "Ignoring invalid API access method \"{}\"",
+                        setting.name
+                    ))
+                );
+            }
+        }
+
+        let statistics = load_statistics(&cache_dir).await;
+        let (statistics_tx, statistics_rx) = watch::channel(statistics.clone());
+        tokio::spawn(save_statistics_task(cache_dir.clone(), statistics_rx));
+
+        let next = Self::find_best(&access_method_settings, &statistics, None);
         let initial_connection_mode =
-            Self::resolve_inner(next, &relay_selector, &address_cache).await;
+            Self::resolve_inner(next, &relay_selector, &address_cache).await?;
+
+        let mut preferred_rx = address_cache.watch_preferred();
+        let preferred_cmd_tx = cmd_tx.clone();
+        tokio::spawn(async move {
+            while preferred_rx.changed().await.is_ok() {
+                if preferred_cmd_tx
+                    .unbounded_send(Message::PreferredAddressChanged)
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
 
         let (change_tx, change_rx) = mpsc::unbounded();
 
-        let api_connection_mode = initial_connection_mode.connection_mode.clone();
+        let initial_resolved = initial_connection_mode.clone();
 
         let selector = AccessModeSelector {
             cmd_rx,
@@ -290,7 +391,8 @@ impl AccessModeSelector {
             access_method_event_sender,
             connection_mode_provider_sender: change_tx,
             current: initial_connection_mode,
-            index,
+            statistics,
+            statistics_tx,
         };
 
         tokio::spawn(selector.into_future());
@@ -298,7 +400,7 @@ impl AccessModeSelector {
         let handle = AccessModeSelectorHandle { cmd_tx };
 
         let connection_mode_provider =
-            AccessModeConnectionModeProvider::new(handle.clone(), api_connection_mode, change_rx)?;
+            AccessModeConnectionModeProvider::new(handle.clone(), initial_resolved, change_rx)?;
 
         Ok((handle, connection_mode_provider))
     }
@@ -312,6 +414,10 @@ impl AccessModeSelector {
                 Message::Rotate(tx) => self.on_next_connection_mode(tx).await,
                 Message::Update(tx, values) => self.on_update_access_methods(tx, values).await,
                 Message::Resolve(tx, setting) => self.on_resolve_access_method(tx, setting).await,
+                Message::Report(access_method, outcome) => self.on_report(access_method, outcome),
+                Message::GetStatistics(tx) => self.on_get_statistics(tx),
+                Message::ResetStatistics(tx) => self.on_reset_statistics(tx),
+                Message::PreferredAddressChanged => self.on_preferred_address_changed().await,
             };
             match execution {
                 Ok(_) => (),
@@ -336,31 +442,29 @@ impl AccessModeSelector {
     }
 
     async fn on_use_access_method(&mut self, tx: ResponseTx<()>, id: Id) -> Result<()> {
-        self.use_access_method(id).await;
-        self.reply(tx, ())
+        let result = self.use_access_method(id).await;
+        tx.send(result).map_err(|_| Error::OneshotSendFailed)
     }
 
     /// Set and announce the specified access method as the current one.
-    async fn use_access_method(&mut self, id: Id) {
+    async fn use_access_method(&mut self, id: Id) -> Result<()> {
         #[cfg(feature = "api-override")]
         {
             if mullvad_api::API.force_direct {
                 log::debug!("API proxies are disabled");
-                return;
+                return Ok(());
             }
         }
 
-        let Some((index, method)) = self
+        let Some(method) = self
             .access_method_settings
             .iter()
-            .enumerate()
-            .find(|(_, access_method)| access_method.get_id() == id)
+            .find(|access_method| access_method.get_id() == id)
         else {
-            return;
+            return Ok(());
         };
 
-        self.index = index;
-        self.set_current(method.to_owned()).await;
+        self.set_current(method.to_owned()).await
     }
 
     async fn on_next_connection_mode(&mut self, tx: ResponseTx<ApiConnectionMode>) -> Result<()> {
@@ -382,15 +486,17 @@ impl AccessModeSelector {
             );
         }
 
-        let (next_index, next) =
-            Self::find_next_active(self.index + 1, &self.access_method_settings);
-        self.index = next_index;
-        self.set_current(next).await;
+        let next = Self::find_best(
+            &self.access_method_settings,
+            &self.statistics,
+            Some(&self.current.setting.get_id()),
+        );
+        self.set_current(next).await?;
         Ok(self.current.connection_mode.clone())
     }
 
-    async fn set_current(&mut self, access_method: AccessMethodSetting) {
-        let resolved = self.resolve(access_method).await;
+    async fn set_current(&mut self, access_method: AccessMethodSetting) -> Result<()> {
+        let resolved = self.resolve(access_method).await?;
 
         // Note: If the daemon is busy waiting for a call to this function
         // to complete while we wait for the daemon to fully handle this
@@ -423,7 +529,7 @@ impl AccessModeSelector {
         // Notify REST client
         let _ = self
             .connection_mode_provider_sender
-            .unbounded_send(resolved.connection_mode.clone());
+            .unbounded_send((resolved.setting.get_id(), resolved.connection_mode.clone()));
 
         self.current = resolved;
 
@@ -431,22 +537,101 @@ impl AccessModeSelector {
             "A new API access method has been selected: {name}",
             name = self.current.setting.name
         );
+        Ok(())
     }
 
-    /// Find the next access method to use.
+    /// Find the enabled access method which is most likely to work.
+    ///
+    /// Access methods with fewer failed requests since their last successful one are preferred,
+    /// followed by those with the highest success rate and then those with the lowest latency.
+    /// Ties are broken by the order of `access_methods`, and `Direct` is used if no valid access
+    /// method is enabled.
     ///
-    /// * `start`: From which point in `access_methods` to start the search.
     /// * `access_methods`: The search space.
-    fn find_next_active(start: usize, access_methods: &Settings) -> (usize, AccessMethodSetting) {
-        access_methods
+    /// * `statistics`: Outcomes of previous requests.
+    /// * `avoid`: An access method which is only picked if there is no other enabled one,
+    ///   typically the one which just failed.
+    fn find_best(
+        access_methods: &Settings,
+        statistics: &Statistics,
+        avoid: Option<&Id>,
+    ) -> AccessMethodSetting {
+        let rank = |access_method: &&AccessMethodSetting| {
+            let stats = statistics
+                .get(&access_method.get_id())
+                .cloned()
+                .unwrap_or_default();
+            // Compare success rates with a precision of 0.1%
+            let success_rate = (stats.success_rate().unwrap_or(0.0) * 1000.0) as u32;
+            // Access methods which have never responded are ranked below those that have
+            let latency = stats.latency.unwrap_or(Duration::MAX);
+            (stats.consecutive_failures, Reverse(success_rate), latency)
+        };
+        let enabled = access_methods.iter().filter(|access_method| {
+            access_method.enabled() && validate_access_method(&access_method.access_method).is_ok()
+        });
+        enabled
+            .clone()
+            .filter(|access_method| Some(&access_method.get_id()) != avoid)
+            .min_by_key(rank)
+            .or_else(|| enabled.clone().next())
+            .unwrap_or(access_methods.direct())
+            .clone()
+    }
+
+    fn on_report(&mut self, access_method: Id, outcome: RequestOutcome) -> Result<()> {
+        // The access method may have been removed while the request was in flight
+        if !self
+            .access_method_settings
             .iter()
-            .cloned()
-            .enumerate()
-            .cycle()
-            .skip(start)
-            .take(access_methods.cardinality())
-            .find(|(_index, access_method)| access_method.enabled())
-            .unwrap_or_else(|| (0, access_methods.direct().clone()))
+            .any(|setting| setting.get_id() == access_method)
+        {
+            return Ok(());
+        }
+        let stats = self.statistics.entry(access_method).or_default();
+        match outcome {
+            RequestOutcome::Success { latency } => stats.record_success(latency),
+            RequestOutcome::Failure => stats.record_failure(),
+        }
+        self.save_statistics();
+        Ok(())
+    }
+
+    /// Direct connections use the preferred API address, which is the only one allowed by the
+    /// firewall. When connection failures make another address preferred, the firewall has to
+    /// be updated for the connections to succeed.
+    async fn on_preferred_address_changed(&mut self) -> Result<()> {
+        let endpoint = resolve_allowed_endpoint(
+            &self.current.connection_mode,
+            self.address_cache.get_address().await,
+        );
+        if endpoint == self.current.endpoint {
+            return Ok(());
+        }
+        self.current.endpoint = endpoint.clone();
+
+        // The completion channel is discarded for the same reason as in `set_current`.
+        let daemon_sender = self.access_method_event_sender.clone();
+        tokio::spawn(async move {
+            let _ = AccessMethodEvent::Allow { endpoint }
+                .send(daemon_sender)
+                .await;
+        });
+        Ok(())
+    }
+
+    fn on_get_statistics(&mut self, tx: ResponseTx<Statistics>) -> Result<()> {
+        self.reply(tx, self.statistics.clone())
+    }
+
+    fn on_reset_statistics(&mut self, tx: ResponseTx<()>) -> Result<()> {
+        self.statistics.clear();
+        self.save_statistics();
+        self.reply(tx, ())
+    }
+
+    fn save_statistics(&self) {
+        self.statistics_tx.send_replace(self.statistics.clone());
     }
 
     async fn on_update_access_methods(
@@ -461,29 +646,33 @@ impl AccessModeSelector {
     async fn update_access_methods(&mut self, access_methods: Settings) -> Result<()> {
         self.access_method_settings = access_methods;
 
+        // Forget about access methods which have been removed
+        let statistics_len = self.statistics.len();
+        let access_method_settings = &self.access_method_settings;
+        self.statistics
+            .retain(|id, _| access_method_settings.iter().any(|m| m.get_id() == *id));
+        if self.statistics.len() != statistics_len {
+            self.save_statistics();
+        }
+
         let new_current = self
             .access_method_settings
             .iter()
-            .enumerate()
-            .find(|(_, access_method)| access_method.get_id() == self.current.setting.get_id());
+            .find(|access_method| access_method.get_id() == self.current.setting.get_id());
 
         match new_current {
-            Some((index, new_current)) => {
+            Some(new_current) => {
                 // If the current method was modified, announce changes
-                self.index = index;
                 if self.current.setting != *new_current {
                     if new_current.enabled() {
-                        self.set_current(new_current.to_owned()).await;
+                        self.set_current(new_current.to_owned()).await?;
                     } else {
                         self.next_connection_mode().await?;
                     }
                 }
             }
             None => {
-                // Current method was removed: A new access method will suddenly have the same index as the one
-                // we are removing, but we want it to still be a candidate. A minor
-                // hack to achieve this is to simply decrement the current index.
-                self.index = self.index.saturating_sub(1);
+                // Current method was removed
                 self.next_connection_mode().await?;
             }
         }
@@ -496,10 +685,13 @@ impl AccessModeSelector {
         setting: AccessMethodSetting,
     ) -> Result<()> {
         let reply = self.resolve(setting).await;
-        self.reply(tx, reply)
+        tx.send(reply).map_err(|_| Error::OneshotSendFailed)
     }
 
-    async fn resolve(&mut self, access_method: AccessMethodSetting) -> ResolvedConnectionMode {
+    async fn resolve(
+        &mut self,
+        access_method: AccessMethodSetting,
+    ) -> Result<ResolvedConnectionMode> {
         Self::resolve_inner(access_method, &self.relay_selector, &self.address_cache).await
     }
 
@@ -507,19 +699,68 @@ impl AccessModeSelector {
         access_method: AccessMethodSetting,
         relay_selector: &RelaySelector,
         address_cache: &AddressCache,
-    ) -> ResolvedConnectionMode {
+    ) -> Result<ResolvedConnectionMode> {
         let connection_mode =
-            resolve_connection_mode(access_method.access_method.clone(), relay_selector);
+            resolve_connection_mode(access_method.access_method.clone(), relay_selector)?;
         let endpoint =
             resolve_allowed_endpoint(&connection_mode, address_cache.get_address().await);
-        ResolvedConnectionMode {
+        Ok(ResolvedConnectionMode {
             connection_mode,
             endpoint,
             setting: access_method,
+        })
+    }
+}
+
+/// Reads [`Statistics`] from [`STATISTICS_FILENAME`]. If this fails, no statistics are
+/// returned.
+async fn load_statistics(cache_dir: &Path) -> Statistics {
+    let path = cache_dir.join(STATISTICS_FILENAME);
+    match fs::read_to_string(path).await {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|error| {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to parse access method statistics")
+            );
+            Statistics::new()
+        }),
+        Err(error) => {
+            if error.kind() != io::ErrorKind::NotFound {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Failed to read access method statistics")
+                );
+            }
+            Statistics::new()
+        }
+    }
+}
+
+/// Stores the latest [`Statistics`] received on `statistics_rx`, waiting
+/// [`STATISTICS_SAVE_DELAY`] after each change so that bursts of changes result in a single
+/// write. Since there is only one writer, writes never race each other.
+async fn save_statistics_task(cache_dir: PathBuf, mut statistics_rx: watch::Receiver<Statistics>) {
+    while statistics_rx.changed().await.is_ok() {
+        tokio::time::sleep(STATISTICS_SAVE_DELAY).await;
+        let statistics = statistics_rx.borrow_and_update().clone();
+        if let Err(error) = save_statistics(&cache_dir, &statistics).await {
+            log::warn!(
+                "{}",
+                error.display_chain_with_msg("Failed to save access method statistics")
+            );
         }
     }
 }
 
+/// Stores `statistics` to [`STATISTICS_FILENAME`].
+async fn save_statistics(cache_dir: &Path, statistics: &Statistics) -> io::Result<()> {
+    let mut file = mullvad_fs::AtomicFile::new(cache_dir.join(STATISTICS_FILENAME)).await?;
+    let json = serde_json::to_string_pretty(statistics)
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+    file.write_all(json.as_bytes()).await?;
+    file.finalize().await
+}
+
 /// Ad-hoc version of [`std::convert::From::from`], but since some
 /// [`ApiConnectionMode`]s require extra logic/data from [`RelaySelector`] to be
 /// instantiated the standard [`std::convert::From`] trait can not be
@@ -527,8 +768,8 @@ impl AccessModeSelector {
 fn resolve_connection_mode(
     access_method: AccessMethod,
     relay_selector: &RelaySelector,
-) -> ApiConnectionMode {
-    match access_method {
+) -> std::result::Result<ApiConnectionMode, ProxyChainError> {
+    Ok(match access_method {
         AccessMethod::BuiltIn(BuiltInAccessMethod::Direct) => ApiConnectionMode::Direct,
         AccessMethod::BuiltIn(BuiltInAccessMethod::Bridge) => relay_selector
             .get_bridge_forced()
@@ -541,9 +782,28 @@ fn resolve_connection_mode(
                 ApiConnectionMode::Direct
             }),
         AccessMethod::Custom(config) => ApiConnectionMode::Proxied(ProxyConfig::from(config)),
+        AccessMethod::Chain(proxies) => {
+            let chain = ProxyChain::new(proxies.into_iter().map(ProxyConfig::from))?;
+            ApiConnectionMode::Proxied(ProxyConfig::Chain(chain))
+        }
+    })
+}
+
+/// Checks that `access_method` can be resolved. Chains are checked when they are added over RPC,
+/// but the settings file may contain chains that were never checked.
+fn validate_access_method(
+    access_method: &AccessMethod,
+) -> std::result::Result<(), ProxyChainError> {
+    match access_method {
+        AccessMethod::Chain(proxies) => {
+            ProxyChain::new(proxies.iter().cloned().map(ProxyConfig::from)).map(|_| ())
+        }
+        AccessMethod::BuiltIn(_) | AccessMethod::Custom(_) => Ok(()),
     }
 }
 
+/// Only the first hop of a proxy chain is connected to from this machine, so that is the only
+/// endpoint which is allowed through the firewall.
 pub fn resolve_allowed_endpoint(
     connection_mode: &ApiConnectionMode,
     fallback: SocketAddr,
@@ -559,7 +819,11 @@ pub fn resolve_allowed_endpoint(
 #[cfg(unix)]
 pub fn allowed_clients(connection_mode: &ApiConnectionMode) -> AllowedClients {
     match connection_mode {
-        ApiConnectionMode::Proxied(ProxyConfig::Socks5Local(_)) => AllowedClients::All,
+        ApiConnectionMode::Proxied(proxy)
+            if matches!(proxy.first_hop(), ProxyConfig::Socks5Local(_)) =>
+        {
+            AllowedClients::All
+        }
         ApiConnectionMode::Direct | ApiConnectionMode::Proxied(_) => AllowedClients::Root,
     }
 }
@@ -567,7 +831,11 @@ pub fn allowed_clients(connection_mode: &ApiConnectionMode) -> AllowedClients {
 #[cfg(windows)]
 pub fn allowed_clients(connection_mode: &ApiConnectionMode) -> AllowedClients {
     match connection_mode {
-        ApiConnectionMode::Proxied(ProxyConfig::Socks5Local(_)) => AllowedClients::all(),
+        ApiConnectionMode::Proxied(proxy)
+            if matches!(proxy.first_hop(), ProxyConfig::Socks5Local(_)) =>
+        {
+            AllowedClients::all()
+        }
         ApiConnectionMode::Direct | ApiConnectionMode::Proxied(_) => {
             let daemon_exe = std::env::current_exe().expect("failed to obtain executable path");
             vec![
@@ -625,3 +893,132 @@ pub(crate) fn create_bypass_tx(
     });
     Some(bypass_tx)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::proxy::{CustomProxy, Socks5Local, Socks5Remote};
+
+    fn settings() -> Settings {
+        let mut settings = Settings::default();
+        settings.append(AccessMethodSetting::new(
+            "proxy".to_owned(),
+            true,
+            AccessMethod::from(Socks5Remote::new(([192, 0, 2, 1], 1080))),
+        ));
+        settings
+    }
+
+    fn find_best_name(settings: &Settings, statistics: &Statistics, avoid: Option<&Id>) -> String {
+        AccessModeSelector::find_best(settings, statistics, avoid).get_name()
+    }
+
+    #[test]
+    fn test_find_best() {
+        let settings = settings();
+        let direct = settings.direct().get_id();
+        let bridges = settings.mullvad_bridges().get_id();
+        let mut statistics = Statistics::new();
+
+        // Without any statistics, the order of the settings is used
+        assert_eq!(find_best_name(&settings, &statistics, None), "Direct");
+        assert_eq!(
+            find_best_name(&settings, &statistics, Some(&direct)),
+            "Mullvad Bridges"
+        );
+
+        // Access methods which have failed are tried last
+        statistics
+            .entry(direct.clone())
+            .or_default()
+            .record_failure();
+        assert_eq!(
+            find_best_name(&settings, &statistics, None),
+            "Mullvad Bridges"
+        );
+
+        // Access methods which have worked before are preferred
+        let proxy = settings.iter_custom().next().unwrap().get_id();
+        statistics
+            .entry(proxy.clone())
+            .or_default()
+            .record_success(Duration::from_millis(100));
+        assert_eq!(find_best_name(&settings, &statistics, None), "proxy");
+
+        // Unless they have failed since
+        statistics
+            .entry(proxy.clone())
+            .or_default()
+            .record_failure();
+        assert_eq!(
+            find_best_name(&settings, &statistics, None),
+            "Mullvad Bridges"
+        );
+
+        // With one failure each, the highest success rate wins
+        statistics.entry(bridges).or_default().record_failure();
+        assert_eq!(find_best_name(&settings, &statistics, None), "proxy");
+    }
+
+    #[test]
+    fn test_find_best_prefers_low_latency() {
+        let mut settings = settings();
+        settings.append(AccessMethodSetting::new(
+            "fast proxy".to_owned(),
+            true,
+            AccessMethod::from(Socks5Remote::new(([192, 0, 2, 2], 1080))),
+        ));
+        let mut statistics = Statistics::new();
+        for (access_method, latency) in settings.iter_custom().zip([500, 50]) {
+            statistics
+                .entry(access_method.get_id())
+                .or_default()
+                .record_success(Duration::from_millis(latency));
+        }
+
+        // With equal success rates, the access method which responded faster wins
+        assert_eq!(find_best_name(&settings, &statistics, None), "fast proxy");
+    }
+
+    #[test]
+    fn test_find_best_skips_invalid_chains() {
+        let mut settings = Settings::default();
+        settings.append(AccessMethodSetting::new(
+            "empty chain".to_owned(),
+            true,
+            AccessMethod::Chain(vec![]),
+        ));
+        settings.append(AccessMethodSetting::new(
+            "local proxy last".to_owned(),
+            true,
+            AccessMethod::Chain(vec![
+                CustomProxy::Socks5Remote(Socks5Remote::new(([192, 0, 2, 1], 1080))),
+                CustomProxy::Socks5Local(Socks5Local::new(([192, 0, 2, 2], 1080), 1080)),
+            ]),
+        ));
+        settings.update(|setting| setting.is_direct(), |setting| setting.disable());
+        settings.update(
+            |setting| setting.is_builtin() && !setting.is_direct(),
+            |setting| setting.disable(),
+        );
+
+        // Invalid chains are never picked, even if nothing else is enabled
+        assert_eq!(
+            find_best_name(&settings, &Statistics::new(), None),
+            "Direct"
+        );
+    }
+
+    #[test]
+    fn test_find_best_avoids_only_if_possible() {
+        let mut settings = settings();
+        settings.update(|setting| !setting.is_direct(), |setting| setting.disable());
+        settings.update(|setting| !setting.is_builtin(), |setting| setting.disable());
+        let direct = settings.direct().get_id();
+
+        assert_eq!(
+            find_best_name(&settings, &Statistics::new(), Some(&direct)),
+            "Direct"
+        );
+    }
+}
@@ -427,11 +427,11 @@ impl AccountManager {
         let token = data.device().map(|state| state.account_token.clone());
         let api_availability = rest_handle.availability.clone();
         let account_service =
-            service::spawn_account_service(rest_handle.clone(), token, api_availability.clone());
+            service::spawn_account_service(rest_handle.clone(), token, api_availability);
 
         let (cmd_tx, cmd_rx) = mpsc::unbounded();
 
-        let device_service = DeviceService::new(rest_handle, api_availability);
+        let device_service = DeviceService::new(rest_handle);
         let manager = AccountManager {
             cacher,
             account_service: account_service.clone(),
@@ -14,30 +14,49 @@ use talpid_types::net::wireguard::PrivateKey;
 use super::{Error, PrivateAccountAndDevice, PrivateDevice};
 use mullvad_api::{
     availability::ApiAvailabilityHandle,
-    rest::{self, MullvadRestHandle},
+    rest::{self, MullvadRestHandle, RetryPolicy},
     AccountsProxy, DevicesProxy,
 };
-use talpid_future::retry::{retry_future, ConstantInterval, ExponentialBackoff, Jittered};
-/// Retry strategy used for user-initiated actions that require immediate feedback
-const RETRY_ACTION_STRATEGY: ConstantInterval = ConstantInterval::new(Duration::ZERO, Some(3));
-/// Retry strategy used for background tasks
-const RETRY_BACKOFF_STRATEGY: Jittered<ExponentialBackoff> = Jittered::jitter(
-    ExponentialBackoff::new(Duration::from_secs(4), 5)
-        .max_delay(Some(Duration::from_secs(24 * 60 * 60))),
-);
+/// Retry policy used for user-initiated actions that require immediate feedback
+const RETRY_ACTION_POLICY: RetryPolicy = RetryPolicy::new(Some(4))
+    .backoff(Duration::ZERO, 1)
+    .retry_status(&[]);
+/// Error codes for which retrying a background task is pointless
+const PERMANENT_ERROR_CODES: &[&str] = &[
+    mullvad_api::DEVICE_NOT_FOUND,
+    mullvad_api::INVALID_ACCOUNT,
+    mullvad_api::MAX_DEVICES_REACHED,
+    mullvad_api::PUBKEY_IN_USE,
+];
+/// Retry policy used for background tasks
+const RETRY_BACKOFF_POLICY: RetryPolicy = RetryPolicy::new(None)
+    .backoff(Duration::from_secs(4), 5)
+    .max_delay(Duration::from_secs(24 * 60 * 60))
+    .retry_all_status_except(&[rest::StatusCode::NOT_FOUND], PERMANENT_ERROR_CODES)
+    .when_online();
+/// Retry policy used for rotating the WireGuard key in the background
+const RETRY_ROTATE_POLICY: RetryPolicy = RetryPolicy::new(None)
+    .backoff(Duration::from_secs(24 * 60 * 60), 1)
+    .max_delay(Duration::from_secs(24 * 60 * 60))
+    .retry_all_status_except(&[rest::StatusCode::NOT_FOUND], PERMANENT_ERROR_CODES)
+    .when_bg_resumes();
+/// Retry policy used for the initial account check, which is retried until the account is known
+/// to be valid or invalid
+const RETRY_ACCOUNT_CHECK_POLICY: RetryPolicy =
+    RETRY_BACKOFF_POLICY.retry_all_status_except(&[], &[mullvad_api::INVALID_ACCOUNT]);
 
 #[derive(Clone)]
 pub struct DeviceService {
-    api_availability: ApiAvailabilityHandle,
-    proxy: DevicesProxy,
+    handle: MullvadRestHandle,
 }
 
 impl DeviceService {
-    pub fn new(handle: rest::MullvadRestHandle, api_availability: ApiAvailabilityHandle) -> Self {
-        Self {
-            proxy: DevicesProxy::new(handle),
-            api_availability,
-        }
+    pub fn new(handle: rest::MullvadRestHandle) -> Self {
+        Self { handle }
+    }
+
+    fn proxy(&self, retry_policy: RetryPolicy) -> DevicesProxy {
+        DevicesProxy::new(self.handle.with_retry_policy(retry_policy))
     }
 
     /// Generate a new device for a given token
@@ -48,17 +67,11 @@ impl DeviceService {
         let private_key = PrivateKey::new_from_random();
         let pubkey = private_key.public_key();
 
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        let token_copy = account_token.clone();
+        let create = self
+            .proxy(RETRY_ACTION_POLICY)
+            .create(account_token.clone(), pubkey);
         async move {
-            let (device, addresses) = retry_future(
-                move || proxy.create(token_copy.clone(), pubkey.clone()),
-                move |result| should_retry(result, &api_handle),
-                RETRY_ACTION_STRATEGY,
-            )
-            .await
-            .map_err(map_rest_error)?;
+            let (device, addresses) = create.await.map_err(map_rest_error)?;
 
             Ok(PrivateAccountAndDevice {
                 account_token,
@@ -81,16 +94,11 @@ impl DeviceService {
         let private_key = PrivateKey::new_from_random();
         let pubkey = private_key.public_key();
 
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        let token_copy = account_token.clone();
-        let (device, addresses) = retry_future(
-            move || api_handle.when_online(proxy.create(token_copy.clone(), pubkey.clone())),
-            should_retry_backoff,
-            RETRY_BACKOFF_STRATEGY,
-        )
-        .await
-        .map_err(map_rest_error)?;
+        let (device, addresses) = self
+            .proxy(RETRY_BACKOFF_POLICY)
+            .create(account_token.clone(), pubkey)
+            .await
+            .map_err(map_rest_error)?;
 
         Ok(PrivateAccountAndDevice {
             account_token,
@@ -120,15 +128,10 @@ impl DeviceService {
         token: AccountToken,
         device: DeviceId,
     ) -> Result<(), Error> {
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        retry_future(
-            move || proxy.remove(token.clone(), device.clone()),
-            move |result| should_retry(result, &api_handle),
-            RETRY_ACTION_STRATEGY,
-        )
-        .await
-        .map_err(map_rest_error)?;
+        self.proxy(RETRY_ACTION_POLICY)
+            .remove(token, device)
+            .await
+            .map_err(map_rest_error)?;
         Ok(())
     }
 
@@ -137,18 +140,11 @@ impl DeviceService {
         token: AccountToken,
         device: DeviceId,
     ) -> Result<(), Error> {
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-
-        retry_future(
-            // NOTE: Not honoring "paused" state, because the account may have no time on it.
-            move || api_handle.when_online(proxy.remove(token.clone(), device.clone())),
-            should_retry_backoff,
-            // Not setting a maximum interval
-            RETRY_BACKOFF_STRATEGY.clone().max_delay(None),
-        )
-        .await
-        .map_err(map_rest_error)?;
+        // NOTE: Not honoring "paused" state, because the account may have no time on it.
+        self.proxy(RETRY_BACKOFF_POLICY)
+            .remove(token, device)
+            .await
+            .map_err(map_rest_error)?;
 
         Ok(())
     }
@@ -160,16 +156,11 @@ impl DeviceService {
     ) -> Result<WireguardData, Error> {
         let private_key = PrivateKey::new_from_random();
 
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        let pubkey = private_key.public_key();
-        let addresses = retry_future(
-            move || proxy.replace_wg_key(token.clone(), device.clone(), pubkey.clone()),
-            move |result| should_retry(result, &api_handle),
-            RETRY_ACTION_STRATEGY,
-        )
-        .await
-        .map_err(map_rest_error)?;
+        let addresses = self
+            .proxy(RETRY_ACTION_POLICY)
+            .replace_wg_key(token, device, private_key.public_key())
+            .await
+            .map_err(map_rest_error)?;
 
         Ok(WireguardData {
             private_key,
@@ -185,25 +176,11 @@ impl DeviceService {
     ) -> Result<WireguardData, Error> {
         let private_key = PrivateKey::new_from_random();
 
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        let pubkey = private_key.public_key();
-
-        let rotate_retry_strategy = std::iter::repeat(Duration::from_secs(24 * 60 * 60));
-
-        let addresses = retry_future(
-            move || {
-                api_handle.when_bg_resumes(proxy.replace_wg_key(
-                    token.clone(),
-                    device.clone(),
-                    pubkey.clone(),
-                ))
-            },
-            should_retry_backoff,
-            rotate_retry_strategy,
-        )
-        .await
-        .map_err(map_rest_error)?;
+        let addresses = self
+            .proxy(RETRY_ROTATE_POLICY)
+            .replace_wg_key(token, device, private_key.public_key())
+            .await
+            .map_err(map_rest_error)?;
 
         Ok(WireguardData {
             private_key,
@@ -213,43 +190,27 @@ impl DeviceService {
     }
 
     pub async fn list_devices(&self, token: AccountToken) -> Result<Vec<Device>, Error> {
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        retry_future(
-            move || proxy.list(token.clone()),
-            move |result| should_retry(result, &api_handle),
-            RETRY_ACTION_STRATEGY,
-        )
-        .await
-        .map_err(map_rest_error)
+        self.proxy(RETRY_ACTION_POLICY)
+            .list(token)
+            .await
+            .map_err(map_rest_error)
     }
 
     pub async fn list_devices_with_backoff(
         &self,
         token: AccountToken,
     ) -> Result<Vec<Device>, Error> {
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-
-        retry_future(
-            move || api_handle.when_online(proxy.list(token.clone())),
-            should_retry_backoff,
-            RETRY_BACKOFF_STRATEGY,
-        )
-        .await
-        .map_err(map_rest_error)
+        self.proxy(RETRY_BACKOFF_POLICY)
+            .list(token)
+            .await
+            .map_err(map_rest_error)
     }
 
     pub async fn get(&self, token: AccountToken, device: DeviceId) -> Result<Device, Error> {
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        retry_future(
-            move || proxy.get(token.clone(), device.clone()),
-            move |result| should_retry(result, &api_handle),
-            RETRY_ACTION_STRATEGY,
-        )
-        .await
-        .map_err(map_rest_error)
+        self.proxy(RETRY_ACTION_POLICY)
+            .get(token, device)
+            .await
+            .map_err(map_rest_error)
     }
 }
 
@@ -262,37 +223,18 @@ pub struct AccountService {
 
 impl AccountService {
     pub fn create_account(&self) -> impl Future<Output = Result<AccountToken, rest::Error>> {
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        retry_future(
-            move || proxy.create_account(),
-            move |result| should_retry(result, &api_handle),
-            RETRY_ACTION_STRATEGY,
-        )
+        self.proxy.create_account()
     }
 
     pub fn get_www_auth_token(
         &self,
         account: AccountToken,
     ) -> impl Future<Output = Result<String, rest::Error>> {
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        retry_future(
-            move || proxy.get_www_auth_token(account.clone()),
-            move |result| should_retry(result, &api_handle),
-            RETRY_ACTION_STRATEGY,
-        )
+        self.proxy.get_www_auth_token(account)
     }
 
     pub async fn get_data(&self, token: AccountToken) -> Result<AccountData, rest::Error> {
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        let result = retry_future(
-            move || proxy.get_data(token.clone()),
-            move |result| should_retry(result, &api_handle),
-            RETRY_ACTION_STRATEGY,
-        )
-        .await;
+        let result = self.proxy.get_data(token).await;
         if handle_account_data_result(&result, &self.api_availability) {
             self.initial_check_abort_handle.abort();
         }
@@ -308,14 +250,7 @@ impl AccountService {
         account_token: AccountToken,
         voucher: String,
     ) -> Result<VoucherSubmission, Error> {
-        let proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        let result = retry_future(
-            move || proxy.submit_voucher(account_token.clone(), voucher.clone()),
-            move |result| should_retry(result, &api_handle),
-            RETRY_ACTION_STRATEGY,
-        )
-        .await;
+        let result = self.proxy.submit_voucher(account_token, voucher).await;
         if result.is_ok() {
             self.initial_check_abort_handle.abort();
             self.api_availability.resume_background();
@@ -329,13 +264,7 @@ impl AccountService {
         account_token: AccountToken,
     ) -> Result<PlayPurchasePaymentToken, Error> {
         let mut proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        let result = retry_future(
-            move || proxy.init_play_purchase(account_token.clone()),
-            move |result| should_retry(result, &api_handle),
-            RETRY_ACTION_STRATEGY,
-        )
-        .await;
+        let result = proxy.init_play_purchase(account_token).await;
         if result.is_ok() {
             self.initial_check_abort_handle.abort();
             self.api_availability.resume_background();
@@ -350,13 +279,9 @@ impl AccountService {
         play_purchase: PlayPurchase,
     ) -> Result<(), Error> {
         let mut proxy = self.proxy.clone();
-        let api_handle = self.api_availability.clone();
-        let result = retry_future(
-            move || proxy.verify_play_purchase(account_token.clone(), play_purchase.clone()),
-            move |result| should_retry(result, &api_handle),
-            RETRY_ACTION_STRATEGY,
-        )
-        .await;
+        let result = proxy
+            .verify_play_purchase(account_token, play_purchase)
+            .await;
         if result.is_ok() {
             self.initial_check_abort_handle.abort();
             self.api_availability.resume_background();
@@ -370,11 +295,11 @@ pub fn spawn_account_service(
     token: Option<String>,
     api_availability: ApiAvailabilityHandle,
 ) -> AccountService {
-    let accounts_proxy = AccountsProxy::new(api_handle);
+    let accounts_proxy =
+        AccountsProxy::new(api_handle.with_retry_policy(RETRY_ACCOUNT_CHECK_POLICY));
     api_availability.pause_background();
 
     let api_availability_copy = api_availability.clone();
-    let accounts_proxy_copy = accounts_proxy.clone();
 
     let (future, initial_check_abort_handle) = abortable(async move {
         let token = if let Some(token) = token {
@@ -384,20 +309,15 @@ pub fn spawn_account_service(
             return;
         };
 
-        let future_generator = move || {
-            let expiry_fut = api_availability.when_online(accounts_proxy.get_data(token.clone()));
-            let api_availability_copy = api_availability.clone();
-            async move { handle_account_data_result(&expiry_fut.await, &api_availability_copy) }
-        };
-        let should_retry = move |state_was_updated: &bool| -> bool { !*state_was_updated };
-        retry_future(future_generator, should_retry, RETRY_BACKOFF_STRATEGY).await;
+        let result = accounts_proxy.get_data(token).await;
+        handle_account_data_result(&result, &api_availability);
     });
     tokio::spawn(future);
 
     AccountService {
         api_availability: api_availability_copy,
         initial_check_abort_handle,
-        proxy: accounts_proxy_copy,
+        proxy: AccountsProxy::new(api_handle.with_retry_policy(RETRY_ACTION_POLICY)),
     }
 }
 
@@ -425,30 +345,6 @@ fn handle_account_data_result(
     }
 }
 
-fn should_retry<T>(result: &Result<T, rest::Error>, api_handle: &ApiAvailabilityHandle) -> bool {
-    match result {
-        Err(error) if error.is_network_error() => !api_handle.get_state().is_offline(),
-        _ => false,
-    }
-}
-
-fn should_retry_backoff<T>(result: &Result<T, rest::Error>) -> bool {
-    match result {
-        Ok(_) => false,
-        Err(error) => {
-            if let rest::Error::ApiError(status, code) = error {
-                *status != rest::StatusCode::NOT_FOUND
-                    && code != mullvad_api::DEVICE_NOT_FOUND
-                    && code != mullvad_api::INVALID_ACCOUNT
-                    && code != mullvad_api::MAX_DEVICES_REACHED
-                    && code != mullvad_api::PUBKEY_IN_USE
-            } else {
-                true
-            }
-        }
-    }
-}
-
 fn map_rest_error(error: rest::Error) -> Error {
     match error {
         rest::Error::ApiError(_status, ref code) => match code.as_str() {
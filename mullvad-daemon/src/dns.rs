@@ -57,3 +57,12 @@ pub fn addresses_from_options(options: &DnsOptions) -> Option<Vec<IpAddr>> {
         }
     }
 }
+
+/// Return the user-supplied domain lists that the filtering resolver should enforce.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn blocklist_config(options: &DnsOptions) -> talpid_core::resolver::BlocklistConfig {
+    talpid_core::resolver::BlocklistConfig {
+        blocklists: options.blocklists.blocklists.clone(),
+        allowlists: options.blocklists.allowlists.clone(),
+    }
+}
@@ -1,11 +1,10 @@
 use std::time::Duration;
 
 use futures::join;
-use mullvad_api::rest::{Error, RequestServiceHandle};
+use mullvad_api::rest::{Error, Request, RequestServiceHandle, RetryPolicy};
 use mullvad_types::location::{AmIMullvad, GeoIpLocation, LocationEventData};
 use once_cell::sync::Lazy;
 use talpid_core::mpsc::Sender;
-use talpid_future::retry::{retry_future, ExponentialBackoff, Jittered};
 use talpid_types::ErrorExt;
 
 use crate::{DaemonEventSender, InternalDaemonEvent};
@@ -39,8 +38,8 @@ static MULLVAD_CONNCHECK_HOST: Lazy<String> = Lazy::new(|| {
     host.to_string()
 });
 
-const LOCATION_RETRY_STRATEGY: Jittered<ExponentialBackoff> =
-    Jittered::jitter(ExponentialBackoff::new(Duration::from_secs(1), 4));
+const LOCATION_RETRY_POLICY: RetryPolicy =
+    RetryPolicy::new(None).backoff(Duration::from_secs(1), 4);
 
 /// Handler for request to am.i.mullvad.net, manages in-flight request and validity of responses.
 pub(crate) struct GeoIpHandler {
@@ -74,7 +73,8 @@ impl GeoIpHandler {
         let rest_service = self.rest_service.clone();
         let location_sender = self.location_sender.clone();
         tokio::spawn(async move {
-            if let Ok(location) = get_geo_location_with_retry(use_ipv6, rest_service).await {
+            log::debug!("Fetching GeoIpLocation");
+            if let Ok(location) = send_location_request(rest_service, use_ipv6).await {
                 let _ =
                     location_sender.send(InternalDaemonEvent::LocationEvent(LocationEventData {
                         request_id,
@@ -90,23 +90,8 @@ impl GeoIpHandler {
     }
 }
 
-/// Fetch the current `GeoIpLocation` from am.i.mullvad.net. Handles retries on network errors.
-async fn get_geo_location_with_retry(
-    use_ipv6: bool,
-    rest_service: RequestServiceHandle,
-) -> Result<GeoIpLocation, Error> {
-    log::debug!("Fetching GeoIpLocation");
-    retry_future(
-        move || send_location_request(rest_service.clone(), use_ipv6),
-        move |result| match result {
-            Err(error) => error.is_network_error(),
-            _ => false,
-        },
-        LOCATION_RETRY_STRATEGY,
-    )
-    .await
-}
-
+/// Fetch the current `GeoIpLocation` from am.i.mullvad.net. Each request is retried on network
+/// errors.
 async fn send_location_request(
     request_sender: RequestServiceHandle,
     use_ipv6: bool,
@@ -154,7 +139,7 @@ async fn send_location_request_internal(
     service: RequestServiceHandle,
 ) -> Result<AmIMullvad, Error> {
     let future_service = service.clone();
-    let request = mullvad_api::rest::Request::get(uri)?;
+    let request = Request::get(uri)?.retry(LOCATION_RETRY_POLICY);
     future_service.request(request).await?.deserialize().await
 }
 
@@ -17,6 +17,8 @@ mod macos;
 #[cfg(not(target_os = "android"))]
 pub mod management_interface;
 mod migrations;
+#[cfg(all(target_os = "linux", feature = "boringtun"))]
+mod obfuscation_probe;
 mod relay_list;
 #[cfg(not(target_os = "android"))]
 pub mod rpc_uniqueness_check;
@@ -40,21 +42,27 @@ use geoip::GeoIpHandler;
 use mullvad_relay_selector::{RelaySelector, SelectorConfig};
 #[cfg(target_os = "android")]
 use mullvad_types::account::{PlayPurchase, PlayPurchasePaymentToken};
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use mullvad_types::settings::DnsBlocklistOptions;
 use mullvad_types::{
     access_method::{AccessMethod, AccessMethodSetting},
     account::{AccountData, AccountToken, VoucherSubmission},
     auth_failed::AuthFailed,
+    constraints::Constraint,
     custom_list::CustomList,
     device::{Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState, RemoveDeviceEvent},
     location::{GeoIpLocation, LocationEventData},
     relay_constraints::{
-        BridgeSettings, BridgeState, BridgeType, ObfuscationSettings, RelayOverride, RelaySettings,
+        BridgeSettings, BridgeState, BridgeType, LocationConstraint, ObfuscationSettings,
+        RelayOverride, RelaySettings,
     },
     relay_list::RelayList,
     settings::{DnsOptions, Settings},
     states::{TargetState, TunnelState},
     version::{AppVersion, AppVersionInfo},
-    wireguard::{PublicKey, QuantumResistantState, RotationInterval},
+    wireguard::{
+        PskRekeyInterval, PublicKey, QuantumResistantState, RotationInterval, WgQuickConfig,
+    },
 };
 use relay_list::{RelayListUpdater, RelayListUpdaterHandle, RELAYS_FILENAME};
 use settings::SettingsPersister;
@@ -70,6 +78,8 @@ use std::{
     sync::{Arc, Weak},
     time::Duration,
 };
+#[cfg(target_os = "linux")]
+use talpid_core::dns::DnsManagerHandle;
 #[cfg(any(target_os = "linux", windows))]
 use talpid_core::split_tunnel;
 use talpid_core::{
@@ -78,10 +88,14 @@ use talpid_core::{
 };
 #[cfg(target_os = "android")]
 use talpid_types::android::AndroidContext;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use talpid_types::net::dns::BlocklistStats;
+#[cfg(target_os = "linux")]
+use talpid_types::net::dns::{DnsConfigurationDrift, DnsManager};
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 use talpid_types::{
-    net::{IpVersion, TunnelEndpoint, TunnelType},
+    net::{wireguard::ConnectivityCheckOptions, IpVersion, TunnelEndpoint, TunnelType},
     tunnel::{ErrorStateCause, TunnelStateTransition},
     ErrorExt,
 };
@@ -184,6 +198,9 @@ pub enum Error {
     #[error("No custom bridge has been specified")]
     NoCustomProxySaved,
 
+    #[error("Failed to export WireGuard configuration")]
+    ExportWireguardConfig(#[source] tunnel::Error),
+
     #[cfg(target_os = "macos")]
     #[error("Failed to set exclusion group")]
     GroupIdError(#[source] io::Error),
@@ -258,6 +275,18 @@ pub enum DaemonCommand {
     SetQuantumResistantTunnel(ResponseTx<(), settings::Error>, QuantumResistantState),
     /// Set DNS options or servers to use
     SetDnsOptions(ResponseTx<(), settings::Error>, DnsOptions),
+    /// Set user-supplied domain lists to enforce using the filtering resolver
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    SetDnsBlocklists(ResponseTx<(), settings::Error>, DnsBlocklistOptions),
+    /// Get entry and hit counts for the user-supplied domain lists
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    GetDnsBlocklistStats(oneshot::Sender<BlocklistStats>),
+    /// Set the system component used to manage DNS
+    #[cfg(target_os = "linux")]
+    SetDnsManager(ResponseTx<(), settings::Error>, DnsManager),
+    /// Get a handle that reports which DNS manager is in use
+    #[cfg(target_os = "linux")]
+    GetDnsManagerHandle(oneshot::Sender<DnsManagerHandle>),
     /// Set override options to use for a given relay
     SetRelayOverride(ResponseTx<(), settings::Error>, RelayOverride),
     /// Remove all relay override options
@@ -265,6 +294,12 @@ pub enum DaemonCommand {
     /// Toggle macOS network check leak
     /// Set MTU for wireguard tunnels
     SetWireguardMtu(ResponseTx<(), settings::Error>, Option<u16>),
+    /// Set the persistent keepalive interval for wireguard tunnels
+    SetWireguardPersistentKeepalive(ResponseTx<(), settings::Error>, Option<u16>),
+    /// Set the parameters used to detect a broken wireguard tunnel
+    SetWireguardConnectivityCheck(ResponseTx<(), settings::Error>, ConnectivityCheckOptions),
+    /// Set how often a new quantum-resistant PSK is negotiated while connected
+    SetWireguardPskRekeyInterval(ResponseTx<(), settings::Error>, Option<PskRekeyInterval>),
     /// Set automatic key rotation interval for wireguard tunnels
     SetWireguardRotationInterval(ResponseTx<(), settings::Error>, Option<RotationInterval>),
     /// Get the daemon settings
@@ -273,6 +308,14 @@ pub enum DaemonCommand {
     RotateWireguardKey(ResponseTx<(), Error>),
     /// Return a public key of the currently set wireguard private key, if there is one
     GetWireguardKey(ResponseTx<Option<PublicKey>, Error>),
+    /// Return `wg-quick` configuration files for WireGuard relays at the given location, or for
+    /// the last used WireGuard relays, optionally including preshared keys. These contain the
+    /// private key.
+    ExportWireguardConfig(
+        ResponseTx<Vec<WgQuickConfig>, Error>,
+        bool,
+        Option<Constraint<LocationConstraint>>,
+    ),
     /// Create custom list
     CreateCustomList(ResponseTx<mullvad_types::custom_list::Id, Error>, String),
     /// Delete custom list
@@ -294,6 +337,10 @@ pub enum DaemonCommand {
     UpdateApiAccessMethod(ResponseTx<(), Error>, AccessMethodSetting),
     /// Get the currently used API access method
     GetCurrentAccessMethod(ResponseTx<AccessMethodSetting, Error>),
+    /// Get the observed outcomes of API requests made using each API access method
+    GetApiAccessMethodStatistics(ResponseTx<api::Statistics, Error>),
+    /// Forget the observed outcomes of API requests made using API access methods
+    ResetApiAccessMethodStatistics(ResponseTx<(), Error>),
     /// Test an API access method
     TestApiAccessMethodById(ResponseTx<bool, Error>, mullvad_types::access_method::Id),
     /// Test a custom API access method
@@ -612,6 +659,13 @@ pub trait EventListener {
 
     /// Notify that the api access method changed.
     fn notify_new_access_method_event(&self, new_access_method: AccessMethodSetting);
+
+    /// Notify that a new PSK could not be negotiated for the established tunnel.
+    fn notify_psk_renegotiation_failed(&self);
+
+    /// Notify that the system DNS configuration was changed by another program.
+    #[cfg(target_os = "linux")]
+    fn notify_dns_configuration_drift(&self, drift: DnsConfigurationDrift);
 }
 
 pub struct Daemon<L: EventListener> {
@@ -804,6 +858,9 @@ where
         });
 
         let (offline_state_tx, offline_state_rx) = mpsc::unbounded();
+        let (psk_renegotiation_failed_tx, mut psk_renegotiation_failed_rx) = mpsc::unbounded();
+        #[cfg(target_os = "linux")]
+        let (dns_drift_tx, mut dns_drift_rx) = mpsc::unbounded();
         #[cfg(target_os = "windows")]
         let (volume_update_tx, volume_update_rx) = mpsc::unbounded();
         let tunnel_state_machine_handle = tunnel_state_machine::spawn(
@@ -819,12 +876,19 @@ where
                 reset_firewall: *target_state != TargetState::Secured,
                 #[cfg(windows)]
                 exclude_paths,
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                dns_blocklists: dns::blocklist_config(&settings.tunnel_options.dns_options),
+                #[cfg(target_os = "linux")]
+                dns_manager: settings.tunnel_options.dns_options.manager,
             },
             parameters_generator.clone(),
             log_dir,
             resource_dir.clone(),
             internal_event_tx.to_specialized_sender(),
             offline_state_tx,
+            psk_renegotiation_failed_tx,
+            #[cfg(target_os = "linux")]
+            dns_drift_tx,
             #[cfg(target_os = "windows")]
             volume_update_rx,
             #[cfg(target_os = "android")]
@@ -840,6 +904,23 @@ where
 
         api::forward_offline_state(api_availability.clone(), offline_state_rx);
 
+        let psk_listener = event_listener.clone();
+        tokio::spawn(async move {
+            while let Some(()) = psk_renegotiation_failed_rx.next().await {
+                psk_listener.notify_psk_renegotiation_failed();
+            }
+        });
+
+        #[cfg(target_os = "linux")]
+        {
+            let drift_listener = event_listener.clone();
+            tokio::spawn(async move {
+                while let Some(drift) = dns_drift_rx.next().await {
+                    drift_listener.notify_dns_configuration_drift(drift);
+                }
+            });
+        }
+
         let relay_list_listener = event_listener.clone();
         let on_relay_list_update = move |relay_list: &RelayList| {
             relay_list_listener.notify_relay_list(relay_list.clone());
@@ -1239,17 +1320,39 @@ where
                     .await
             }
             SetDnsOptions(tx, dns_servers) => self.on_set_dns_options(tx, dns_servers).await,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            SetDnsBlocklists(tx, blocklists) => self.on_set_dns_blocklists(tx, blocklists).await,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            GetDnsBlocklistStats(tx) => self.on_get_dns_blocklist_stats(tx),
+            #[cfg(target_os = "linux")]
+            SetDnsManager(tx, manager) => self.on_set_dns_manager(tx, manager).await,
+            #[cfg(target_os = "linux")]
+            GetDnsManagerHandle(tx) => self.on_get_dns_manager_handle(tx),
             SetRelayOverride(tx, relay_override) => {
                 self.on_set_relay_override(tx, relay_override).await
             }
             ClearAllRelayOverrides(tx) => self.on_clear_all_relay_overrides(tx).await,
             SetWireguardMtu(tx, mtu) => self.on_set_wireguard_mtu(tx, mtu).await,
+            SetWireguardPersistentKeepalive(tx, interval) => {
+                self.on_set_wireguard_persistent_keepalive(tx, interval)
+                    .await
+            }
+            SetWireguardConnectivityCheck(tx, options) => {
+                self.on_set_wireguard_connectivity_check(tx, options).await
+            }
+            SetWireguardPskRekeyInterval(tx, interval) => {
+                self.on_set_wireguard_psk_rekey_interval(tx, interval).await
+            }
             SetWireguardRotationInterval(tx, interval) => {
                 self.on_set_wireguard_rotation_interval(tx, interval).await
             }
             GetSettings(tx) => self.on_get_settings(tx),
             RotateWireguardKey(tx) => self.on_rotate_wireguard_key(tx),
             GetWireguardKey(tx) => self.on_get_wireguard_key(tx).await,
+            ExportWireguardConfig(tx, include_psk, location) => {
+                self.on_export_wireguard_config(tx, include_psk, location)
+                    .await
+            }
             CreateCustomList(tx, name) => self.on_create_custom_list(tx, name).await,
             DeleteCustomList(tx, id) => self.on_delete_custom_list(tx, id).await,
             UpdateCustomList(tx, update) => self.on_update_custom_list(tx, update).await,
@@ -1261,6 +1364,8 @@ where
             RemoveApiAccessMethod(tx, method) => self.on_remove_api_access_method(tx, method).await,
             UpdateApiAccessMethod(tx, method) => self.on_update_api_access_method(tx, method).await,
             GetCurrentAccessMethod(tx) => self.on_get_current_api_access_method(tx),
+            GetApiAccessMethodStatistics(tx) => self.on_get_api_access_method_statistics(tx),
+            ResetApiAccessMethodStatistics(tx) => self.on_reset_api_access_method_statistics(tx),
             SetApiAccessMethod(tx, method) => self.on_set_api_access_method(tx, method).await,
             TestApiAccessMethodById(tx, method) => self.on_test_api_access_method(tx, method).await,
             TestCustomApiAccessMethod(tx, proxy) => self.on_test_proxy_as_access_method(tx, proxy),
@@ -2260,7 +2365,21 @@ where
     ) {
         match self
             .settings
-            .update(move |settings| settings.tunnel_options.dns_options = dns_options)
+            .update(move |settings| {
+                // Domain lists are only changed using `SetDnsBlocklists`
+                #[cfg(target_os = "macos")]
+                let dns_options = DnsOptions {
+                    blocklists: settings.tunnel_options.dns_options.blocklists.clone(),
+                    ..dns_options
+                };
+                // The DNS manager is only changed using `SetDnsManager`
+                #[cfg(target_os = "linux")]
+                let dns_options = DnsOptions {
+                    manager: settings.tunnel_options.dns_options.manager,
+                    ..dns_options
+                };
+                settings.tunnel_options.dns_options = dns_options
+            })
             .await
         {
             Ok(settings_changed) => {
@@ -2285,6 +2404,103 @@ where
         }
     }
 
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    async fn on_set_dns_blocklists(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        blocklists: DnsBlocklistOptions,
+    ) {
+        match self
+            .settings
+            .update(move |settings| settings.tunnel_options.dns_options.blocklists = blocklists)
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    let settings = self.settings.to_settings();
+                    self.send_tunnel_command(TunnelCommand::DnsBlocklists(
+                        dns::blocklist_config(&settings.tunnel_options.dns_options),
+                        oneshot_map(tx, |tx, ()| {
+                            Self::oneshot_send(tx, Ok(()), "set_dns_blocklists response");
+                        }),
+                    ));
+                } else {
+                    Self::oneshot_send(tx, Ok(()), "set_dns_blocklists response");
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_dns_blocklists response");
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn on_set_dns_manager(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        manager: DnsManager,
+    ) {
+        match self
+            .settings
+            .update(move |settings| settings.tunnel_options.dns_options.manager = manager)
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.send_tunnel_command(TunnelCommand::DnsManager(
+                        manager,
+                        oneshot_map(tx, |tx, ()| {
+                            Self::oneshot_send(tx, Ok(()), "set_dns_manager response");
+                        }),
+                    ));
+                } else {
+                    Self::oneshot_send(tx, Ok(()), "set_dns_manager response");
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_dns_manager response");
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn on_get_dns_manager_handle(&self, tx: oneshot::Sender<DnsManagerHandle>) {
+        Self::oneshot_send(
+            tx,
+            self.tunnel_state_machine_handle.dns_manager().clone(),
+            "get_dns_manager_handle response",
+        );
+    }
+
+    #[cfg(target_os = "macos")]
+    fn on_get_dns_blocklist_stats(&self, tx: oneshot::Sender<BlocklistStats>) {
+        let resolver = self
+            .tunnel_state_machine_handle
+            .filtering_resolver()
+            .clone();
+        tokio::spawn(async move {
+            Self::oneshot_send(
+                tx,
+                resolver.blocklist_stats().await,
+                "get_dns_blocklist_stats response",
+            );
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    fn on_get_dns_blocklist_stats(&self, tx: oneshot::Sender<BlocklistStats>) {
+        let dns_manager = self.tunnel_state_machine_handle.dns_manager().clone();
+        tokio::spawn(async move {
+            Self::oneshot_send(
+                tx,
+                dns_manager.blocklist_stats().await,
+                "get_dns_blocklist_stats response",
+            );
+        });
+    }
+
     async fn on_set_relay_override(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2355,6 +2571,97 @@ where
         }
     }
 
+    async fn on_set_wireguard_persistent_keepalive(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        interval: Option<u16>,
+    ) {
+        match self
+            .settings
+            .update(move |settings| {
+                settings.tunnel_options.wireguard.persistent_keepalive = interval
+            })
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_wireguard_persistent_keepalive response");
+                if settings_changed {
+                    if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
+                        log::info!(
+                            "Initiating tunnel restart because the WireGuard keepalive setting changed"
+                        );
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_wireguard_persistent_keepalive response");
+            }
+        }
+    }
+
+    async fn on_set_wireguard_connectivity_check(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        options: ConnectivityCheckOptions,
+    ) {
+        match self
+            .settings
+            .update(move |settings| settings.tunnel_options.wireguard.connectivity_check = options)
+            .await
+        {
+            Ok(settings_changed) => {
+                Self::oneshot_send(tx, Ok(()), "set_wireguard_connectivity_check response");
+                if settings_changed {
+                    if let Some(TunnelType::Wireguard) = self.get_connected_tunnel_type() {
+                        log::info!(
+                            "Initiating tunnel restart because the WireGuard connectivity check settings changed"
+                        );
+                        self.reconnect_tunnel();
+                    }
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_wireguard_connectivity_check response");
+            }
+        }
+    }
+
+    async fn on_set_wireguard_psk_rekey_interval(
+        &mut self,
+        tx: ResponseTx<(), settings::Error>,
+        interval: Option<PskRekeyInterval>,
+    ) {
+        match self
+            .settings
+            .update(move |settings| settings.tunnel_options.wireguard.psk_rekey_interval = interval)
+            .await
+        {
+            Ok(settings_changed) => {
+                if settings_changed {
+                    self.send_tunnel_command(TunnelCommand::PskRekeyInterval(
+                        interval.map(Duration::from),
+                        oneshot_map(tx, |tx, ()| {
+                            Self::oneshot_send(
+                                tx,
+                                Ok(()),
+                                "set_wireguard_psk_rekey_interval response",
+                            );
+                        }),
+                    ));
+                } else {
+                    Self::oneshot_send(tx, Ok(()), "set_wireguard_psk_rekey_interval response");
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e.display_chain_with_msg("Unable to save settings"));
+                Self::oneshot_send(tx, Err(e), "set_wireguard_psk_rekey_interval response");
+            }
+        }
+    }
+
     async fn on_set_wireguard_rotation_interval(
         &mut self,
         tx: ResponseTx<(), settings::Error>,
@@ -2409,6 +2716,20 @@ where
         Self::oneshot_send(tx, result, "get_wireguard_key response");
     }
 
+    async fn on_export_wireguard_config(
+        &self,
+        tx: ResponseTx<Vec<WgQuickConfig>, Error>,
+        include_psk: bool,
+        location: Option<Constraint<LocationConstraint>>,
+    ) {
+        let result = self
+            .parameters_generator
+            .export_wireguard_config(include_psk, location)
+            .await
+            .map_err(Error::ExportWireguardConfig);
+        Self::oneshot_send(tx, result, "export_wireguard_config response");
+    }
+
     async fn on_create_custom_list(
         &mut self,
         tx: ResponseTx<mullvad_types::custom_list::Id, Error>,
@@ -2494,6 +2815,28 @@ where
         });
     }
 
+    fn on_get_api_access_method_statistics(&mut self, tx: ResponseTx<api::Statistics, Error>) {
+        let handle = self.access_mode_handler.clone();
+        tokio::spawn(async move {
+            let result = handle
+                .get_statistics()
+                .await
+                .map_err(Error::ApiConnectionModeError);
+            Self::oneshot_send(tx, result, "get_api_access_method_statistics response");
+        });
+    }
+
+    fn on_reset_api_access_method_statistics(&mut self, tx: ResponseTx<(), Error>) {
+        let handle = self.access_mode_handler.clone();
+        tokio::spawn(async move {
+            let result = handle
+                .reset_statistics()
+                .await
+                .map_err(Error::ApiConnectionModeError);
+            Self::oneshot_send(tx, result, "reset_api_access_method_statistics response");
+        });
+    }
+
     fn on_test_proxy_as_access_method(
         &mut self,
         tx: ResponseTx<bool, Error>,
@@ -1,4 +1,4 @@
-use crate::{account_history, device, DaemonCommand, DaemonCommandSender, EventListener};
+use crate::{account_history, device, tunnel, DaemonCommand, DaemonCommandSender, EventListener};
 use futures::{
     channel::{mpsc, oneshot},
     StreamExt,
@@ -8,18 +8,22 @@ use mullvad_management_interface::{
     types::{self, daemon_event, management_service_server::ManagementService},
     Code, Request, Response, Status,
 };
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use mullvad_types::settings::DnsBlocklistOptions;
 #[cfg(not(target_os = "android"))]
 use mullvad_types::settings::DnsOptions;
 use mullvad_types::{
     account::AccountToken,
+    constraints::Constraint,
     relay_constraints::{
-        BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
+        BridgeSettings, BridgeState, LocationConstraint, ObfuscationSettings, RelayOverride,
+        RelaySettings,
     },
     relay_list::RelayList,
     settings::Settings,
     states::{TargetState, TunnelState},
     version,
-    wireguard::{RotationInterval, RotationIntervalError},
+    wireguard::{PskRekeyInterval, RotationInterval, RotationIntervalError},
 };
 #[cfg(windows)]
 use std::path::PathBuf;
@@ -28,6 +32,11 @@ use std::{
     sync::{Arc, Mutex},
     time::Duration,
 };
+#[cfg(target_os = "linux")]
+use talpid_core::dns::DnsManagerHandle;
+#[cfg(target_os = "linux")]
+use talpid_types::net::dns::DnsManager;
+use talpid_types::net::wireguard::ConnectivityCheckOptions;
 use talpid_types::ErrorExt;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
@@ -302,6 +311,56 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    async fn set_wireguard_persistent_keepalive(&self, request: Request<u32>) -> ServiceResult<()> {
+        let interval = request.into_inner();
+        let interval = if interval != 0 {
+            Some(u16::try_from(interval).map_err(|_| {
+                Status::invalid_argument("the keepalive interval must be at most 65535 seconds")
+            })?)
+        } else {
+            None
+        };
+        log::debug!("set_wireguard_persistent_keepalive({:?})", interval);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardPersistentKeepalive(tx, interval))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn set_wireguard_connectivity_check(
+        &self,
+        request: Request<types::ConnectivityCheckOptions>,
+    ) -> ServiceResult<()> {
+        let options = ConnectivityCheckOptions::try_from(request.into_inner())
+            .map_err(map_protobuf_type_err)?;
+        log::debug!("set_wireguard_connectivity_check({:?})", options);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardConnectivityCheck(tx, options))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    async fn set_wireguard_psk_rekey_interval(
+        &self,
+        request: Request<types::Duration>,
+    ) -> ServiceResult<()> {
+        let interval = Duration::try_from(request.into_inner())
+            .map_err(|_| Status::invalid_argument("invalid duration"))?;
+        let interval = if interval.is_zero() {
+            None
+        } else {
+            Some(
+                PskRekeyInterval::try_from(interval)
+                    .map_err(|error| Status::invalid_argument(error.to_string()))?,
+            )
+        };
+        log::debug!("set_wireguard_psk_rekey_interval({:?})", interval);
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetWireguardPskRekeyInterval(tx, interval))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
     async fn set_enable_ipv6(&self, request: Request<bool>) -> ServiceResult<()> {
         let enable_ipv6 = request.into_inner();
         log::debug!("set_enable_ipv6({})", enable_ipv6);
@@ -341,6 +400,104 @@ impl ManagementService for ManagementServiceImpl {
         Ok(Response::new(()))
     }
 
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    async fn set_dns_blocklists(
+        &self,
+        request: Request<types::DnsBlocklists>,
+    ) -> ServiceResult<()> {
+        let options = DnsBlocklistOptions::from(request.into_inner());
+        log::debug!("set_dns_blocklists({:?})", options);
+
+        if options
+            .blocklists
+            .iter()
+            .chain(options.allowlists.iter())
+            .any(|path| !path.is_absolute())
+        {
+            return Err(Status::invalid_argument(
+                "domain list paths must be absolute",
+            ));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetDnsBlocklists(tx, options))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    async fn set_dns_blocklists(&self, _: Request<types::DnsBlocklists>) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "DNS blocklists are only supported on Linux and macOS",
+        ))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    async fn get_dns_blocklist_stats(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::DnsBlocklistStats> {
+        log::debug!("get_dns_blocklist_stats");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetDnsBlocklistStats(tx))?;
+        let stats = self.wait_for_result(rx).await?;
+        Ok(Response::new(types::DnsBlocklistStats::from(stats)))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    async fn get_dns_blocklist_stats(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::DnsBlocklistStats> {
+        Err(Status::unimplemented(
+            "DNS blocklists are only supported on Linux and macOS",
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn set_dns_manager(&self, request: Request<types::DnsManager>) -> ServiceResult<()> {
+        let manager = DnsManager::try_from(request.into_inner()).map_err(map_protobuf_type_err)?;
+        log::debug!("set_dns_manager({})", manager);
+
+        if let Some(overridden) = talpid_core::dns::manager_override() {
+            return Err(Status::failed_precondition(format!(
+                "The DNS manager is set to \"{overridden}\" using the {} environment variable",
+                talpid_core::dns::MANAGER_OVERRIDE_VAR
+            )));
+        }
+        resolve_dns_manager(self.get_dns_manager_handle().await?, manager).await?;
+
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::SetDnsManager(tx, manager))?;
+        self.wait_for_result(rx).await??;
+        Ok(Response::new(()))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn set_dns_manager(&self, _: Request<types::DnsManager>) -> ServiceResult<()> {
+        Err(Status::unimplemented(
+            "DNS managers can only be selected on Linux",
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn get_active_dns_manager(&self, _: Request<()>) -> ServiceResult<types::DnsManager> {
+        log::debug!("get_active_dns_manager");
+        let manager = self
+            .get_dns_manager_handle()
+            .await?
+            .active()
+            .ok_or_else(|| Status::not_found("No DNS configuration is applied"))?;
+        Ok(Response::new(types::DnsManager::from(manager)))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn get_active_dns_manager(&self, _: Request<()>) -> ServiceResult<types::DnsManager> {
+        Err(Status::unimplemented(
+            "DNS managers can only be selected on Linux",
+        ))
+    }
+
     async fn set_relay_override(
         &self,
         request: Request<types::RelayOverride>,
@@ -560,6 +717,37 @@ impl ManagementService for ManagementServiceImpl {
         }
     }
 
+    async fn export_wireguard_config(
+        &self,
+        request: Request<types::ExportWireguardConfigRequest>,
+    ) -> ServiceResult<types::WgQuickConfigs> {
+        log::debug!("export_wireguard_config");
+        let request = request.into_inner();
+        if !request.expose_private_key {
+            return Err(Status::failed_precondition(
+                "the exported configuration contains the private key, which must be confirmed",
+            ));
+        }
+        let location = request
+            .location
+            .map(Constraint::<LocationConstraint>::try_from)
+            .transpose()
+            .map_err(map_protobuf_type_err)?;
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ExportWireguardConfig(
+            tx,
+            request.include_psk,
+            location,
+        ))?;
+        let configs = self.wait_for_result(rx).await?.map_err(map_daemon_error)?;
+        Ok(Response::new(types::WgQuickConfigs {
+            configs: configs
+                .into_iter()
+                .map(types::WgQuickConfig::from)
+                .collect(),
+        }))
+    }
+
     // Custom lists
     //
 
@@ -679,6 +867,30 @@ impl ManagementService for ManagementServiceImpl {
             .map_err(map_daemon_error)
     }
 
+    async fn get_api_access_method_statistics(
+        &self,
+        _: Request<()>,
+    ) -> ServiceResult<types::AccessMethodStatisticsList> {
+        log::debug!("get_api_access_method_statistics");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetApiAccessMethodStatistics(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(types::AccessMethodStatisticsList::from)
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
+    async fn reset_api_access_method_statistics(&self, _: Request<()>) -> ServiceResult<()> {
+        log::debug!("reset_api_access_method_statistics");
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::ResetApiAccessMethodStatistics(tx))?;
+        self.wait_for_result(rx)
+            .await?
+            .map(Response::new)
+            .map_err(map_daemon_error)
+    }
+
     async fn test_custom_api_access_method(
         &self,
         config: Request<types::CustomProxy>,
@@ -930,6 +1142,13 @@ impl ManagementServiceImpl {
     async fn wait_for_result<T>(&self, rx: oneshot::Receiver<T>) -> Result<T, Status> {
         rx.await.map_err(|_| Status::internal("sender was dropped"))
     }
+
+    #[cfg(target_os = "linux")]
+    async fn get_dns_manager_handle(&self) -> Result<DnsManagerHandle, Status> {
+        let (tx, rx) = oneshot::channel();
+        self.send_command_to_daemon(DaemonCommand::GetDnsManagerHandle(tx))?;
+        self.wait_for_result(rx).await
+    }
 }
 
 pub struct ManagementInterfaceServer(());
@@ -1046,6 +1265,25 @@ impl EventListener for ManagementInterfaceEventBroadcaster {
             )),
         })
     }
+
+    fn notify_psk_renegotiation_failed(&self) {
+        log::debug!("Broadcasting PSK renegotiation failure event");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::PskRenegotiationFailed(
+                types::PskRenegotiationFailed {},
+            )),
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn notify_dns_configuration_drift(&self, drift: talpid_types::net::dns::DnsConfigurationDrift) {
+        log::debug!("Broadcasting DNS configuration drift event");
+        self.notify(types::DaemonEvent {
+            event: Some(daemon_event::Event::DnsConfigurationDrift(
+                types::DnsConfigurationDrift::from(drift),
+            )),
+        })
+    }
 }
 
 impl ManagementInterfaceEventBroadcaster {
@@ -1055,6 +1293,24 @@ impl ManagementInterfaceEventBroadcaster {
     }
 }
 
+/// Returns the DNS manager that will be used if `manager` is preferred, or an error if it cannot be
+/// used on this system.
+#[cfg(target_os = "linux")]
+async fn resolve_dns_manager(
+    handle: DnsManagerHandle,
+    manager: DnsManager,
+) -> Result<DnsManager, Status> {
+    // Detecting DNS managers involves blocking D-Bus calls
+    tokio::task::spawn_blocking(move || handle.resolve(manager))
+        .await
+        .map_err(|_| Status::internal("DNS manager detection failed"))?
+        .map_err(|error| {
+            Status::failed_precondition(
+                error.display_chain_with_msg(&format!("DNS manager \"{manager}\" cannot be used")),
+            )
+        })
+}
+
 /// Converts [`mullvad_daemon::Error`] into a tonic status.
 fn map_daemon_error(error: crate::Error) -> Status {
     use crate::Error as DaemonError;
@@ -1076,6 +1332,12 @@ fn map_daemon_error(error: crate::Error) -> Status {
         DaemonError::NoAccountToken | DaemonError::NoAccountTokenHistory => {
             Status::unauthenticated(error.to_string())
         }
+        DaemonError::ExportWireguardConfig(tunnel::Error::NoAuthDetails) => {
+            Status::unauthenticated(error.to_string())
+        }
+        DaemonError::ExportWireguardConfig(
+            tunnel::Error::NoWireguardParameters | tunnel::Error::SelectRelay(_),
+        ) => Status::not_found(error.to_string()),
         DaemonError::CustomListExists => Status::with_details(
             Code::AlreadyExists,
             error.to_string(),
@@ -1117,6 +1379,9 @@ fn map_rest_error(error: &RestError) -> Status {
         }
         RestError::TimeoutError => Status::deadline_exceeded("API request timed out"),
         RestError::HyperError(_) => Status::unavailable("Cannot reach the API"),
+        RestError::UntrustedCertificate(_) | RestError::PinnedKeyMismatch => {
+            Status::unavailable("Cannot verify the API certificate")
+        }
         error => Status::unknown(format!("REST error: {error}")),
     }
 }
@@ -38,8 +38,7 @@ pub(crate) fn generate_device(
                 })
         });
 
-        let api_handle = rest_handle.availability.clone();
-        let service = DeviceService::new(rest_handle, api_handle);
+        let service = DeviceService::new(rest_handle);
         let result = match (migration_data.token, wg_data) {
             (token, Some(wg_data)) => {
                 log::info!("Creating a new device cache from previous settings");
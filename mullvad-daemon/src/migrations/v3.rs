@@ -71,6 +71,10 @@ pub fn migrate(settings: &mut serde_json::Value) -> Result<()> {
                 state: new_state,
                 default_options: DefaultDnsOptions::default(),
                 custom_options: CustomDnsOptions { addresses },
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                blocklists: Default::default(),
+                #[cfg(target_os = "linux")]
+                manager: Default::default(),
             });
         }
     }
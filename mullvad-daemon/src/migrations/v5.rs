@@ -168,6 +168,7 @@ fn create_migrated_obfuscation_settings(port: Constraint<u16>) -> ObfuscationSet
     ObfuscationSettings {
         selected_obfuscation: SelectedObfuscation::Udp2Tcp,
         udp2tcp: Udp2TcpObfuscationSettings { port },
+        ..Default::default()
     }
 }
 
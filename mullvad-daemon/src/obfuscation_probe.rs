@@ -0,0 +1,508 @@
+//! Probes whether plain UDP traffic to the relays is blocked on the current network, so that
+//! obfuscation can be used right away instead of after several connection attempts have timed
+//! out. Results are cached per network.
+//!
+//! The probes are sent before connecting, from sockets marked with the tunnel fwmark. The tunnel
+//! state machine lets such traffic reach the endpoints returned by
+//! [`ObfuscationProbe::endpoints`] while the probe runs.
+
+use boringtun::{
+    noise::{Tunn, TunnResult},
+    x25519,
+};
+use nix::sys::socket::{setsockopt, sockopt};
+use std::{
+    collections::HashMap,
+    fs, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    os::unix::io::AsRawFd,
+    time::{Duration, Instant},
+};
+use talpid_types::net::{
+    wireguard::{PrivateKey, PublicKey},
+    Endpoint, TransportProtocol,
+};
+use tokio::net::{TcpSocket, UdpSocket};
+
+/// How long to wait for the relay to respond to a probe.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(1500);
+/// How many WireGuard handshake initiations to send during the UDP probe.
+const UDP_PROBE_ATTEMPTS: u32 = 3;
+/// How long a probe result is trusted. Results expire since networks may change their policies,
+/// and since a relay that does not know about a newly rotated key does not respond to the UDP
+/// probe either.
+const PROBE_RESULT_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+const IPV4_ROUTES_PATH: &str = "/proc/net/route";
+const IPV6_ROUTES_PATH: &str = "/proc/net/ipv6_route";
+const ARP_TABLE_PATH: &str = "/proc/net/arp";
+
+/// Identifies a network by how it is reached: the interface and gateway of the default route,
+/// along with the hardware address of the gateway if it is known. Unlike the local address, this
+/// tells apart networks that hand out addresses from the same private range.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NetworkIdentity {
+    interface: String,
+    gateway: IpAddr,
+    gateway_mac: Option<String>,
+}
+
+/// Returns the identity of the network that traffic to `relay_ip` leaves through, or `None` if
+/// there is no default route for its address family.
+pub fn network_identity(relay_ip: IpAddr) -> Option<NetworkIdentity> {
+    let (interface, gateway) = match relay_ip {
+        IpAddr::V4(_) => {
+            let routes = fs::read_to_string(IPV4_ROUTES_PATH).ok()?;
+            parse_ipv4_default_route(&routes)?
+        }
+        IpAddr::V6(_) => {
+            let routes = fs::read_to_string(IPV6_ROUTES_PATH).ok()?;
+            parse_ipv6_default_route(&routes)?
+        }
+    };
+    let gateway_mac = fs::read_to_string(ARP_TABLE_PATH)
+        .ok()
+        .and_then(|arp_table| parse_arp_entry(&arp_table, gateway, &interface));
+    Some(NetworkIdentity {
+        interface,
+        gateway,
+        gateway_mac,
+    })
+}
+
+/// Relay that should be probed before connecting to it.
+pub struct ProbeTarget {
+    /// Network that the result applies to.
+    network: NetworkIdentity,
+    /// WireGuard endpoint of the relay, reached using plain UDP.
+    wireguard_endpoint: SocketAddr,
+    /// Endpoint used to check whether the relay can be reached using UDP-over-TCP.
+    tcp_endpoint: SocketAddr,
+    private_key: PrivateKey,
+    peer_public_key: PublicKey,
+}
+
+impl ProbeTarget {
+    pub fn new(
+        network: NetworkIdentity,
+        wireguard_endpoint: SocketAddr,
+        private_key: PrivateKey,
+        peer_public_key: PublicKey,
+    ) -> Self {
+        ProbeTarget {
+            network,
+            wireguard_endpoint,
+            tcp_endpoint: SocketAddr::new(
+                wireguard_endpoint.ip(),
+                mullvad_relay_selector::UDP2TCP_PORTS[0],
+            ),
+            private_key,
+            peer_public_key,
+        }
+    }
+}
+
+/// Cache of probe results for the networks that have been probed.
+pub struct ObfuscationProbe {
+    results: HashMap<NetworkIdentity, ProbeResult>,
+    /// Mark set on the probe sockets, so that they are routed outside the tunnel and allowed by
+    /// the firewall.
+    fwmark: Option<u32>,
+}
+
+struct ProbeResult {
+    udp_blocked: bool,
+    probed_at: Instant,
+}
+
+impl ObfuscationProbe {
+    pub fn new(fwmark: Option<u32>) -> Self {
+        ObfuscationProbe {
+            results: HashMap::new(),
+            fwmark,
+        }
+    }
+
+    /// Returns whether plain UDP traffic is known to be blocked on `network`, or `None` if the
+    /// network has not been probed recently.
+    pub fn udp_blocked(&self, network: &NetworkIdentity) -> Option<bool> {
+        self.results
+            .get(network)
+            .filter(|result| result.probed_at.elapsed() < PROBE_RESULT_TIMEOUT)
+            .map(|result| result.udp_blocked)
+    }
+
+    /// Returns the endpoints that [`ObfuscationProbe::probe`] sends traffic to for `target`.
+    pub fn endpoints(target: &ProbeTarget) -> Vec<Endpoint> {
+        vec![
+            Endpoint::from_socket_address(target.wireguard_endpoint, TransportProtocol::Udp),
+            Endpoint::from_socket_address(target.tcp_endpoint, TransportProtocol::Tcp),
+        ]
+    }
+
+    /// Probes whether UDP traffic is blocked on the network of `target` by sending a WireGuard
+    /// handshake to the relay, and by connecting to one of its UDP-over-TCP ports.
+    ///
+    /// The result is only conclusive, and cached, if the relay can be reached in some way.
+    /// Otherwise the network is likely offline or blocking everything, and `None` is returned.
+    pub async fn probe(&mut self, target: ProbeTarget) -> Option<bool> {
+        let (udp_result, tcp_result) = tokio::join!(
+            self.probe_udp(
+                target.wireguard_endpoint,
+                &target.private_key,
+                &target.peer_public_key
+            ),
+            self.probe_tcp(target.tcp_endpoint),
+        );
+        let udp_reachable = udp_result.unwrap_or_else(|error| {
+            log::debug!("UDP probe failed: {error}");
+            false
+        });
+        let tcp_reachable = tcp_result.unwrap_or_else(|error| {
+            log::debug!("TCP probe failed: {error}");
+            false
+        });
+        log::debug!(
+            "Probed relay: UDP {}, TCP {}",
+            reachability(udp_reachable),
+            reachability(tcp_reachable),
+        );
+
+        if !udp_reachable && !tcp_reachable {
+            return None;
+        }
+        let udp_blocked = !udp_reachable;
+        if udp_blocked {
+            log::info!("UDP appears to be blocked on the current network");
+        }
+        self.results.insert(
+            target.network,
+            ProbeResult {
+                udp_blocked,
+                probed_at: Instant::now(),
+            },
+        );
+        Some(udp_blocked)
+    }
+
+    /// Sends WireGuard handshake initiations to `endpoint` and returns whether anything was
+    /// received in response. The relay only responds to handshakes from keys that it knows about.
+    async fn probe_udp(
+        &self,
+        endpoint: SocketAddr,
+        private_key: &PrivateKey,
+        peer_public_key: &PublicKey,
+    ) -> io::Result<bool> {
+        let bind_addr = match endpoint {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = UdpSocket::bind(bind_addr).await?;
+        self.set_fwmark(&socket)?;
+        socket.connect(endpoint).await?;
+
+        let mut tunn = Tunn::new(
+            x25519::StaticSecret::from(private_key.to_bytes()),
+            x25519::PublicKey::from(*peer_public_key.as_bytes()),
+            None,
+            None,
+            0,
+            None,
+        );
+        let mut send_buf = [0u8; 256];
+        let mut recv_buf = [0u8; 256];
+        for _ in 0..UDP_PROBE_ATTEMPTS {
+            let TunnResult::WriteToNetwork(initiation) =
+                tunn.format_handshake_initiation(&mut send_buf, true)
+            else {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "Failed to create handshake initiation",
+                ));
+            };
+            socket.send(initiation).await?;
+
+            let timeout = PROBE_TIMEOUT / UDP_PROBE_ATTEMPTS;
+            if let Ok(result) = tokio::time::timeout(timeout, socket.recv(&mut recv_buf)).await {
+                return result.map(|_| true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns whether a TCP connection to `endpoint` can be established.
+    async fn probe_tcp(&self, endpoint: SocketAddr) -> io::Result<bool> {
+        let socket = match endpoint {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        self.set_fwmark(&socket)?;
+        match tokio::time::timeout(PROBE_TIMEOUT, socket.connect(endpoint)).await {
+            Ok(result) => result.map(|_| true),
+            Err(_timeout) => Ok(false),
+        }
+    }
+
+    fn set_fwmark(&self, socket: &impl AsRawFd) -> io::Result<()> {
+        if let Some(fwmark) = self.fwmark {
+            setsockopt(socket.as_raw_fd(), sockopt::Mark, &fwmark)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the interface and gateway of the IPv4 default route with the lowest metric in the
+/// contents of `/proc/net/route`.
+fn parse_ipv4_default_route(routes: &str) -> Option<(String, IpAddr)> {
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (interface, destination, gateway, mask, metric) = (
+                fields.first()?,
+                fields.get(1)?,
+                fields.get(2)?,
+                fields.get(7)?,
+                fields.get(6)?,
+            );
+            if *destination != "00000000" || *mask != "00000000" {
+                return None;
+            }
+            // Addresses are written as native integers holding bytes in network order
+            let gateway = u32::from_str_radix(gateway, 16).ok()?;
+            let gateway = Ipv4Addr::from(gateway.to_ne_bytes());
+            let metric = metric.parse::<u32>().ok()?;
+            Some((metric, interface.to_string(), IpAddr::V4(gateway)))
+        })
+        .min_by_key(|(metric, ..)| *metric)
+        .map(|(_, interface, gateway)| (interface, gateway))
+}
+
+/// Returns the interface and gateway of the IPv6 default route with the lowest metric in the
+/// contents of `/proc/net/ipv6_route`.
+fn parse_ipv6_default_route(routes: &str) -> Option<(String, IpAddr)> {
+    routes
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (destination, prefix_len, gateway, metric, interface) = (
+                fields.first()?,
+                fields.get(1)?,
+                fields.get(4)?,
+                fields.get(5)?,
+                fields.get(9)?,
+            );
+            if destination.chars().any(|c| c != '0') || *prefix_len != "00" || *interface == "lo" {
+                return None;
+            }
+            let gateway = Ipv6Addr::from(u128::from_str_radix(gateway, 16).ok()?);
+            let metric = u32::from_str_radix(metric, 16).ok()?;
+            Some((metric, interface.to_string(), IpAddr::V6(gateway)))
+        })
+        .min_by_key(|(metric, ..)| *metric)
+        .map(|(_, interface, gateway)| (interface, gateway))
+}
+
+/// Returns the hardware address of `ip` on `interface` in the contents of `/proc/net/arp`.
+fn parse_arp_entry(arp_table: &str, ip: IpAddr, interface: &str) -> Option<String> {
+    arp_table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (entry_ip, hw_address, device) = (fields.first()?, fields.get(3)?, fields.get(5)?);
+        (entry_ip.parse::<IpAddr>().ok()? == ip
+            && *device == interface
+            && *hw_address != "00:00:00:00:00:00")
+            .then(|| hw_address.to_string())
+    })
+}
+
+fn reachability(reachable: bool) -> &'static str {
+    if reachable {
+        "reachable"
+    } else {
+        "unreachable"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    fn new_probe() -> ObfuscationProbe {
+        ObfuscationProbe::new(None)
+    }
+
+    fn network(gateway_mac: &str) -> NetworkIdentity {
+        NetworkIdentity {
+            interface: "wlan0".to_owned(),
+            gateway: IpAddr::from(Ipv4Addr::new(192, 168, 1, 1)),
+            gateway_mac: Some(gateway_mac.to_owned()),
+        }
+    }
+
+    fn target(
+        network: NetworkIdentity,
+        wireguard_endpoint: SocketAddr,
+        tcp_endpoint: SocketAddr,
+    ) -> ProbeTarget {
+        ProbeTarget {
+            network,
+            wireguard_endpoint,
+            tcp_endpoint,
+            private_key: PrivateKey::new_from_random(),
+            peer_public_key: PrivateKey::new_from_random().public_key(),
+        }
+    }
+
+    /// Binds a UDP socket that responds to every datagram, like a relay that accepts the key.
+    async fn spawn_udp_relay() -> SocketAddr {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 256];
+            while let Ok((len, peer)) = socket.recv_from(&mut buf).await {
+                let _ = socket.send_to(&buf[..len], peer).await;
+            }
+        });
+        addr
+    }
+
+    /// Binds a UDP socket that never responds, like a relay behind a network that drops UDP.
+    async fn spawn_silent_udp_relay() -> (UdpSocket, SocketAddr) {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        (socket, addr)
+    }
+
+    /// Returns a loopback address that nothing is listening on.
+    async fn closed_tcp_endpoint() -> SocketAddr {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    #[test]
+    fn test_probe_endpoints() {
+        let wireguard_endpoint = SocketAddr::from((Ipv4Addr::new(1, 2, 3, 4), 51820));
+        let endpoints = ObfuscationProbe::endpoints(&ProbeTarget::new(
+            network("00:00:5e:00:53:01"),
+            wireguard_endpoint,
+            PrivateKey::new_from_random(),
+            PrivateKey::new_from_random().public_key(),
+        ));
+        assert_eq!(
+            endpoints,
+            vec![
+                Endpoint::from_socket_address(wireguard_endpoint, TransportProtocol::Udp),
+                Endpoint::new(
+                    Ipv4Addr::new(1, 2, 3, 4),
+                    mullvad_relay_selector::UDP2TCP_PORTS[0],
+                    TransportProtocol::Tcp
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_ipv4_default_route() {
+        let routes = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+eth0\t00000000\t0100000A\t0003\t0\t0\t100\t00000000\t0\t0\t0
+eth0\t0000000A\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+";
+        assert_eq!(
+            parse_ipv4_default_route(routes),
+            Some(("eth0".to_owned(), IpAddr::from(Ipv4Addr::new(10, 0, 0, 1))))
+        );
+        assert_eq!(parse_ipv4_default_route(""), None);
+    }
+
+    #[test]
+    fn test_parse_ipv6_default_route() {
+        let routes = "\
+fd000000000000000000000000000000 40 00000000000000000000000000000000 00 00000000000000000000000000000000 00000100 00000001 00000000 00000001     eth0
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 fe800000000000000000000000000001 00000064 00000001 00000000 00000003     eth0
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 00000000000000000000000000000000 ffffffff 00000001 00000000 00200200       lo
+";
+        assert_eq!(
+            parse_ipv6_default_route(routes),
+            Some((
+                "eth0".to_owned(),
+                IpAddr::from(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_arp_entry() {
+        let arp_table = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.1      0x1         0x2         00:00:5e:00:53:01     *        wlan0
+10.0.0.1         0x1         0x0         00:00:00:00:00:00     *        eth0
+";
+        let gateway = IpAddr::from(Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(
+            parse_arp_entry(arp_table, gateway, "wlan0"),
+            Some("00:00:5e:00:53:01".to_owned())
+        );
+        assert_eq!(parse_arp_entry(arp_table, gateway, "eth0"), None);
+        let incomplete = IpAddr::from(Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(parse_arp_entry(arp_table, incomplete, "eth0"), None);
+    }
+
+    #[tokio::test]
+    async fn test_udp_reachable() {
+        let udp = spawn_udp_relay().await;
+        let tcp = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mut obfuscation_probe = new_probe();
+        let network = network("00:00:5e:00:53:01");
+
+        let target = target(network.clone(), udp, tcp.local_addr().unwrap());
+        let result = obfuscation_probe.probe(target).await;
+        assert_eq!(result, Some(false));
+        assert_eq!(obfuscation_probe.udp_blocked(&network), Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_udp_blocked() {
+        let (_udp_socket, udp) = spawn_silent_udp_relay().await;
+        let tcp = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mut obfuscation_probe = new_probe();
+        let network = network("00:00:5e:00:53:01");
+
+        let target = target(network.clone(), udp, tcp.local_addr().unwrap());
+        let result = obfuscation_probe.probe(target).await;
+        assert_eq!(result, Some(true));
+        assert_eq!(obfuscation_probe.udp_blocked(&network), Some(true));
+    }
+
+    /// Results only apply to the network that was probed, even if another network uses the same
+    /// gateway address.
+    #[tokio::test]
+    async fn test_udp_blocked_per_network() {
+        let udp = spawn_udp_relay().await;
+        let tcp = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let mut obfuscation_probe = new_probe();
+
+        let target = target(network("00:00:5e:00:53:01"), udp, tcp.local_addr().unwrap());
+        obfuscation_probe.probe(target).await;
+        assert_eq!(
+            obfuscation_probe.udp_blocked(&network("00:00:5e:00:53:02")),
+            None
+        );
+    }
+
+    /// If the relay cannot be reached at all, the result is not cached.
+    #[tokio::test]
+    async fn test_relay_unreachable() {
+        let (_udp_socket, udp) = spawn_silent_udp_relay().await;
+        let tcp = closed_tcp_endpoint().await;
+        let mut obfuscation_probe = new_probe();
+        let network = network("00:00:5e:00:53:01");
+
+        let target = target(network.clone(), udp, tcp);
+        assert_eq!(obfuscation_probe.probe(target).await, None);
+        assert_eq!(obfuscation_probe.udp_blocked(&network), None);
+    }
+}
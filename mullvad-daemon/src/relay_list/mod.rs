@@ -11,10 +11,12 @@ use std::{
 };
 use tokio::fs::File;
 
-use mullvad_api::{availability::ApiAvailabilityHandle, rest::MullvadRestHandle, RelayListProxy};
+use mullvad_api::{
+    rest::{MullvadRestHandle, RetryPolicy},
+    RelayListProxy,
+};
 use mullvad_relay_selector::RelaySelector;
 use mullvad_types::relay_list::RelayList;
-use talpid_future::retry::{retry_future, ExponentialBackoff, Jittered};
 use talpid_types::ErrorExt;
 
 /// How often the updater should wake up to check the cache of the in-memory cache of relays.
@@ -24,10 +26,11 @@ const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 15);
 /// How old the cached relays need to be to trigger an update
 const UPDATE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
-const DOWNLOAD_RETRY_STRATEGY: Jittered<ExponentialBackoff> = Jittered::jitter(
-    ExponentialBackoff::new(Duration::from_secs(16), 8)
-        .max_delay(Some(Duration::from_secs(2 * 60 * 60))),
-);
+const DOWNLOAD_RETRY_POLICY: RetryPolicy = RetryPolicy::new(None)
+    .backoff(Duration::from_secs(16), 8)
+    .max_delay(Duration::from_secs(2 * 60 * 60))
+    .retry_status(RetryPolicy::SERVER_ERRORS)
+    .when_bg_resumes();
 
 /// Where the relay list is cached on disk.
 pub(crate) const RELAYS_FILENAME: &str = "relays.json";
@@ -68,7 +71,6 @@ pub struct RelayListUpdater {
     relay_selector: RelaySelector,
     on_update: Box<dyn Fn(&RelayList) + Send + 'static>,
     last_check: SystemTime,
-    api_availability: ApiAvailabilityHandle,
 }
 
 impl RelayListUpdater {
@@ -79,15 +81,13 @@ impl RelayListUpdater {
         on_update: impl Fn(&RelayList) + Send + 'static,
     ) -> RelayListUpdaterHandle {
         let (tx, cmd_rx) = mpsc::channel(1);
-        let api_availability = api_handle.availability.clone();
-        let api_client = RelayListProxy::new(api_handle);
+        let api_client = RelayListProxy::new(api_handle.with_retry_policy(DOWNLOAD_RETRY_POLICY));
         let updater = RelayListUpdater {
             api_client,
             cache_path: cache_dir.join(RELAYS_FILENAME),
             relay_selector: selector,
             on_update: Box::new(on_update),
             last_check: UNIX_EPOCH,
-            api_availability,
         };
 
         tokio::spawn(updater.run(cmd_rx));
@@ -105,7 +105,7 @@ impl RelayListUpdater {
                 _check_update = next_check => {
                     if download_future.is_terminated() && self.should_update() {
                         let tag = self.relay_selector.etag();
-                        download_future = Box::pin(Self::download_relay_list(self.api_availability.clone(), self.api_client.clone(), tag).fuse());
+                        download_future = Box::pin(Self::download_relay_list(self.api_client.clone(), tag).fuse());
                         self.last_check = SystemTime::now();
                     }
                 },
@@ -118,7 +118,7 @@ impl RelayListUpdater {
                     match cmd {
                         Some(()) => {
                             let tag = self.relay_selector.etag();
-                            download_future = Box::pin(Self::download_relay_list(self.api_availability.clone(), self.api_client.clone(), tag).fuse());
+                            download_future = Box::pin(Self::download_relay_list(self.api_client.clone(), tag).fuse());
                             self.last_check = SystemTime::now();
                         },
                         None => {
@@ -163,24 +163,12 @@ impl RelayListUpdater {
     }
 
     fn download_relay_list(
-        api_handle: ApiAvailabilityHandle,
         proxy: RelayListProxy,
         tag: Option<String>,
     ) -> impl Future<Output = Result<Option<RelayList>, mullvad_api::Error>> + 'static {
-        let download_futures = move || {
-            let available = api_handle.wait_background();
-            let req = proxy.relay_list(tag.clone());
-            async move {
-                available.await?;
-                req.await.map_err(mullvad_api::Error::from)
-            }
-        };
-
-        retry_future(
-            download_futures,
-            |result| result.is_err(),
-            DOWNLOAD_RETRY_STRATEGY,
-        )
+        proxy
+            .relay_list(tag)
+            .map(|result| result.map_err(mullvad_api::Error::from))
     }
 
     async fn update_cache(&mut self, new_relay_list: RelayList) -> Result<(), Error> {
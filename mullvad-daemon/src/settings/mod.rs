@@ -491,6 +491,17 @@ mod test {
             serde_json::from_str("2").expect("Failed to deserialize valid version");
     }
 
+    #[test]
+    fn test_deserialize_psk_rekey_interval() {
+        use mullvad_types::wireguard::PskRekeyInterval;
+
+        serde_json::from_str::<PskRekeyInterval>(r#"{"secs": 3600, "nanos": 0}"#)
+            .expect("Failed to deserialize valid PSK rekey interval");
+        // A short interval would renegotiate the PSK continuously
+        assert!(serde_json::from_str::<PskRekeyInterval>(r#"{"secs": 0, "nanos": 0}"#).is_err());
+        assert!(serde_json::from_str::<PskRekeyInterval>(r#"{"secs": 1, "nanos": 0}"#).is_err());
+    }
+
     #[test]
     fn test_serialization_success() {
         let version = SettingsVersion::V2;
@@ -10,8 +10,9 @@ use tokio::sync::Mutex;
 
 use mullvad_relay_selector::{GetRelay, RelaySelector, RuntimeParameters, WireguardConfig};
 use mullvad_types::{
-    endpoint::MullvadWireguardEndpoint, location::GeoIpLocation, relay_list::Relay,
-    settings::TunnelOptions,
+    constraints::Constraint, endpoint::MullvadWireguardEndpoint, location::GeoIpLocation,
+    relay_constraints::LocationConstraint, relay_list::Relay, settings::TunnelOptions,
+    wireguard::WgQuickConfig,
 };
 use once_cell::sync::Lazy;
 use talpid_core::tunnel_state_machine::TunnelParametersGenerator;
@@ -26,6 +27,8 @@ use talpid_types::net::{obfuscation::ObfuscatorConfig, wireguard, TunnelParamete
 use talpid_types::{tunnel::ParameterGenerationError, ErrorExt};
 
 use crate::device::{AccountManagerHandle, PrivateAccountAndDevice};
+#[cfg(all(target_os = "linux", feature = "boringtun"))]
+use crate::obfuscation_probe::{self, ObfuscationProbe, ProbeTarget};
 
 /// The IP-addresses that the client uses when it connects to a server that supports the
 /// "Same IP" functionality. This means all clients have the same in-tunnel IP on these
@@ -49,6 +52,9 @@ pub enum Error {
 
     #[error("Failed to resolve hostname for custom relay")]
     ResolveCustomHostname,
+
+    #[error("No WireGuard tunnel parameters have been generated")]
+    NoWireguardParameters,
 }
 
 #[derive(Clone)]
@@ -58,8 +64,14 @@ struct InnerParametersGenerator {
     relay_selector: RelaySelector,
     tunnel_options: TunnelOptions,
     account_manager: AccountManagerHandle,
+    #[cfg(all(target_os = "linux", feature = "boringtun"))]
+    obfuscation_probe: ObfuscationProbe,
+    /// Relay in the last generated parameters that should be probed before connecting to it.
+    #[cfg(all(target_os = "linux", feature = "boringtun"))]
+    pending_probe: Option<PendingProbe>,
 
     last_generated_relays: Option<LastSelectedRelays>,
+    last_wireguard_connection: Option<wireguard::ConnectionConfig>,
 }
 
 impl ParametersGenerator {
@@ -74,8 +86,13 @@ impl ParametersGenerator {
             relay_selector,
 
             account_manager,
+            #[cfg(all(target_os = "linux", feature = "boringtun"))]
+            obfuscation_probe: ObfuscationProbe::new(Some(mullvad_types::TUNNEL_FWMARK)),
+            #[cfg(all(target_os = "linux", feature = "boringtun"))]
+            pending_probe: None,
 
             last_generated_relays: None,
+            last_wireguard_connection: None,
         })))
     }
 
@@ -134,18 +151,115 @@ impl ParametersGenerator {
             obfuscator_hostname,
         })
     }
+
+    /// Returns `wg-quick` configuration files for WireGuard relays at `location`, which are
+    /// selected according to the current constraints, or for the relays in the last generated
+    /// WireGuard tunnel parameters if `location` is `None`. The current device key is used.
+    /// Multihop results in one file for the entry relay and one for the exit relay.
+    pub async fn export_wireguard_config(
+        &self,
+        include_psk: bool,
+        location: Option<Constraint<LocationConstraint>>,
+    ) -> Result<Vec<WgQuickConfig>, Error> {
+        let inner = self.0.lock().await;
+
+        let (connection, names) = match location {
+            Some(location) => inner.select_wireguard_connection(location).await?,
+            None => inner.last_wireguard_connection().await?,
+        };
+
+        let configs = connection.to_wg_quick(inner.tunnel_options.wireguard.mtu, include_psk);
+        Ok(names
+            .into_iter()
+            .zip(configs)
+            .map(|(name, contents)| WgQuickConfig { name, contents })
+            .collect())
+    }
 }
 
 impl InnerParametersGenerator {
+    /// Selects WireGuard relays at `location`, and returns the connection config for them along
+    /// with their hostnames, starting with the entry relay.
+    async fn select_wireguard_connection(
+        &self,
+        location: Constraint<LocationConstraint>,
+    ) -> Result<(wireguard::ConnectionConfig, Vec<String>), Error> {
+        let data = self.device().await?;
+        match self.relay_selector.get_wireguard_relay_at(location)? {
+            GetRelay::Wireguard {
+                endpoint, inner, ..
+            } => {
+                let names = match inner {
+                    WireguardConfig::Singlehop { exit } => vec![exit.hostname],
+                    WireguardConfig::Multihop { exit, entry } => {
+                        vec![entry.hostname, exit.hostname]
+                    }
+                };
+                let parameters = self.create_wireguard_tunnel_parameters(endpoint, data, None);
+                let connection =
+                    wireguard_connection(&parameters).ok_or(Error::NoWireguardParameters)?;
+                Ok((connection, names))
+            }
+            _ => Err(Error::NoWireguardParameters),
+        }
+    }
+
+    /// Returns the connection config in the last generated WireGuard tunnel parameters along
+    /// with the hostnames of its relays, starting with the entry relay.
+    async fn last_wireguard_connection(
+        &self,
+    ) -> Result<(wireguard::ConnectionConfig, Vec<String>), Error> {
+        let mut connection = self
+            .last_wireguard_connection
+            .clone()
+            .ok_or(Error::NoWireguardParameters)?;
+
+        let names = match &self.last_generated_relays {
+            Some(LastSelectedRelays::WireGuard {
+                wg_entry, wg_exit, ..
+            }) => {
+                // The key may have been rotated since the parameters were generated
+                let data = self.device().await?;
+                connection.tunnel = Self::wireguard_tunnel_config(&data);
+
+                wg_entry
+                    .iter()
+                    .chain(std::iter::once(wg_exit))
+                    .map(|relay| relay.hostname.clone())
+                    .collect()
+            }
+            // Custom tunnel endpoints use their own keys
+            _ => vec!["mullvad-custom".to_owned()],
+        };
+        Ok((connection, names))
+    }
+
     async fn generate(
         &mut self,
         retry_attempt: u32,
         ipv6: bool,
     ) -> Result<TunnelParameters, Error> {
         let data = self.device().await?;
+        let runtime_params = RuntimeParameters {
+            ipv6,
+            udp_blocked: false,
+        };
         let selected_relay = self
             .relay_selector
-            .get_relay(retry_attempt as usize, RuntimeParameters { ipv6 })?;
+            .get_relay(retry_attempt as usize, runtime_params.clone())?;
+        #[cfg(all(target_os = "linux", feature = "boringtun"))]
+        let selected_relay =
+            self.avoid_blocked_udp(selected_relay, retry_attempt, runtime_params)?;
+        #[cfg(all(target_os = "linux", feature = "boringtun"))]
+        {
+            self.pending_probe =
+                self.probe_target(&selected_relay, &data)
+                    .map(|target| PendingProbe {
+                        target,
+                        retry_attempt,
+                        ipv6,
+                    });
+        }
 
         match selected_relay {
             #[cfg(not(target_os = "android"))]
@@ -159,6 +273,7 @@ impl InnerParametersGenerator {
                     relay: exit.clone(),
                     bridge: bridge_relay.cloned(),
                 });
+                self.last_wireguard_connection = None;
                 let bridge_settings = bridge.as_ref().map(|bridge| bridge.settings());
                 Ok(self.create_openvpn_tunnel_parameters(endpoint, data, bridge_settings.cloned()))
             }
@@ -182,19 +297,104 @@ impl InnerParametersGenerator {
                     obfuscator: obfuscator_relay,
                 });
 
-                Ok(self.create_wireguard_tunnel_parameters(endpoint, data, obfuscator_config))
+                let parameters =
+                    self.create_wireguard_tunnel_parameters(endpoint, data, obfuscator_config);
+                self.last_wireguard_connection = wireguard_connection(&parameters);
+                Ok(parameters)
             }
             GetRelay::Custom(custom_relay) => {
                 self.last_generated_relays = None;
-                custom_relay
+                let parameters = custom_relay
                      // TODO: generate proxy settings for custom tunnels
                      .to_tunnel_parameters(self.tunnel_options.clone(), None)
                      .map_err(|e| {
                          log::error!("Failed to resolve hostname for custom tunnel config: {}", e);
                          Error::ResolveCustomHostname
-                     })
+                     })?;
+                self.last_wireguard_connection = wireguard_connection(&parameters);
+                Ok(parameters)
+            }
+        }
+    }
+
+    /// Selects a relay again, skipping those that would be reached using plain UDP, if plain UDP
+    /// traffic is known to be blocked on the current network.
+    #[cfg(all(target_os = "linux", feature = "boringtun"))]
+    fn avoid_blocked_udp(
+        &self,
+        selected_relay: GetRelay,
+        retry_attempt: u32,
+        mut runtime_params: RuntimeParameters,
+    ) -> Result<GetRelay, Error> {
+        let Some(relay_ip) = plain_udp_relay_ip(&selected_relay) else {
+            return Ok(selected_relay);
+        };
+        let udp_blocked = obfuscation_probe::network_identity(relay_ip)
+            .and_then(|network| self.obfuscation_probe.udp_blocked(&network));
+        if udp_blocked != Some(true) {
+            return Ok(selected_relay);
+        }
+        log::debug!("Skipping relays that would be reached using plain UDP");
+        runtime_params.udp_blocked = true;
+        Ok(self
+            .relay_selector
+            .get_relay(retry_attempt as usize, runtime_params)?)
+    }
+
+    /// Returns the relay to probe before connecting to `selected_relay`, if it would be reached
+    /// using plain UDP and the current network has not been probed recently.
+    #[cfg(all(target_os = "linux", feature = "boringtun"))]
+    fn probe_target(
+        &self,
+        selected_relay: &GetRelay,
+        data: &PrivateAccountAndDevice,
+    ) -> Option<ProbeTarget> {
+        let GetRelay::Wireguard {
+            endpoint,
+            obfuscator: None,
+            ..
+        } = selected_relay
+        else {
+            return None;
+        };
+        let peer = &endpoint.peer;
+        let network = obfuscation_probe::network_identity(peer.endpoint.ip())?;
+        if self.obfuscation_probe.udp_blocked(&network).is_some() {
+            return None;
+        }
+        Some(ProbeTarget::new(
+            network,
+            peer.endpoint,
+            data.device.wg_data.private_key.clone(),
+            peer.public_key.clone(),
+        ))
+    }
+
+    /// Returns the endpoints to probe before connecting using the last generated parameters.
+    #[cfg(target_os = "linux")]
+    fn probe_endpoints(&self) -> Vec<Endpoint> {
+        #[cfg(feature = "boringtun")]
+        if let Some(pending_probe) = &self.pending_probe {
+            return ObfuscationProbe::endpoints(&pending_probe.target);
+        }
+        vec![]
+    }
+
+    /// Probes the relay in the last generated parameters. Returns new parameters if plain UDP
+    /// turned out to be blocked on the current network, in which case relays that would be
+    /// reached using plain UDP are skipped.
+    #[cfg(target_os = "linux")]
+    async fn probe(&mut self) -> Result<Option<TunnelParameters>, Error> {
+        #[cfg(feature = "boringtun")]
+        if let Some(pending_probe) = self.pending_probe.take() {
+            if self.obfuscation_probe.probe(pending_probe.target).await == Some(true) {
+                return self
+                    .generate(pending_probe.retry_attempt, pending_probe.ipv6)
+                    .await
+                    .map(Some);
             }
         }
+        Ok(None)
     }
 
     #[cfg(not(target_os = "android"))]
@@ -223,10 +423,7 @@ impl InnerParametersGenerator {
     ) -> TunnelParameters {
         let tunnel_ipv4 = data.device.wg_data.addresses.ipv4_address.ip();
         let tunnel_ipv6 = data.device.wg_data.addresses.ipv6_address.ip();
-        let tunnel = wireguard::TunnelConfig {
-            private_key: data.device.wg_data.private_key,
-            addresses: vec![IpAddr::from(tunnel_ipv4), IpAddr::from(tunnel_ipv6)],
-        };
+        let tunnel = Self::wireguard_tunnel_config(&data);
         // FIXME: Used for debugging purposes during the migration to same IP. Remove when
         // the migration is over.
         if tunnel_ipv4 == *SAME_IP_V4 || tunnel_ipv6 == *SAME_IP_V6 {
@@ -256,6 +453,15 @@ impl InnerParametersGenerator {
         .into()
     }
 
+    fn wireguard_tunnel_config(data: &PrivateAccountAndDevice) -> wireguard::TunnelConfig {
+        let tunnel_ipv4 = data.device.wg_data.addresses.ipv4_address.ip();
+        let tunnel_ipv6 = data.device.wg_data.addresses.ipv6_address.ip();
+        wireguard::TunnelConfig {
+            private_key: data.device.wg_data.private_key.clone(),
+            addresses: vec![IpAddr::from(tunnel_ipv4), IpAddr::from(tunnel_ipv6)],
+        }
+    }
+
     async fn device(&self) -> Result<PrivateAccountAndDevice, Error> {
         self.account_manager
             .data()
@@ -279,25 +485,75 @@ impl TunnelParametersGenerator for ParametersGenerator {
             inner
                 .generate(retry_attempt, ipv6)
                 .await
-                .map_err(|error| match error {
-                    Error::SelectRelay(mullvad_relay_selector::Error::NoBridge) => {
-                        ParameterGenerationError::NoMatchingBridgeRelay
-                    }
-                    Error::ResolveCustomHostname => {
-                        ParameterGenerationError::CustomTunnelHostResultionError
-                    }
-                    error => {
-                        log::error!(
-                            "{}",
-                            error.display_chain_with_msg("Failed to generate tunnel parameters")
-                        );
-                        ParameterGenerationError::NoMatchingRelay
-                    }
-                })
+                .map_err(parameter_generation_error)
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn probe_endpoints(&mut self) -> Pin<Box<dyn Future<Output = Vec<Endpoint>>>> {
+        let generator = self.0.clone();
+        Box::pin(async move { generator.lock().await.probe_endpoints() })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn probe(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<TunnelParameters>, ParameterGenerationError>>>>
+    {
+        let generator = self.0.clone();
+        Box::pin(async move {
+            let mut inner = generator.lock().await;
+            inner.probe().await.map_err(parameter_generation_error)
         })
     }
 }
 
+fn parameter_generation_error(error: Error) -> ParameterGenerationError {
+    match error {
+        Error::SelectRelay(mullvad_relay_selector::Error::NoBridge) => {
+            ParameterGenerationError::NoMatchingBridgeRelay
+        }
+        Error::ResolveCustomHostname => ParameterGenerationError::CustomTunnelHostResultionError,
+        error => {
+            log::error!(
+                "{}",
+                error.display_chain_with_msg("Failed to generate tunnel parameters")
+            );
+            ParameterGenerationError::NoMatchingRelay
+        }
+    }
+}
+
+/// Relay to probe before connecting, along with the arguments that the parameters were generated
+/// with, so that they can be generated again if plain UDP turns out to be blocked.
+#[cfg(all(target_os = "linux", feature = "boringtun"))]
+struct PendingProbe {
+    target: ProbeTarget,
+    retry_attempt: u32,
+    ipv6: bool,
+}
+
+/// Returns the address of the relay that `selected_relay` would be reached at, if plain UDP would
+/// be used to reach it.
+#[cfg(all(target_os = "linux", feature = "boringtun"))]
+fn plain_udp_relay_ip(selected_relay: &GetRelay) -> Option<IpAddr> {
+    match selected_relay {
+        GetRelay::Wireguard {
+            endpoint,
+            obfuscator: None,
+            ..
+        } => Some(endpoint.peer.endpoint.ip()),
+        _ => None,
+    }
+}
+
+fn wireguard_connection(parameters: &TunnelParameters) -> Option<wireguard::ConnectionConfig> {
+    match parameters {
+        TunnelParameters::Wireguard(parameters) => Some(parameters.connection.clone()),
+        TunnelParameters::OpenVpn(_) => None,
+    }
+}
+
 /// Contains all relays that were selected last time when tunnel parameters were generated.
 enum LastSelectedRelays {
     /// Represents all relays generated for a WireGuard tunnel.
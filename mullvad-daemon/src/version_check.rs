@@ -16,7 +16,7 @@ use std::{
     time::Duration,
 };
 use talpid_core::mpsc::Sender;
-use talpid_future::retry::{retry_future, ConstantInterval};
+use talpid_future::retry::retry_future;
 use talpid_types::ErrorExt;
 use tokio::fs::{self, File};
 
@@ -32,8 +32,6 @@ const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(15);
 const UPDATE_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24);
 /// Wait this long until next try if an update failed
 const UPDATE_INTERVAL_ERROR: Duration = Duration::from_secs(60 * 60 * 6);
-/// Retry strategy for `RunVersionCheck`.
-const IMMEDIATE_RETRY_STRATEGY: ConstantInterval = ConstantInterval::new(Duration::ZERO, Some(3));
 
 #[cfg(target_os = "linux")]
 const PLATFORM: &str = "linux";
@@ -180,36 +178,16 @@ impl VersionUpdater {
     > {
         self.internal_done_tx = Some(done_tx);
 
-        let api_handle = self.availability_handle.clone();
-        let version_proxy = self.version_proxy.clone();
-        let platform_version = self.platform_version.clone();
-        let download_future_factory = move || {
-            version_proxy
+        // Network errors are retried by `AppVersionProxy`
+        Box::pin(
+            self.version_proxy
                 .version_check(
                     mullvad_version::VERSION.to_owned(),
                     PLATFORM,
-                    platform_version.clone(),
+                    self.platform_version.clone(),
                 )
-                .map_err(Error::Download)
-        };
-
-        Box::pin(retry_future(
-            download_future_factory,
-            move |result| Self::should_retry_immediate(result, &api_handle),
-            IMMEDIATE_RETRY_STRATEGY,
-        ))
-    }
-
-    fn should_retry_immediate<T>(
-        result: &Result<T, Error>,
-        api_handle: &ApiAvailabilityHandle,
-    ) -> bool {
-        match result {
-            Err(Error::Download(error)) if error.is_network_error() => {
-                !api_handle.get_state().is_offline()
-            }
-            _ => false,
-        }
+                .map_err(Error::Download),
+        )
     }
 
     fn create_update_background_future(
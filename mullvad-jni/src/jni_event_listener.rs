@@ -76,6 +76,9 @@ impl EventListener for JniEventListener {
     // the Android app.
     #[allow(dead_code, unused_variables)]
     fn notify_new_access_method_event(&self, access_method: AccessMethodSetting) {}
+
+    // PSK renegotiation is not supported on Android.
+    fn notify_psk_renegotiation_failed(&self) {}
 }
 
 struct JniEventHandler<'env> {
@@ -2,22 +2,33 @@
 
 use crate::types;
 use futures::{Stream, StreamExt};
+#[cfg(target_os = "macos")]
+use mullvad_types::settings::DnsBlocklistOptions;
 use mullvad_types::{
     access_method::{self, AccessMethod, AccessMethodSetting},
     account::{AccountData, AccountToken, VoucherSubmission},
+    constraints::Constraint,
     custom_list::{CustomList, Id},
     device::{Device, DeviceEvent, DeviceId, DeviceState, RemoveDeviceEvent},
     relay_constraints::{
-        BridgeSettings, BridgeState, ObfuscationSettings, RelayOverride, RelaySettings,
+        BridgeSettings, BridgeState, LocationConstraint, ObfuscationSettings, RelayOverride,
+        RelaySettings,
     },
     relay_list::RelayList,
     settings::{DnsOptions, Settings},
     states::TunnelState,
     version::AppVersionInfo,
-    wireguard::{PublicKey, QuantumResistantState, RotationInterval},
+    wireguard::{PublicKey, QuantumResistantState, RotationInterval, WgQuickConfig},
 };
+use std::collections::HashMap;
 use std::path::Path;
 use std::str::FromStr;
+#[cfg(target_os = "macos")]
+use talpid_types::net::dns::BlocklistStats;
+use talpid_types::net::dns::DnsConfigurationDrift;
+#[cfg(target_os = "linux")]
+use talpid_types::net::dns::DnsManager;
+use talpid_types::net::wireguard::ConnectivityCheckOptions;
 #[cfg(target_os = "windows")]
 use talpid_types::split_tunnel::ExcludedProcess;
 use tonic::{Code, Status};
@@ -37,6 +48,8 @@ pub enum DaemonEvent {
     Device(DeviceEvent),
     RemoveDevice(RemoveDeviceEvent),
     NewAccessMethod(AccessMethodSetting),
+    DnsConfigurationDrift(DnsConfigurationDrift),
+    PskRenegotiationFailed,
 }
 
 impl TryFrom<types::daemon_event::Event> for DaemonEvent {
@@ -67,6 +80,14 @@ impl TryFrom<types::daemon_event::Event> for DaemonEvent {
                     .map(DaemonEvent::NewAccessMethod)
                     .map_err(Error::InvalidResponse)
             }
+            types::daemon_event::Event::DnsConfigurationDrift(event) => {
+                DnsConfigurationDrift::try_from(event)
+                    .map(DaemonEvent::DnsConfigurationDrift)
+                    .map_err(Error::InvalidResponse)
+            }
+            types::daemon_event::Event::PskRenegotiationFailed(_) => {
+                Ok(DaemonEvent::PskRenegotiationFailed)
+            }
         }
     }
 }
@@ -213,6 +234,26 @@ impl MullvadProxyClient {
             })
     }
 
+    pub async fn get_api_access_method_statistics(
+        &mut self,
+    ) -> Result<HashMap<access_method::Id, access_method::AccessMethodStatistics>> {
+        let statistics = self
+            .0
+            .get_api_access_method_statistics(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        HashMap::try_from(statistics).map_err(Error::InvalidResponse)
+    }
+
+    pub async fn reset_api_access_method_statistics(&mut self) -> Result<()> {
+        self.0
+            .reset_api_access_method_statistics(())
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn test_api_access_method(&mut self, id: access_method::Id) -> Result<bool> {
         let result = self
             .0
@@ -327,6 +368,41 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    pub async fn set_wireguard_persistent_keepalive(
+        &mut self,
+        interval: Option<u16>,
+    ) -> Result<()> {
+        self.0
+            .set_wireguard_persistent_keepalive(interval.map(u32::from).unwrap_or(0))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn set_wireguard_connectivity_check(
+        &mut self,
+        options: &ConnectivityCheckOptions,
+    ) -> Result<()> {
+        self.0
+            .set_wireguard_connectivity_check(types::ConnectivityCheckOptions::from(options))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    pub async fn set_wireguard_psk_rekey_interval(
+        &mut self,
+        interval: Option<std::time::Duration>,
+    ) -> Result<()> {
+        let interval = types::Duration::try_from(interval.unwrap_or_default())
+            .map_err(|_| Error::DurationTooLarge)?;
+        self.0
+            .set_wireguard_psk_rekey_interval(interval)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
     pub async fn set_enable_ipv6(&mut self, state: bool) -> Result<()> {
         self.0.set_enable_ipv6(state).await.map_err(Error::Rpc)?;
         Ok(())
@@ -350,6 +426,47 @@ impl MullvadProxyClient {
         Ok(())
     }
 
+    #[cfg(target_os = "macos")]
+    pub async fn set_dns_blocklists(&mut self, options: DnsBlocklistOptions) -> Result<()> {
+        let options = types::DnsBlocklists::from(&options);
+        self.0
+            .set_dns_blocklists(options)
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    pub async fn get_dns_blocklist_stats(&mut self) -> Result<BlocklistStats> {
+        let stats = self
+            .0
+            .get_dns_blocklist_stats(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        BlocklistStats::try_from(stats).map_err(Error::InvalidResponse)
+    }
+
+    #[cfg(target_os = "linux")]
+    pub async fn set_dns_manager(&mut self, manager: DnsManager) -> Result<()> {
+        self.0
+            .set_dns_manager(types::DnsManager::from(manager))
+            .await
+            .map_err(Error::Rpc)?;
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    pub async fn get_active_dns_manager(&mut self) -> Result<DnsManager> {
+        let manager = self
+            .0
+            .get_active_dns_manager(())
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        DnsManager::try_from(manager).map_err(Error::InvalidResponse)
+    }
+
     pub async fn set_relay_override(&mut self, relay_override: RelayOverride) -> Result<()> {
         let r#override = types::RelayOverride::from(relay_override);
         self.0
@@ -509,6 +626,35 @@ impl MullvadProxyClient {
         PublicKey::try_from(key).map_err(Error::InvalidResponse)
     }
 
+    /// Returns `wg-quick` configuration files for WireGuard relays at `location`, or for the
+    /// last used WireGuard relays if `location` is `None`. Since these contain the private key,
+    /// `expose_private_key` must be set.
+    pub async fn export_wireguard_config(
+        &mut self,
+        expose_private_key: bool,
+        include_psk: bool,
+        location: Option<Constraint<LocationConstraint>>,
+    ) -> Result<Vec<WgQuickConfig>> {
+        let configs = self
+            .0
+            .export_wireguard_config(types::ExportWireguardConfigRequest {
+                expose_private_key,
+                include_psk,
+                location: location.map(|location| match location {
+                    Constraint::Any => types::LocationConstraint { r#type: None },
+                    Constraint::Only(location) => types::LocationConstraint::from(location),
+                }),
+            })
+            .await
+            .map_err(Error::Rpc)?
+            .into_inner();
+        Ok(configs
+            .configs
+            .into_iter()
+            .map(WgQuickConfig::from)
+            .collect())
+    }
+
     pub async fn create_custom_list(&mut self, name: String) -> Result<Id> {
         let id = self
             .0
@@ -60,7 +60,9 @@ mod data {
     use mullvad_types::access_method::{
         AccessMethod, AccessMethodSetting, BuiltInAccessMethod, Id,
     };
-    use talpid_types::net::proxy::{CustomProxy, Shadowsocks, Socks5Local, Socks5Remote};
+    use talpid_types::net::proxy::{
+        CustomProxy, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote,
+    };
 
     impl TryFrom<proto::AccessMethodSetting> for AccessMethodSetting {
         type Error = FromProtobufTypeError;
@@ -121,6 +123,20 @@ mod data {
                 proto::access_method::AccessMethod::Custom(custom) => {
                     CustomProxy::try_from(custom).map(AccessMethod::from)?
                 }
+                proto::access_method::AccessMethod::Chain(chain) => {
+                    if chain.proxies.is_empty() {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "Proxy chain must contain at least one proxy",
+                        ));
+                    }
+                    AccessMethod::Chain(
+                        chain
+                            .proxies
+                            .into_iter()
+                            .map(CustomProxy::try_from)
+                            .collect::<Result<_, _>>()?,
+                    )
+                }
             })
         }
     }
@@ -130,6 +146,13 @@ mod data {
             match value {
                 AccessMethod::Custom(value) => proto::AccessMethod::from(value),
                 AccessMethod::BuiltIn(value) => proto::AccessMethod::from(value),
+                AccessMethod::Chain(proxies) => proto::AccessMethod {
+                    access_method: Some(proto::access_method::AccessMethod::Chain(
+                        proto::access_method::Chain {
+                            proxies: proxies.into_iter().map(proto::CustomProxy::from).collect(),
+                        },
+                    )),
+                },
             }
         }
     }
@@ -162,6 +185,14 @@ mod data {
         }
     }
 
+    impl TryFrom<proto::HttpConnect> for AccessMethod {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(value: proto::HttpConnect) -> Result<Self, Self::Error> {
+            HttpConnect::try_from(value).map(AccessMethod::from)
+        }
+    }
+
     impl TryFrom<proto::Shadowsocks> for AccessMethod {
         type Error = FromProtobufTypeError;
 
@@ -237,3 +268,81 @@ mod data {
         }
     }
 }
+
+/// Implements conversions for the observed outcomes of requests made using
+/// each access method.
+mod statistics {
+    use crate::types::{proto, FromProtobufTypeError};
+    use chrono::TimeZone;
+    use mullvad_types::access_method::{AccessMethodStatistics, Id};
+    use std::collections::HashMap;
+
+    impl From<HashMap<Id, AccessMethodStatistics>> for proto::AccessMethodStatisticsList {
+        fn from(value: HashMap<Id, AccessMethodStatistics>) -> Self {
+            let statistics = value
+                .into_iter()
+                .map(|(id, statistics)| proto::AccessMethodStatistics {
+                    id: Some(proto::Uuid::from(id)),
+                    successes: statistics.successes,
+                    failures: statistics.failures,
+                    consecutive_failures: statistics.consecutive_failures,
+                    last_success: statistics.last_success.map(|last_success| {
+                        prost_types::Timestamp {
+                            seconds: last_success.timestamp(),
+                            nanos: 0,
+                        }
+                    }),
+                    latency: statistics
+                        .latency
+                        .and_then(|latency| prost_types::Duration::try_from(latency).ok()),
+                })
+                .collect();
+            proto::AccessMethodStatisticsList { statistics }
+        }
+    }
+
+    impl TryFrom<proto::AccessMethodStatisticsList> for HashMap<Id, AccessMethodStatistics> {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(value: proto::AccessMethodStatisticsList) -> Result<Self, Self::Error> {
+            value
+                .statistics
+                .into_iter()
+                .map(|statistics| {
+                    let id = Id::try_from(statistics.id.ok_or(
+                        FromProtobufTypeError::InvalidArgument("Missing access method id"),
+                    )?)?;
+                    let last_success = statistics
+                        .last_success
+                        .map(|last_success| {
+                            chrono::Utc
+                                .timestamp_opt(last_success.seconds, 0)
+                                .single()
+                                .ok_or(FromProtobufTypeError::InvalidArgument(
+                                    "Invalid time of last success",
+                                ))
+                        })
+                        .transpose()?;
+                    let latency = statistics
+                        .latency
+                        .map(|latency| {
+                            std::time::Duration::try_from(latency).map_err(|_| {
+                                FromProtobufTypeError::InvalidArgument("Invalid latency")
+                            })
+                        })
+                        .transpose()?;
+                    Ok((
+                        id,
+                        AccessMethodStatistics {
+                            successes: statistics.successes,
+                            failures: statistics.failures,
+                            consecutive_failures: statistics.consecutive_failures,
+                            last_success,
+                            latency,
+                        },
+                    ))
+                })
+                .collect()
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::types::{
-    conversions::{bytes_to_privkey, bytes_to_pubkey},
+    conversions::{bytes_to_privkey, bytes_to_pubkey, bytes_to_wg_key},
     proto, FromProtobufTypeError,
 };
 use talpid_types::net::wireguard;
@@ -32,6 +32,10 @@ impl TryFrom<proto::ConnectionConfig> for mullvad_types::ConnectionConfig {
                         },
                         username: config.username,
                         password: config.password,
+                        custom_server: config
+                            .custom_server
+                            .map(openvpn::CustomServerConfig::try_from)
+                            .transpose()?,
                     },
                 ))
             }
@@ -118,6 +122,9 @@ impl From<mullvad_types::ConnectionConfig> for proto::ConnectionConfig {
                         )),
                         username: config.username,
                         password: config.password,
+                        custom_server: config
+                            .custom_server
+                            .map(connection_config::openvpn_config::CustomServer::from),
                     })
                 }
                 mullvad_types::ConnectionConfig::Wireguard(config) => {
@@ -152,3 +159,169 @@ impl From<mullvad_types::ConnectionConfig> for proto::ConnectionConfig {
         }
     }
 }
+
+impl TryFrom<proto::connection_config::openvpn_config::CustomServer>
+    for talpid_types::net::openvpn::CustomServerConfig
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(
+        server: proto::connection_config::openvpn_config::CustomServer,
+    ) -> Result<Self, Self::Error> {
+        use proto::connection_config::openvpn_config::custom_server::verify_x509_name::NameType;
+        use talpid_types::net::openvpn;
+
+        let is_allowed = |value: &str, allowed: &[&str]| allowed.contains(&value);
+        if !server
+            .cipher
+            .iter()
+            .chain(&server.data_ciphers)
+            .all(|cipher| is_allowed(cipher, openvpn::ALLOWED_DATA_CIPHERS))
+        {
+            return Err(FromProtobufTypeError::InvalidArgument("invalid cipher"));
+        }
+        if !server
+            .auth
+            .iter()
+            .all(|auth| is_allowed(auth, openvpn::ALLOWED_AUTH_DIGESTS))
+        {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "invalid auth digest",
+            ));
+        }
+        if !server
+            .compress
+            .iter()
+            .all(|compress| is_allowed(compress, openvpn::ALLOWED_COMPRESSION))
+        {
+            return Err(FromProtobufTypeError::InvalidArgument(
+                "invalid compression algorithm",
+            ));
+        }
+        let key_direction = server
+            .key_direction
+            .map(|direction| match direction {
+                0 => Ok(0),
+                1 => Ok(1),
+                _ => Err(FromProtobufTypeError::InvalidArgument(
+                    "invalid key direction",
+                )),
+            })
+            .transpose()?;
+        let verify_x509_name = server
+            .verify_x509_name
+            .map(|verify| {
+                let name_type = match NameType::try_from(verify.name_type) {
+                    Ok(NameType::Subject) => openvpn::X509NameType::Subject,
+                    Ok(NameType::Name) => openvpn::X509NameType::Name,
+                    Ok(NameType::NamePrefix) => openvpn::X509NameType::NamePrefix,
+                    Err(_) => {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "invalid X.509 name type",
+                        ))
+                    }
+                };
+                Ok(openvpn::VerifyX509Name {
+                    name: verify.name,
+                    name_type,
+                })
+            })
+            .transpose()?;
+
+        Ok(openvpn::CustomServerConfig {
+            ca: server.ca,
+            cert: server.cert,
+            key: server.key,
+            tls_crypt: server.tls_crypt,
+            tls_auth: server.tls_auth,
+            key_direction,
+            cipher: server.cipher,
+            data_ciphers: server.data_ciphers,
+            auth: server.auth,
+            verify_x509_name,
+            compress: server.compress,
+            auth_user_pass: server.auth_user_pass,
+        })
+    }
+}
+
+impl From<talpid_types::net::openvpn::CustomServerConfig>
+    for proto::connection_config::openvpn_config::CustomServer
+{
+    fn from(server: talpid_types::net::openvpn::CustomServerConfig) -> Self {
+        use proto::connection_config::openvpn_config::custom_server::{
+            verify_x509_name::NameType, VerifyX509Name,
+        };
+        use talpid_types::net::openvpn::X509NameType;
+
+        Self {
+            ca: server.ca,
+            cert: server.cert,
+            key: server.key,
+            tls_crypt: server.tls_crypt,
+            cipher: server.cipher,
+            auth_user_pass: server.auth_user_pass,
+            tls_auth: server.tls_auth,
+            key_direction: server.key_direction.map(u32::from),
+            data_ciphers: server.data_ciphers,
+            auth: server.auth,
+            verify_x509_name: server.verify_x509_name.map(|verify| {
+                let name_type = match verify.name_type {
+                    X509NameType::Subject => NameType::Subject,
+                    X509NameType::Name => NameType::Name,
+                    X509NameType::NamePrefix => NameType::NamePrefix,
+                };
+                VerifyX509Name {
+                    name: verify.name,
+                    name_type: i32::from(name_type),
+                }
+            }),
+            compress: server.compress,
+        }
+    }
+}
+
+impl TryFrom<proto::CustomWireguardOptions> for mullvad_types::CustomWireguardOptions {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(options: proto::CustomWireguardOptions) -> Result<Self, Self::Error> {
+        let mtu = options
+            .mtu
+            .map(|mtu| {
+                u16::try_from(mtu)
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid MTU"))
+            })
+            .transpose()?;
+        let persistent_keepalive = options
+            .persistent_keepalive
+            .map(|interval| {
+                u16::try_from(interval).map_err(|_| {
+                    FromProtobufTypeError::InvalidArgument("invalid keepalive interval")
+                })
+            })
+            .transpose()?;
+        let psk = options
+            .psk
+            .map(|psk| {
+                bytes_to_wg_key(&psk, "invalid preshared key")
+                    .map(|psk| wireguard::PresharedKey::from(*psk))
+            })
+            .transpose()?;
+
+        Ok(mullvad_types::CustomWireguardOptions {
+            mtu,
+            persistent_keepalive,
+            psk,
+        })
+    }
+}
+
+impl From<mullvad_types::CustomWireguardOptions> for proto::CustomWireguardOptions {
+    fn from(options: mullvad_types::CustomWireguardOptions) -> Self {
+        Self {
+            mtu: options.mtu.map(u32::from),
+            persistent_keepalive: options.persistent_keepalive.map(u32::from),
+            psk: options.psk.map(|psk| psk.as_bytes().to_vec()),
+        }
+    }
+}
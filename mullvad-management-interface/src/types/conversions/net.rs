@@ -30,6 +30,10 @@ impl From<talpid_types::net::TunnelEndpoint> for proto::TunnelEndpoint {
                     )),
                     obfuscation_type: match obfuscation_endpoint.obfuscation_type {
                         net::ObfuscationType::Udp2Tcp => i32::from(proto::ObfuscationType::Udp2tcp),
+                        net::ObfuscationType::Shadowsocks => {
+                            i32::from(proto::ObfuscationType::Shadowsocks)
+                        }
+                        net::ObfuscationType::Quic => i32::from(proto::ObfuscationType::Quic),
                     },
                 }
             }),
@@ -37,9 +41,15 @@ impl From<talpid_types::net::TunnelEndpoint> for proto::TunnelEndpoint {
                 address: entry.address.to_string(),
                 protocol: i32::from(proto::TransportProtocol::from(entry.protocol)),
             }),
-            tunnel_metadata: endpoint
-                .tunnel_interface
-                .map(|tunnel_interface| proto::TunnelMetadata { tunnel_interface }),
+            tunnel_metadata: endpoint.tunnel_interface.map(|tunnel_interface| {
+                proto::TunnelMetadata {
+                    tunnel_interface,
+                    openvpn_session: endpoint.openvpn_session.map(proto::OpenvpnSession::from),
+                }
+            }),
+            reconnect_reason: endpoint
+                .reconnect_reason
+                .map(|failure| i32::from(proto::TunnelFailure::from(failure))),
         }
     }
 }
@@ -102,6 +112,10 @@ impl TryFrom<proto::TunnelEndpoint> for talpid_types::net::TunnelEndpoint {
                             Ok(proto::ObfuscationType::Udp2tcp) => {
                                 talpid_net::ObfuscationType::Udp2Tcp
                             }
+                            Ok(proto::ObfuscationType::Shadowsocks) => {
+                                talpid_net::ObfuscationType::Shadowsocks
+                            }
+                            Ok(proto::ObfuscationType::Quic) => talpid_net::ObfuscationType::Quic,
                             Err(_) => {
                                 return Err(FromProtobufTypeError::InvalidArgument(
                                     "unknown obfuscation type",
@@ -120,13 +134,67 @@ impl TryFrom<proto::TunnelEndpoint> for talpid_types::net::TunnelEndpoint {
                     })
                 })
                 .transpose()?,
+            openvpn_session: endpoint
+                .tunnel_metadata
+                .as_ref()
+                .and_then(|tunnel_metadata| tunnel_metadata.openvpn_session.clone())
+                .map(talpid_net::openvpn::SessionInfo::from),
             tunnel_interface: endpoint
                 .tunnel_metadata
                 .map(|tunnel_metadata| tunnel_metadata.tunnel_interface),
+            reconnect_reason: endpoint
+                .reconnect_reason
+                .map(try_tunnel_failure_from_i32)
+                .transpose()?,
         })
     }
 }
 
+impl From<talpid_types::tunnel::TunnelFailure> for proto::TunnelFailure {
+    fn from(failure: talpid_types::tunnel::TunnelFailure) -> Self {
+        use talpid_types::tunnel::TunnelFailure;
+        match failure {
+            TunnelFailure::TlsError => proto::TunnelFailure::TlsError,
+            TunnelFailure::ServerUnresponsive => proto::TunnelFailure::ServerUnresponsive,
+            TunnelFailure::ConnectionReset => proto::TunnelFailure::ConnectionReset,
+        }
+    }
+}
+
+fn try_tunnel_failure_from_i32(
+    failure: i32,
+) -> Result<talpid_types::tunnel::TunnelFailure, FromProtobufTypeError> {
+    use talpid_types::tunnel::TunnelFailure;
+    match proto::TunnelFailure::try_from(failure) {
+        Ok(proto::TunnelFailure::TlsError) => Ok(TunnelFailure::TlsError),
+        Ok(proto::TunnelFailure::ServerUnresponsive) => Ok(TunnelFailure::ServerUnresponsive),
+        Ok(proto::TunnelFailure::ConnectionReset) => Ok(TunnelFailure::ConnectionReset),
+        Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+            "invalid tunnel failure",
+        )),
+    }
+}
+
+impl From<talpid_types::net::openvpn::SessionInfo> for proto::OpenvpnSession {
+    fn from(session: talpid_types::net::openvpn::SessionInfo) -> Self {
+        proto::OpenvpnSession {
+            cipher: session.cipher,
+            pushed_options: session.pushed_options,
+            server_certificate: session.server_certificate,
+        }
+    }
+}
+
+impl From<proto::OpenvpnSession> for talpid_types::net::openvpn::SessionInfo {
+    fn from(session: proto::OpenvpnSession) -> Self {
+        talpid_types::net::openvpn::SessionInfo {
+            cipher: session.cipher,
+            pushed_options: session.pushed_options,
+            server_certificate: session.server_certificate,
+        }
+    }
+}
+
 impl From<talpid_types::net::TransportProtocol> for proto::TransportProtocol {
     fn from(protocol: talpid_types::net::TransportProtocol) -> Self {
         match protocol {
@@ -188,7 +256,7 @@ mod proxy {
 
     use crate::types::{proto, FromProtobufTypeError};
     use talpid_types::net::proxy::{
-        CustomProxy, Shadowsocks, Socks5Local, Socks5Remote, SocksAuth,
+        CustomProxy, HttpAuth, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote, SocksAuth,
     };
 
     impl TryFrom<proto::CustomProxy> for CustomProxy {
@@ -205,6 +273,9 @@ mod proxy {
                 Some(proto::custom_proxy::ProxyMethod::Shadowsocks(shadowsocks)) => {
                     CustomProxy::Shadowsocks(Shadowsocks::try_from(shadowsocks)?)
                 }
+                Some(proto::custom_proxy::ProxyMethod::HttpConnect(http)) => {
+                    CustomProxy::HttpConnect(HttpConnect::try_from(http)?)
+                }
                 None => {
                     return Err(FromProtobufTypeError::InvalidArgument(
                         "CustomProxy missing proxy_method field",
@@ -254,6 +325,28 @@ mod proxy {
         }
     }
 
+    impl TryFrom<proto::HttpConnect> for HttpConnect {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(value: proto::HttpConnect) -> Result<Self, Self::Error> {
+            let ip = value.ip.parse::<Ipv4Addr>().map_err(|_| {
+                FromProtobufTypeError::InvalidArgument(
+                    "Could not parse HTTP proxy message from protobuf",
+                )
+            })?;
+            let port = value.port as u16;
+            let http = match value.auth {
+                Some(credentials) => {
+                    let auth = HttpAuth::try_from(credentials)?;
+                    HttpConnect::new_with_authentication((ip, port), auth)
+                }
+                None => HttpConnect::new((ip, port)),
+            };
+
+            Ok(http)
+        }
+    }
+
     impl TryFrom<proto::Shadowsocks> for Shadowsocks {
         type Error = FromProtobufTypeError;
 
@@ -291,6 +384,11 @@ mod proxy {
                             config,
                         ))
                     }
+                    CustomProxy::HttpConnect(config) => {
+                        proto::custom_proxy::ProxyMethod::HttpConnect(proto::HttpConnect::from(
+                            config,
+                        ))
+                    }
                 }),
             }
         }
@@ -330,6 +428,38 @@ mod proxy {
         }
     }
 
+    impl From<HttpConnect> for proto::HttpConnect {
+        fn from(value: HttpConnect) -> Self {
+            proto::HttpConnect {
+                ip: value.endpoint.ip().to_string(),
+                port: value.endpoint.port() as u32,
+                auth: value.auth.map(proto::HttpAuth::from),
+            }
+        }
+    }
+
+    impl From<HttpAuth> for proto::HttpAuth {
+        fn from(value: HttpAuth) -> Self {
+            proto::HttpAuth {
+                username: value.username().to_string(),
+                password: value.password().to_string(),
+            }
+        }
+    }
+
+    impl TryFrom<proto::HttpAuth> for HttpAuth {
+        type Error = FromProtobufTypeError;
+
+        fn try_from(value: proto::HttpAuth) -> Result<Self, Self::Error> {
+            HttpAuth::new(value.username, value.password).map_err(|_| {
+                FromProtobufTypeError::InvalidArgument(
+                    "Failed to parse HTTP proxy with authentication. \
+                     Make sure the credentials are valid.",
+                )
+            })
+        }
+    }
+
     impl From<SocksAuth> for proto::SocksAuth {
         fn from(value: SocksAuth) -> Self {
             proto::SocksAuth {
@@ -352,3 +482,141 @@ mod proxy {
         }
     }
 }
+
+impl From<talpid_types::net::dns::BlocklistStats> for proto::DnsBlocklistStats {
+    fn from(stats: talpid_types::net::dns::BlocklistStats) -> Self {
+        use proto::dns_blocklist_stats::{domain_list::Kind, DomainList};
+        use talpid_types::net::dns::DomainListKind;
+
+        proto::DnsBlocklistStats {
+            lists: stats
+                .lists
+                .into_iter()
+                .map(|list| DomainList {
+                    path: list.path.to_string_lossy().into_owned(),
+                    kind: match list.kind {
+                        DomainListKind::Blocklist => Kind::Blocklist as i32,
+                        DomainListKind::Allowlist => Kind::Allowlist as i32,
+                    },
+                    entries: list.entries,
+                    hits: list.hits,
+                    error: list.error,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::DnsBlocklistStats> for talpid_types::net::dns::BlocklistStats {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(stats: proto::DnsBlocklistStats) -> Result<Self, Self::Error> {
+        use proto::dns_blocklist_stats::domain_list::Kind;
+        use talpid_types::net::dns::{DomainListKind, DomainListStats};
+
+        let lists = stats
+            .lists
+            .into_iter()
+            .map(|list| {
+                let kind = match Kind::try_from(list.kind) {
+                    Ok(Kind::Blocklist) => DomainListKind::Blocklist,
+                    Ok(Kind::Allowlist) => DomainListKind::Allowlist,
+                    Err(_) => {
+                        return Err(FromProtobufTypeError::InvalidArgument(
+                            "invalid domain list kind",
+                        ))
+                    }
+                };
+                Ok(DomainListStats {
+                    path: list.path.into(),
+                    kind,
+                    entries: list.entries,
+                    hits: list.hits,
+                    error: list.error,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { lists })
+    }
+}
+
+impl From<talpid_types::net::dns::DnsConfigurationDrift> for proto::DnsConfigurationDrift {
+    fn from(drift: talpid_types::net::dns::DnsConfigurationDrift) -> Self {
+        let to_strings = |servers: Vec<std::net::IpAddr>| {
+            servers.iter().map(|server| server.to_string()).collect()
+        };
+
+        proto::DnsConfigurationDrift {
+            manager: Some(proto::DnsManager::from(drift.manager)),
+            expected_servers: to_strings(drift.expected_servers),
+            actual_servers: to_strings(drift.actual_servers),
+            restored: drift.restored,
+        }
+    }
+}
+
+impl TryFrom<proto::DnsConfigurationDrift> for talpid_types::net::dns::DnsConfigurationDrift {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(drift: proto::DnsConfigurationDrift) -> Result<Self, Self::Error> {
+        let from_strings = |servers: Vec<String>| {
+            servers
+                .iter()
+                .map(|server| arg_from_str(server, "invalid DNS server address"))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(Self {
+            manager: drift
+                .manager
+                .ok_or(FromProtobufTypeError::InvalidArgument(
+                    "missing DNS manager",
+                ))
+                .and_then(talpid_types::net::dns::DnsManager::try_from)?,
+            expected_servers: from_strings(drift.expected_servers)?,
+            actual_servers: from_strings(drift.actual_servers)?,
+            restored: drift.restored,
+        })
+    }
+}
+
+impl From<talpid_types::net::dns::DnsManager> for proto::DnsManager {
+    fn from(manager: talpid_types::net::dns::DnsManager) -> Self {
+        use proto::dns_manager::Manager;
+        use talpid_types::net::dns::DnsManager;
+
+        let manager = match manager {
+            DnsManager::Auto => Manager::Auto,
+            DnsManager::SystemdResolved => Manager::SystemdResolved,
+            DnsManager::NetworkManager => Manager::NetworkManager,
+            DnsManager::Resolvconf => Manager::Resolvconf,
+            DnsManager::StaticFile => Manager::StaticFile,
+            DnsManager::LocalResolver => Manager::LocalResolver,
+        };
+        proto::DnsManager {
+            manager: manager as i32,
+        }
+    }
+}
+
+impl TryFrom<proto::DnsManager> for talpid_types::net::dns::DnsManager {
+    type Error = FromProtobufTypeError;
+
+    fn try_from(manager: proto::DnsManager) -> Result<Self, Self::Error> {
+        use proto::dns_manager::Manager;
+        use talpid_types::net::dns::DnsManager;
+
+        match Manager::try_from(manager.manager) {
+            Ok(Manager::Auto) => Ok(DnsManager::Auto),
+            Ok(Manager::SystemdResolved) => Ok(DnsManager::SystemdResolved),
+            Ok(Manager::NetworkManager) => Ok(DnsManager::NetworkManager),
+            Ok(Manager::Resolvconf) => Ok(DnsManager::Resolvconf),
+            Ok(Manager::StaticFile) => Ok(DnsManager::StaticFile),
+            Ok(Manager::LocalResolver) => Ok(DnsManager::LocalResolver),
+            Err(_) => Err(FromProtobufTypeError::InvalidArgument(
+                "invalid DNS manager",
+            )),
+        }
+    }
+}
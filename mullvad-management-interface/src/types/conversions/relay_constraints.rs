@@ -1,4 +1,7 @@
-use crate::types::{conversions::net::try_tunnel_type_from_i32, proto, FromProtobufTypeError};
+use crate::types::{
+    conversions::{arg_from_str, net::try_tunnel_type_from_i32},
+    proto, FromProtobufTypeError,
+};
 use mullvad_types::{
     constraints::Constraint, custom_list::Id, relay_constraints::GeographicLocationConstraint,
 };
@@ -82,10 +85,16 @@ impl TryFrom<proto::RelaySettings> for mullvad_types::relay_constraints::RelaySe
                         "missing relay connection config",
                     ))?;
                 let config = mullvad_types::ConnectionConfig::try_from(config)?;
+                let wireguard_options = settings
+                    .wireguard_options
+                    .map(mullvad_types::CustomWireguardOptions::try_from)
+                    .transpose()?
+                    .unwrap_or_default();
                 Ok(mullvad_constraints::RelaySettings::CustomTunnelEndpoint(
                     CustomTunnelEndpoint {
                         host: settings.host,
                         config,
+                        wireguard_options,
                     },
                 ))
             }
@@ -152,10 +161,22 @@ impl From<&mullvad_types::relay_constraints::ObfuscationSettings> for proto::Obf
             SelectedObfuscation::Udp2Tcp => {
                 proto::obfuscation_settings::SelectedObfuscation::Udp2tcp
             }
+            SelectedObfuscation::Shadowsocks => {
+                proto::obfuscation_settings::SelectedObfuscation::Shadowsocks
+            }
+            SelectedObfuscation::Custom => proto::obfuscation_settings::SelectedObfuscation::Custom,
+            SelectedObfuscation::Quic => proto::obfuscation_settings::SelectedObfuscation::Quic,
         });
         Self {
             selected_obfuscation,
             udp2tcp: Some(proto::Udp2TcpObfuscationSettings::from(&settings.udp2tcp)),
+            shadowsocks: Some(proto::ShadowsocksObfuscationSettings::from(
+                &settings.shadowsocks,
+            )),
+            custom: settings
+                .custom
+                .as_ref()
+                .map(proto::CustomObfuscationEndpoint::from),
         }
     }
 }
@@ -176,6 +197,42 @@ impl From<&mullvad_types::relay_constraints::Udp2TcpObfuscationSettings>
     }
 }
 
+impl From<&mullvad_types::relay_constraints::ShadowsocksObfuscationSettings>
+    for proto::ShadowsocksObfuscationSettings
+{
+    fn from(settings: &mullvad_types::relay_constraints::ShadowsocksObfuscationSettings) -> Self {
+        Self {
+            port: settings.port.map(u32::from).option(),
+        }
+    }
+}
+
+impl From<&mullvad_types::relay_constraints::CustomObfuscationEndpoint>
+    for proto::CustomObfuscationEndpoint
+{
+    fn from(endpoint: &mullvad_types::relay_constraints::CustomObfuscationEndpoint) -> Self {
+        use mullvad_types::relay_constraints::CustomObfuscationProtocol;
+        use proto::custom_obfuscation_endpoint::{Protocol, Shadowsocks, Udp2Tcp};
+
+        let protocol = match &endpoint.protocol {
+            CustomObfuscationProtocol::Udp2Tcp { relay } => Protocol::Udp2tcp(Udp2Tcp {
+                relay: relay.clone(),
+            }),
+            CustomObfuscationProtocol::Shadowsocks { password, cipher } => {
+                Protocol::Shadowsocks(Shadowsocks {
+                    password: password.clone(),
+                    cipher: cipher.clone(),
+                })
+            }
+        };
+        Self {
+            address: endpoint.address.ip().to_string(),
+            port: u32::from(endpoint.address.port()),
+            protocol: Some(protocol),
+        }
+    }
+}
+
 impl From<mullvad_types::relay_constraints::BridgeSettings> for proto::BridgeSettings {
     fn from(settings: mullvad_types::relay_constraints::BridgeSettings) -> Self {
         use proto::bridge_settings;
@@ -221,6 +278,9 @@ impl From<mullvad_types::relay_constraints::RelaySettings> for proto::RelaySetti
                 relay_settings::Endpoint::Custom(proto::CustomRelaySettings {
                     host: endpoint.host,
                     config: Some(proto::ConnectionConfig::from(endpoint.config)),
+                    wireguard_options: Some(proto::CustomWireguardOptions::from(
+                        endpoint.wireguard_options,
+                    )),
                 })
             }
             MullvadRelaySettings::Normal(constraints) => {
@@ -438,6 +498,9 @@ impl TryFrom<proto::ObfuscationSettings> for mullvad_types::relay_constraints::O
                 Ok(IpcSelectedObfuscation::Auto) => SelectedObfuscation::Auto,
                 Ok(IpcSelectedObfuscation::Off) => SelectedObfuscation::Off,
                 Ok(IpcSelectedObfuscation::Udp2tcp) => SelectedObfuscation::Udp2Tcp,
+                Ok(IpcSelectedObfuscation::Shadowsocks) => SelectedObfuscation::Shadowsocks,
+                Ok(IpcSelectedObfuscation::Custom) => SelectedObfuscation::Custom,
+                Ok(IpcSelectedObfuscation::Quic) => SelectedObfuscation::Quic,
                 Err(_) => {
                     return Err(FromProtobufTypeError::InvalidArgument(
                         "invalid selected obfuscator",
@@ -456,9 +519,24 @@ impl TryFrom<proto::ObfuscationSettings> for mullvad_types::relay_constraints::O
             }
         };
 
+        // Older clients do not send any Shadowsocks settings
+        let shadowsocks = settings
+            .shadowsocks
+            .as_ref()
+            .map(mullvad_types::relay_constraints::ShadowsocksObfuscationSettings::try_from)
+            .transpose()?
+            .unwrap_or_default();
+
+        let custom = settings
+            .custom
+            .map(mullvad_types::relay_constraints::CustomObfuscationEndpoint::try_from)
+            .transpose()?;
+
         Ok(Self {
             selected_obfuscation,
             udp2tcp,
+            shadowsocks,
+            custom,
         })
     }
 }
@@ -475,6 +553,69 @@ impl TryFrom<&proto::Udp2TcpObfuscationSettings>
     }
 }
 
+impl TryFrom<&proto::ShadowsocksObfuscationSettings>
+    for mullvad_types::relay_constraints::ShadowsocksObfuscationSettings
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(settings: &proto::ShadowsocksObfuscationSettings) -> Result<Self, Self::Error> {
+        Ok(Self {
+            port: Constraint::from(settings.port.map(|port| port as u16)),
+        })
+    }
+}
+
+impl TryFrom<proto::CustomObfuscationEndpoint>
+    for mullvad_types::relay_constraints::CustomObfuscationEndpoint
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(endpoint: proto::CustomObfuscationEndpoint) -> Result<Self, Self::Error> {
+        use mullvad_types::relay_constraints::CustomObfuscationProtocol;
+        use proto::custom_obfuscation_endpoint::Protocol;
+
+        let ip = arg_from_str(&endpoint.address, "invalid obfuscation server address")?;
+        let port = u16::try_from(endpoint.port).map_err(|_| {
+            FromProtobufTypeError::InvalidArgument("invalid obfuscation server port")
+        })?;
+        let protocol = match endpoint.protocol {
+            Some(Protocol::Udp2tcp(udp2tcp)) => {
+                if udp2tcp.relay.is_empty() {
+                    return Err(FromProtobufTypeError::InvalidArgument(
+                        "missing relay for udp2tcp obfuscation server",
+                    ));
+                }
+                CustomObfuscationProtocol::Udp2Tcp {
+                    relay: udp2tcp.relay,
+                }
+            }
+            Some(Protocol::Shadowsocks(shadowsocks)) => {
+                if !talpid_types::net::proxy::SHADOWSOCKS_CIPHERS
+                    .contains(&shadowsocks.cipher.as_str())
+                {
+                    return Err(FromProtobufTypeError::InvalidArgument(
+                        "unsupported Shadowsocks cipher",
+                    ));
+                }
+                CustomObfuscationProtocol::Shadowsocks {
+                    password: shadowsocks.password,
+                    cipher: shadowsocks.cipher,
+                }
+            }
+            None => {
+                return Err(FromProtobufTypeError::InvalidArgument(
+                    "missing obfuscation server protocol",
+                ))
+            }
+        };
+
+        Ok(Self {
+            address: std::net::SocketAddr::new(ip, port),
+            protocol,
+        })
+    }
+}
+
 impl TryFrom<proto::BridgeState> for mullvad_types::relay_constraints::BridgeState {
     type Error = FromProtobufTypeError;
 
@@ -73,6 +73,14 @@ impl From<mullvad_types::relay_list::WireguardEndpointData> for proto::Wireguard
             ipv4_gateway: wireguard.ipv4_gateway.to_string(),
             ipv6_gateway: wireguard.ipv6_gateway.to_string(),
             udp2tcp_ports: wireguard.udp2tcp_ports.into_iter().map(u32::from).collect(),
+            shadowsocks_port_ranges: wireguard
+                .shadowsocks_port_ranges
+                .into_iter()
+                .map(|(first, last)| proto::PortRange {
+                    first: u32::from(first),
+                    last: u32::from(last),
+                })
+                .collect(),
         }
     }
 }
@@ -367,11 +375,26 @@ impl TryFrom<proto::WireguardEndpointData> for mullvad_types::relay_list::Wiregu
             })
             .collect::<Result<Vec<u16>, FromProtobufTypeError>>()?;
 
+        let shadowsocks_port_ranges = wireguard
+            .shadowsocks_port_ranges
+            .into_iter()
+            .map(|range| {
+                let first = u16::try_from(range.first).map_err(|_| {
+                    FromProtobufTypeError::InvalidArgument("invalid shadowsocks port")
+                })?;
+                let last = u16::try_from(range.last).map_err(|_| {
+                    FromProtobufTypeError::InvalidArgument("invalid shadowsocks port")
+                })?;
+                Ok((first, last))
+            })
+            .collect::<Result<Vec<(u16, u16)>, FromProtobufTypeError>>()?;
+
         Ok(mullvad_types::relay_list::WireguardEndpointData {
             port_ranges,
             ipv4_gateway,
             ipv6_gateway,
             udp2tcp_ports,
+            shadowsocks_port_ranges,
         })
     }
 }
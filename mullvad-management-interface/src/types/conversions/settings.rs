@@ -80,6 +80,30 @@ impl From<&mullvad_types::settings::DnsOptions> for proto::DnsOptions {
                     .map(|addr| addr.to_string())
                     .collect(),
             }),
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            blocklists: Some(proto::DnsBlocklists::from(&options.blocklists)),
+            #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+            blocklists: None,
+            #[cfg(target_os = "linux")]
+            manager: Some(proto::DnsManager::from(options.manager)),
+            #[cfg(not(target_os = "linux"))]
+            manager: None,
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+impl From<&mullvad_types::settings::DnsBlocklistOptions> for proto::DnsBlocklists {
+    fn from(options: &mullvad_types::settings::DnsBlocklistOptions) -> Self {
+        let paths_to_strings = |paths: &[std::path::PathBuf]| {
+            paths
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect()
+        };
+        proto::DnsBlocklists {
+            blocklists: paths_to_strings(&options.blocklists),
+            allowlists: paths_to_strings(&options.allowlists),
         }
     }
 }
@@ -97,6 +121,12 @@ impl From<&mullvad_types::settings::TunnelOptions> for proto::TunnelOptions {
                         .expect("Failed to convert std::time::Duration to prost_types::Duration for tunnel_options.wireguard.rotation_interval")
                 }),
                 quantum_resistant: Some(proto::QuantumResistantState::from(options.wireguard.quantum_resistant)),
+                persistent_keepalive: options.wireguard.persistent_keepalive.map(u32::from),
+                connectivity_check: Some(proto::ConnectivityCheckOptions::from(&options.wireguard.connectivity_check)),
+                psk_rekey_interval: options.wireguard.psk_rekey_interval.map(|ivl| {
+                    prost_types::Duration::try_from(std::time::Duration::from(ivl))
+                        .expect("Failed to convert std::time::Duration to prost_types::Duration for tunnel_options.wireguard.psk_rekey_interval")
+                }),
             }),
             generic: Some(proto::tunnel_options::GenericOptions {
                 enable_ipv6: options.generic.enable_ipv6,
@@ -282,6 +312,32 @@ impl TryFrom<proto::TunnelOptions> for mullvad_types::settings::TunnelOptions {
                     .ok_or(FromProtobufTypeError::InvalidArgument(
                         "missing quantum resistant state",
                     ))??,
+                persistent_keepalive: wireguard_options
+                    .persistent_keepalive
+                    .map(u16::try_from)
+                    .transpose()
+                    .map_err(|_| {
+                        FromProtobufTypeError::InvalidArgument("invalid keepalive interval")
+                    })?,
+                connectivity_check: wireguard_options
+                    .connectivity_check
+                    .map(net::wireguard::ConnectivityCheckOptions::try_from)
+                    .transpose()?
+                    .unwrap_or_default(),
+                psk_rekey_interval: wireguard_options
+                    .psk_rekey_interval
+                    .map(std::time::Duration::try_from)
+                    .transpose()
+                    .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid duration"))?
+                    .map(mullvad_types::wireguard::PskRekeyInterval::try_from)
+                    .transpose()
+                    .map_err(|error: mullvad_types::wireguard::PskRekeyIntervalError| {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Invalid PSK rekey interval")
+                        );
+                        FromProtobufTypeError::InvalidArgument("invalid PSK rekey interval")
+                    })?,
             },
             generic: net::GenericTunnelOptions {
                 enable_ipv6: generic_options.enable_ipv6,
@@ -346,6 +402,27 @@ impl TryFrom<proto::DnsOptions> for mullvad_types::settings::DnsOptions {
                     })
                     .collect::<Result<Vec<_>, _>>()?,
             },
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            blocklists: options
+                .blocklists
+                .map(mullvad_types::settings::DnsBlocklistOptions::from)
+                .unwrap_or_default(),
+            #[cfg(target_os = "linux")]
+            manager: options
+                .manager
+                .map(talpid_types::net::dns::DnsManager::try_from)
+                .transpose()?
+                .unwrap_or_default(),
         })
     }
 }
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+impl From<proto::DnsBlocklists> for mullvad_types::settings::DnsBlocklistOptions {
+    fn from(options: proto::DnsBlocklists) -> Self {
+        Self {
+            blocklists: options.blocklists.into_iter().map(Into::into).collect(),
+            allowlists: options.allowlists.into_iter().map(Into::into).collect(),
+        }
+    }
+}
@@ -71,3 +71,70 @@ impl TryFrom<proto::QuantumResistantState> for mullvad_types::wireguard::Quantum
         }
     }
 }
+
+impl From<mullvad_types::wireguard::WgQuickConfig> for proto::WgQuickConfig {
+    fn from(config: mullvad_types::wireguard::WgQuickConfig) -> Self {
+        proto::WgQuickConfig {
+            name: config.name,
+            contents: config.contents,
+        }
+    }
+}
+
+impl From<proto::WgQuickConfig> for mullvad_types::wireguard::WgQuickConfig {
+    fn from(config: proto::WgQuickConfig) -> Self {
+        mullvad_types::wireguard::WgQuickConfig {
+            name: config.name,
+            contents: config.contents,
+        }
+    }
+}
+
+impl From<&talpid_types::net::wireguard::ConnectivityCheckOptions>
+    for proto::ConnectivityCheckOptions
+{
+    fn from(options: &talpid_types::net::wireguard::ConnectivityCheckOptions) -> Self {
+        let to_proto_duration = |duration: std::time::Duration| {
+            prost_types::Duration::try_from(duration)
+                .expect("Failed to convert std::time::Duration to prost_types::Duration")
+        };
+        proto::ConnectivityCheckOptions {
+            rx_timeout: options.rx_timeout.map(to_proto_duration),
+            ping_timeout: options.ping_timeout.map(to_proto_duration),
+            establish_timeout: options.establish_timeout.map(to_proto_duration),
+            ping_target: options.ping_target.map(|addr| addr.to_string()),
+        }
+    }
+}
+
+impl TryFrom<proto::ConnectivityCheckOptions>
+    for talpid_types::net::wireguard::ConnectivityCheckOptions
+{
+    type Error = FromProtobufTypeError;
+
+    fn try_from(options: proto::ConnectivityCheckOptions) -> Result<Self, Self::Error> {
+        // Zero is not a valid timeout, so it cannot be used to mean "unset"
+        let from_proto_duration = |duration: Option<prost_types::Duration>| {
+            duration
+                .map(|duration| {
+                    std::time::Duration::try_from(duration)
+                        .ok()
+                        .filter(|duration| !duration.is_zero())
+                        .ok_or(FromProtobufTypeError::InvalidArgument(
+                            "connectivity check timeouts must be positive",
+                        ))
+                })
+                .transpose()
+        };
+        Ok(talpid_types::net::wireguard::ConnectivityCheckOptions {
+            rx_timeout: from_proto_duration(options.rx_timeout)?,
+            ping_timeout: from_proto_duration(options.ping_timeout)?,
+            establish_timeout: from_proto_duration(options.establish_timeout)?,
+            ping_target: options
+                .ping_target
+                .map(|addr| addr.parse())
+                .transpose()
+                .map_err(|_| FromProtobufTypeError::InvalidArgument("invalid ping target"))?,
+        })
+    }
+}
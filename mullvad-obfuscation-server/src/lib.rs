@@ -0,0 +1,164 @@
+//! Server half of the obfuscation protocols in `tunnel-obfuscation`. Each server accepts
+//! obfuscated traffic and forwards the WireGuard packets in it to a UDP endpoint, which makes it
+//! possible to run obfuscation front servers outside of the Mullvad relays, and to test the
+//! obfuscators end to end.
+
+use std::{
+    convert::Infallible,
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream, UdpSocket,
+    },
+    task::JoinSet,
+};
+
+pub use tunnel_obfuscation::quic_server::{Certificate, QuicServer};
+
+/// Size of the length header that precedes every datagram in a udp2tcp stream.
+const UDP2TCP_HEADER_SIZE: usize = 2;
+
+/// Largest possible UDP payload.
+const MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Failed to listen for udp2tcp connections
+    #[error("Failed to listen for udp2tcp connections on {0}")]
+    Udp2TcpListen(SocketAddr, #[source] io::Error),
+
+    /// Failed to start the QUIC server
+    #[error("Failed to start QUIC server")]
+    Quic(#[source] tunnel_obfuscation::quic_server::Error),
+}
+
+/// Server half of udp2tcp. Every TCP connection gets its own UDP socket, which the datagrams in
+/// the connection are sent from, and responses are sent back over the same connection.
+pub struct Udp2TcpServer {
+    listeners: Vec<TcpListener>,
+    forward_addr: SocketAddr,
+}
+
+impl Udp2TcpServer {
+    /// Listens for udp2tcp connections on all of `listen_addrs`, and forwards the traffic to
+    /// `forward_addr`.
+    pub async fn bind(
+        listen_addrs: &[SocketAddr],
+        forward_addr: SocketAddr,
+    ) -> Result<Self, Error> {
+        let mut listeners = Vec::with_capacity(listen_addrs.len());
+        for &addr in listen_addrs {
+            let listener = TcpListener::bind(addr)
+                .await
+                .map_err(|error| Error::Udp2TcpListen(addr, error))?;
+            listeners.push(listener);
+        }
+        Ok(Self::from_listeners(listeners, forward_addr))
+    }
+
+    /// Accepts udp2tcp connections on already bound listeners.
+    pub fn from_listeners(listeners: Vec<TcpListener>, forward_addr: SocketAddr) -> Self {
+        Self {
+            listeners,
+            forward_addr,
+        }
+    }
+
+    /// Accepts connections until the task is aborted.
+    pub async fn run(self) -> Infallible {
+        // Dropping the set aborts the accept loops along with this future
+        let mut accept_loops = JoinSet::new();
+        for listener in self.listeners {
+            accept_loops.spawn(accept_connections(listener, self.forward_addr));
+        }
+        while let Some(result) = accept_loops.join_next().await {
+            match result {
+                Ok(never) => match never {},
+                Err(error) => log::error!("udp2tcp listener stopped: {error}"),
+            }
+        }
+        std::future::pending().await
+    }
+}
+
+async fn accept_connections(listener: TcpListener, forward_addr: SocketAddr) -> Infallible {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                log::debug!("Accepted udp2tcp connection from {peer}");
+                tokio::spawn(async move {
+                    if let Err(error) = forward_connection(stream, forward_addr).await {
+                        log::debug!("udp2tcp connection from {peer} closed: {error}");
+                    }
+                });
+            }
+            Err(error) => log::error!("Failed to accept udp2tcp connection: {error}"),
+        }
+    }
+}
+
+async fn forward_connection(stream: TcpStream, forward_addr: SocketAddr) -> io::Result<()> {
+    // Disables the Nagle algorithm on the TCP socket. Improves performance
+    stream.set_nodelay(true)?;
+
+    let bind_addr = if forward_addr.is_ipv4() {
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+    } else {
+        SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+    };
+    let udp_socket = UdpSocket::bind(bind_addr).await?;
+    udp_socket.connect(forward_addr).await?;
+
+    let (tcp_read, tcp_write) = stream.into_split();
+    let result = tokio::select! {
+        result = forward_tcp_to_udp(tcp_read, &udp_socket) => result,
+        result = forward_udp_to_tcp(&udp_socket, tcp_write) => result,
+    };
+    result.map(|never| match never {})
+}
+
+/// Reads length-prefixed datagrams from the TCP stream and sends them over UDP.
+async fn forward_tcp_to_udp(
+    mut tcp_read: OwnedReadHalf,
+    udp_socket: &UdpSocket,
+) -> io::Result<Infallible> {
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    loop {
+        let len = usize::from(tcp_read.read_u16().await?);
+        tcp_read.read_exact(&mut buf[..len]).await?;
+        udp_socket.send(&buf[..len]).await?;
+    }
+}
+
+/// Receives datagrams over UDP and writes them, length-prefixed, to the TCP stream.
+async fn forward_udp_to_tcp(
+    udp_socket: &UdpSocket,
+    mut tcp_write: OwnedWriteHalf,
+) -> io::Result<Infallible> {
+    let mut buf = vec![0u8; UDP2TCP_HEADER_SIZE + MAX_DATAGRAM_SIZE];
+    loop {
+        let len = udp_socket.recv(&mut buf[UDP2TCP_HEADER_SIZE..]).await?;
+        // A datagram can never be larger than `MAX_DATAGRAM_SIZE`
+        let header = u16::try_from(len).unwrap().to_be_bytes();
+        buf[..UDP2TCP_HEADER_SIZE].copy_from_slice(&header);
+        tcp_write
+            .write_all(&buf[..UDP2TCP_HEADER_SIZE + len])
+            .await?;
+    }
+}
+
+/// Accepts QUIC connections on `listen_addr` and forwards their datagrams to `forward_addr`.
+/// Clients must trust `certificate`.
+pub async fn run_quic(
+    listen_addr: SocketAddr,
+    forward_addr: SocketAddr,
+    certificate: &Certificate,
+) -> Result<(), Error> {
+    let server = QuicServer::bind(listen_addr, forward_addr, certificate).map_err(Error::Quic)?;
+    server.run().await;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use clap::{Parser, Subcommand};
+use mullvad_obfuscation_server::{run_quic, Certificate, Udp2TcpServer};
+use std::{net::SocketAddr, path::PathBuf, process::exit};
+
+/// Accepts obfuscated WireGuard traffic and forwards it to a WireGuard server
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Cli {
+    #[command(subcommand)]
+    obfuscation: Obfuscation,
+}
+
+#[derive(Subcommand, Debug)]
+enum Obfuscation {
+    /// Accept udp2tcp connections
+    Udp2tcp {
+        /// Address to accept TCP connections on. Can be given multiple times
+        #[arg(long = "listen", required = true)]
+        listen_addrs: Vec<SocketAddr>,
+        /// Address of the WireGuard server to forward the traffic to
+        #[arg(long = "forward")]
+        forward_addr: SocketAddr,
+    },
+
+    /// Accept QUIC connections. A new self-signed certificate is created every time the server
+    /// starts, and clients must trust it
+    Quic {
+        /// Address to accept QUIC connections on
+        #[arg(long = "listen")]
+        listen_addr: SocketAddr,
+        /// Address of the WireGuard server to forward the traffic to
+        #[arg(long = "forward")]
+        forward_addr: SocketAddr,
+        /// Hostname to create the certificate for
+        #[arg(long, default_value = "localhost")]
+        hostname: String,
+        /// Where to write the DER-encoded certificate
+        #[arg(long, default_value = "quic-server.der")]
+        certificate: PathBuf,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let result = match Cli::parse().obfuscation {
+        Obfuscation::Udp2tcp {
+            listen_addrs,
+            forward_addr,
+        } => {
+            log::info!("Forwarding udp2tcp traffic on {listen_addrs:?} to {forward_addr}");
+            match Udp2TcpServer::bind(&listen_addrs, forward_addr).await {
+                Ok(server) => match server.run().await {},
+                Err(error) => Err(error),
+            }
+        }
+        Obfuscation::Quic {
+            listen_addr,
+            forward_addr,
+            hostname,
+            certificate: certificate_path,
+        } => {
+            let certificate = match Certificate::self_signed(&hostname) {
+                Ok(certificate) => certificate,
+                Err(error) => {
+                    eprintln!("Failed to create certificate: {error}");
+                    exit(1);
+                }
+            };
+            if let Err(error) = std::fs::write(&certificate_path, &certificate.certificate) {
+                eprintln!(
+                    "Failed to write certificate to {}: {error}",
+                    certificate_path.display()
+                );
+                exit(1);
+            }
+            log::info!(
+                "Forwarding QUIC traffic on {listen_addr} to {forward_addr}. Certificate for \
+                {hostname} written to {}",
+                certificate_path.display()
+            );
+            run_quic(listen_addr, forward_addr, &certificate).await
+        }
+    };
+
+    if let Err(error) = result {
+        eprintln!("{error}");
+        exit(1);
+    }
+}
@@ -0,0 +1,82 @@
+//! Runs the obfuscators in `tunnel-obfuscation` against the servers in this crate, with a UDP echo
+//! server in place of the WireGuard server.
+
+use mullvad_obfuscation_server::{Certificate, QuicServer, Udp2TcpServer};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::net::{TcpListener, UdpSocket};
+use tunnel_obfuscation::{create_obfuscator, QuicSettings, Settings, Udp2TcpSettings};
+
+async fn spawn_echo_server() -> SocketAddr {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; 2048];
+        loop {
+            let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+            socket.send_to(&buf[..len], peer).await.unwrap();
+        }
+    });
+    addr
+}
+
+/// Sends packets through the obfuscator and checks that they are echoed back.
+async fn assert_echo(settings: &Settings) {
+    let obfuscator = create_obfuscator(settings).await.unwrap();
+    let endpoint = obfuscator.endpoint();
+    tokio::spawn(obfuscator.run());
+
+    let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    client.connect(endpoint).await.unwrap();
+    let mut buf = vec![0u8; 2048];
+    for packet in [&b"first packet"[..], &[0xab; 1000]] {
+        client.send(packet).await.unwrap();
+        let len = tokio::time::timeout(Duration::from_secs(5), client.recv(&mut buf))
+            .await
+            .expect("timed out waiting for echo")
+            .unwrap();
+        assert_eq!(&buf[..len], packet);
+    }
+}
+
+#[tokio::test]
+async fn test_udp2tcp() {
+    let forward_addr = spawn_echo_server().await;
+    // The listener is bound before the server is spawned, so the obfuscator can connect to it
+    // right away
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let listen_addr = listener.local_addr().unwrap();
+    tokio::spawn(Udp2TcpServer::from_listeners(vec![listener], forward_addr).run());
+
+    assert_echo(&Settings::Udp2Tcp(Udp2TcpSettings {
+        peer: listen_addr,
+        #[cfg(target_os = "linux")]
+        fwmark: None,
+    }))
+    .await;
+}
+
+#[tokio::test]
+async fn test_quic() {
+    let forward_addr = spawn_echo_server().await;
+    let certificate = Certificate::self_signed("localhost").unwrap();
+    let server = QuicServer::bind(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        forward_addr,
+        &certificate,
+    )
+    .unwrap();
+    let listen_addr = server.local_addr().unwrap();
+    tokio::spawn(server.run());
+
+    assert_echo(&Settings::Quic(QuicSettings {
+        quic_endpoint: listen_addr,
+        hostname: "localhost".to_owned(),
+        trusted_certificates: vec![certificate.certificate],
+        #[cfg(target_os = "linux")]
+        fwmark: None,
+    }))
+    .await;
+}
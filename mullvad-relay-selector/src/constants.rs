@@ -1,4 +1,13 @@
 //! Constants used throughout the relay selector
 
 /// All the valid ports when using UDP2TCP obfuscation.
-pub(crate) const UDP2TCP_PORTS: [u16; 2] = [80, 5001];
+pub const UDP2TCP_PORTS: [u16; 2] = [80, 5001];
+
+/// Port that relays accept QUIC obfuscation on. The same as HTTP/3.
+#[cfg(feature = "quic")]
+pub const QUIC_PORT: u16 = 443;
+
+/// Domain that the hostnames of the relays are under. Certificates for QUIC obfuscation are valid
+/// for the hostname of the relay in this domain.
+#[cfg(feature = "quic")]
+pub const RELAY_DOMAIN: &str = "relays.mullvad.net";
@@ -7,6 +7,7 @@ mod error;
 mod relay_selector;
 
 // Re-exports
+pub use constants::UDP2TCP_PORTS;
 pub use error::Error;
 pub use relay_selector::detailer;
 pub use relay_selector::{
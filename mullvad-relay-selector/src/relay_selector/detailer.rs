@@ -203,7 +203,7 @@ const fn get_public_key(relay: &Relay) -> Result<&PublicKey, Error> {
 /// # Returns
 /// - `Option<u16>`: A randomly selected port number within the given ranges, or `None` if the input
 ///   is empty or the total number of available ports is zero.
-fn select_random_port(port_ranges: &[(u16, u16)]) -> Result<u16, Error> {
+pub(crate) fn select_random_port(port_ranges: &[(u16, u16)]) -> Result<u16, Error> {
     use rand::Rng;
     let get_port_amount = |range: &(u16, u16)| -> u64 { (1 + range.1 - range.0) as u64 };
     let port_amount: u64 = port_ranges.iter().map(get_port_amount).sum();
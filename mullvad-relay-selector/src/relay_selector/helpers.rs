@@ -3,12 +3,20 @@
 use std::net::SocketAddr;
 
 use mullvad_types::{
-    constraints::Constraint, endpoint::MullvadWireguardEndpoint,
-    relay_constraints::Udp2TcpObfuscationSettings, relay_list::Relay,
+    constraints::Constraint,
+    endpoint::MullvadWireguardEndpoint,
+    relay_constraints::{
+        CustomObfuscationEndpoint, CustomObfuscationProtocol, ShadowsocksObfuscationSettings,
+        Udp2TcpObfuscationSettings,
+    },
+    relay_list::Relay,
 };
 use rand::{seq::SliceRandom, thread_rng, Rng};
-use talpid_types::net::obfuscation::ObfuscatorConfig;
+use talpid_types::net::obfuscation::{ObfuscatorConfig, ShadowsocksCredentials};
 
+use super::detailer::select_random_port;
+#[cfg(feature = "quic")]
+use crate::constants::{QUIC_PORT, RELAY_DOMAIN};
 use crate::SelectedObfuscator;
 
 /// Picks a relay using [pick_random_relay_fn], using the `weight` member of each relay
@@ -87,3 +95,76 @@ pub fn get_udp2tcp_obfuscator_port(
         Constraint::Any | Constraint::Only(_) => udp2tcp_ports.choose(&mut thread_rng()).copied(),
     }
 }
+
+pub fn get_shadowsocks_obfuscator(
+    obfuscation_settings_constraint: &Constraint<ShadowsocksObfuscationSettings>,
+    shadowsocks_port_ranges: &[(u16, u16)],
+    relay: Relay,
+    endpoint: &MullvadWireguardEndpoint,
+) -> Option<SelectedObfuscator> {
+    let shadowsocks_endpoint_port =
+        get_shadowsocks_obfuscator_port(obfuscation_settings_constraint, shadowsocks_port_ranges)?;
+    let config = ObfuscatorConfig::Shadowsocks {
+        endpoint: SocketAddr::new(endpoint.peer.endpoint.ip(), shadowsocks_endpoint_port),
+        wireguard_endpoint: None,
+        credentials: None,
+    };
+
+    Some(SelectedObfuscator { config, relay })
+}
+
+pub fn get_shadowsocks_obfuscator_port(
+    obfuscation_settings_constraint: &Constraint<ShadowsocksObfuscationSettings>,
+    shadowsocks_port_ranges: &[(u16, u16)],
+) -> Option<u16> {
+    match obfuscation_settings_constraint {
+        Constraint::Only(ShadowsocksObfuscationSettings {
+            port: Constraint::Only(port),
+        }) => shadowsocks_port_ranges
+            .iter()
+            .any(|&(start, end)| (start..=end).contains(port))
+            .then_some(*port),
+        // There are no specific obfuscation settings to take into consideration in this case.
+        Constraint::Any | Constraint::Only(_) => select_random_port(shadowsocks_port_ranges).ok(),
+    }
+}
+
+/// Returns an obfuscator that carries the traffic to `relay` in a QUIC connection.
+#[cfg(feature = "quic")]
+pub fn get_quic_obfuscator(
+    relay: Relay,
+    endpoint: &MullvadWireguardEndpoint,
+) -> SelectedObfuscator {
+    let config = ObfuscatorConfig::Quic {
+        endpoint: SocketAddr::new(endpoint.peer.endpoint.ip(), QUIC_PORT),
+        hostname: format!("{}.{RELAY_DOMAIN}", relay.hostname),
+    };
+
+    SelectedObfuscator { config, relay }
+}
+
+/// Returns an obfuscator that connects through a user-provided obfuscation server instead of
+/// `relay`. The server is expected to forward the traffic to `endpoint`.
+pub fn get_custom_obfuscator(
+    custom_endpoint: &CustomObfuscationEndpoint,
+    relay: Relay,
+    endpoint: &MullvadWireguardEndpoint,
+) -> SelectedObfuscator {
+    let config = match &custom_endpoint.protocol {
+        CustomObfuscationProtocol::Udp2Tcp { .. } => ObfuscatorConfig::Udp2Tcp {
+            endpoint: custom_endpoint.address,
+        },
+        CustomObfuscationProtocol::Shadowsocks { password, cipher } => {
+            ObfuscatorConfig::Shadowsocks {
+                endpoint: custom_endpoint.address,
+                wireguard_endpoint: Some(endpoint.peer.endpoint),
+                credentials: Some(ShadowsocksCredentials {
+                    password: password.clone(),
+                    cipher: cipher.clone(),
+                }),
+            }
+        }
+    };
+
+    SelectedObfuscator { config, relay }
+}
@@ -22,9 +22,10 @@ use mullvad_types::{
     endpoint::MullvadWireguardEndpoint,
     location::{Coordinates, Location},
     relay_constraints::{
-        BridgeSettings, BridgeState, InternalBridgeConstraints, ObfuscationSettings,
-        OpenVpnConstraints, RelayConstraints, RelayOverride, RelaySettings, ResolvedBridgeSettings,
-        SelectedObfuscation, WireguardConstraints,
+        BridgeSettings, BridgeState, CustomObfuscationEndpoint, CustomObfuscationProtocol,
+        GeographicLocationConstraint, InternalBridgeConstraints, LocationConstraint,
+        ObfuscationSettings, OpenVpnConstraints, RelayConstraints, RelayOverride, RelaySettings,
+        ResolvedBridgeSettings, SelectedObfuscation, TransportPort, WireguardConstraints,
     },
     relay_list::{Relay, RelayEndpointData, RelayList},
     settings::Settings,
@@ -86,6 +87,9 @@ pub static RETRY_ORDER: Lazy<Vec<RelayQuery>> = Lazy::new(|| {
             .transport_protocol(TransportProtocol::Tcp)
             .bridge()
             .build(),
+        // 8
+        #[cfg(feature = "quic")]
+        RelayQueryBuilder::new().wireguard().quic().build(),
     ]
 });
 
@@ -113,6 +117,8 @@ pub struct SelectorConfig {
 pub struct RuntimeParameters {
     /// Whether IPv6 is available
     pub ipv6: bool,
+    /// Whether plain UDP traffic to the relays is known to be blocked on the current network
+    pub udp_blocked: bool,
 }
 
 impl RuntimeParameters {
@@ -130,16 +136,40 @@ impl RuntimeParameters {
                 return false;
             }
         }
+        if self.udp_blocked && may_use_udp(query) {
+            log::trace!("{query:?} is incompatible with {self:?} due to UDP being blocked");
+            return false;
+        }
         true
     }
 }
 
+/// Returns whether a tunnel created from `query` may send plain UDP traffic to the relay.
+fn may_use_udp(query: &RelayQuery) -> bool {
+    match query.tunnel_protocol {
+        Constraint::Only(TunnelType::OpenVpn) => !matches!(
+            query.openvpn_constraints.port,
+            Constraint::Only(TransportPort {
+                protocol: TransportProtocol::Tcp,
+                ..
+            })
+        ),
+        Constraint::Only(TunnelType::Wireguard) | Constraint::Any => !matches!(
+            query.wireguard_constraints.obfuscation,
+            SelectedObfuscation::Udp2Tcp
+        ),
+    }
+}
+
 // Note: It is probably not a good idea to rely on derived default values to be correct for our use
 // case.
 #[allow(clippy::derivable_impls)]
 impl Default for RuntimeParameters {
     fn default() -> Self {
-        RuntimeParameters { ipv6: false }
+        RuntimeParameters {
+            ipv6: false,
+            udp_blocked: false,
+        }
     }
 }
 
@@ -322,6 +352,8 @@ impl<'a> From<NormalSelectorConfig<'a>> for RelayQuery {
                 entry_location,
                 obfuscation: obfuscation_settings.selected_obfuscation,
                 udp2tcp_port: Constraint::Only(obfuscation_settings.udp2tcp.clone()),
+                shadowsocks_port: Constraint::Only(obfuscation_settings.shadowsocks.clone()),
+                custom_obfuscation: Constraint::from(obfuscation_settings.custom.clone()),
             }
         }
 
@@ -493,6 +525,34 @@ impl RelaySelector {
         }
     }
 
+    /// Returns a random WireGuard relay and relay endpoint at `location`, which otherwise matches
+    /// the current constraints. Obfuscation is never used. If a custom tunnel endpoint is set, the
+    /// default constraints are used instead.
+    pub fn get_wireguard_relay_at(
+        &self,
+        location: Constraint<LocationConstraint>,
+    ) -> Result<GetRelay, Error> {
+        let config_guard = self.config.lock().unwrap();
+        let default_constraints = RelayConstraints::default();
+        let config = match SpecializedSelectorConfig::from(&*config_guard) {
+            SpecializedSelectorConfig::Normal(normal_config) => normal_config,
+            SpecializedSelectorConfig::Custom(_) => NormalSelectorConfig {
+                user_preferences: &default_constraints,
+                obfuscation_settings: &config_guard.obfuscation_settings,
+                bridge_state: &config_guard.bridge_state,
+                bridge_settings: &config_guard.bridge_settings,
+                custom_lists: &config_guard.custom_lists,
+            },
+        };
+        let mut query = RelayQuery::from(config.clone());
+        query.location = location;
+        query.tunnel_protocol = Constraint::Only(TunnelType::Wireguard);
+        query.wireguard_constraints.obfuscation = SelectedObfuscation::Off;
+
+        let parsed_relays = &self.parsed_relays.lock().unwrap();
+        Self::get_relay_inner(query, parsed_relays, &config)
+    }
+
     /// Returns a random relay and relay endpoint matching the current constraints corresponding to
     /// `retry_attempt` in [`RETRY_ORDER`] while considering [runtime_params][`RuntimeParameters`].
     ///
@@ -570,7 +630,8 @@ impl RelaySelector {
     ///
     /// Runtime parameters may affect which of the default queries that are considered. For example,
     /// queries which rely on IPv6 will not be considered if working IPv6 is not available at
-    /// runtime.
+    /// runtime. If UDP is blocked but none of the queries that avoid UDP are compatible with
+    /// `user_preferences`, blocked UDP is disregarded rather than ignoring every query.
     fn pick_and_merge_query(
         retry_attempt: usize,
         retry_order: &[RelayQuery],
@@ -578,13 +639,26 @@ impl RelaySelector {
         user_preferences: RelayQuery,
     ) -> RelayQuery {
         log::trace!("Merging user preferences {user_preferences:?} with default retry strategy");
-        retry_order
-            .iter()
-            // Remove candidate queries based on runtime parameters before trying to merge user
-            // settings
-            .filter(|query| runtime_params.compatible(query))
+        let merge = |runtime_params: &RuntimeParameters| -> Vec<RelayQuery> {
+            retry_order
+                .iter()
+                // Remove candidate queries based on runtime parameters before trying to merge user
+                // settings
+                .filter(|query| runtime_params.compatible(query))
+                .filter_map(|query| query.clone().intersection(user_preferences.clone()))
+                .collect()
+        };
+        let mut candidates = merge(&runtime_params);
+        if candidates.is_empty() && runtime_params.udp_blocked {
+            log::debug!("No query avoids UDP with the current settings, ignoring blocked UDP");
+            candidates = merge(&RuntimeParameters {
+                udp_blocked: false,
+                ..runtime_params
+            });
+        }
+        candidates
+            .into_iter()
             .cycle()
-            .filter_map(|query| query.clone().intersection(user_preferences.clone()))
             .nth(retry_attempt)
             .unwrap_or(user_preferences)
     }
@@ -669,6 +743,7 @@ impl RelaySelector {
             query.tunnel_protocol,
             Constraint::Only(TunnelType::Wireguard)
         );
+        let query = Self::pin_custom_udp2tcp_relay(query, parsed_relays)?;
         let inner = if !query.wireguard_constraints.multihop() {
             Self::get_wireguard_singlehop_config(&query, config, parsed_relays)?
         } else {
@@ -685,6 +760,47 @@ impl RelaySelector {
         })
     }
 
+    /// A udp2tcp server always forwards the traffic to the same relay, since the protocol cannot
+    /// carry the destination. If one is used, the relay that is connected to through it is
+    /// pinned to the relay named by the server. This is the entry relay if multihop is enabled.
+    ///
+    /// # Returns
+    /// * An `Err` if the named relay is not in the relay list
+    /// * `Ok(query)` with the location of the obfuscated hop replaced otherwise
+    fn pin_custom_udp2tcp_relay(
+        mut query: RelayQuery,
+        parsed_relays: &ParsedRelays,
+    ) -> Result<RelayQuery, Error> {
+        if query.wireguard_constraints.obfuscation != SelectedObfuscation::Custom {
+            return Ok(query);
+        }
+        let Constraint::Only(CustomObfuscationEndpoint {
+            protocol: CustomObfuscationProtocol::Udp2Tcp { relay: hostname },
+            ..
+        }) = &query.wireguard_constraints.custom_obfuscation
+        else {
+            return Ok(query);
+        };
+        let location = parsed_relays
+            .relays()
+            .find(|relay| relay.hostname == *hostname)
+            .and_then(|relay| relay.location.as_ref())
+            .ok_or(Error::NoRelay)?;
+        let pinned = Constraint::Only(LocationConstraint::Location(
+            GeographicLocationConstraint::Hostname(
+                location.country_code.clone(),
+                location.city_code.clone(),
+                hostname.clone(),
+            ),
+        ));
+        if query.wireguard_constraints.multihop() {
+            query.wireguard_constraints.entry_location = pinned;
+        } else {
+            query.location = pinned;
+        }
+        Ok(query)
+    }
+
     /// Select a valid Wireguard exit relay.
     ///
     /// # Returns
@@ -800,6 +916,58 @@ impl RelaySelector {
                 .map(Some)
                 .ok_or(Error::NoObfuscator)
             }
+            SelectedObfuscation::Shadowsocks => {
+                let obfuscator_relay = match relay {
+                    WireguardConfig::Singlehop { exit } => exit,
+                    WireguardConfig::Multihop { entry, .. } => entry,
+                };
+                let shadowsocks_port_ranges = &parsed_relays
+                    .parsed_list()
+                    .wireguard
+                    .shadowsocks_port_ranges;
+
+                helpers::get_shadowsocks_obfuscator(
+                    &query.wireguard_constraints.shadowsocks_port,
+                    shadowsocks_port_ranges,
+                    obfuscator_relay,
+                    endpoint,
+                )
+                .map(Some)
+                .ok_or(Error::NoObfuscator)
+            }
+            SelectedObfuscation::Custom => {
+                let Constraint::Only(custom_endpoint) =
+                    &query.wireguard_constraints.custom_obfuscation
+                else {
+                    return Err(Error::NoObfuscator);
+                };
+                let obfuscator_relay = match relay {
+                    WireguardConfig::Singlehop { exit } => exit,
+                    WireguardConfig::Multihop { entry, .. } => entry,
+                };
+
+                Ok(Some(helpers::get_custom_obfuscator(
+                    custom_endpoint,
+                    obfuscator_relay,
+                    endpoint,
+                )))
+            }
+            #[cfg(feature = "quic")]
+            SelectedObfuscation::Quic => {
+                let obfuscator_relay = match relay {
+                    WireguardConfig::Singlehop { exit } => exit,
+                    WireguardConfig::Multihop { entry, .. } => entry,
+                };
+                Ok(Some(helpers::get_quic_obfuscator(
+                    obfuscator_relay,
+                    endpoint,
+                )))
+            }
+            #[cfg(not(feature = "quic"))]
+            SelectedObfuscation::Quic => {
+                log::warn!("QUIC obfuscation is not supported in this build");
+                Err(Error::NoObfuscator)
+            }
         }
     }
 
@@ -31,8 +31,9 @@
 use mullvad_types::{
     constraints::Constraint,
     relay_constraints::{
-        BridgeConstraints, LocationConstraint, OpenVpnConstraints, Ownership, Providers,
-        RelayConstraints, SelectedObfuscation, TransportPort, Udp2TcpObfuscationSettings,
+        BridgeConstraints, CustomObfuscationEndpoint, LocationConstraint, OpenVpnConstraints,
+        Ownership, Providers, RelayConstraints, SelectedObfuscation,
+        ShadowsocksObfuscationSettings, TransportPort, Udp2TcpObfuscationSettings,
         WireguardConstraints,
     },
     Intersection,
@@ -139,6 +140,8 @@ pub struct WireguardRelayQuery {
     pub entry_location: Constraint<LocationConstraint>,
     pub obfuscation: SelectedObfuscation,
     pub udp2tcp_port: Constraint<Udp2TcpObfuscationSettings>,
+    pub shadowsocks_port: Constraint<ShadowsocksObfuscationSettings>,
+    pub custom_obfuscation: Constraint<CustomObfuscationEndpoint>,
 }
 
 impl WireguardRelayQuery {
@@ -156,6 +159,8 @@ impl WireguardRelayQuery {
             entry_location: Constraint::Any,
             obfuscation: SelectedObfuscation::Auto,
             udp2tcp_port: Constraint::Any,
+            shadowsocks_port: Constraint::Any,
+            custom_obfuscation: Constraint::Any,
         }
     }
 }
@@ -267,7 +272,7 @@ pub mod builder {
         constraints::Constraint,
         relay_constraints::{
             BridgeConstraints, LocationConstraint, RelayConstraints, SelectedObfuscation,
-            TransportPort, Udp2TcpObfuscationSettings,
+            ShadowsocksObfuscationSettings, TransportPort, Udp2TcpObfuscationSettings,
         },
     };
     use talpid_types::net::TunnelType;
@@ -298,6 +303,9 @@ pub mod builder {
     ///  in the final `RelayQuery` is `Constraint::Any`.
     pub struct Any;
 
+    /// QUIC obfuscation, which has no settings of its own.
+    pub struct Quic;
+
     // This impl-block is quantified over all configurations, e.g. [`Any`],
     // [`WireguardRelayQuery`] & [`OpenVpnRelayQuery`]
     impl<VpnProtocol> RelayQueryBuilder<VpnProtocol> {
@@ -450,6 +458,39 @@ pub mod builder {
                 protocol,
             }
         }
+
+        /// Enable Shadowsocks obfuscation. This will in turn enable the option to configure the
+        /// Shadowsocks port.
+        pub fn shadowsocks(
+            mut self,
+        ) -> RelayQueryBuilder<Wireguard<Multihop, ShadowsocksObfuscationSettings>> {
+            let obfuscation = ShadowsocksObfuscationSettings {
+                port: Constraint::Any,
+            };
+            let protocol = Wireguard {
+                multihop: self.protocol.multihop,
+                obfuscation: obfuscation.clone(),
+            };
+            self.query.wireguard_constraints.shadowsocks_port = Constraint::Only(obfuscation);
+            self.query.wireguard_constraints.obfuscation = SelectedObfuscation::Shadowsocks;
+            RelayQueryBuilder {
+                query: self.query,
+                protocol,
+            }
+        }
+
+        /// Enable QUIC obfuscation.
+        pub fn quic(mut self) -> RelayQueryBuilder<Wireguard<Multihop, Quic>> {
+            let protocol = Wireguard {
+                multihop: self.protocol.multihop,
+                obfuscation: Quic,
+            };
+            self.query.wireguard_constraints.obfuscation = SelectedObfuscation::Quic;
+            RelayQueryBuilder {
+                query: self.query,
+                protocol,
+            }
+        }
     }
 
     impl<Multihop> RelayQueryBuilder<Wireguard<Multihop, Udp2TcpObfuscationSettings>> {
@@ -463,6 +504,17 @@ pub mod builder {
         }
     }
 
+    impl<Multihop> RelayQueryBuilder<Wireguard<Multihop, ShadowsocksObfuscationSettings>> {
+        /// Set the Shadowsocks port. This is the UDP port which the Shadowsocks obfuscation
+        /// protocol should use to connect to a relay.
+        pub fn shadowsocks_port(mut self, port: u16) -> Self {
+            self.protocol.obfuscation.port = Constraint::Only(port);
+            self.query.wireguard_constraints.shadowsocks_port =
+                Constraint::Only(self.protocol.obfuscation.clone());
+            self
+        }
+    }
+
     // Type-safe builder pattern for OpenVPN relay constraints.
 
     /// Internal builder state for a [`OpenVpnRelayQuery`] configuration.
@@ -19,8 +19,9 @@ use mullvad_types::{
     constraints::Constraint,
     endpoint::MullvadEndpoint,
     relay_constraints::{
-        BridgeConstraints, BridgeState, GeographicLocationConstraint, Ownership, Providers,
-        SelectedObfuscation, TransportPort,
+        BridgeConstraints, BridgeState, CustomObfuscationEndpoint, CustomObfuscationProtocol,
+        GeographicLocationConstraint, LocationConstraint, ObfuscationSettings, Ownership,
+        Providers, RelayConstraints, RelaySettings, SelectedObfuscation, TransportPort,
     },
     relay_list::{
         BridgeEndpointData, OpenVpnEndpoint, OpenVpnEndpointData, Relay, RelayEndpointData,
@@ -162,6 +163,7 @@ static RELAYS: Lazy<RelayList> = Lazy::new(|| RelayList {
         ipv4_gateway: "10.64.0.1".parse().unwrap(),
         ipv6_gateway: "fc00:bbbb:bbbb:bb01::1".parse().unwrap(),
         udp2tcp_ports: vec![],
+        shadowsocks_port_ranges: vec![(100, 100), (443, 443), (51900, 51949)],
     },
 });
 
@@ -239,6 +241,9 @@ fn assert_retry_order() {
             .transport_protocol(TransportProtocol::Tcp)
             .bridge()
             .build(),
+        // 8
+        #[cfg(feature = "quic")]
+        RelayQueryBuilder::new().wireguard().quic().build(),
     ];
 
     assert!(
@@ -262,7 +267,13 @@ fn test_retry_order() {
     let relay_selector = default_relay_selector();
     for (retry_attempt, query) in RETRY_ORDER.iter().enumerate() {
         let relay = relay_selector
-            .get_relay(retry_attempt, RuntimeParameters { ipv6: true })
+            .get_relay(
+                retry_attempt,
+                RuntimeParameters {
+                    ipv6: true,
+                    udp_blocked: false,
+                },
+            )
             .unwrap_or_else(|_| panic!("Retry attempt {retry_attempt} did not yield any relay"));
         // For each relay, cross-check that the it has the expected tunnel protocol
         let tunnel_type = tunnel_type(&unwrap_relay(relay.clone()));
@@ -292,7 +303,10 @@ fn test_retry_order() {
                 assert!(match query.wireguard_constraints.obfuscation {
                     SelectedObfuscation::Auto => true,
                     SelectedObfuscation::Off => obfuscator.is_none(),
-                    SelectedObfuscation::Udp2Tcp => obfuscator.is_some(),
+                    SelectedObfuscation::Udp2Tcp
+                    | SelectedObfuscation::Shadowsocks
+                    | SelectedObfuscation::Custom
+                    | SelectedObfuscation::Quic => obfuscator.is_some(),
                 });
             }
             GetRelay::OpenVpn {
@@ -452,6 +466,7 @@ fn test_wireguard_entry() {
             ipv4_gateway: "10.64.0.1".parse().unwrap(),
             ipv6_gateway: "fc00:bbbb:bbbb:bb01::1".parse().unwrap(),
             udp2tcp_ports: vec![],
+            shadowsocks_port_ranges: vec![],
         },
     };
 
@@ -736,6 +751,72 @@ fn test_selecting_wireguard_endpoint_with_auto_obfuscation() {
     }
 }
 
+/// If UDP is known to be blocked, retry attempts which would connect using plain UDP should be
+/// skipped in favor of ones using UDP2TCP obfuscation.
+#[test]
+fn test_skip_udp_retry_attempts_when_udp_is_blocked() {
+    let relay_selector = default_relay_selector();
+    let retry_order = [
+        RelayQueryBuilder::new().wireguard().build(),
+        RelayQueryBuilder::new().wireguard().udp2tcp().build(),
+    ];
+    let runtime_params = RuntimeParameters {
+        ipv6: false,
+        udp_blocked: true,
+    };
+
+    for retry_attempt in 0..(10 * retry_order.len()) {
+        let relay = relay_selector
+            .get_relay_with_custom_params(retry_attempt, &retry_order, runtime_params.clone())
+            .unwrap();
+        match relay {
+            GetRelay::Wireguard { obfuscator, .. } => {
+                assert!(obfuscator.is_some_and(|obfuscator| matches!(
+                    obfuscator.config,
+                    ObfuscatorConfig::Udp2Tcp { .. }
+                )))
+            }
+            wrong_relay => panic!(
+                "Relay selector should have picked a Wireguard relay, instead chose {wrong_relay:?}"
+            ),
+        }
+    }
+}
+
+/// If UDP is known to be blocked but the user settings rule out every retry attempt that avoids
+/// UDP, the relay selector should fall back to the attempts that use UDP instead of looping forever.
+#[test]
+fn test_blocked_udp_without_compatible_retry_attempts() {
+    let config = SelectorConfig {
+        obfuscation_settings: ObfuscationSettings {
+            selected_obfuscation: SelectedObfuscation::Off,
+            ..ObfuscationSettings::default()
+        },
+        ..SelectorConfig::default()
+    };
+    let relay_selector = RelaySelector::from_list(config, RELAYS.clone());
+    let retry_order = [
+        RelayQueryBuilder::new().wireguard().build(),
+        RelayQueryBuilder::new().wireguard().udp2tcp().build(),
+    ];
+    let runtime_params = RuntimeParameters {
+        ipv6: false,
+        udp_blocked: true,
+    };
+
+    for retry_attempt in 0..(10 * retry_order.len()) {
+        let relay = relay_selector
+            .get_relay_with_custom_params(retry_attempt, &retry_order, runtime_params.clone())
+            .unwrap();
+        match relay {
+            GetRelay::Wireguard { obfuscator, .. } => assert!(obfuscator.is_none()),
+            wrong_relay => panic!(
+                "Relay selector should have picked a Wireguard relay, instead chose {wrong_relay:?}"
+            ),
+        }
+    }
+}
+
 /// Construct a query for a Wireguard configuration with UDP2TCP obfuscation, and make sure that
 /// all configurations contain a valid port.
 #[test]
@@ -759,6 +840,7 @@ fn test_selected_wireguard_endpoints_use_correct_port_ranges() {
                 assert!(match obfuscator.config {
                     ObfuscatorConfig::Udp2Tcp { endpoint } =>
                         TCP2UDP_PORTS.contains(&endpoint.port()),
+                    ObfuscatorConfig::Shadowsocks { .. } => false,
                 })
             }
             wrong_relay => panic!(
@@ -768,6 +850,208 @@ fn test_selected_wireguard_endpoints_use_correct_port_ranges() {
     }
 }
 
+/// Construct a query for a Wireguard configuration with Shadowsocks obfuscation, and make sure
+/// that all configurations contain a port from the Shadowsocks port ranges of the relay list.
+#[test]
+fn test_selecting_wireguard_endpoint_with_shadowsocks_obfuscation() {
+    let relay_selector = default_relay_selector();
+    let query = RelayQueryBuilder::new().wireguard().shadowsocks().build();
+
+    for _ in 0..1000 {
+        let relay = relay_selector.get_relay_by_query(query.clone()).unwrap();
+        match relay {
+            GetRelay::Wireguard {
+                obfuscator,
+                inner: WireguardConfig::Singlehop { .. },
+                ..
+            } => {
+                let Some(obfuscator) = obfuscator else {
+                    panic!("Relay selector should have picked an obfuscator")
+                };
+                let ObfuscatorConfig::Shadowsocks { endpoint, .. } = obfuscator.config else {
+                    panic!("Relay selector should have picked a Shadowsocks obfuscator")
+                };
+                let port = endpoint.port();
+                assert!(port == 100 || port == 443 || (51900..=51949).contains(&port));
+            }
+            wrong_relay => panic!(
+            "Relay selector should have picked a Wireguard relay, instead chose {wrong_relay:?}"
+        ),
+        };
+    }
+}
+
+/// Construct a query for a Wireguard configuration with QUIC obfuscation, and make sure that the
+/// obfuscator connects to port 443 of the relay and expects a certificate for its hostname.
+#[cfg(feature = "quic")]
+#[test]
+fn test_selecting_wireguard_endpoint_with_quic_obfuscation() {
+    let relay_selector = default_relay_selector();
+    let query = RelayQueryBuilder::new().wireguard().quic().build();
+
+    let relay = relay_selector.get_relay_by_query(query).unwrap();
+    match relay {
+        GetRelay::Wireguard {
+            endpoint,
+            obfuscator: Some(obfuscator),
+            ..
+        } => {
+            let ObfuscatorConfig::Quic {
+                endpoint: quic_endpoint,
+                hostname,
+            } = obfuscator.config
+            else {
+                panic!("Relay selector should have picked a QUIC obfuscator")
+            };
+            assert_eq!(quic_endpoint.ip(), endpoint.peer.endpoint.ip());
+            assert_eq!(quic_endpoint.port(), 443);
+            assert_eq!(
+                hostname,
+                format!("{}.relays.mullvad.net", obfuscator.relay.hostname)
+            );
+        }
+        wrong_relay => panic!(
+            "Relay selector should have picked a Wireguard relay with an obfuscator, instead chose {wrong_relay:?}"
+        ),
+    }
+}
+
+/// Verify that an explicit Shadowsocks port is used if it is in the Shadowsocks port ranges, and
+/// that no relay is returned if it is not.
+#[test]
+fn test_selecting_wireguard_endpoint_with_shadowsocks_port() {
+    let relay_selector = default_relay_selector();
+
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .shadowsocks()
+        .shadowsocks_port(51910)
+        .build();
+    let relay = relay_selector.get_relay_by_query(query).unwrap();
+    match relay {
+        GetRelay::Wireguard {
+            obfuscator: Some(obfuscator),
+            ..
+        } => {
+            assert!(matches!(
+                obfuscator.config,
+                ObfuscatorConfig::Shadowsocks { endpoint, .. } if endpoint.port() == 51910
+            ));
+        }
+        wrong_relay => panic!(
+            "Relay selector should have picked an obfuscated Wireguard relay, instead chose {wrong_relay:?}"
+        ),
+    }
+
+    let query = RelayQueryBuilder::new()
+        .wireguard()
+        .shadowsocks()
+        .shadowsocks_port(51950)
+        .build();
+    assert!(relay_selector.get_relay_by_query(query).is_err());
+}
+
+/// Construct a query for a Wireguard configuration with a user-provided Shadowsocks server. Assert
+/// that the obfuscator connects to that server, and that it forwards the traffic to the selected
+/// relay.
+#[test]
+fn test_selecting_wireguard_endpoint_with_custom_shadowsocks_obfuscation() {
+    let relay_selector = default_relay_selector();
+    let custom_endpoint = CustomObfuscationEndpoint {
+        address: "192.0.2.1:8443".parse().unwrap(),
+        protocol: CustomObfuscationProtocol::Shadowsocks {
+            password: "hunter2".to_owned(),
+            cipher: "chacha20-ietf-poly1305".to_owned(),
+        },
+    };
+    let mut query = RelayQueryBuilder::new().wireguard().build();
+    query.wireguard_constraints.obfuscation = SelectedObfuscation::Custom;
+    query.wireguard_constraints.custom_obfuscation = Constraint::Only(custom_endpoint.clone());
+
+    let relay = relay_selector.get_relay_by_query(query).unwrap();
+    match relay {
+        GetRelay::Wireguard {
+            endpoint,
+            obfuscator: Some(obfuscator),
+            ..
+        } => match obfuscator.config {
+            ObfuscatorConfig::Shadowsocks {
+                endpoint: shadowsocks_endpoint,
+                wireguard_endpoint,
+                credentials: Some(credentials),
+            } => {
+                assert_eq!(shadowsocks_endpoint, custom_endpoint.address);
+                assert_eq!(wireguard_endpoint, Some(endpoint.peer.endpoint));
+                assert_eq!(credentials.password, "hunter2");
+                assert_eq!(credentials.cipher, "chacha20-ietf-poly1305");
+            }
+            config => panic!("Expected a custom Shadowsocks obfuscator, got {config:?}"),
+        },
+        wrong_relay => panic!(
+            "Relay selector should have picked an obfuscated Wireguard relay, instead chose {wrong_relay:?}"
+        ),
+    }
+}
+
+/// Construct a query for a Wireguard configuration with a user-provided udp2tcp server, and make
+/// sure that the obfuscator connects to that server instead of the relay. Since the server always
+/// forwards to the same relay, that relay must be selected.
+#[test]
+fn test_selecting_wireguard_endpoint_with_custom_udp2tcp_obfuscation() {
+    let relay_selector = default_relay_selector();
+    let address = "192.0.2.1:443".parse().unwrap();
+    let custom_udp2tcp = |relay: &str| CustomObfuscationEndpoint {
+        address,
+        protocol: CustomObfuscationProtocol::Udp2Tcp {
+            relay: relay.to_owned(),
+        },
+    };
+
+    for _ in 0..10 {
+        let mut query = RelayQueryBuilder::new().wireguard().build();
+        query.wireguard_constraints.obfuscation = SelectedObfuscation::Custom;
+        query.wireguard_constraints.custom_obfuscation =
+            Constraint::Only(custom_udp2tcp("se10-wireguard"));
+
+        let relay = relay_selector.get_relay_by_query(query).unwrap();
+        match relay {
+            GetRelay::Wireguard {
+                obfuscator: Some(obfuscator),
+                inner: WireguardConfig::Singlehop { exit },
+                ..
+            } => {
+                assert_eq!(exit.hostname, "se10-wireguard");
+                assert_eq!(obfuscator.relay.hostname, "se10-wireguard");
+                assert_eq!(
+                    obfuscator.config,
+                    ObfuscatorConfig::Udp2Tcp { endpoint: address }
+                );
+            }
+            wrong_relay => panic!(
+                "Relay selector should have picked an obfuscated Wireguard relay, instead chose {wrong_relay:?}"
+            ),
+        }
+    }
+
+    // A relay that does not exist cannot be connected to
+    let mut query = RelayQueryBuilder::new().wireguard().build();
+    query.wireguard_constraints.obfuscation = SelectedObfuscation::Custom;
+    query.wireguard_constraints.custom_obfuscation =
+        Constraint::Only(custom_udp2tcp("se99-wireguard"));
+    assert!(relay_selector.get_relay_by_query(query).is_err());
+}
+
+/// Selecting custom obfuscation without providing a server must fail rather than silently
+/// connecting without obfuscation.
+#[test]
+fn test_selecting_custom_obfuscation_without_endpoint() {
+    let relay_selector = default_relay_selector();
+    let mut query = RelayQueryBuilder::new().wireguard().build();
+    query.wireguard_constraints.obfuscation = SelectedObfuscation::Custom;
+
+    assert!(relay_selector.get_relay_by_query(query).is_err());
+}
+
 /// Verify that any query which sets an explicit [`Ownership`] is respected by the relay selector.
 #[test]
 fn test_ownership() {
@@ -979,6 +1263,7 @@ fn test_include_in_country() {
             ipv4_gateway: "10.64.0.1".parse().unwrap(),
             ipv6_gateway: "fc00:bbbb:bbbb:bb01::1".parse().unwrap(),
             udp2tcp_ports: vec![],
+            shadowsocks_port_ranges: vec![],
         },
     };
 
@@ -1023,6 +1308,46 @@ fn ignore_bridge_state_when_wireguard_is_used() {
     }
 }
 
+/// Verify that a WireGuard relay at a given location can be selected even if the settings ask for
+/// another tunnel protocol, location and obfuscation.
+#[test]
+fn test_get_wireguard_relay_at() {
+    let config = SelectorConfig {
+        relay_settings: RelaySettings::Normal(RelayConstraints {
+            tunnel_protocol: Constraint::Only(TunnelType::OpenVpn),
+            location: Constraint::Only(LocationConstraint::Location(
+                GeographicLocationConstraint::hostname("se", "got", "se-got-001"),
+            )),
+            ..RelayConstraints::default()
+        }),
+        obfuscation_settings: ObfuscationSettings {
+            selected_obfuscation: SelectedObfuscation::Udp2Tcp,
+            ..ObfuscationSettings::default()
+        },
+        ..SelectorConfig::default()
+    };
+    let relay_selector = RelaySelector::from_list(config, RELAYS.clone());
+    let location = Constraint::Only(LocationConstraint::Location(
+        GeographicLocationConstraint::hostname("se", "got", "se10-wireguard"),
+    ));
+
+    for _ in 0..10 {
+        let relay = relay_selector
+            .get_wireguard_relay_at(location.clone())
+            .unwrap();
+        match relay {
+            GetRelay::Wireguard {
+                obfuscator: None,
+                inner: WireguardConfig::Singlehop { exit },
+                ..
+            } => assert_eq!(exit.hostname, "se10-wireguard"),
+            wrong_relay => panic!(
+                "Relay selector should have picked an unobfuscated Wireguard relay, instead chose {wrong_relay:?}"
+            ),
+        }
+    }
+}
+
 /// Handle bridge setting when falling back on OpenVPN
 #[test]
 fn openvpn_handle_bridge_settings() {
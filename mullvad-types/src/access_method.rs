@@ -1,5 +1,7 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use talpid_types::net::proxy::{CustomProxy, Shadowsocks, Socks5Local, Socks5Remote};
+use std::time::Duration;
+use talpid_types::net::proxy::{CustomProxy, HttpConnect, Shadowsocks, Socks5Local, Socks5Remote};
 
 /// Settings for API access methods.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -43,7 +45,7 @@ impl Settings {
                 AccessMethod::BuiltIn(ref built_in) => Err(Error::RemoveBuiltin {
                     attempted: built_in.clone(),
                 }),
-                AccessMethod::Custom(_) => {
+                AccessMethod::Custom(_) | AccessMethod::Chain(_) => {
                     self.custom
                         .retain(|method| method.get_id() != *api_access_method);
                     self.ensure_consistent_state();
@@ -159,7 +161,7 @@ pub struct AccessMethodSetting {
     pub access_method: AccessMethod,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Id(uuid::Uuid);
 
 impl Id {
@@ -187,6 +189,9 @@ impl std::fmt::Display for Id {
 pub enum AccessMethod {
     BuiltIn(BuiltInAccessMethod),
     Custom(CustomProxy),
+    /// Custom proxies which are used in order. The first proxy is connected to directly, and
+    /// every other proxy is reached through the one before it.
+    Chain(Vec<CustomProxy>),
 }
 
 impl AccessMethodSetting {
@@ -238,7 +243,7 @@ impl AccessMethodSetting {
     }
 
     pub fn is_builtin(&self) -> bool {
-        self.as_custom().is_none()
+        matches!(self.access_method, AccessMethod::BuiltIn(_))
     }
 
     pub fn is_direct(&self) -> bool {
@@ -259,6 +264,53 @@ impl AccessMethodSetting {
     }
 }
 
+/// Observed outcomes of API requests made using some [`AccessMethodSetting`].
+///
+/// These are used to try the access methods which are most likely to work first.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AccessMethodStatistics {
+    /// Number of requests which reached the API.
+    pub successes: u32,
+    /// Number of requests which failed to reach the API.
+    pub failures: u32,
+    /// Number of failed requests since the last successful one.
+    pub consecutive_failures: u32,
+    pub last_success: Option<DateTime<Utc>>,
+    /// Moving average of the time it took to get a response.
+    pub latency: Option<Duration>,
+}
+
+impl AccessMethodStatistics {
+    /// How much of the previous latency average is kept when a new sample is added, out of
+    /// [`Self::LATENCY_WEIGHT_TOTAL`].
+    const LATENCY_WEIGHT_PREVIOUS: u32 = 3;
+    const LATENCY_WEIGHT_TOTAL: u32 = 4;
+
+    pub fn record_success(&mut self, latency: Duration) {
+        self.successes = self.successes.saturating_add(1);
+        self.consecutive_failures = 0;
+        self.last_success = Some(Utc::now());
+        self.latency = Some(match self.latency {
+            Some(average) => {
+                (average * Self::LATENCY_WEIGHT_PREVIOUS + latency) / Self::LATENCY_WEIGHT_TOTAL
+            }
+            None => latency,
+        });
+    }
+
+    pub fn record_failure(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+    }
+
+    /// Returns the share of requests which reached the API, or `None` if no requests have been
+    /// made.
+    pub fn success_rate(&self) -> Option<f64> {
+        let total = u64::from(self.successes) + u64::from(self.failures);
+        (total > 0).then(|| self.successes as f64 / total as f64)
+    }
+}
+
 /// Built-In access method datastructure.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -270,7 +322,7 @@ pub enum BuiltInAccessMethod {
 impl AccessMethod {
     pub fn as_custom(&self) -> Option<&CustomProxy> {
         match self {
-            AccessMethod::BuiltIn(_) => None,
+            AccessMethod::BuiltIn(_) | AccessMethod::Chain(_) => None,
             AccessMethod::Custom(access_method) => Some(access_method),
         }
     }
@@ -315,6 +367,12 @@ impl From<Socks5Local> for AccessMethod {
     }
 }
 
+impl From<HttpConnect> for AccessMethod {
+    fn from(value: HttpConnect) -> Self {
+        CustomProxy::HttpConnect(value).into()
+    }
+}
+
 impl From<Shadowsocks> for AccessMethod {
     fn from(value: Shadowsocks) -> Self {
         CustomProxy::Shadowsocks(value).into()
@@ -136,6 +136,7 @@ impl_intersection_partialeq!(relay_constraints::Providers);
 // NOTE: should take actual intersection
 impl_intersection_partialeq!(relay_constraints::LocationConstraint);
 impl_intersection_partialeq!(relay_constraints::Ownership);
+impl_intersection_partialeq!(relay_constraints::CustomObfuscationEndpoint);
 // NOTE: it contains an inner constraint
 impl_intersection_partialeq!(talpid_types::net::TransportProtocol);
 impl_intersection_partialeq!(talpid_types::net::TunnelType);
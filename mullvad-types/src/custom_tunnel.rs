@@ -8,6 +8,9 @@ use std::{
 };
 use talpid_types::net::{openvpn, proxy::CustomProxy, wireguard, Endpoint, TunnelParameters};
 
+pub mod ovpn;
+pub mod wg_quick;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Invalid host/domain: {0}")]
@@ -25,11 +28,33 @@ pub enum Error {
 pub struct CustomTunnelEndpoint {
     pub host: String,
     pub config: ConnectionConfig,
+    #[serde(default)]
+    pub wireguard_options: CustomWireguardOptions,
+}
+
+/// WireGuard settings that are specific to a custom tunnel endpoint. These take precedence over
+/// the WireGuard tunnel options.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CustomWireguardOptions {
+    pub mtu: Option<u16>,
+    /// Interval in seconds between keepalive packets sent to the peer
+    pub persistent_keepalive: Option<u16>,
+    /// Static preshared key shared with the peer
+    pub psk: Option<wireguard::PresharedKey>,
 }
 
 impl CustomTunnelEndpoint {
     pub fn new(host: String, config: ConnectionConfig) -> Self {
-        Self { host, config }
+        Self {
+            host,
+            config,
+            wireguard_options: CustomWireguardOptions::default(),
+        }
+    }
+
+    /// Creates a custom OpenVPN endpoint from an `.ovpn` configuration file.
+    pub fn from_ovpn(config: &str) -> Result<Self, ovpn::Error> {
+        ovpn::parse(config)
     }
 
     pub fn endpoint(&self) -> Endpoint {
@@ -58,12 +83,19 @@ impl CustomTunnelEndpoint {
                 fwmark: crate::TUNNEL_FWMARK,
             }
             .into(),
-            ConnectionConfig::Wireguard(connection) => {
+            ConnectionConfig::Wireguard(mut connection) => {
                 let mut options = tunnel_options.wireguard.into_talpid_tunnel_options();
                 if options.quantum_resistant {
                     options.quantum_resistant = false;
                     log::info!("Ignoring quantum resistant option for custom tunnel");
                 }
+                if let Some(mtu) = self.wireguard_options.mtu {
+                    options.mtu = Some(mtu);
+                }
+                if let Some(interval) = self.wireguard_options.persistent_keepalive {
+                    options.persistent_keepalive = Some(interval);
+                }
+                connection.peer.psk = self.wireguard_options.psk.clone();
                 wireguard::TunnelParameters {
                     connection,
                     options,
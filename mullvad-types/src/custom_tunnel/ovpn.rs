@@ -0,0 +1,545 @@
+//! Parsing of OpenVPN client configuration files (`.ovpn`) into custom tunnel endpoints.
+
+use super::{ConnectionConfig, CustomTunnelEndpoint, CustomWireguardOptions};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use talpid_types::net::{openvpn, Endpoint, TransportProtocol};
+
+/// Port used by OpenVPN unless another one is specified.
+const DEFAULT_PORT: u16 = 1194;
+
+/// Directives that run commands, load code or touch files on the host. These are rejected, since
+/// OpenVPN runs with elevated privileges.
+const UNSAFE_DIRECTIVES: &[&str] = &[
+    "up",
+    "down",
+    "route-up",
+    "route-pre-down",
+    "ipchange",
+    "tls-verify",
+    "auth-user-pass-verify",
+    "client-connect",
+    "client-disconnect",
+    "learn-address",
+    "script-security",
+    "plugin",
+    "setenv",
+    "setenv-safe",
+    "config",
+    "cd",
+    "chroot",
+    "daemon",
+    "writepid",
+    "log",
+    "log-append",
+    "status",
+    "management",
+    "user",
+    "group",
+];
+
+/// Directives that are either implied by how the daemon runs OpenVPN, or which do not affect the
+/// connection. These are ignored.
+const IGNORED_DIRECTIVES: &[&str] = &[
+    "client",
+    "tls-client",
+    "pull",
+    "dev",
+    "dev-type",
+    "nobind",
+    "persist-key",
+    "persist-tun",
+    "resolv-retry",
+    "remote-cert-tls",
+    "auth-nocache",
+    "verb",
+    "mute",
+    "mute-replay-warnings",
+    "redirect-gateway",
+    "keepalive",
+    "tls-version-min",
+    "explicit-exit-notify",
+    "remote-random",
+    "tun-mtu",
+    "reneg-sec",
+    "auth-retry",
+    "comp-lzo",
+    "sndbuf",
+    "rcvbuf",
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Line {0}: {1} is not allowed, since it could run commands or modify the host")]
+    UnsafeDirective(usize, String),
+
+    #[error("Line {0}: Unknown directive {1}")]
+    UnknownDirective(usize, String),
+
+    #[error("Line {0}: Invalid value for {1}")]
+    InvalidValue(usize, String),
+
+    #[error("Line {0}: {1} must be inlined as <{1}>...</{1}>")]
+    NotInline(usize, String),
+
+    #[error("Line {0}: Reading the username and password from a file is not supported")]
+    CredentialsFile(usize),
+
+    #[error("Line {0}: <{1}> is never closed")]
+    UnclosedBlock(usize, String),
+
+    #[error("Missing {0}")]
+    MissingDirective(&'static str),
+}
+
+#[derive(Default)]
+struct Config {
+    remote: Option<Remote>,
+    protocol: Option<TransportProtocol>,
+    port: Option<u16>,
+    ca: Option<String>,
+    cert: Option<String>,
+    key: Option<String>,
+    tls_crypt: Option<String>,
+    tls_auth: Option<String>,
+    key_direction: Option<u8>,
+    cipher: Option<String>,
+    data_ciphers: Vec<String>,
+    auth: Option<String>,
+    verify_x509_name: Option<openvpn::VerifyX509Name>,
+    compress: Option<String>,
+    auth_user_pass: bool,
+}
+
+struct Remote {
+    host: String,
+    port: Option<u16>,
+    protocol: Option<TransportProtocol>,
+}
+
+/// A directive and its arguments.
+struct Directive<'a> {
+    line: usize,
+    name: &'a str,
+    args: Vec<&'a str>,
+}
+
+impl Directive<'_> {
+    fn is(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+    }
+
+    fn arg(&self, index: usize) -> Result<&str, Error> {
+        self.args
+            .get(index)
+            .copied()
+            .ok_or_else(|| self.invalid_value())
+    }
+
+    fn invalid_value(&self) -> Error {
+        Error::InvalidValue(self.line, self.name.to_owned())
+    }
+
+    /// Returns the canonical form of `value` if it is in `allowed`, ignoring case.
+    fn allowed_value<'b>(&self, value: &str, allowed: &[&'b str]) -> Result<&'b str, Error> {
+        allowed
+            .iter()
+            .find(|allowed| allowed.eq_ignore_ascii_case(value))
+            .copied()
+            .ok_or_else(|| self.invalid_value())
+    }
+}
+
+impl Config {
+    fn set(&mut self, directive: Directive<'_>) -> Result<(), Error> {
+        if directive.is("remote") {
+            // Only the first remote is used
+            if self.remote.is_none() {
+                self.remote = Some(Remote {
+                    host: directive.arg(0)?.to_owned(),
+                    port: directive
+                        .args
+                        .get(1)
+                        .map(|port| port.parse().map_err(|_| directive.invalid_value()))
+                        .transpose()?,
+                    protocol: directive
+                        .args
+                        .get(2)
+                        .map(|protocol| parse_protocol(protocol).ok_or(directive.invalid_value()))
+                        .transpose()?,
+                });
+            }
+        } else if directive.is("proto") {
+            self.protocol =
+                Some(parse_protocol(directive.arg(0)?).ok_or(directive.invalid_value())?);
+        } else if directive.is("port") || directive.is("rport") {
+            self.port = Some(
+                directive
+                    .arg(0)?
+                    .parse()
+                    .map_err(|_| directive.invalid_value())?,
+            );
+        } else if directive.is("cipher") {
+            let cipher =
+                directive.allowed_value(directive.arg(0)?, openvpn::ALLOWED_DATA_CIPHERS)?;
+            self.cipher = Some(cipher.to_owned());
+        } else if directive.is("data-ciphers") || directive.is("ncp-ciphers") {
+            self.data_ciphers = directive
+                .arg(0)?
+                .split(':')
+                .map(|cipher| {
+                    directive
+                        .allowed_value(cipher, openvpn::ALLOWED_DATA_CIPHERS)
+                        .map(str::to_owned)
+                })
+                .collect::<Result<_, _>>()?;
+        } else if directive.is("auth") {
+            let digest =
+                directive.allowed_value(directive.arg(0)?, openvpn::ALLOWED_AUTH_DIGESTS)?;
+            self.auth = Some(digest.to_owned());
+        } else if directive.is("key-direction") {
+            self.key_direction = match directive.arg(0)? {
+                "0" => Some(0),
+                "1" => Some(1),
+                _ => return Err(directive.invalid_value()),
+            };
+        } else if directive.is("verify-x509-name") {
+            let name_type = match directive.args.get(1) {
+                Some(name_type) => name_type
+                    .to_ascii_lowercase()
+                    .parse()
+                    .map_err(|_| directive.invalid_value())?,
+                None => openvpn::X509NameType::Subject,
+            };
+            self.verify_x509_name = Some(openvpn::VerifyX509Name {
+                name: directive.arg(0)?.to_owned(),
+                name_type,
+            });
+        } else if directive.is("compress") {
+            // Without an algorithm, only the framing is enabled, which is what the stub does
+            let algorithm = directive.args.first().copied().unwrap_or("stub");
+            let algorithm = directive.allowed_value(algorithm, openvpn::ALLOWED_COMPRESSION)?;
+            self.compress = Some(algorithm.to_owned());
+        } else if directive.is("auth-user-pass") {
+            // The file is not imported along with the config
+            if !directive.args.is_empty() {
+                return Err(Error::CredentialsFile(directive.line));
+            }
+            self.auth_user_pass = true;
+        } else if ["ca", "cert", "key", "tls-crypt", "tls-auth"]
+            .iter()
+            .any(|name| directive.is(name))
+        {
+            return Err(Error::NotInline(directive.line, directive.name.to_owned()));
+        } else if UNSAFE_DIRECTIVES.iter().any(|name| directive.is(name)) {
+            return Err(Error::UnsafeDirective(
+                directive.line,
+                directive.name.to_owned(),
+            ));
+        } else if !IGNORED_DIRECTIVES.iter().any(|name| directive.is(name)) {
+            return Err(Error::UnknownDirective(
+                directive.line,
+                directive.name.to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Stores the contents of an inline `<name>...</name>` block.
+    fn set_block(&mut self, line: usize, name: &str, contents: String) -> Result<(), Error> {
+        let field = match name {
+            "ca" => &mut self.ca,
+            "cert" => &mut self.cert,
+            "key" => &mut self.key,
+            "tls-crypt" => &mut self.tls_crypt,
+            "tls-auth" => &mut self.tls_auth,
+            _ if UNSAFE_DIRECTIVES.contains(&name) => {
+                return Err(Error::UnsafeDirective(line, name.to_owned()))
+            }
+            _ => return Err(Error::UnknownDirective(line, name.to_owned())),
+        };
+        *field = Some(contents);
+        Ok(())
+    }
+}
+
+fn parse_protocol(protocol: &str) -> Option<TransportProtocol> {
+    match protocol.to_ascii_lowercase().as_str() {
+        "udp" | "udp4" | "udp6" => Some(TransportProtocol::Udp),
+        "tcp" | "tcp4" | "tcp6" | "tcp-client" | "tcp4-client" | "tcp6-client" => {
+            Some(TransportProtocol::Tcp)
+        }
+        _ => None,
+    }
+}
+
+/// Splits a line into words separated by whitespace. Words may be enclosed in single or double
+/// quotes to include whitespace.
+fn split_words(line: &str) -> impl Iterator<Item = &str> {
+    let mut rest = line.trim_start();
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let (word, remaining) = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => match rest[1..].find(quote) {
+                Some(end) => (&rest[1..end + 1], &rest[end + 2..]),
+                None => (&rest[1..], ""),
+            },
+            _ => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+        };
+        rest = remaining.trim_start();
+        Some(word)
+    })
+}
+
+/// Parses an OpenVPN client configuration file. Certificates and keys must be inlined. The
+/// username and password are left empty, and must be filled in if
+/// [`openvpn::CustomServerConfig::auth_user_pass`] is set.
+pub fn parse(config: &str) -> Result<CustomTunnelEndpoint, Error> {
+    let mut parsed = Config::default();
+    let mut lines = config.lines().enumerate();
+
+    while let Some((index, line)) = lines.next() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix('<')
+            .and_then(|line| line.strip_suffix('>'))
+        {
+            let name = name.to_ascii_lowercase();
+            let end_tag = format!("</{name}>");
+            let mut contents = String::new();
+            loop {
+                let Some((_, line)) = lines.next() else {
+                    return Err(Error::UnclosedBlock(line_number, name));
+                };
+                if line.trim().eq_ignore_ascii_case(&end_tag) {
+                    break;
+                }
+                contents.push_str(line.trim());
+                contents.push('\n');
+            }
+            parsed.set_block(line_number, &name, contents)?;
+            continue;
+        }
+
+        let mut words = split_words(line);
+        let name = words.next().unwrap_or_default();
+        // Strip trailing comments
+        let args = words
+            .take_while(|word| !word.starts_with('#') && !word.starts_with(';'))
+            .collect();
+        parsed.set(Directive {
+            line: line_number,
+            name: name.strip_prefix("--").unwrap_or(name),
+            args,
+        })?;
+    }
+
+    let remote = parsed.remote.ok_or(Error::MissingDirective("remote"))?;
+    let ca = parsed.ca.ok_or(Error::MissingDirective("<ca>"))?;
+    let port = remote.port.or(parsed.port).unwrap_or(DEFAULT_PORT);
+    let protocol = remote
+        .protocol
+        .or(parsed.protocol)
+        .unwrap_or(TransportProtocol::Udp);
+
+    Ok(CustomTunnelEndpoint {
+        host: remote.host,
+        config: ConnectionConfig::OpenVpn(openvpn::ConnectionConfig {
+            // The host is resolved when connecting
+            endpoint: Endpoint::from_socket_address(
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
+                protocol,
+            ),
+            username: String::new(),
+            password: String::new(),
+            custom_server: Some(openvpn::CustomServerConfig {
+                ca,
+                cert: parsed.cert,
+                key: parsed.key,
+                tls_crypt: parsed.tls_crypt,
+                tls_auth: parsed.tls_auth,
+                key_direction: parsed.key_direction,
+                cipher: parsed.cipher,
+                data_ciphers: parsed.data_ciphers,
+                auth: parsed.auth,
+                verify_x509_name: parsed.verify_x509_name,
+                compress: parsed.compress,
+                auth_user_pass: parsed.auth_user_pass,
+            }),
+        }),
+        wireguard_options: CustomWireguardOptions::default(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CA: &str = "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n";
+
+    fn config(extra: &str) -> String {
+        format!(
+            "client
+dev tun
+# A comment
+remote vpn.example.com 443 tcp
+remote backup.example.com 1194
+cipher AES-256-GCM
+auth-user-pass
+{extra}
+<ca>
+{CA}</ca>
+<tls-crypt>
+-----BEGIN OpenVPN Static key V1-----
+abcd
+-----END OpenVPN Static key V1-----
+</tls-crypt>
+"
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let endpoint = parse(&config("")).unwrap();
+        assert_eq!(endpoint.host, "vpn.example.com");
+
+        let ConnectionConfig::OpenVpn(config) = endpoint.config else {
+            panic!("expected an OpenVPN config");
+        };
+        assert_eq!(config.endpoint.address.port(), 443);
+        assert_eq!(config.endpoint.protocol, TransportProtocol::Tcp);
+
+        let server = config.custom_server.unwrap();
+        assert_eq!(server.ca, CA);
+        assert!(server.tls_crypt.unwrap().contains("abcd"));
+        assert_eq!(server.cert, None);
+        assert_eq!(server.cipher.as_deref(), Some("AES-256-GCM"));
+        assert!(server.auth_user_pass);
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = format!("remote 192.0.2.1\n<ca>\n{CA}</ca>\n");
+        let endpoint = parse(&config).unwrap();
+        let ConnectionConfig::OpenVpn(config) = endpoint.config else {
+            panic!("expected an OpenVPN config");
+        };
+        assert_eq!(config.endpoint.address.port(), DEFAULT_PORT);
+        assert_eq!(config.endpoint.protocol, TransportProtocol::Udp);
+        assert!(!config.custom_server.unwrap().auth_user_pass);
+    }
+
+    #[test]
+    fn test_unsafe_directive() {
+        for extra in ["script-security 2", "up /etc/openvpn/update-resolv-conf"] {
+            let error = parse(&config(extra)).unwrap_err();
+            assert!(matches!(error, Error::UnsafeDirective(8, _)), "{extra}");
+        }
+    }
+
+    #[test]
+    fn test_not_inline() {
+        let error = parse(&config("cert client.crt")).unwrap_err();
+        assert!(matches!(error, Error::NotInline(8, name) if name == "cert"));
+    }
+
+    #[test]
+    fn test_tls_auth() {
+        let contents = format!(
+            "remote vpn.example.com
+key-direction 1
+<ca>
+{CA}</ca>
+<tls-auth>
+efgh
+</tls-auth>
+"
+        );
+        let ConnectionConfig::OpenVpn(connection) = parse(&contents).unwrap().config else {
+            panic!("expected an OpenVPN config");
+        };
+        let server = connection.custom_server.unwrap();
+        assert_eq!(server.tls_auth.as_deref(), Some("efgh\n"));
+        assert_eq!(server.key_direction, Some(1));
+
+        let error = parse(&config("tls-auth ta.key 1")).unwrap_err();
+        assert!(matches!(error, Error::NotInline(8, name) if name == "tls-auth"));
+        let error = parse(&config("key-direction 2")).unwrap_err();
+        assert!(matches!(error, Error::InvalidValue(8, _)));
+    }
+
+    #[test]
+    fn test_security_options() {
+        let extra = "data-ciphers aes-256-gcm:CHACHA20-POLY1305
+auth sha256
+verify-x509-name 'C=SE, CN=vpn.example.com' subject
+compress";
+        let ConnectionConfig::OpenVpn(config) = parse(&config(extra)).unwrap().config else {
+            panic!("expected an OpenVPN config");
+        };
+        let server = config.custom_server.unwrap();
+        assert_eq!(server.data_ciphers, ["AES-256-GCM", "CHACHA20-POLY1305"]);
+        assert_eq!(server.auth.as_deref(), Some("SHA256"));
+        assert_eq!(
+            server.verify_x509_name,
+            Some(openvpn::VerifyX509Name {
+                name: "C=SE, CN=vpn.example.com".to_owned(),
+                name_type: openvpn::X509NameType::Subject,
+            })
+        );
+        assert_eq!(server.compress.as_deref(), Some("stub"));
+    }
+
+    #[test]
+    fn test_ignored_directives() {
+        let extra = "redirect-gateway def1
+keepalive 10 60
+tls-version-min 1.2
+explicit-exit-notify 2
+remote-random
+tun-mtu 1500
+reneg-sec 0
+auth-retry nointeract
+comp-lzo no
+sndbuf 393216
+rcvbuf 393216";
+        assert!(parse(&config(extra)).is_ok());
+    }
+
+    /// `ncp-ciphers` is the name that `data-ciphers` had before OpenVPN 2.5.
+    #[test]
+    fn test_ncp_ciphers() {
+        let ConnectionConfig::OpenVpn(config) =
+            parse(&config("ncp-ciphers AES-256-GCM")).unwrap().config
+        else {
+            panic!("expected an OpenVPN config");
+        };
+        assert_eq!(config.custom_server.unwrap().data_ciphers, ["AES-256-GCM"]);
+    }
+
+    #[test]
+    fn test_disallowed_values() {
+        for extra in [
+            "cipher BF-CBC",
+            "data-ciphers AES-256-GCM:BF-CBC",
+            "auth MD5",
+            "compress lzma",
+            "verify-x509-name vpn.example.com issuer",
+        ] {
+            let error = parse(&config(extra)).unwrap_err();
+            assert!(matches!(error, Error::InvalidValue(8, _)), "{extra}");
+        }
+    }
+
+    #[test]
+    fn test_missing_ca() {
+        let error = parse("remote vpn.example.com\n").unwrap_err();
+        assert!(matches!(error, Error::MissingDirective("<ca>")));
+    }
+}
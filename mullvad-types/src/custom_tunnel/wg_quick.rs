@@ -0,0 +1,487 @@
+//! Parsing of `wg-quick` configuration files into custom tunnel endpoints.
+
+use super::{ConnectionConfig, CustomTunnelEndpoint, CustomWireguardOptions};
+use ipnetwork::IpNetwork;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use talpid_types::net::wireguard;
+
+/// Directives that `wg-quick` understands but which cannot be applied to a custom tunnel
+/// endpoint, since the daemon manages the interface, routes and firewall itself.
+const UNSUPPORTED_DIRECTIVES: &[&str] = &[
+    "ListenPort",
+    "FwMark",
+    "Table",
+    "PreUp",
+    "PostUp",
+    "PreDown",
+    "PostDown",
+    "SaveConfig",
+];
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Line {0}: Expected a section header or \"Key = Value\"")]
+    InvalidLine(usize),
+
+    #[error("Line {0}: Unknown section [{1}]")]
+    UnknownSection(usize, String),
+
+    #[error("Line {0}: {1} must be inside an [Interface] or [Peer] section")]
+    OutsideSection(usize, String),
+
+    #[error("Line {0}: {1} is not supported for custom tunnel endpoints")]
+    UnsupportedDirective(usize, String),
+
+    #[error("Line {0}: Unknown directive {1}")]
+    UnknownDirective(usize, String),
+
+    #[error("Line {0}: Invalid value for {1}")]
+    InvalidValue(usize, String),
+
+    #[error("Line {0}: DNS search domains are not supported")]
+    DnsSearchDomain(usize),
+
+    #[error("Expected exactly one [Peer] section, found {0}")]
+    PeerCount(usize),
+
+    #[error("Missing {0}")]
+    MissingDirective(&'static str),
+
+    #[error(
+        "Cannot determine the IPv4 gateway. The IPv4 Address must include the prefix length of \
+         the tunnel network, e.g. 10.8.0.2/24"
+    )]
+    NoIpv4Gateway,
+}
+
+/// A parsed `wg-quick` configuration file.
+#[derive(Debug)]
+pub struct WgQuickConfig {
+    pub endpoint: CustomTunnelEndpoint,
+    /// DNS servers listed in the configuration. These are not part of the endpoint, since the DNS
+    /// servers are set using the DNS settings.
+    pub dns_servers: Vec<IpAddr>,
+}
+
+enum Section {
+    Interface,
+    Peer,
+}
+
+#[derive(Default)]
+struct Interface {
+    private_key: Option<wireguard::PrivateKey>,
+    addresses: Vec<IpNetwork>,
+    dns: Vec<IpAddr>,
+    mtu: Option<u16>,
+}
+
+#[derive(Default)]
+struct Peer {
+    public_key: Option<wireguard::PublicKey>,
+    psk: Option<wireguard::PresharedKey>,
+    allowed_ips: Vec<IpNetwork>,
+    endpoint: Option<(String, u16)>,
+    persistent_keepalive: Option<u16>,
+}
+
+/// A single `Key = Value` line.
+struct Directive<'a> {
+    line: usize,
+    key: &'a str,
+    value: &'a str,
+}
+
+impl Directive<'_> {
+    fn is(&self, key: &str) -> bool {
+        self.key.eq_ignore_ascii_case(key)
+    }
+
+    fn parse<T: std::str::FromStr>(&self) -> Result<T, Error> {
+        self.value.parse().map_err(|_| self.invalid_value())
+    }
+
+    fn parse_list<T: std::str::FromStr>(&self) -> Result<Vec<T>, Error> {
+        self.list()
+            .map(|item| item.parse().map_err(|_| self.invalid_value()))
+            .collect()
+    }
+
+    fn list(&self) -> impl Iterator<Item = &str> {
+        self.value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+    }
+
+    fn invalid_value(&self) -> Error {
+        Error::InvalidValue(self.line, self.key.to_owned())
+    }
+
+    fn unknown(&self) -> Error {
+        match UNSUPPORTED_DIRECTIVES
+            .iter()
+            .find(|unsupported| self.is(unsupported))
+        {
+            Some(unsupported) => Error::UnsupportedDirective(self.line, (*unsupported).to_owned()),
+            None => Error::UnknownDirective(self.line, self.key.to_owned()),
+        }
+    }
+}
+
+impl Interface {
+    fn set(&mut self, directive: Directive<'_>) -> Result<(), Error> {
+        if directive.is("PrivateKey") {
+            self.private_key = Some(
+                wireguard::PrivateKey::from_base64(directive.value)
+                    .map_err(|_| directive.invalid_value())?,
+            );
+        } else if directive.is("Address") {
+            self.addresses.extend(directive.parse_list::<IpNetwork>()?);
+        } else if directive.is("DNS") {
+            for server in directive.list() {
+                match server.parse() {
+                    Ok(server) => self.dns.push(server),
+                    Err(_) if is_domain_name(server) => {
+                        return Err(Error::DnsSearchDomain(directive.line))
+                    }
+                    Err(_) => return Err(directive.invalid_value()),
+                }
+            }
+        } else if directive.is("MTU") {
+            self.mtu = Some(directive.parse()?);
+        } else {
+            return Err(directive.unknown());
+        }
+        Ok(())
+    }
+}
+
+impl Peer {
+    fn set(&mut self, directive: Directive<'_>) -> Result<(), Error> {
+        if directive.is("PublicKey") {
+            self.public_key = Some(
+                wireguard::PublicKey::from_base64(directive.value)
+                    .map_err(|_| directive.invalid_value())?,
+            );
+        } else if directive.is("PresharedKey") {
+            self.psk = Some(
+                wireguard::PresharedKey::from_base64(directive.value)
+                    .map_err(|_| directive.invalid_value())?,
+            );
+        } else if directive.is("AllowedIPs") {
+            self.allowed_ips
+                .extend(directive.parse_list::<IpNetwork>()?);
+        } else if directive.is("Endpoint") {
+            self.endpoint = Some(parse_endpoint(directive.value).ok_or(directive.invalid_value())?);
+        } else if directive.is("PersistentKeepalive") {
+            self.persistent_keepalive = match directive.value {
+                "off" => None,
+                _ => Some(directive.parse()?).filter(|&interval| interval != 0),
+            };
+        } else {
+            return Err(directive.unknown());
+        }
+        Ok(())
+    }
+}
+
+/// Splits an endpoint into a host and a port. IPv6 addresses must be enclosed in brackets.
+fn parse_endpoint(endpoint: &str) -> Option<(String, u16)> {
+    let (host, port) = endpoint.rsplit_once(':')?;
+    let host = match host.strip_prefix('[') {
+        Some(host) => host.strip_suffix(']')?,
+        None if host.contains(':') => return None,
+        None => host,
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some((host.to_owned(), port.parse().ok()?))
+}
+
+/// Returns whether `name` is a syntactically valid domain name, which `wg-quick` would treat as a
+/// DNS search domain.
+fn is_domain_name(name: &str) -> bool {
+    let labels: Vec<&str> = name.trim_end_matches('.').split('.').collect();
+    let valid_label = |label: &&str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    // A top-level domain is never numeric, so that mistyped IP addresses are not accepted
+    labels.iter().all(valid_label)
+        && labels
+            .last()
+            .is_some_and(|tld| !tld.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Parses a `wg-quick` configuration file with a single peer. The first host in the network of
+/// the first IPv4 and IPv6 tunnel address is used as the gateway.
+pub fn parse(config: &str) -> Result<WgQuickConfig, Error> {
+    let mut interface = Interface::default();
+    let mut peers: Vec<Peer> = vec![];
+    let mut section = None;
+
+    for (index, line) in config.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            let name = name.trim();
+            section = if name.eq_ignore_ascii_case("Interface") {
+                Some(Section::Interface)
+            } else if name.eq_ignore_ascii_case("Peer") {
+                peers.push(Peer::default());
+                Some(Section::Peer)
+            } else {
+                return Err(Error::UnknownSection(line_number, name.to_owned()));
+            };
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or(Error::InvalidLine(line_number))?;
+        let directive = Directive {
+            line: line_number,
+            key: key.trim(),
+            value: value.trim(),
+        };
+        match section {
+            Some(Section::Interface) => interface.set(directive)?,
+            // A peer is added whenever a [Peer] section starts
+            Some(Section::Peer) => peers.last_mut().unwrap().set(directive)?,
+            None => return Err(Error::OutsideSection(line_number, directive.key.to_owned())),
+        }
+    }
+
+    if peers.len() != 1 {
+        return Err(Error::PeerCount(peers.len()));
+    }
+    let peer = peers.remove(0);
+
+    let private_key = interface
+        .private_key
+        .ok_or(Error::MissingDirective("PrivateKey"))?;
+    if interface.addresses.is_empty() {
+        return Err(Error::MissingDirective("Address"));
+    }
+    let public_key = peer
+        .public_key
+        .ok_or(Error::MissingDirective("PublicKey"))?;
+    let (host, port) = peer.endpoint.ok_or(Error::MissingDirective("Endpoint"))?;
+    if peer.allowed_ips.is_empty() {
+        return Err(Error::MissingDirective("AllowedIPs"));
+    }
+
+    let ipv4_gateway = ipv4_gateway(&interface.addresses).ok_or(Error::NoIpv4Gateway)?;
+    let ipv6_gateway = ipv6_gateway(&interface.addresses);
+
+    let endpoint = CustomTunnelEndpoint {
+        host,
+        config: ConnectionConfig::Wireguard(wireguard::ConnectionConfig {
+            tunnel: wireguard::TunnelConfig {
+                private_key,
+                addresses: interface
+                    .addresses
+                    .iter()
+                    .map(|address| address.ip())
+                    .collect(),
+            },
+            peer: wireguard::PeerConfig {
+                public_key,
+                allowed_ips: peer.allowed_ips,
+                // The host is resolved when connecting
+                endpoint: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
+                psk: None,
+            },
+            exit_peer: None,
+            ipv4_gateway,
+            ipv6_gateway,
+            #[cfg(target_os = "linux")]
+            fwmark: None,
+        }),
+        wireguard_options: CustomWireguardOptions {
+            mtu: interface.mtu,
+            persistent_keepalive: peer.persistent_keepalive,
+            psk: peer.psk,
+        },
+    };
+    Ok(WgQuickConfig {
+        endpoint,
+        dns_servers: interface.dns,
+    })
+}
+
+/// Returns the first host in the network of the first IPv4 tunnel address, if the network
+/// contains any other hosts.
+fn ipv4_gateway(addresses: &[IpNetwork]) -> Option<Ipv4Addr> {
+    let network = addresses.iter().find_map(|address| match address {
+        IpNetwork::V4(network) => Some(network),
+        IpNetwork::V6(_) => None,
+    })?;
+    if network.prefix() > 30 {
+        return None;
+    }
+    let gateway = Ipv4Addr::from(u32::from(network.network()) + 1);
+    (gateway != network.ip()).then_some(gateway)
+}
+
+/// Returns the first host in the network of the first IPv6 tunnel address, if the network
+/// contains any other hosts.
+fn ipv6_gateway(addresses: &[IpNetwork]) -> Option<Ipv6Addr> {
+    let network = addresses.iter().find_map(|address| match address {
+        IpNetwork::V6(network) => Some(network),
+        IpNetwork::V4(_) => None,
+    })?;
+    if network.prefix() > 126 {
+        return None;
+    }
+    let gateway = Ipv6Addr::from(u128::from(network.network()) + 1);
+    (gateway != network.ip()).then_some(gateway)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv6Addr;
+
+    const PRIVATE_KEY: &str = "mPue6Xt0pdz4NRAhfQSp/SLKo7kV7DW+2zvBq0N9iUI=";
+    const PUBLIC_KEY: &str = "7svBwGBefP7KVmH/yes+pZCfO6uSOYeGieYYa1+kZ0E=";
+    const PSK: &str = "WnGoBhH9ojAMXotbTJ+3ZTZPyRdPGvwH/ShVPvUg/Jg=";
+
+    fn config(extra_interface: &str, extra_peer: &str) -> String {
+        format!(
+            "[Interface]
+# A comment
+PrivateKey = {PRIVATE_KEY}
+Address = 10.66.0.2/16, fc00:bbbb::2/64
+DNS = 1.1.1.1, 2606:4700::1111
+{extra_interface}
+
+[Peer]
+PublicKey = {PUBLIC_KEY}
+AllowedIPs = 0.0.0.0/0
+AllowedIPs = ::/0
+Endpoint = vpn.example.com:51820
+{extra_peer}
+"
+        )
+    }
+
+    #[test]
+    fn test_parse() {
+        let WgQuickConfig {
+            endpoint,
+            dns_servers,
+        } = parse(&config(
+            "MTU = 1280",
+            &format!("PresharedKey = {PSK}\npersistentkeepalive = 25"),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            dns_servers,
+            vec![
+                IpAddr::from(Ipv4Addr::new(1, 1, 1, 1)),
+                "2606:4700::1111".parse::<IpAddr>().unwrap()
+            ]
+        );
+        assert_eq!(endpoint.host, "vpn.example.com");
+        assert_eq!(endpoint.wireguard_options.mtu, Some(1280));
+        assert_eq!(endpoint.wireguard_options.persistent_keepalive, Some(25));
+        assert_eq!(
+            endpoint.wireguard_options.psk,
+            Some(wireguard::PresharedKey::from_base64(PSK).unwrap())
+        );
+
+        let ConnectionConfig::Wireguard(config) = endpoint.config else {
+            panic!("expected a WireGuard config");
+        };
+        assert_eq!(
+            config.tunnel.private_key,
+            wireguard::PrivateKey::from_base64(PRIVATE_KEY).unwrap()
+        );
+        assert_eq!(
+            config.tunnel.addresses,
+            vec![
+                IpAddr::from(Ipv4Addr::new(10, 66, 0, 2)),
+                "fc00:bbbb::2".parse::<IpAddr>().unwrap()
+            ]
+        );
+        assert_eq!(config.peer.public_key.to_base64(), PUBLIC_KEY);
+        assert_eq!(config.peer.endpoint.port(), 51820);
+        assert_eq!(config.peer.allowed_ips.len(), 2);
+        assert_eq!(config.ipv4_gateway, Ipv4Addr::new(10, 66, 0, 1));
+        assert_eq!(
+            config.ipv6_gateway,
+            Some("fc00:bbbb::1".parse::<Ipv6Addr>().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_gateway_from_address() {
+        let config = format!(
+            "[Interface]\nPrivateKey = {PRIVATE_KEY}\nAddress = 10.8.0.5/24\n\
+             [Peer]\nPublicKey = {PUBLIC_KEY}\nAllowedIPs = 0.0.0.0/0\nEndpoint = [2001:db8::1]:443"
+        );
+        let endpoint = parse(&config).unwrap().endpoint;
+        assert_eq!(endpoint.host, "2001:db8::1");
+        let ConnectionConfig::Wireguard(config) = endpoint.config else {
+            panic!("expected a WireGuard config");
+        };
+        assert_eq!(config.ipv4_gateway, Ipv4Addr::new(10, 8, 0, 1));
+        assert_eq!(config.ipv6_gateway, None);
+    }
+
+    #[test]
+    fn test_no_gateway_for_host_address() {
+        let config = config("", "").replace("10.66.0.2/16", "10.66.0.2/32");
+        assert!(matches!(parse(&config), Err(Error::NoIpv4Gateway)));
+    }
+
+    #[test]
+    fn test_invalid_dns() {
+        let config = |dns| {
+            format!(
+                "[Interface]\nPrivateKey = {PRIVATE_KEY}\nAddress = 10.8.0.5/24\nDNS = {dns}\n\
+                 [Peer]\nPublicKey = {PUBLIC_KEY}\nAllowedIPs = 0.0.0.0/0\nEndpoint = 1.2.3.4:443"
+            )
+        };
+        assert!(matches!(
+            parse(&config("10.8.0.1, example.com")),
+            Err(Error::DnsSearchDomain(4))
+        ));
+        assert!(matches!(
+            parse(&config("10.8.0.1.5")),
+            Err(Error::InvalidValue(4, directive)) if directive == "DNS"
+        ));
+        assert!(matches!(
+            parse(&config("10.8.0.1 10.8.0.2")),
+            Err(Error::InvalidValue(4, _))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_directive() {
+        let error = parse(&config("PostUp = iptables -A FORWARD", "")).unwrap_err();
+        assert!(
+            matches!(error, Error::UnsupportedDirective(6, directive) if directive == "PostUp")
+        );
+    }
+
+    #[test]
+    fn test_multiple_peers() {
+        let config = format!("{}\n[Peer]\nPublicKey = {PUBLIC_KEY}", config("", ""));
+        assert!(matches!(parse(&config), Err(Error::PeerCount(2))));
+    }
+}
@@ -14,7 +14,7 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::HashSet,
     fmt,
-    net::{Ipv4Addr, Ipv6Addr},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
     str::FromStr,
 };
 use talpid_types::net::{proxy::CustomProxy, IpVersion, TransportProtocol, TunnelType};
@@ -611,6 +611,14 @@ pub enum SelectedObfuscation {
     Off,
     #[cfg_attr(feature = "clap", clap(name = "udp2tcp"))]
     Udp2Tcp,
+    #[cfg_attr(feature = "clap", clap(name = "shadowsocks"))]
+    Shadowsocks,
+    /// Use the user-provided obfuscation server in [`ObfuscationSettings::custom`].
+    Custom,
+    /// Carry the traffic in a QUIC connection to the relay. Only available if the daemon is built
+    /// with QUIC support.
+    #[cfg_attr(feature = "clap", clap(name = "quic"))]
+    Quic,
 }
 
 impl Intersection for SelectedObfuscation {
@@ -634,6 +642,9 @@ impl fmt::Display for SelectedObfuscation {
             SelectedObfuscation::Auto => "auto".fmt(f),
             SelectedObfuscation::Off => "off".fmt(f),
             SelectedObfuscation::Udp2Tcp => "udp2tcp".fmt(f),
+            SelectedObfuscation::Shadowsocks => "shadowsocks".fmt(f),
+            SelectedObfuscation::Custom => "custom".fmt(f),
+            SelectedObfuscation::Quic => "quic".fmt(f),
         }
     }
 }
@@ -686,6 +697,68 @@ impl fmt::Display for Udp2TcpObfuscationSettings {
     }
 }
 
+#[derive(Default, Debug, Clone, Eq, PartialEq, Deserialize, Serialize, Intersection)]
+#[serde(rename_all = "snake_case")]
+pub struct ShadowsocksObfuscationSettings {
+    pub port: Constraint<u16>,
+}
+
+impl fmt::Display for ShadowsocksObfuscationSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.port {
+            Constraint::Any => write!(f, "any port"),
+            Constraint::Only(port) => write!(f, "port {port}"),
+        }
+    }
+}
+
+/// An obfuscation server operated by the user. It is connected to instead of the selected relay,
+/// and is expected to forward the WireGuard traffic to the relay.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CustomObfuscationEndpoint {
+    pub address: SocketAddr,
+    pub protocol: CustomObfuscationProtocol,
+}
+
+#[derive(Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CustomObfuscationProtocol {
+    /// A udp2tcp server. It must be configured to forward the traffic to `relay`, since the
+    /// protocol cannot carry the destination. Only that relay is connected to.
+    Udp2Tcp { relay: Hostname },
+    /// A Shadowsocks server, which forwards the traffic to the WireGuard endpoint of the relay.
+    Shadowsocks { password: String, cipher: String },
+}
+
+impl fmt::Debug for CustomObfuscationProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CustomObfuscationProtocol::Udp2Tcp { relay } => {
+                f.debug_struct("Udp2Tcp").field("relay", relay).finish()
+            }
+            CustomObfuscationProtocol::Shadowsocks { cipher, .. } => f
+                .debug_struct("Shadowsocks")
+                .field("password", &"<redacted>")
+                .field("cipher", cipher)
+                .finish(),
+        }
+    }
+}
+
+impl fmt::Display for CustomObfuscationEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.protocol {
+            CustomObfuscationProtocol::Udp2Tcp { relay } => {
+                write!(f, "udp2tcp server {} forwarding to {relay}", self.address)
+            }
+            CustomObfuscationProtocol::Shadowsocks { cipher, .. } => {
+                write!(f, "Shadowsocks server {} ({cipher})", self.address)
+            }
+        }
+    }
+}
+
 /// Contains obfuscation settings
 #[derive(Default, Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 #[cfg_attr(target_os = "android", derive(FromJava, IntoJava))]
@@ -695,6 +768,11 @@ impl fmt::Display for Udp2TcpObfuscationSettings {
 pub struct ObfuscationSettings {
     pub selected_obfuscation: SelectedObfuscation,
     pub udp2tcp: Udp2TcpObfuscationSettings,
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub shadowsocks: ShadowsocksObfuscationSettings,
+    /// Obfuscation server to use when [`SelectedObfuscation::Custom`] is selected.
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub custom: Option<CustomObfuscationEndpoint>,
 }
 
 /// Limits the set of bridge servers to use in `mullvad-daemon`.
@@ -202,6 +202,9 @@ pub struct WireguardEndpointData {
     pub ipv6_gateway: Ipv6Addr,
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub udp2tcp_ports: Vec<u16>,
+    /// Ports on which the relays accept Shadowsocks traffic
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub shadowsocks_port_ranges: Vec<(u16, u16)>,
 }
 
 impl Default for WireguardEndpointData {
@@ -211,6 +214,7 @@ impl Default for WireguardEndpointData {
             ipv4_gateway: "0.0.0.0".parse().unwrap(),
             ipv6_gateway: "::".parse().unwrap(),
             udp2tcp_ports: vec![],
+            shadowsocks_port_ranges: vec![],
         }
     }
 }
@@ -2,6 +2,10 @@
 use jnix::{FromJava, IntoJava};
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::path::PathBuf;
+#[cfg(target_os = "linux")]
+use talpid_types::net::dns::DnsManager;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
 #[serde(rename_all = "snake_case")]
@@ -22,6 +26,13 @@ pub struct DnsOptions {
     pub state: DnsState,
     pub default_options: DefaultDnsOptions,
     pub custom_options: CustomDnsOptions,
+    /// User-supplied domain lists, enforced by the local resolver in addition to
+    /// `default_options` or `custom_options`.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub blocklists: DnsBlocklistOptions,
+    /// The system component used to manage DNS.
+    #[cfg(target_os = "linux")]
+    pub manager: DnsManager,
 }
 
 /// Default DNS config
@@ -45,3 +56,15 @@ pub struct DefaultDnsOptions {
 pub struct CustomDnsOptions {
     pub addresses: Vec<IpAddr>,
 }
+
+/// User-supplied domain lists. Each file may be in hosts format or contain one domain per line.
+/// The lists are reloaded when they change on disk.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Hash)]
+#[serde(default)]
+pub struct DnsBlocklistOptions {
+    /// Files containing domains to block, including their subdomains.
+    pub blocklists: Vec<PathBuf>,
+    /// Files containing domains that are never blocked, even if they appear in a blocklist.
+    pub allowlists: Vec<PathBuf>,
+}
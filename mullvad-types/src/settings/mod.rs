@@ -216,6 +216,8 @@ pub struct TunnelOptions {
     pub dns_options: DnsOptions,
 }
 
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub use dns::DnsBlocklistOptions;
 pub use dns::{CustomDnsOptions, DefaultDnsOptions, DnsOptions, DnsState};
 
 impl Default for TunnelOptions {
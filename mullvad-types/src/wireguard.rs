@@ -9,6 +9,7 @@ use talpid_types::net::wireguard;
 pub const MIN_ROTATION_INTERVAL: Duration = Duration::from_secs(1 * 24 * 60 * 60);
 pub const MAX_ROTATION_INTERVAL: Duration = Duration::from_secs(14 * 24 * 60 * 60);
 pub const DEFAULT_ROTATION_INTERVAL: Duration = MAX_ROTATION_INTERVAL;
+pub const MIN_PSK_REKEY_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 /// Whether to enable or disable quantum resistant tunnels when the setting is set to
 /// `QuantumResistantState::Auto`. It is currently enabled by default on Linux but disabled on all
@@ -204,6 +205,87 @@ pub struct TunnelOptions {
     /// Interval used for automatic key rotation
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub rotation_interval: Option<RotationInterval>,
+    /// Interval in seconds between keepalive packets sent to the relay
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub persistent_keepalive: Option<u16>,
+    /// Advanced parameters for detecting a broken tunnel
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub connectivity_check: wireguard::ConnectivityCheckOptions,
+    /// Interval at which a new quantum-resistant PSK is negotiated while connected
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub psk_rekey_interval: Option<PskRekeyInterval>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PskRekeyIntervalError {
+    TooSmall,
+}
+
+impl fmt::Display for PskRekeyIntervalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PskRekeyIntervalError::TooSmall => write!(
+                f,
+                "PSK rekey interval must be at least {} minutes",
+                MIN_PSK_REKEY_INTERVAL.as_secs() / 60
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PskRekeyIntervalError {}
+
+/// Interval at which a new quantum-resistant PSK is negotiated while connected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PskRekeyInterval(Duration);
+
+impl PskRekeyInterval {
+    pub fn new(interval: Duration) -> Result<PskRekeyInterval, PskRekeyIntervalError> {
+        if interval < MIN_PSK_REKEY_INTERVAL {
+            Err(PskRekeyIntervalError::TooSmall)
+        } else {
+            Ok(PskRekeyInterval(interval))
+        }
+    }
+
+    pub fn as_duration(&self) -> &Duration {
+        &self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for PskRekeyInterval {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ivl = <Duration>::deserialize(deserializer)?;
+        PskRekeyInterval::new(ivl).map_err(|_error| {
+            serde::de::Error::invalid_value(
+                serde::de::Unexpected::Other("Duration"),
+                &"interval within allowed range",
+            )
+        })
+    }
+}
+
+impl TryFrom<Duration> for PskRekeyInterval {
+    type Error = PskRekeyIntervalError;
+
+    fn try_from(duration: Duration) -> Result<PskRekeyInterval, PskRekeyIntervalError> {
+        PskRekeyInterval::new(duration)
+    }
+}
+
+impl fmt::Display for PskRekeyInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} hours", self.as_duration().as_secs() / 60 / 60)
+    }
+}
+
+impl From<PskRekeyInterval> for Duration {
+    fn from(interval: PskRekeyInterval) -> Duration {
+        *interval.as_duration()
+    }
 }
 
 #[allow(clippy::derivable_impls)]
@@ -213,6 +295,9 @@ impl Default for TunnelOptions {
             mtu: None,
             quantum_resistant: QuantumResistantState::Auto,
             rotation_interval: None,
+            persistent_keepalive: None,
+            connectivity_check: wireguard::ConnectivityCheckOptions::default(),
+            psk_rekey_interval: None,
         }
     }
 }
@@ -226,6 +311,9 @@ impl TunnelOptions {
                 QuantumResistantState::On => true,
                 QuantumResistantState::Off => false,
             },
+            persistent_keepalive: self.persistent_keepalive,
+            connectivity_check: self.connectivity_check,
+            psk_rekey_interval: self.psk_rekey_interval.map(Duration::from),
         }
     }
 }
@@ -241,6 +329,14 @@ pub struct PublicKey {
     pub created: DateTime<Utc>,
 }
 
+/// A `wg-quick` configuration file that can be used to connect to a relay without the app
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WgQuickConfig {
+    /// Name of the configuration, which `wg-quick` also uses as the interface name
+    pub name: String,
+    pub contents: String,
+}
+
 /// Contains a pair of local link addresses that are paired with a specific wireguard
 /// public/private keypair.
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
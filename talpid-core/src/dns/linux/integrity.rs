@@ -0,0 +1,283 @@
+//! Periodically verifies that the DNS configuration applied by [`super::DnsMonitor`] is still in
+//! effect. Other programs, such as VPN clients or NetworkManager reconnecting a device, may
+//! overwrite it. If that happens, the configuration is re-applied and the drift is reported.
+
+use super::{AppliedConfig, DnsMonitorHolder, DriftSender, Result};
+use parking_lot::Mutex;
+use std::{net::IpAddr, sync::Arc, time::Duration};
+use talpid_routing::RouteManagerHandle;
+use talpid_types::{
+    net::dns::{DnsConfigurationDrift, DnsManager},
+    ErrorExt,
+};
+use tokio::time::MissedTickBehavior;
+use triggered::{trigger, Trigger};
+
+/// How often the effective DNS configuration is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct IntegrityMonitor {
+    cancel_trigger: Trigger,
+}
+
+impl Drop for IntegrityMonitor {
+    fn drop(&mut self) {
+        self.cancel_trigger.trigger();
+    }
+}
+
+impl IntegrityMonitor {
+    pub fn start(
+        handle: tokio::runtime::Handle,
+        route_manager: RouteManagerHandle,
+        applied: Arc<Mutex<Option<AppliedConfig>>>,
+        drift_tx: DriftSender,
+    ) -> Self {
+        let (cancel_trigger, mut cancel_listener) = trigger();
+
+        handle.clone().spawn(async move {
+            let mut interval = tokio::time::interval(CHECK_INTERVAL);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                tokio::select! {
+                    _ = &mut cancel_listener => break,
+                    _ = interval.tick() => {
+                        let handle = handle.clone();
+                        let route_manager = route_manager.clone();
+                        let applied = applied.clone();
+                        let drift_tx = drift_tx.clone();
+                        // Querying and applying the configuration is blocking
+                        let result = tokio::task::spawn_blocking(move || {
+                            check_integrity(&handle, &route_manager, &applied, &drift_tx)
+                        })
+                        .await;
+                        if let Err(error) = result {
+                            log::error!("DNS integrity check panicked: {error}");
+                        }
+                    }
+                }
+            }
+        });
+
+        IntegrityMonitor { cancel_trigger }
+    }
+}
+
+/// Compares the effective DNS servers to the applied ones, and re-applies the configuration if
+/// they differ.
+fn check_integrity(
+    handle: &tokio::runtime::Handle,
+    route_manager: &RouteManagerHandle,
+    applied: &Mutex<Option<AppliedConfig>>,
+    drift_tx: &DriftSender,
+) {
+    let mut applied = applied.lock();
+    let Some(config) = applied.as_mut() else {
+        return;
+    };
+    let mut manager = AppliedManager {
+        handle,
+        route_manager,
+        manager: &mut config.manager,
+    };
+    check_servers(&mut manager, &config.interface, &config.servers, drift_tx);
+}
+
+/// The operations needed to verify and restore an applied DNS configuration.
+trait ManagedDns {
+    /// Returns the DNS manager that applied the configuration.
+    fn kind(&self) -> DnsManager;
+
+    /// Returns the DNS servers that are in effect, or `None` if they cannot be determined.
+    fn current_servers(&self, expected: &[IpAddr]) -> Result<Option<Vec<IpAddr>>>;
+
+    /// Returns whether `actual` matches the `expected` DNS servers.
+    fn is_applied(&self, expected: &[IpAddr], actual: &[IpAddr]) -> bool;
+
+    /// Applies `servers` to `interface` again.
+    fn reapply(&mut self, interface: &str, servers: &[IpAddr]) -> Result<()>;
+}
+
+struct AppliedManager<'a> {
+    handle: &'a tokio::runtime::Handle,
+    route_manager: &'a RouteManagerHandle,
+    manager: &'a mut DnsMonitorHolder,
+}
+
+impl ManagedDns for AppliedManager<'_> {
+    fn kind(&self) -> DnsManager {
+        self.manager.kind()
+    }
+
+    fn current_servers(&self, expected: &[IpAddr]) -> Result<Option<Vec<IpAddr>>> {
+        self.manager.current_servers(self.handle, expected)
+    }
+
+    fn is_applied(&self, expected: &[IpAddr], actual: &[IpAddr]) -> bool {
+        self.manager.is_applied(expected, actual)
+    }
+
+    fn reapply(&mut self, interface: &str, servers: &[IpAddr]) -> Result<()> {
+        self.manager
+            .set(self.handle, self.route_manager, interface, servers)
+    }
+}
+
+/// Re-applies `servers` and reports the drift if they are no longer in effect.
+fn check_servers(
+    manager: &mut impl ManagedDns,
+    interface: &str,
+    servers: &[IpAddr],
+    drift_tx: &DriftSender,
+) {
+    let actual_servers = match manager.current_servers(servers) {
+        Ok(Some(actual_servers)) => actual_servers,
+        Ok(None) => return,
+        Err(error) => {
+            log::debug!(
+                "{}",
+                error.display_chain_with_msg("Failed to read effective DNS configuration")
+            );
+            return;
+        }
+    };
+    if manager.is_applied(servers, &actual_servers) {
+        return;
+    }
+
+    log::warn!(
+        "DNS configuration managed by {} was changed by another program. Expected {:?}, found {:?}",
+        manager.kind(),
+        servers,
+        actual_servers
+    );
+
+    let result = manager.reapply(interface, servers);
+    if let Err(error) = &result {
+        log::error!(
+            "{}",
+            error.display_chain_with_msg("Failed to re-apply DNS configuration")
+        );
+    }
+
+    let _ = drift_tx.unbounded_send(DnsConfigurationDrift {
+        manager: manager.kind(),
+        expected_servers: servers.to_vec(),
+        actual_servers,
+        restored: result.is_ok(),
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::{super::Error, *};
+    use futures::channel::mpsc;
+    use std::net::Ipv4Addr;
+
+    const INTERFACE: &str = "wg0-mullvad";
+    const TUNNEL_DNS: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 64, 0, 1));
+    const OTHER_DNS: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+
+    /// DNS manager that reports a fixed set of servers
+    struct MockManager {
+        current: Result<Option<Vec<IpAddr>>>,
+        fail_reapply: bool,
+        reapplied: Vec<(String, Vec<IpAddr>)>,
+    }
+
+    impl MockManager {
+        fn new(current: Result<Option<Vec<IpAddr>>>) -> Self {
+            MockManager {
+                current,
+                fail_reapply: false,
+                reapplied: vec![],
+            }
+        }
+    }
+
+    impl ManagedDns for MockManager {
+        fn kind(&self) -> DnsManager {
+            DnsManager::Resolvconf
+        }
+
+        fn current_servers(&self, _expected: &[IpAddr]) -> Result<Option<Vec<IpAddr>>> {
+            match &self.current {
+                Ok(servers) => Ok(servers.clone()),
+                Err(_) => Err(Error::NoDnsMonitor),
+            }
+        }
+
+        fn is_applied(&self, expected: &[IpAddr], actual: &[IpAddr]) -> bool {
+            expected == actual
+        }
+
+        fn reapply(&mut self, interface: &str, servers: &[IpAddr]) -> Result<()> {
+            self.reapplied
+                .push((interface.to_owned(), servers.to_vec()));
+            if self.fail_reapply {
+                return Err(Error::NoDnsMonitor);
+            }
+            Ok(())
+        }
+    }
+
+    fn check(manager: &mut MockManager) -> Vec<DnsConfigurationDrift> {
+        let (drift_tx, mut drift_rx) = mpsc::unbounded();
+        check_servers(manager, INTERFACE, &[TUNNEL_DNS], &drift_tx);
+        drop(drift_tx);
+
+        std::iter::from_fn(|| drift_rx.try_next().ok().flatten()).collect()
+    }
+
+    #[test]
+    fn test_no_drift() {
+        let mut manager = MockManager::new(Ok(Some(vec![TUNNEL_DNS])));
+
+        assert!(check(&mut manager).is_empty());
+        assert!(manager.reapplied.is_empty());
+    }
+
+    #[test]
+    fn test_drift_is_restored_and_reported() {
+        let mut manager = MockManager::new(Ok(Some(vec![OTHER_DNS])));
+
+        let drifts = check(&mut manager);
+
+        assert_eq!(
+            manager.reapplied,
+            vec![(INTERFACE.to_owned(), vec![TUNNEL_DNS])]
+        );
+        assert_eq!(
+            drifts,
+            vec![DnsConfigurationDrift {
+                manager: DnsManager::Resolvconf,
+                expected_servers: vec![TUNNEL_DNS],
+                actual_servers: vec![OTHER_DNS],
+                restored: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_failed_restore_is_reported() {
+        let mut manager = MockManager::new(Ok(Some(vec![])));
+        manager.fail_reapply = true;
+
+        let drifts = check(&mut manager);
+
+        assert_eq!(drifts.len(), 1);
+        assert!(!drifts[0].restored);
+    }
+
+    #[test]
+    fn test_unknown_servers_are_ignored() {
+        let mut manager = MockManager::new(Ok(None));
+        assert!(check(&mut manager).is_empty());
+
+        let mut manager = MockManager::new(Err(Error::NoDnsMonitor));
+        assert!(check(&mut manager).is_empty());
+
+        assert!(manager.reapplied.is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+use super::{
+    static_resolv_conf::{self, StaticResolvConf},
+    DriftSender,
+};
+use crate::resolver::{self, BlocklistConfig, ResolverHandle};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    time::Duration,
+};
+use talpid_types::net::dns::DnsManager;
+
+/// Address that the local resolver listens on, and that /etc/resolv.conf points to.
+pub const LOCAL_RESOLVER_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const DNS_PORT: u16 = 53;
+
+/// A previous resolver may still be releasing the port when it is started again.
+const START_ATTEMPTS: usize = 10;
+const START_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Failed to start local resolver")]
+    StartResolver(#[source] resolver::Error),
+
+    #[error("Port {DNS_PORT} on the loopback interface is not available")]
+    PortUnavailable(#[source] io::Error),
+
+    #[error("Failed to point /etc/resolv.conf to the local resolver")]
+    StaticResolvConf(#[from] static_resolv_conf::Error),
+}
+
+/// Forwards DNS queries to the tunnel DNS servers using a resolver run by the daemon. The system
+/// resolver is pointed to it by editing /etc/resolv.conf. Queries for domains in user-supplied
+/// blocklists are refused.
+pub struct LocalResolver {
+    resolver: Option<ResolverHandle>,
+    resolv_conf: StaticResolvConf,
+    blocklists: BlocklistConfig,
+}
+
+impl LocalResolver {
+    pub fn new(drift_tx: DriftSender, blocklists: BlocklistConfig) -> Result<Self> {
+        Ok(LocalResolver {
+            resolver: None,
+            resolv_conf: StaticResolvConf::new(DnsManager::LocalResolver, drift_tx)?,
+            blocklists,
+        })
+    }
+
+    pub async fn set_dns(&mut self, servers: &[IpAddr]) -> Result<()> {
+        let resolver = match self.resolver.take() {
+            Some(resolver) => resolver,
+            None => {
+                let resolver = start_resolver().await?;
+                resolver.set_blocklists(self.blocklists.clone()).await;
+                resolver
+            }
+        };
+        resolver.set_upstream(Some(servers.to_vec())).await;
+        self.resolver = Some(resolver);
+
+        self.resolv_conf.set_dns(vec![LOCAL_RESOLVER_ADDR])?;
+        Ok(())
+    }
+
+    /// Returns a handle to the resolver, if it is running.
+    pub fn resolver(&self) -> Option<&ResolverHandle> {
+        self.resolver.as_ref()
+    }
+
+    pub fn reset(&mut self) -> Result<()> {
+        // Dropping the last handle stops the resolver
+        self.resolver = None;
+        self.resolv_conf.reset()?;
+        Ok(())
+    }
+}
+
+async fn start_resolver() -> Result<ResolverHandle> {
+    let mut attempt = 1;
+    loop {
+        match resolver::start_resolver_on_port(DNS_PORT).await {
+            Ok(resolver) => return Ok(resolver),
+            Err(resolver::Error::UdpBindError(error))
+                if error.kind() == io::ErrorKind::AddrInUse && attempt < START_ATTEMPTS =>
+            {
+                attempt += 1;
+                tokio::time::sleep(START_RETRY_DELAY).await;
+            }
+            Err(error) => return Err(Error::StartResolver(error)),
+        }
+    }
+}
+
+/// Checks whether the local resolver can be started, without starting it. If `running` is set, a
+/// local resolver already occupies the port, so it is not checked.
+pub fn ensure_usable(running: bool) -> Result<()> {
+    static_resolv_conf::ensure_writable()?;
+    if running {
+        return Ok(());
+    }
+    UdpSocket::bind(SocketAddr::new(LOCAL_RESOLVER_ADDR, DNS_PORT))
+        .map_err(Error::PortUnavailable)?;
+    Ok(())
+}
@@ -1,17 +1,36 @@
+mod integrity;
+mod local_resolver;
 mod network_manager;
 mod resolvconf;
 mod static_resolv_conf;
 mod systemd_resolved;
 
 use self::{
-    network_manager::NetworkManager, resolvconf::Resolvconf, static_resolv_conf::StaticResolvConf,
+    integrity::IntegrityMonitor, local_resolver::LocalResolver, network_manager::NetworkManager,
+    resolvconf::Resolvconf, static_resolv_conf::StaticResolvConf,
     systemd_resolved::SystemdResolved,
 };
-use std::{env, fmt, net::IpAddr};
+use crate::resolver::{BlocklistConfig, ResolverHandle};
+use futures::channel::mpsc;
+use parking_lot::Mutex;
+use resolv_conf::ScopedIp;
+use std::{env, fmt, fs, io, net::IpAddr, sync::Arc};
 use talpid_routing::RouteManagerHandle;
+use talpid_types::net::dns::{BlocklistStats, DnsConfigurationDrift, DnsManager};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Channel on which changes to the applied DNS configuration made by other programs are reported.
+type DriftSender = mpsc::UnboundedSender<DnsConfigurationDrift>;
+
+const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+
+/// Environment variable that overrides the preferred DNS manager.
+pub const MANAGER_OVERRIDE_VAR: &str = "TALPID_DNS_MODULE";
+
+/// Maximum number of nameservers in /etc/resolv.conf that are used by the system resolver.
+const RESOLV_CONF_MAX_NAMESERVERS: usize = 3;
+
 /// Errors that can happen in the Linux DNS monitor
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -31,42 +50,169 @@ pub enum Error {
     #[error("Error in static /etc/resolv.conf DNS monitor")]
     StaticResolvConf(#[from] static_resolv_conf::Error),
 
+    /// Error in local resolver DNS monitor
+    #[error("Error in local resolver DNS monitor")]
+    LocalResolver(#[from] local_resolver::Error),
+
     /// No suitable DNS monitor implementation detected
     #[error("No suitable DNS monitor implementation detected")]
     NoDnsMonitor,
+
+    /// Failed to read /etc/resolv.conf
+    #[error("Failed to read /etc/resolv.conf")]
+    ReadResolvConf(#[source] io::Error),
+
+    /// Failed to parse /etc/resolv.conf
+    #[error("/etc/resolv.conf could not be parsed")]
+    ParseResolvConf(#[source] resolv_conf::ParseError),
 }
 
 pub struct DnsMonitor {
     route_manager: RouteManagerHandle,
     handle: tokio::runtime::Handle,
-    inner: Option<DnsMonitorHolder>,
+    inner: Arc<Mutex<Option<AppliedConfig>>>,
+    preferred_manager: DnsManager,
+    manager_handle: DnsManagerHandle,
+    drift_tx: DriftSender,
+    _integrity_monitor: IntegrityMonitor,
+}
+
+impl DnsMonitor {
+    /// Sets the DNS manager to use the next time DNS is set.
+    pub fn set_preferred_manager(&mut self, manager: DnsManager) {
+        self.preferred_manager = manager;
+    }
+
+    /// Sets the domain lists enforced by the local resolver. They are applied immediately if the
+    /// local resolver is running.
+    pub fn set_blocklists(&mut self, blocklists: BlocklistConfig) {
+        *self.manager_handle.blocklists.lock() = blocklists.clone();
+        let resolver = self.manager_handle.resolver.lock().clone();
+        if let Some(resolver) = resolver {
+            self.handle.block_on(resolver.set_blocklists(blocklists));
+        }
+    }
+
+    /// Returns a handle that reports which DNS manager is in use.
+    pub fn manager_handle(&self) -> DnsManagerHandle {
+        self.manager_handle.clone()
+    }
+}
+
+/// Reports which DNS manager a [`DnsMonitor`] has used to apply the current DNS configuration.
+#[derive(Clone, Default)]
+pub struct DnsManagerHandle {
+    active: Arc<Mutex<Option<DnsManager>>>,
+    /// The resolver run by the local resolver DNS manager, if it is in use.
+    resolver: Arc<Mutex<Option<ResolverHandle>>>,
+    blocklists: Arc<Mutex<BlocklistConfig>>,
+}
+
+impl DnsManagerHandle {
+    /// Returns the DNS manager used to apply the current DNS configuration, or `None` if no DNS
+    /// configuration is applied.
+    pub fn active(&self) -> Option<DnsManager> {
+        *self.active.lock()
+    }
+
+    /// Returns the number of entries in and queries matched by each user-supplied domain list.
+    /// Lists are only loaded while the local resolver is running, so no entries or hits are
+    /// reported otherwise.
+    pub async fn blocklist_stats(&self) -> BlocklistStats {
+        let resolver = self.resolver.lock().clone();
+        match resolver {
+            Some(resolver) => resolver.blocklist_stats().await,
+            None => self.blocklists.lock().unloaded_stats(),
+        }
+    }
+
+    /// Returns the DNS manager that will be used if `preferred_manager` is preferred, without
+    /// changing any system settings. Fails if the preferred DNS manager cannot be used.
+    ///
+    /// This ignores [`MANAGER_OVERRIDE_VAR`].
+    pub fn resolve(&self, preferred_manager: DnsManager) -> Result<DnsManager> {
+        let active = self.active();
+        match preferred_manager {
+            DnsManager::Auto => [
+                DnsManager::SystemdResolved,
+                DnsManager::NetworkManager,
+                DnsManager::Resolvconf,
+                DnsManager::StaticFile,
+            ]
+            .into_iter()
+            .find(|&manager| ensure_usable(manager, active).is_ok())
+            .ok_or(Error::NoDnsMonitor),
+            manager => ensure_usable(manager, active).map(|()| manager),
+        }
+    }
+}
+
+/// DNS configuration that has been applied using a particular DNS manager.
+struct AppliedConfig {
+    manager: DnsMonitorHolder,
+    interface: String,
+    servers: Vec<IpAddr>,
 }
 
 impl super::DnsMonitorT for DnsMonitor {
     type Error = Error;
 
-    fn new(handle: tokio::runtime::Handle, route_manager: RouteManagerHandle) -> Result<Self> {
+    fn new(
+        handle: tokio::runtime::Handle,
+        route_manager: RouteManagerHandle,
+        drift_tx: DriftSender,
+    ) -> Result<Self> {
+        let inner = Arc::new(Mutex::new(None));
+        let integrity_monitor = IntegrityMonitor::start(
+            handle.clone(),
+            route_manager.clone(),
+            inner.clone(),
+            drift_tx.clone(),
+        );
+
         Ok(DnsMonitor {
             route_manager,
             handle,
-            inner: None,
+            inner,
+            preferred_manager: DnsManager::Auto,
+            manager_handle: DnsManagerHandle::default(),
+            drift_tx,
+            _integrity_monitor: integrity_monitor,
         })
     }
 
     fn set(&mut self, interface: &str, servers: &[IpAddr]) -> Result<()> {
         self.reset()?;
         // Creating a new DNS monitor for each set, in case the system changed how it manages DNS.
-        let mut inner = DnsMonitorHolder::new()?;
+        let blocklists = self.manager_handle.blocklists.lock().clone();
+        let mut manager =
+            DnsMonitorHolder::new(self.preferred_manager, self.drift_tx.clone(), &blocklists)?;
         if !servers.is_empty() {
-            inner.set(&self.handle, &self.route_manager, interface, servers)?;
-            self.inner = Some(inner);
+            let mut inner = self.inner.lock();
+            manager.set(&self.handle, &self.route_manager, interface, servers)?;
+            if blocklists.is_active() && manager.kind() != DnsManager::LocalResolver {
+                log::warn!(
+                    "Domain lists are not enforced since DNS is managed via {}",
+                    manager
+                );
+            }
+            *self.manager_handle.active.lock() = Some(manager.kind());
+            *self.manager_handle.resolver.lock() = manager.resolver().cloned();
+            *inner = Some(AppliedConfig {
+                manager,
+                interface: interface.to_owned(),
+                servers: servers.to_vec(),
+            });
         }
         Ok(())
     }
 
     fn reset(&mut self) -> Result<()> {
-        if let Some(mut inner) = self.inner.take() {
-            inner.reset(&self.handle)?;
+        if let Some(mut applied) = self.inner.lock().take() {
+            *self.manager_handle.active.lock() = None;
+            // The resolver stops once the last handle to it is dropped
+            *self.manager_handle.resolver.lock() = None;
+            applied.manager.reset(&self.handle)?;
         }
         Ok(())
     }
@@ -77,37 +223,73 @@ pub enum DnsMonitorHolder {
     NetworkManager(NetworkManager),
     Resolvconf(Resolvconf),
     StaticResolvConf(StaticResolvConf),
+    LocalResolver(LocalResolver),
 }
 
 impl fmt::Display for DnsMonitorHolder {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use self::DnsMonitorHolder::*;
-        let name = match self {
-            Resolvconf(..) => "resolvconf",
-            StaticResolvConf(..) => "/etc/resolv.conf",
-            SystemdResolved(..) => "systemd-resolved",
-            NetworkManager(..) => "network manager",
-        };
-        f.write_str(name)
+        self.kind().fmt(f)
     }
 }
 
 impl DnsMonitorHolder {
-    fn new() -> Result<Self> {
-        let dns_module = env::var_os("TALPID_DNS_MODULE");
-
-        let manager = match dns_module.as_ref().and_then(|value| value.to_str()) {
-            Some("static-file") => DnsMonitorHolder::StaticResolvConf(StaticResolvConf::new()?),
-            Some("resolvconf") => DnsMonitorHolder::Resolvconf(Resolvconf::new()?),
-            Some("systemd") => DnsMonitorHolder::SystemdResolved(SystemdResolved::new()?),
-            Some("network-manager") => DnsMonitorHolder::NetworkManager(NetworkManager::new()?),
-            Some(_) | None => Self::with_detected_dns_manager()?,
+    /// Returns the DNS manager implemented by this monitor.
+    fn kind(&self) -> DnsManager {
+        use self::DnsMonitorHolder::*;
+        match self {
+            Resolvconf(..) => DnsManager::Resolvconf,
+            StaticResolvConf(..) => DnsManager::StaticFile,
+            SystemdResolved(..) => DnsManager::SystemdResolved,
+            NetworkManager(..) => DnsManager::NetworkManager,
+            LocalResolver(..) => DnsManager::LocalResolver,
+        }
+    }
+
+    /// Returns a handle to the resolver run by the local resolver DNS manager, if it is running.
+    fn resolver(&self) -> Option<&ResolverHandle> {
+        match self {
+            DnsMonitorHolder::LocalResolver(local_resolver) => local_resolver.resolver(),
+            _ => None,
+        }
+    }
+
+    fn new(
+        preferred_manager: DnsManager,
+        drift_tx: DriftSender,
+        blocklists: &BlocklistConfig,
+    ) -> Result<Self> {
+        let preferred_manager = match manager_override() {
+            Some(manager) => {
+                if manager != preferred_manager {
+                    log::warn!(
+                        "Using DNS manager \"{manager}\" set by {MANAGER_OVERRIDE_VAR} instead of \
+                         \"{preferred_manager}\""
+                    );
+                }
+                manager
+            }
+            None => preferred_manager,
+        };
+        let manager = match preferred_manager {
+            DnsManager::StaticFile => DnsMonitorHolder::StaticResolvConf(StaticResolvConf::new(
+                DnsManager::StaticFile,
+                drift_tx,
+            )?),
+            DnsManager::Resolvconf => DnsMonitorHolder::Resolvconf(Resolvconf::new()?),
+            DnsManager::SystemdResolved => {
+                DnsMonitorHolder::SystemdResolved(SystemdResolved::new()?)
+            }
+            DnsManager::NetworkManager => DnsMonitorHolder::NetworkManager(NetworkManager::new()?),
+            DnsManager::LocalResolver => {
+                DnsMonitorHolder::LocalResolver(LocalResolver::new(drift_tx, blocklists.clone())?)
+            }
+            DnsManager::Auto => Self::with_detected_dns_manager(drift_tx)?,
         };
         log::debug!("Managing DNS via {}", manager);
         Ok(manager)
     }
 
-    fn with_detected_dns_manager() -> Result<Self> {
+    fn with_detected_dns_manager(drift_tx: DriftSender) -> Result<Self> {
         SystemdResolved::new()
             .map(DnsMonitorHolder::SystemdResolved)
             .or_else(|err| {
@@ -122,7 +304,10 @@ impl DnsMonitorHolder {
                 NetworkManager::new().map(DnsMonitorHolder::NetworkManager)
             })
             .or_else(|_| Resolvconf::new().map(DnsMonitorHolder::Resolvconf))
-            .or_else(|_| StaticResolvConf::new().map(DnsMonitorHolder::StaticResolvConf))
+            .or_else(|_| {
+                StaticResolvConf::new(DnsManager::StaticFile, drift_tx)
+                    .map(DnsMonitorHolder::StaticResolvConf)
+            })
             .map_err(|_| Error::NoDnsMonitor)
     }
 
@@ -144,6 +329,9 @@ impl DnsMonitorHolder {
             NetworkManager(ref mut network_manager) => {
                 network_manager.set_dns(interface, servers)?
             }
+            LocalResolver(ref mut local_resolver) => {
+                handle.block_on(local_resolver.set_dns(servers))?
+            }
         }
         Ok(())
     }
@@ -157,9 +345,100 @@ impl DnsMonitorHolder {
                 handle.block_on(systemd_resolved.reset())?
             }
             NetworkManager(ref mut network_manager) => network_manager.reset()?,
+            LocalResolver(ref mut local_resolver) => local_resolver.reset()?,
         }
         Ok(())
     }
+
+    /// Returns the DNS servers that are currently in effect, or `None` if they cannot be
+    /// determined for this DNS manager.
+    fn current_servers(
+        &self,
+        handle: &tokio::runtime::Handle,
+        expected: &[IpAddr],
+    ) -> Result<Option<Vec<IpAddr>>> {
+        use self::DnsMonitorHolder::*;
+        match self {
+            SystemdResolved(systemd_resolved) => {
+                Ok(Some(handle.block_on(systemd_resolved.get_dns())?))
+            }
+            // Changes to a static /etc/resolv.conf are already detected, reverted and reported by
+            // its own file watcher
+            StaticResolvConf(..) | LocalResolver(..) => Ok(None),
+            Resolvconf(..) | NetworkManager(..) => {
+                let servers = read_resolv_conf_nameservers()?;
+                Ok((!uses_local_stub(&servers, expected)).then_some(servers))
+            }
+        }
+    }
+
+    /// Returns whether `actual` matches the `expected` DNS servers for this DNS manager.
+    fn is_applied(&self, expected: &[IpAddr], actual: &[IpAddr]) -> bool {
+        match self {
+            DnsMonitorHolder::SystemdResolved(..) => expected == actual,
+            _ => resolv_conf_is_applied(expected, actual),
+        }
+    }
+}
+
+/// Returns whether the nameservers in /etc/resolv.conf point to a local stub resolver, such as
+/// dnsmasq, which does not reveal its upstream servers.
+fn uses_local_stub(servers: &[IpAddr], expected: &[IpAddr]) -> bool {
+    !servers.is_empty()
+        && servers.iter().all(IpAddr::is_loopback)
+        && !expected.iter().all(IpAddr::is_loopback)
+}
+
+/// Returns whether the `expected` servers are in effect if /etc/resolv.conf lists `actual`. Only
+/// the first few nameservers are used by the system resolver, and these should be ours.
+fn resolv_conf_is_applied(expected: &[IpAddr], actual: &[IpAddr]) -> bool {
+    let used = expected.len().min(RESOLV_CONF_MAX_NAMESERVERS);
+    actual.len() >= used && expected[..used] == actual[..used]
+}
+
+fn read_resolv_conf_nameservers() -> Result<Vec<IpAddr>> {
+    let contents = match fs::read_to_string(RESOLV_CONF_PATH) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(Error::ReadResolvConf(error)),
+    };
+    let config = resolv_conf::Config::parse(contents).map_err(Error::ParseResolvConf)?;
+    Ok(config.nameservers.iter().map(nameserver_address).collect())
+}
+
+fn nameserver_address(nameserver: &ScopedIp) -> IpAddr {
+    match nameserver {
+        ScopedIp::V4(address) => IpAddr::V4(*address),
+        ScopedIp::V6(address, _) => IpAddr::V6(*address),
+    }
+}
+
+/// Checks whether `manager` can be used to manage DNS, given that `active` is currently in use.
+fn ensure_usable(manager: DnsManager, active: Option<DnsManager>) -> Result<()> {
+    match manager {
+        DnsManager::Auto => (),
+        DnsManager::SystemdResolved => {
+            SystemdResolved::new()?;
+        }
+        DnsManager::NetworkManager => {
+            NetworkManager::new()?;
+        }
+        DnsManager::Resolvconf => {
+            Resolvconf::new()?;
+        }
+        DnsManager::StaticFile => static_resolv_conf::ensure_writable()?,
+        DnsManager::LocalResolver => {
+            local_resolver::ensure_usable(active == Some(DnsManager::LocalResolver))?
+        }
+    }
+    Ok(())
+}
+
+/// Returns the DNS manager set using the [`MANAGER_OVERRIDE_VAR`] environment variable, if any.
+/// This takes precedence over the preferred DNS manager.
+pub fn manager_override() -> Option<DnsManager> {
+    let dns_module = env::var_os(MANAGER_OVERRIDE_VAR)?;
+    dns_module.to_str()?.parse().ok()
 }
 
 /// Returns true if DnsMonitor will use NetworkManager to manage DNS.
@@ -167,3 +446,58 @@ pub fn will_use_nm() -> bool {
     crate::dns::imp::SystemdResolved::new().is_err()
         && crate::dns::imp::NetworkManager::new().is_ok()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const TUNNEL_DNS: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 64, 0, 1));
+    const OTHER_DNS: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+    const LOCAL_STUB: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 53));
+
+    #[test]
+    fn test_local_stub_detection() {
+        assert!(uses_local_stub(&[LOCAL_STUB], &[TUNNEL_DNS]));
+        assert!(uses_local_stub(
+            &[LOCAL_STUB, IpAddr::V6(Ipv6Addr::LOCALHOST)],
+            &[TUNNEL_DNS]
+        ));
+        assert!(!uses_local_stub(&[LOCAL_STUB, OTHER_DNS], &[TUNNEL_DNS]));
+        assert!(!uses_local_stub(&[], &[TUNNEL_DNS]));
+        // A loopback address is expected when custom DNS points to a local resolver
+        assert!(!uses_local_stub(&[LOCAL_STUB], &[LOCAL_STUB]));
+    }
+
+    #[test]
+    fn test_resolv_conf_is_applied() {
+        assert!(resolv_conf_is_applied(&[TUNNEL_DNS], &[TUNNEL_DNS]));
+        assert!(resolv_conf_is_applied(
+            &[TUNNEL_DNS],
+            &[TUNNEL_DNS, OTHER_DNS]
+        ));
+        assert!(!resolv_conf_is_applied(
+            &[TUNNEL_DNS],
+            &[OTHER_DNS, TUNNEL_DNS]
+        ));
+        assert!(!resolv_conf_is_applied(&[TUNNEL_DNS], &[]));
+        assert!(!resolv_conf_is_applied(
+            &[TUNNEL_DNS, OTHER_DNS],
+            &[TUNNEL_DNS]
+        ));
+    }
+
+    #[test]
+    fn test_resolv_conf_only_first_servers_are_used() {
+        let expected: Vec<IpAddr> = (1..=RESOLV_CONF_MAX_NAMESERVERS as u8 + 1)
+            .map(|i| IpAddr::V4(Ipv4Addr::new(10, 64, 0, i)))
+            .collect();
+        let actual = &expected[..RESOLV_CONF_MAX_NAMESERVERS];
+
+        assert!(resolv_conf_is_applied(&expected, actual));
+        assert!(!resolv_conf_is_applied(
+            &expected,
+            &actual[..RESOLV_CONF_MAX_NAMESERVERS - 1]
+        ));
+    }
+}
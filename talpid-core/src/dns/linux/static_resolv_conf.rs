@@ -1,9 +1,13 @@
+use super::{nameserver_address, DriftSender};
 use futures::StreamExt;
 use inotify::{Inotify, WatchMask};
 use parking_lot::Mutex;
 use resolv_conf::{Config, ScopedIp};
 use std::{fs, io, net::IpAddr, sync::Arc};
-use talpid_types::ErrorExt;
+use talpid_types::{
+    net::dns::{DnsConfigurationDrift, DnsManager},
+    ErrorExt,
+};
 use triggered::{trigger, Listener, Trigger};
 
 const RESOLV_CONF_BACKUP_PATH: &str = "/etc/resolv.conf.mullvadbackup";
@@ -35,11 +39,13 @@ pub struct StaticResolvConf {
 }
 
 impl StaticResolvConf {
-    pub fn new() -> Result<Self> {
+    /// Changes made to /etc/resolv.conf by other programs are reported on `drift_tx` as changes
+    /// to the configuration applied by `manager`.
+    pub fn new(manager: DnsManager, drift_tx: DriftSender) -> Result<Self> {
         restore_from_backup()?;
 
         let state = Arc::new(Mutex::new(None));
-        let watcher = DnsWatcher::start(state.clone())?;
+        let watcher = DnsWatcher::start(state.clone(), manager, drift_tx)?;
 
         Ok(StaticResolvConf {
             state,
@@ -112,7 +118,11 @@ impl Drop for DnsWatcher {
 }
 
 impl DnsWatcher {
-    fn start(state: Arc<Mutex<Option<State>>>) -> Result<Self> {
+    fn start(
+        state: Arc<Mutex<Option<State>>>,
+        manager: DnsManager,
+        drift_tx: DriftSender,
+    ) -> Result<Self> {
         let watcher = Inotify::init().map_err(Error::WatchResolvConf)?;
         let mut mask = WatchMask::empty();
         // Documentation for the meaning of these masks can be found in `man inotify`
@@ -131,7 +141,9 @@ impl DnsWatcher {
 
         let (cancel_trigger, cancel_listener) = trigger();
 
-        tokio::spawn(async move { Self::event_loop(watcher, cancel_listener, &state).await });
+        tokio::spawn(async move {
+            Self::event_loop(watcher, cancel_listener, &state, manager, &drift_tx).await
+        });
 
         Ok(DnsWatcher { cancel_trigger })
     }
@@ -140,6 +152,8 @@ impl DnsWatcher {
         watcher: Inotify,
         mut cancel_listener: Listener,
         state: &Arc<Mutex<Option<State>>>,
+        manager: DnsManager,
+        drift_tx: &DriftSender,
     ) {
         const EVENT_BUFFER_SIZE: usize = 1024;
         let mut buffer = [0; EVENT_BUFFER_SIZE];
@@ -154,7 +168,7 @@ impl DnsWatcher {
                 },
                 Some(_) = events.next() => {
                     let mut locked_state = state.lock();
-                    if let Err(error) = Self::update(locked_state.as_mut()) {
+                    if let Err(error) = Self::update(locked_state.as_mut(), manager, drift_tx) {
                         log::error!(
                             "{}",
                             error.display_chain_with_msg(
@@ -167,7 +181,11 @@ impl DnsWatcher {
         }
     }
 
-    fn update(state: Option<&mut State>) -> Result<()> {
+    fn update(
+        state: Option<&mut State>,
+        manager: DnsManager,
+        drift_tx: &DriftSender,
+    ) -> Result<()> {
         if let Some(state) = state {
             let mut new_config = read_config()?;
             let desired_nameservers = state
@@ -178,9 +196,21 @@ impl DnsWatcher {
 
             if new_config.nameservers != desired_nameservers {
                 state.backup = new_config.clone();
+                let actual_servers = new_config
+                    .nameservers
+                    .iter()
+                    .map(nameserver_address)
+                    .collect();
                 new_config.nameservers = desired_nameservers;
 
-                write_config(&new_config)
+                let result = write_config(&new_config);
+                let _ = drift_tx.unbounded_send(DnsConfigurationDrift {
+                    manager,
+                    expected_servers: state.desired_dns.clone(),
+                    actual_servers,
+                    restored: result.is_ok(),
+                });
+                result
             } else {
                 new_config.nameservers.clear();
                 new_config.nameservers.append(&mut state.backup.nameservers);
@@ -194,6 +224,15 @@ impl DnsWatcher {
     }
 }
 
+/// Checks whether /etc/resolv.conf can be modified, without modifying it.
+pub fn ensure_writable() -> Result<()> {
+    match fs::OpenOptions::new().append(true).open(RESOLV_CONF_PATH) {
+        Ok(_) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(Error::WriteResolvConf(RESOLV_CONF_PATH, error)),
+    }
+}
+
 fn read_config() -> Result<Config> {
     if !std::path::Path::new(RESOLV_CONF_PATH).exists() {
         return Ok(Config::new());
@@ -63,6 +63,12 @@ impl SystemdResolved {
         Ok(())
     }
 
+    /// Returns the DNS servers currently configured for the tunnel interface.
+    pub async fn get_dns(&self) -> Result<Vec<IpAddr>> {
+        let state = self.dbus_interface.get_dns(self.tunnel_index).await?;
+        Ok(state.set_servers)
+    }
+
     pub async fn reset(&mut self) -> Result<()> {
         if let Err(error) = self
             .dbus_interface
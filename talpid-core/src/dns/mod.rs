@@ -1,6 +1,10 @@
+#[cfg(target_os = "linux")]
+use futures::channel::mpsc;
 use std::net::IpAddr;
 #[cfg(target_os = "linux")]
 use talpid_routing::RouteManagerHandle;
+#[cfg(target_os = "linux")]
+use talpid_types::net::dns::{DnsConfigurationDrift, DnsManager};
 
 #[cfg(target_os = "macos")]
 #[path = "macos.rs"]
@@ -11,7 +15,7 @@ mod imp;
 mod imp;
 
 #[cfg(target_os = "linux")]
-pub use imp::will_use_nm;
+pub use imp::{manager_override, will_use_nm, DnsManagerHandle, MANAGER_OVERRIDE_VAR};
 
 #[cfg(windows)]
 #[path = "windows/mod.rs"]
@@ -30,9 +34,13 @@ pub struct DnsMonitor {
 
 impl DnsMonitor {
     /// Returns a new `DnsMonitor` that can set and monitor the system DNS.
+    ///
+    /// On Linux, `drift_tx` receives an event whenever the applied configuration is found to have
+    /// been changed by something else.
     pub fn new(
         #[cfg(target_os = "linux")] handle: tokio::runtime::Handle,
         #[cfg(target_os = "linux")] route_manager: RouteManagerHandle,
+        #[cfg(target_os = "linux")] drift_tx: mpsc::UnboundedSender<DnsConfigurationDrift>,
     ) -> Result<Self, Error> {
         Ok(DnsMonitor {
             inner: imp::DnsMonitor::new(
@@ -40,6 +48,8 @@ impl DnsMonitor {
                 handle,
                 #[cfg(target_os = "linux")]
                 route_manager,
+                #[cfg(target_os = "linux")]
+                drift_tx,
             )?,
         })
     }
@@ -57,6 +67,24 @@ impl DnsMonitor {
         self.inner.set(interface, servers)
     }
 
+    /// Set the system component used to manage DNS. This takes effect the next time DNS is set.
+    #[cfg(target_os = "linux")]
+    pub fn set_preferred_manager(&mut self, manager: DnsManager) {
+        self.inner.set_preferred_manager(manager)
+    }
+
+    /// Set the domain lists enforced by the local resolver DNS manager.
+    #[cfg(target_os = "linux")]
+    pub fn set_blocklists(&mut self, blocklists: crate::resolver::BlocklistConfig) {
+        self.inner.set_blocklists(blocklists)
+    }
+
+    /// Returns a handle that reports which DNS manager is in use.
+    #[cfg(target_os = "linux")]
+    pub fn manager_handle(&self) -> DnsManagerHandle {
+        self.inner.manager_handle()
+    }
+
     /// Reset system DNS settings to what it was before being set by this instance.
     /// This succeeds if the interface does not exist.
     pub fn reset(&mut self) -> Result<(), Error> {
@@ -79,6 +107,7 @@ trait DnsMonitorT: Sized {
     fn new(
         #[cfg(target_os = "linux")] handle: tokio::runtime::Handle,
         #[cfg(target_os = "linux")] route_manager: RouteManagerHandle,
+        #[cfg(target_os = "linux")] drift_tx: mpsc::UnboundedSender<DnsConfigurationDrift>,
     ) -> Result<Self, Self::Error>;
 
     fn set(&mut self, interface: &str, servers: &[IpAddr]) -> Result<(), Self::Error>;
@@ -12,7 +12,9 @@ use std::{
     fs, io,
     net::{IpAddr, Ipv4Addr},
 };
-use talpid_types::net::{AllowedEndpoint, AllowedTunnelTraffic, Endpoint, TransportProtocol};
+use talpid_types::net::{
+    AllowedClients, AllowedEndpoint, AllowedTunnelTraffic, Endpoint, TransportProtocol,
+};
 
 /// Priority for rules that tag split tunneling packets. Equals NF_IP_PRI_MANGLE.
 const MANGLE_CHAIN_PRIORITY: i32 = libc::NF_IP_PRI_MANGLE;
@@ -524,8 +526,16 @@ impl<'a> PolicyBatch<'a> {
                 allow_lan,
                 allowed_endpoint,
                 allowed_tunnel_traffic,
+                probe_endpoints,
             } => {
                 self.add_allow_tunnel_endpoint_rules(peer_endpoint, fwmark);
+                for endpoint in probe_endpoints {
+                    let probe_endpoint = AllowedEndpoint {
+                        endpoint: *endpoint,
+                        clients: AllowedClients::Root,
+                    };
+                    self.add_allow_tunnel_endpoint_rules(&probe_endpoint, fwmark);
+                }
                 self.add_allow_endpoint_rules(allowed_endpoint);
 
                 // Important to block DNS after allow relay rule (so the relay can operate
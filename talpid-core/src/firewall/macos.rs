@@ -92,19 +92,26 @@ impl Firewall {
         &mut self,
         policy: &FirewallPolicy,
     ) -> Result<Vec<pfctl::RedirectRule>> {
-        let redirect_rules = match policy {
+        let dns_redirect_port = match policy {
             FirewallPolicy::Blocked {
                 dns_redirect_port, ..
-            } => {
+            } => Some(*dns_redirect_port),
+            FirewallPolicy::Connected {
+                dns_redirect_port, ..
+            } => *dns_redirect_port,
+            _ => None,
+        };
+        let redirect_rules = match dns_redirect_port {
+            Some(dns_redirect_port) => {
                 vec![pfctl::RedirectRuleBuilder::default()
                     .action(pfctl::RedirectRuleAction::Redirect)
                     .interface("lo0")
                     .proto(pfctl::Proto::Udp)
                     .to(pfctl::Port::from(53))
-                    .redirect_to(pfctl::Port::from(*dns_redirect_port))
+                    .redirect_to(pfctl::Port::from(dns_redirect_port))
                     .build()?]
             }
-            _ => vec![],
+            None => vec![],
         };
         Ok(redirect_rules)
     }
@@ -144,6 +151,7 @@ impl Firewall {
                 tunnel,
                 allow_lan,
                 dns_servers,
+                ..
             } => {
                 let mut rules = vec![];
 
@@ -123,6 +123,10 @@ pub enum FirewallPolicy {
         allowed_endpoint: AllowedEndpoint,
         /// Networks for which to permit in-tunnel traffic.
         allowed_tunnel_traffic: AllowedTunnelTraffic,
+        /// Relay endpoints that may be probed before connecting, using traffic marked with the
+        /// tunnel fwmark.
+        #[cfg(target_os = "linux")]
+        probe_endpoints: Vec<talpid_types::net::Endpoint>,
     },
 
     /// Allow traffic only to server and over tunnel interface
@@ -136,6 +140,11 @@ pub enum FirewallPolicy {
         /// Servers that are allowed to respond to DNS requests.
         #[cfg(not(target_os = "android"))]
         dns_servers: Vec<IpAddr>,
+        /// Destination port for DNS traffic redirection, if DNS should be handled by the local
+        /// filtering resolver. Traffic destined to `127.0.0.1:53` will be redirected to
+        /// `127.0.0.1:$dns_redirect_port`.
+        #[cfg(target_os = "macos")]
+        dns_redirect_port: Option<u16>,
     },
 
     /// Block all network traffic in and out from the computer.
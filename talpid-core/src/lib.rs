@@ -40,5 +40,5 @@ pub mod tunnel_state_machine;
 mod linux;
 
 /// A resolver that's controlled by the tunnel state machine
-#[cfg(target_os = "macos")]
+#[cfg(any(target_os = "macos", target_os = "linux"))]
 pub mod resolver;
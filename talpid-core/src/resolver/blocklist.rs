@@ -0,0 +1,404 @@
+//! User-supplied domain lists that the filtering resolver enforces on top of whatever the upstream
+//! resolvers do. Lists can be in hosts format (`0.0.0.0 ads.example.com`) or contain one domain
+//! per line. An entry matches the domain itself and all of its subdomains.
+
+use hickory_proto::rr::LowerName;
+use std::{
+    collections::HashSet,
+    io,
+    net::IpAddr,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+use talpid_types::net::dns::{BlocklistStats, DomainListKind, DomainListStats};
+
+/// How often the lists are checked for modifications.
+pub(super) const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Hostnames that commonly appear in hosts files but should never be treated as list entries.
+const IGNORED_HOSTNAMES: &[&str] = &[
+    "0.0.0.0",
+    "broadcasthost",
+    "local",
+    "localhost",
+    "localhost.localdomain",
+    "ip6-allhosts",
+    "ip6-allnodes",
+    "ip6-allrouters",
+    "ip6-localhost",
+    "ip6-localnet",
+    "ip6-loopback",
+    "ip6-mcastprefix",
+];
+
+/// Paths to the domain lists that the filtering resolver should enforce.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlocklistConfig {
+    /// Files containing domains to block.
+    pub blocklists: Vec<PathBuf>,
+    /// Files containing domains that should never be blocked, even if they appear in a blocklist.
+    pub allowlists: Vec<PathBuf>,
+}
+
+impl BlocklistConfig {
+    /// Returns whether any queries may be refused with this configuration.
+    pub fn is_active(&self) -> bool {
+        !self.blocklists.is_empty()
+    }
+
+    /// Returns statistics for the configured lists before any of them have been loaded.
+    pub fn unloaded_stats(&self) -> BlocklistStats {
+        let lists = self
+            .allowlists
+            .iter()
+            .map(|path| (path, DomainListKind::Allowlist))
+            .chain(
+                self.blocklists
+                    .iter()
+                    .map(|path| (path, DomainListKind::Blocklist)),
+            )
+            .map(|(path, kind)| DomainListStats {
+                path: path.clone(),
+                kind,
+                entries: 0,
+                hits: 0,
+                error: None,
+            })
+            .collect();
+        BlocklistStats { lists }
+    }
+}
+
+/// Outcome of checking a query against the domain lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Verdict {
+    Pass,
+    Block,
+}
+
+/// The set of loaded domain lists and their hit counters.
+#[derive(Default)]
+pub(super) struct Blocklists {
+    config: BlocklistConfig,
+    lists: Vec<DomainList>,
+}
+
+impl Blocklists {
+    /// Replaces the current lists. Hit counters are reset if the configuration changed.
+    pub async fn set_config(&mut self, config: BlocklistConfig) {
+        if self.config == config {
+            return;
+        }
+
+        self.lists = config
+            .allowlists
+            .iter()
+            .map(|path| DomainList::new(path.clone(), DomainListKind::Allowlist))
+            .chain(
+                config
+                    .blocklists
+                    .iter()
+                    .map(|path| DomainList::new(path.clone(), DomainListKind::Blocklist)),
+            )
+            .collect();
+        self.config = config;
+
+        self.refresh().await;
+    }
+
+    /// Reloads any list whose file has been modified since it was last read.
+    pub async fn refresh(&mut self) {
+        for list in &mut self.lists {
+            list.reload_if_modified().await;
+        }
+    }
+
+    /// Checks whether a query for `name` should be answered. Allowlists take precedence over
+    /// blocklists.
+    pub fn check(&mut self, name: &LowerName) -> Verdict {
+        if !self.config.is_active() {
+            return Verdict::Pass;
+        }
+
+        let name = name.to_string();
+        let name = name.trim_end_matches('.');
+
+        let verdict_by_kind = [
+            (DomainListKind::Allowlist, Verdict::Pass),
+            (DomainListKind::Blocklist, Verdict::Block),
+        ];
+        for (kind, verdict) in verdict_by_kind {
+            if let Some(list) = self
+                .lists
+                .iter_mut()
+                .find(|list| list.kind == kind && list.matches(name))
+            {
+                list.hits += 1;
+                return verdict;
+            }
+        }
+
+        Verdict::Pass
+    }
+
+    /// Returns the number of entries and hits for each list.
+    pub fn stats(&self) -> BlocklistStats {
+        BlocklistStats {
+            lists: self
+                .lists
+                .iter()
+                .map(|list| DomainListStats {
+                    path: list.path.clone(),
+                    kind: list.kind,
+                    entries: list.domains.len() as u64,
+                    hits: list.hits,
+                    error: list.error.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+struct DomainList {
+    path: PathBuf,
+    kind: DomainListKind,
+    modified: Option<SystemTime>,
+    domains: HashSet<String>,
+    hits: u64,
+    error: Option<String>,
+}
+
+impl DomainList {
+    fn new(path: PathBuf, kind: DomainListKind) -> Self {
+        Self {
+            path,
+            kind,
+            modified: None,
+            domains: HashSet::new(),
+            hits: 0,
+            error: None,
+        }
+    }
+
+    async fn reload_if_modified(&mut self) {
+        let modified = match tokio::fs::metadata(&self.path)
+            .await
+            .and_then(|metadata| metadata.modified())
+        {
+            Ok(modified) => modified,
+            Err(error) => return self.set_error(error),
+        };
+        if self.modified == Some(modified) {
+            return;
+        }
+
+        match tokio::fs::read_to_string(&self.path).await {
+            Ok(contents) => {
+                self.domains = parse_domain_list(&contents);
+                self.modified = Some(modified);
+                self.error = None;
+                log::debug!(
+                    "Loaded {} domains from DNS {} {}",
+                    self.domains.len(),
+                    self.kind,
+                    self.path.display()
+                );
+            }
+            Err(error) => self.set_error(error),
+        }
+    }
+
+    /// Records a failure to read the list. Previously loaded entries are kept so that a list
+    /// being replaced on disk does not briefly unblock everything in it.
+    fn set_error(&mut self, error: io::Error) {
+        if self.error.is_none() {
+            log::error!(
+                "Failed to read DNS {} {}: {}",
+                self.kind,
+                self.path.display(),
+                error
+            );
+        }
+        self.modified = None;
+        self.error = Some(error.to_string());
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        std::iter::successors(Some(name), |domain| {
+            domain.split_once('.').map(|(_, parent)| parent)
+        })
+        .any(|domain| self.domains.contains(domain))
+    }
+}
+
+/// Parses a list in hosts format or with one domain per line. Comments, invalid entries and
+/// loopback names commonly found in hosts files are skipped.
+fn parse_domain_list(contents: &str) -> HashSet<String> {
+    let mut domains = HashSet::new();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(first) = tokens.next() else {
+            continue;
+        };
+        if first.parse::<IpAddr>().is_ok() {
+            domains.extend(tokens.filter_map(normalize_domain));
+        } else {
+            domains.extend(normalize_domain(first));
+        }
+    }
+    domains
+}
+
+fn normalize_domain(entry: &str) -> Option<String> {
+    let domain = entry
+        .trim_start_matches("*.")
+        .trim_start_matches('.')
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+
+    let is_valid = !domain.is_empty()
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+
+    if !is_valid || IGNORED_HOSTNAMES.contains(&domain.as_str()) {
+        return None;
+    }
+    Some(domain)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hickory_proto::rr::Name;
+    use std::str::FromStr;
+
+    fn lower_name(name: &str) -> LowerName {
+        LowerName::from(Name::from_str(name).unwrap())
+    }
+
+    fn loaded_list(kind: DomainListKind, contents: &str) -> DomainList {
+        let mut list = DomainList::new(PathBuf::from(format!("/{kind}")), kind);
+        list.domains = parse_domain_list(contents);
+        list
+    }
+
+    fn blocklists(blocklist: &str, allowlist: &str) -> Blocklists {
+        Blocklists {
+            config: BlocklistConfig {
+                blocklists: vec![PathBuf::from("/blocklist")],
+                allowlists: vec![PathBuf::from("/allowlist")],
+            },
+            lists: vec![
+                loaded_list(DomainListKind::Allowlist, allowlist),
+                loaded_list(DomainListKind::Blocklist, blocklist),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_parse_hosts_format() {
+        let domains = parse_domain_list(
+            "# A hosts file\n\
+             127.0.0.1 localhost\n\
+             ::1 ip6-localhost ip6-loopback\n\
+             0.0.0.0 0.0.0.0\n\
+             0.0.0.0 ads.example.com tracker.example.org # inline comment\n\
+             \n\
+             0.0.0.0   Metrics.Example.NET.\n",
+        );
+
+        let expected: HashSet<String> = [
+            "ads.example.com",
+            "tracker.example.org",
+            "metrics.example.net",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        assert_eq!(domains, expected);
+    }
+
+    #[test]
+    fn test_parse_domain_list_format() {
+        let domains = parse_domain_list(
+            "example.com\n\
+             *.wildcard.example.org\n\
+             .leading-dot.example\n\
+             not/a/valid/entry\n\
+             invalid..domain\n",
+        );
+
+        let expected: HashSet<String> =
+            ["example.com", "wildcard.example.org", "leading-dot.example"]
+                .into_iter()
+                .map(String::from)
+                .collect();
+        assert_eq!(domains, expected);
+    }
+
+    #[test]
+    fn test_subdomains_are_blocked() {
+        let mut lists = blocklists("example.com\n", "");
+
+        assert_eq!(lists.check(&lower_name("example.com.")), Verdict::Block);
+        assert_eq!(lists.check(&lower_name("a.b.example.com.")), Verdict::Block);
+        assert_eq!(lists.check(&lower_name("notexample.com.")), Verdict::Pass);
+        assert_eq!(lists.check(&lower_name("example.org.")), Verdict::Pass);
+
+        let stats = lists.stats();
+        assert_eq!(stats.blocked_queries(), 2);
+        assert_eq!(stats.allowed_queries(), 0);
+    }
+
+    #[test]
+    fn test_allowlist_takes_precedence() {
+        let mut lists = blocklists("example.com\n", "safe.example.com\n");
+
+        assert_eq!(lists.check(&lower_name("safe.example.com.")), Verdict::Pass);
+        assert_eq!(
+            lists.check(&lower_name("cdn.safe.example.com.")),
+            Verdict::Pass
+        );
+        assert_eq!(lists.check(&lower_name("ads.example.com.")), Verdict::Block);
+
+        let stats = lists.stats();
+        assert_eq!(stats.blocked_queries(), 1);
+        assert_eq!(stats.allowed_queries(), 2);
+    }
+
+    #[test]
+    fn test_allowlist_only_is_inactive() {
+        let mut lists = Blocklists {
+            config: BlocklistConfig {
+                blocklists: vec![],
+                allowlists: vec![PathBuf::from("/allowlist")],
+            },
+            lists: vec![loaded_list(DomainListKind::Allowlist, "example.com\n")],
+        };
+
+        assert_eq!(lists.check(&lower_name("example.com.")), Verdict::Pass);
+        assert_eq!(lists.stats().allowed_queries(), 0);
+    }
+
+    #[test]
+    fn test_unloaded_stats_match_configured_lists() {
+        let lists = blocklists("example.com\n", "example.com\n");
+        let unloaded = lists.config.unloaded_stats();
+
+        let describe = |stats: &BlocklistStats| {
+            stats
+                .lists
+                .iter()
+                .map(|list| (list.path.clone(), list.kind))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(describe(&unloaded), describe(&lists.stats()));
+        assert!(unloaded.lists.iter().all(|list| list.entries == 0));
+    }
+}
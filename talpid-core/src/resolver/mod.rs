@@ -1,6 +1,6 @@
 use std::{
     io,
-    net::{Ipv4Addr, SocketAddr},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     str::FromStr,
     sync::{Arc, Weak},
 };
@@ -24,11 +24,21 @@ use hickory_server::{
         op::{header::MessageType, op_code::OpCode, Header},
         rr::{domain::Name, rdata, record_data::RData, Record},
     },
-    resolver::lookup::Lookup,
+    resolver::{
+        config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+        lookup::Lookup,
+        TokioAsyncResolver,
+    },
     server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
     ServerFuture,
 };
 use once_cell::sync::Lazy;
+use talpid_types::net::dns::BlocklistStats;
+
+mod blocklist;
+
+pub use blocklist::BlocklistConfig;
+use blocklist::{Blocklists, Verdict};
 
 const ALLOWED_RECORD_TYPES: &[RecordType] = &[RecordType::A, RecordType::CNAME];
 const CAPTIVE_PORTAL_DOMAINS: &[&str] = &["captive.apple.com", "netcts.cdn-apple.com"];
@@ -47,12 +57,35 @@ const RESOLVED_ADDR: Ipv4Addr = Ipv4Addr::new(198, 51, 100, 1);
 
 /// Starts a resolver. Returns a cloneable handle, which can activate, deactivate and shut down the
 /// resolver. When all instances of a handle are dropped, the server will stop.
+#[cfg(target_os = "macos")]
 pub(crate) async fn start_resolver() -> Result<ResolverHandle, Error> {
-    let (resolver, resolver_handle) = FilteringResolver::new().await?;
+    start_resolver_on_port(0).await
+}
+
+/// Starts a resolver listening on `port` on the loopback interface. If `port` is 0, any free port
+/// is used.
+pub(crate) async fn start_resolver_on_port(port: u16) -> Result<ResolverHandle, Error> {
+    let (resolver, resolver_handle) = FilteringResolver::new(port).await?;
     tokio::spawn(resolver.run());
+    tokio::spawn(refresh_blocklists(Arc::downgrade(&resolver_handle.tx)));
     Ok(resolver_handle)
 }
 
+/// Periodically asks the resolver to reload domain lists that have changed on disk. Returns once
+/// all resolver handles have been dropped.
+async fn refresh_blocklists(tx: Weak<mpsc::Sender<ResolverMessage>>) {
+    loop {
+        tokio::time::sleep(blocklist::REFRESH_INTERVAL).await;
+
+        let Some(mut tx) = tx.upgrade().map(|tx| (*tx).clone()) else {
+            break;
+        };
+        if tx.send(ResolverMessage::RefreshBlocklists).await.is_err() {
+            break;
+        }
+    }
+}
+
 /// Resolver errors
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -67,45 +100,86 @@ pub enum Error {
 
 /// A filtering resolver. Listens on a specified port for DNS queries and responds queries for
 /// `catpive.apple.com`. Can be toggled to unbind, be bound but not respond or bound and responding
-/// to some queries.
+/// to some queries. When upstream resolvers are set, all queries that are not refused by a
+/// user-supplied blocklist are forwarded to them instead.
 struct FilteringResolver {
     rx: mpsc::Receiver<ResolverMessage>,
     dns_server: Option<(tokio::task::JoinHandle<()>, oneshot::Receiver<()>)>,
+    blocklists: Blocklists,
+    upstream: Option<TokioAsyncResolver>,
 }
 
 /// The `FilteringResolver` is an actor responding to DNS queries.
-type ResolverMessage = (LowerQuery, oneshot::Sender<Box<dyn LookupObject>>);
+enum ResolverMessage {
+    /// Resolve a DNS query
+    Query(LowerQuery, oneshot::Sender<Box<dyn LookupObject>>),
+    /// Replace the user-supplied domain lists
+    SetBlocklists(BlocklistConfig, oneshot::Sender<()>),
+    /// Reload domain lists that have changed on disk
+    RefreshBlocklists,
+    /// Return entry and hit counts for the domain lists
+    GetBlocklistStats(oneshot::Sender<BlocklistStats>),
+    /// Forward queries to the given resolvers, or stop forwarding if `None`
+    SetUpstream(Option<Vec<IpAddr>>, oneshot::Sender<()>),
+}
 
 /// A handle to control a filtering resolver. When all resolver handles are dropped, custom
 /// resolver will stop.
 #[derive(Clone)]
-pub(crate) struct ResolverHandle {
-    _tx: Arc<mpsc::Sender<ResolverMessage>>,
+pub struct ResolverHandle {
+    tx: Arc<mpsc::Sender<ResolverMessage>>,
     listening_port: u16,
 }
 
 impl ResolverHandle {
     fn new(tx: Arc<mpsc::Sender<ResolverMessage>>, listening_port: u16) -> Self {
-        Self {
-            _tx: tx,
-            listening_port,
-        }
+        Self { tx, listening_port }
     }
 
     /// Get listening port for resolver handle
     pub fn listening_port(&self) -> u16 {
         self.listening_port
     }
+
+    /// Set the user-supplied domain lists to enforce. Returns once the lists have been loaded.
+    pub async fn set_blocklists(&self, config: BlocklistConfig) {
+        self.request(|done_tx| ResolverMessage::SetBlocklists(config, done_tx))
+            .await;
+    }
+
+    /// Get the number of entries in and queries matched by each user-supplied domain list.
+    pub async fn blocklist_stats(&self) -> BlocklistStats {
+        self.request(ResolverMessage::GetBlocklistStats)
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Forward queries to `servers` instead of only answering captive portal checks. Passing
+    /// `None` restores the default behavior.
+    pub(crate) async fn set_upstream(&self, servers: Option<Vec<IpAddr>>) {
+        self.request(|done_tx| ResolverMessage::SetUpstream(servers, done_tx))
+            .await;
+    }
+
+    async fn request<T>(
+        &self,
+        message: impl FnOnce(oneshot::Sender<T>) -> ResolverMessage,
+    ) -> Option<T> {
+        let (response_tx, response_rx) = oneshot::channel();
+        let mut tx = (*self.tx).clone();
+        tx.send(message(response_tx)).await.ok()?;
+        response_rx.await.ok()
+    }
 }
 
 impl FilteringResolver {
     /// Constructs a new filtering resolver and it's handle.
-    async fn new() -> Result<(Self, ResolverHandle), Error> {
+    async fn new(port: u16) -> Result<(Self, ResolverHandle), Error> {
         let (tx, rx) = mpsc::channel(0);
         let command_tx = Arc::new(tx);
 
         let weak_tx = Arc::downgrade(&command_tx);
-        let (mut server, port) = Self::new_server(0, weak_tx.clone()).await?;
+        let (mut server, port) = Self::new_server(port, weak_tx.clone()).await?;
 
         let (server_done_tx, server_done_rx) = oneshot::channel();
         let server_handle = tokio::spawn(async move {
@@ -134,6 +208,8 @@ impl FilteringResolver {
         let resolver = Self {
             rx,
             dns_server: Some((server_handle, server_done_rx)),
+            blocklists: Blocklists::default(),
+            upstream: None,
         };
 
         Ok((resolver, ResolverHandle::new(command_tx, port)))
@@ -162,8 +238,22 @@ impl FilteringResolver {
     /// related [ResolverHandle] instances are dropped, this function will return, closing the DNS
     /// server.
     async fn run(mut self) {
-        while let Some((query, tx)) = self.rx.next().await {
-            self.resolve(query, tx);
+        while let Some(message) = self.rx.next().await {
+            match message {
+                ResolverMessage::Query(query, tx) => self.resolve(query, tx),
+                ResolverMessage::SetBlocklists(config, done_tx) => {
+                    self.blocklists.set_config(config).await;
+                    let _ = done_tx.send(());
+                }
+                ResolverMessage::RefreshBlocklists => self.blocklists.refresh().await,
+                ResolverMessage::GetBlocklistStats(tx) => {
+                    let _ = tx.send(self.blocklists.stats());
+                }
+                ResolverMessage::SetUpstream(servers, done_tx) => {
+                    self.set_upstream(servers);
+                    let _ = done_tx.send(());
+                }
+            }
         }
 
         if let Some((server_handle, done_rx)) = self.dns_server.take() {
@@ -172,8 +262,20 @@ impl FilteringResolver {
         }
     }
 
-    /// Resolvers a query to nothing or a documentation address
+    /// Resolvers a query to nothing, a documentation address, or whatever the upstream resolvers
+    /// respond with
     fn resolve(&mut self, query: LowerQuery, tx: oneshot::Sender<Box<dyn LookupObject>>) {
+        if self.blocklists.check(query.name()) == Verdict::Block {
+            log::trace!("Refusing query for blocklisted domain {}", query.name());
+            let _ = tx.send(Box::new(EmptyLookup) as Box<dyn LookupObject>);
+            return;
+        }
+
+        if let Some(upstream) = &self.upstream {
+            Self::forward(upstream.clone(), query, tx);
+            return;
+        }
+
         if !self.allow_query(&query) {
             let _ = tx.send(Box::new(EmptyLookup) as Box<dyn LookupObject>);
             return;
@@ -195,6 +297,40 @@ impl FilteringResolver {
         let _ = tx.send(Box::new(ForwardLookup(lookup)));
     }
 
+    /// Resolves a query using the upstream resolvers. The lookup runs in a separate task so that
+    /// a slow upstream does not hold up other queries.
+    fn forward(
+        upstream: TokioAsyncResolver,
+        query: LowerQuery,
+        tx: oneshot::Sender<Box<dyn LookupObject>>,
+    ) {
+        tokio::spawn(async move {
+            let original = query.original();
+            let lookup: Box<dyn LookupObject> = match upstream
+                .lookup(original.name().clone(), original.query_type())
+                .await
+            {
+                Ok(lookup) => Box::new(ForwardLookup(lookup)),
+                Err(error) => {
+                    log::trace!("Upstream lookup for {} failed: {}", original.name(), error);
+                    Box::new(EmptyLookup)
+                }
+            };
+            let _ = tx.send(lookup);
+        });
+    }
+
+    fn set_upstream(&mut self, servers: Option<Vec<IpAddr>>) {
+        self.upstream = servers.map(|servers| {
+            let config = ResolverConfig::from_parts(
+                None,
+                vec![],
+                NameServerConfigGroup::from_ips_clear(&servers, 53, true),
+            );
+            TokioAsyncResolver::tokio(config, ResolverOpts::default())
+        });
+    }
+
     /// Determines whether a DNS query is allowable. Currently, this implies that the query is
     /// either a `A`, `AAAA` or a `CNAME` query for `captive.apple.com`.
     fn allow_query(&self, query: &LowerQuery) -> bool {
@@ -243,7 +379,9 @@ impl ResolverImpl {
             let mut tx = (*tx_ref).clone();
             let query = message.query();
             let (lookup_tx, lookup_rx) = oneshot::channel();
-            let _ = tx.send((query.clone(), lookup_tx)).await;
+            let _ = tx
+                .send(ResolverMessage::Query(query.clone(), lookup_tx))
+                .await;
             let lookup_result: Box<dyn LookupObject> = lookup_rx
                 .await
                 .unwrap_or_else(|_| Box::new(EmptyLookup) as Box<dyn LookupObject>);
@@ -303,14 +441,10 @@ impl LookupObject for ForwardLookup {
 #[cfg(test)]
 mod test {
     use super::*;
-    use hickory_server::resolver::{
-        config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
-        TokioAsyncResolver,
-    };
     use std::{mem, net::UdpSocket, thread, time::Duration};
 
     async fn start_resolver() -> ResolverHandle {
-        super::start_resolver().await.unwrap()
+        super::start_resolver_on_port(0).await.unwrap()
     }
 
     fn get_test_resolver(port: u16) -> hickory_server::resolver::TokioAsyncResolver {
@@ -136,7 +136,9 @@ impl TunnelMonitor {
             TunnelParameters::OpenVpn(params) => match &params.proxy {
                 Some(CustomProxy::Shadowsocks(_)) => Some(std::env::current_exe().unwrap()),
                 Some(CustomProxy::Socks5Local(_)) => None,
-                Some(CustomProxy::Socks5Remote(_)) | None => Some(resource_dir.join("openvpn.exe")),
+                Some(CustomProxy::Socks5Remote(_)) | Some(CustomProxy::HttpConnect(_)) | None => {
+                    Some(resource_dir.join("openvpn.exe"))
+                }
             },
             _ => Some(std::env::current_exe().unwrap()),
         }
@@ -178,6 +180,7 @@ impl TunnelMonitor {
         let monitor = talpid_wireguard::WireguardMonitor::start(
             config,
             params.options.quantum_resistant,
+            &params.options.connectivity_check,
             #[cfg(not(target_os = "android"))]
             detect_mtu,
             log.as_deref(),
@@ -13,6 +13,8 @@ use futures::{
     StreamExt,
 };
 use std::net::IpAddr;
+#[cfg(target_os = "macos")]
+use std::net::Ipv4Addr;
 use talpid_types::{
     net::{AllowedClients, AllowedEndpoint, TunnelParameters},
     tunnel::{ErrorStateCause, FirewallPolicyError},
@@ -57,6 +59,7 @@ impl ConnectedState {
         let tunnel_interface = Some(connected_state.metadata.interface.clone());
         let tunnel_endpoint = talpid_types::net::TunnelEndpoint {
             tunnel_interface,
+            openvpn_session: connected_state.metadata.openvpn_session.clone(),
             ..connected_state.tunnel_parameters.get_tunnel_endpoint()
         };
 
@@ -158,6 +161,8 @@ impl ConnectedState {
             allow_lan: shared_values.allow_lan,
             #[cfg(not(target_os = "android"))]
             dns_servers: self.get_dns_servers(shared_values),
+            #[cfg(target_os = "macos")]
+            dns_redirect_port: shared_values.connected_dns_redirect_port(),
         }
     }
 
@@ -174,6 +179,23 @@ impl ConnectedState {
             })
             .collect::<Vec<_>>();
 
+        #[cfg(target_os = "macos")]
+        if shared_values.connected_dns_redirect_port().is_some() {
+            // Let the filtering resolver forward queries to the tunnel DNS servers, so that
+            // user-supplied blocklists can be enforced
+            shared_values
+                .runtime
+                .block_on(shared_values.filtering_resolver.set_upstream(Some(dns_ips)));
+            return shared_values
+                .dns_monitor
+                .set("lo", &[Ipv4Addr::LOCALHOST.into()])
+                .map_err(BoxedError::new);
+        }
+        #[cfg(target_os = "macos")]
+        shared_values
+            .runtime
+            .block_on(shared_values.filtering_resolver.set_upstream(None));
+
         shared_values
             .dns_monitor
             .set(&self.metadata.interface, &dns_ips)
@@ -186,6 +208,10 @@ impl ConnectedState {
         if let Err(error) = shared_values.dns_monitor.reset_before_interface_removal() {
             log::error!("{}", error.display_chain_with_msg("Unable to reset DNS"));
         }
+        #[cfg(target_os = "macos")]
+        shared_values
+            .runtime
+            .block_on(shared_values.filtering_resolver.set_upstream(None));
     }
 
     fn reset_routes(
@@ -293,11 +319,53 @@ impl ConnectedState {
                 let _ = complete_tx.send(());
                 consequence
             }
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            Some(TunnelCommand::DnsBlocklists(dns_blocklists, complete_tx)) => {
+                let consequence = if !shared_values.set_dns_blocklists(dns_blocklists) {
+                    SameState(self)
+                } else if let Err(error) = self.set_firewall_policy(shared_values) {
+                    self.disconnect(
+                        shared_values,
+                        AfterDisconnect::Block(ErrorStateCause::SetFirewallPolicyError(error)),
+                    )
+                } else if let Err(error) = self.set_dns(shared_values) {
+                    log::error!("{}", error.display_chain_with_msg("Failed to set DNS"));
+                    self.disconnect(
+                        shared_values,
+                        AfterDisconnect::Block(ErrorStateCause::SetDnsError),
+                    )
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::DnsManager(dns_manager, complete_tx)) => {
+                let consequence = if !shared_values.set_dns_manager(dns_manager) {
+                    SameState(self)
+                } else if let Err(error) = self.set_dns(shared_values) {
+                    log::error!("{}", error.display_chain_with_msg("Failed to set DNS"));
+                    self.disconnect(
+                        shared_values,
+                        AfterDisconnect::Block(ErrorStateCause::SetDnsError),
+                    )
+                } else {
+                    SameState(self)
+                };
+                let _ = complete_tx.send(());
+                consequence
+            }
             Some(TunnelCommand::BlockWhenDisconnected(block_when_disconnected, complete_tx)) => {
                 shared_values.block_when_disconnected = block_when_disconnected;
                 let _ = complete_tx.send(());
                 SameState(self)
             }
+            Some(TunnelCommand::PskRekeyInterval(interval, complete_tx)) => {
+                shared_values.set_psk_rekey_interval(interval);
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
             Some(TunnelCommand::Connectivity(connectivity)) => {
                 shared_values.connectivity = connectivity;
                 if connectivity.is_offline() {
@@ -342,6 +410,16 @@ impl ConnectedState {
             Some((TunnelEvent::Down, _)) | None => {
                 self.disconnect(shared_values, AfterDisconnect::Reconnect(0))
             }
+            Some((TunnelEvent::Failure(failure), _)) => {
+                log::warn!("Tunnel is closing: {failure}");
+                shared_values.tunnel_failure = Some(failure);
+                SameState(self)
+            }
+            Some((TunnelEvent::PskRenegotiationFailed, _)) => {
+                log::warn!("Failed to renegotiate PSK. Keeping the current one");
+                let _ = shared_values.psk_renegotiation_failed_tx.unbounded_send(());
+                SameState(self)
+            }
             Some(_) => SameState(self),
         }
     }
@@ -21,13 +21,17 @@ use std::{
 use talpid_routing::RouteManagerHandle;
 use talpid_tunnel::{tun_provider::TunProvider, TunnelArgs, TunnelEvent, TunnelMetadata};
 use talpid_types::{
-    net::{AllowedClients, AllowedEndpoint, AllowedTunnelTraffic, TunnelParameters},
+    net::{
+        AllowedClients, AllowedEndpoint, AllowedTunnelTraffic, TunnelEndpoint, TunnelParameters,
+    },
     tunnel::{ErrorStateCause, FirewallPolicyError},
     ErrorExt,
 };
 
 #[cfg(target_os = "android")]
 use talpid_tunnel::tun_provider;
+#[cfg(target_os = "linux")]
+use talpid_types::net::Endpoint;
 
 use super::connected_state::TunnelEventsReceiver;
 
@@ -57,6 +61,7 @@ impl ConnectingState {
         shared_values: &mut SharedTunnelStateValues,
         retry_attempt: u32,
     ) -> (Box<dyn TunnelState>, TunnelStateTransition) {
+        let reconnect_reason = shared_values.tunnel_failure.take();
         if shared_values.connectivity.is_offline() {
             // FIXME: Temporary: Nudge route manager to update the default interface
             #[cfg(target_os = "macos")]
@@ -75,6 +80,12 @@ impl ConnectingState {
                 ErrorState::enter(shared_values, ErrorStateCause::TunnelParameterError(err))
             }
             Ok(tunnel_parameters) => {
+                #[cfg(target_os = "linux")]
+                let tunnel_parameters = match Self::probe_relay(shared_values, tunnel_parameters) {
+                    Ok(tunnel_parameters) => tunnel_parameters,
+                    Err(cause) => return ErrorState::enter(shared_values, cause),
+                };
+
                 #[cfg(windows)]
                 if let Err(error) = shared_values.split_tunnel.set_tunnel_addresses(None) {
                     log::error!(
@@ -119,23 +130,77 @@ impl ConnectingState {
                         &shared_values.resource_dir,
                         shared_values.tun_provider.clone(),
                         &shared_values.route_manager,
+                        shared_values.psk_rekey_interval_tx.subscribe(),
                         retry_attempt,
                     );
                     let params = connecting_state.tunnel_parameters.clone();
                     (
                         Box::new(connecting_state),
-                        TunnelStateTransition::Connecting(params.get_tunnel_endpoint()),
+                        TunnelStateTransition::Connecting(TunnelEndpoint {
+                            reconnect_reason,
+                            ..params.get_tunnel_endpoint()
+                        }),
                     )
                 }
             }
         }
     }
 
+    /// Lets the tunnel parameters generator probe the relay in `params` before connecting to it.
+    /// Returns the parameters to connect with.
+    #[cfg(target_os = "linux")]
+    fn probe_relay(
+        shared_values: &mut SharedTunnelStateValues,
+        params: TunnelParameters,
+    ) -> Result<TunnelParameters, ErrorStateCause> {
+        let probe_endpoints = shared_values
+            .runtime
+            .block_on(shared_values.tunnel_parameters_generator.probe_endpoints());
+        if probe_endpoints.is_empty() {
+            return Ok(params);
+        }
+
+        Self::apply_firewall_policy(
+            shared_values,
+            &params,
+            &None,
+            AllowedTunnelTraffic::None,
+            probe_endpoints,
+        )
+        .map_err(ErrorStateCause::SetFirewallPolicyError)?;
+
+        match shared_values
+            .runtime
+            .block_on(shared_values.tunnel_parameters_generator.probe())
+        {
+            Ok(Some(new_params)) => Ok(new_params),
+            Ok(None) => Ok(params),
+            Err(error) => Err(ErrorStateCause::TunnelParameterError(error)),
+        }
+    }
+
     fn set_firewall_policy(
         shared_values: &mut SharedTunnelStateValues,
         params: &TunnelParameters,
         tunnel_metadata: &Option<TunnelMetadata>,
         allowed_tunnel_traffic: AllowedTunnelTraffic,
+    ) -> Result<(), FirewallPolicyError> {
+        Self::apply_firewall_policy(
+            shared_values,
+            params,
+            tunnel_metadata,
+            allowed_tunnel_traffic,
+            #[cfg(target_os = "linux")]
+            vec![],
+        )
+    }
+
+    fn apply_firewall_policy(
+        shared_values: &mut SharedTunnelStateValues,
+        params: &TunnelParameters,
+        tunnel_metadata: &Option<TunnelMetadata>,
+        allowed_tunnel_traffic: AllowedTunnelTraffic,
+        #[cfg(target_os = "linux")] probe_endpoints: Vec<Endpoint>,
     ) -> Result<(), FirewallPolicyError> {
         #[cfg(target_os = "linux")]
         shared_values.disable_connectivity_check();
@@ -164,6 +229,8 @@ impl ConnectingState {
             allow_lan: shared_values.allow_lan,
             allowed_endpoint: shared_values.allowed_endpoint.clone(),
             allowed_tunnel_traffic,
+            #[cfg(target_os = "linux")]
+            probe_endpoints,
         };
         shared_values
             .firewall
@@ -190,6 +257,7 @@ impl ConnectingState {
         resource_dir: &Path,
         tun_provider: Arc<Mutex<TunProvider>>,
         route_manager: &RouteManagerHandle,
+        psk_rekey_interval_rx: tokio::sync::watch::Receiver<Option<Duration>>,
         retry_attempt: u32,
     ) -> Self {
         let (event_tx, event_rx) = mpsc::unbounded();
@@ -222,6 +290,7 @@ impl ConnectingState {
                 tun_provider,
                 retry_attempt,
                 route_manager,
+                psk_rekey_interval_rx,
             };
 
             let block_reason = match TunnelMonitor::start(&mut tunnel_parameters, &log_dir, args) {
@@ -433,11 +502,30 @@ impl ConnectingState {
                 let _ = complete_tx.send(());
                 consequence
             }
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            Some(TunnelCommand::DnsBlocklists(dns_blocklists, complete_tx)) => {
+                // Applied to the DNS configuration once connected
+                let _ = shared_values.set_dns_blocklists(dns_blocklists);
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::DnsManager(dns_manager, complete_tx)) => {
+                // Applied to the DNS configuration once connected
+                let _ = shared_values.set_dns_manager(dns_manager);
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
             Some(TunnelCommand::BlockWhenDisconnected(block_when_disconnected, complete_tx)) => {
                 shared_values.block_when_disconnected = block_when_disconnected;
                 let _ = complete_tx.send(());
                 SameState(self)
             }
+            Some(TunnelCommand::PskRekeyInterval(interval, complete_tx)) => {
+                shared_values.set_psk_rekey_interval(interval);
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
             Some(TunnelCommand::Connectivity(connectivity)) => {
                 shared_values.connectivity = connectivity;
                 if connectivity.is_offline() {
@@ -534,6 +622,12 @@ impl ConnectingState {
 
                 SameState(self)
             }
+            Some((TunnelEvent::Failure(failure), _)) => {
+                log::warn!("Tunnel is closing: {failure}");
+                shared_values.tunnel_failure = Some(failure);
+                SameState(self)
+            }
+            Some((TunnelEvent::PskRenegotiationFailed, _)) => SameState(self),
             None => {
                 // The channel was closed
                 log::debug!("The tunnel disconnected unexpectedly");
@@ -166,6 +166,23 @@ impl TunnelState for DisconnectedState {
                 let _ = complete_tx.send(());
                 SameState(self)
             }
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            Some(TunnelCommand::DnsBlocklists(dns_blocklists, complete_tx)) => {
+                let _ = shared_values.set_dns_blocklists(dns_blocklists);
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            #[cfg(target_os = "linux")]
+            Some(TunnelCommand::DnsManager(dns_manager, complete_tx)) => {
+                let _ = shared_values.set_dns_manager(dns_manager);
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
+            Some(TunnelCommand::PskRekeyInterval(interval, complete_tx)) => {
+                shared_values.set_psk_rekey_interval(interval);
+                let _ = complete_tx.send(());
+                SameState(self)
+            }
             Some(TunnelCommand::BlockWhenDisconnected(block_when_disconnected, complete_tx)) => {
                 if shared_values.block_when_disconnected != block_when_disconnected {
                     shared_values.block_when_disconnected = block_when_disconnected;
@@ -55,6 +55,18 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Nothing
                 }
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                Some(TunnelCommand::DnsBlocklists(dns_blocklists, complete_tx)) => {
+                    let _ = shared_values.set_dns_blocklists(dns_blocklists);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Nothing
+                }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::DnsManager(dns_manager, complete_tx)) => {
+                    let _ = shared_values.set_dns_manager(dns_manager);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Nothing
+                }
                 Some(TunnelCommand::BlockWhenDisconnected(
                     block_when_disconnected,
                     complete_tx,
@@ -63,6 +75,11 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Nothing
                 }
+                Some(TunnelCommand::PskRekeyInterval(interval, complete_tx)) => {
+                    shared_values.set_psk_rekey_interval(interval);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Nothing
+                }
                 Some(TunnelCommand::Connectivity(connectivity)) => {
                     shared_values.connectivity = connectivity;
                     AfterDisconnect::Nothing
@@ -98,6 +115,18 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Block(reason)
                 }
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                Some(TunnelCommand::DnsBlocklists(dns_blocklists, complete_tx)) => {
+                    let _ = shared_values.set_dns_blocklists(dns_blocklists);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Block(reason)
+                }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::DnsManager(dns_manager, complete_tx)) => {
+                    let _ = shared_values.set_dns_manager(dns_manager);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Block(reason)
+                }
                 Some(TunnelCommand::BlockWhenDisconnected(
                     block_when_disconnected,
                     complete_tx,
@@ -106,6 +135,11 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Block(reason)
                 }
+                Some(TunnelCommand::PskRekeyInterval(interval, complete_tx)) => {
+                    shared_values.set_psk_rekey_interval(interval);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Block(reason)
+                }
                 Some(TunnelCommand::Connectivity(connectivity)) => {
                     shared_values.connectivity = connectivity;
                     if !connectivity.is_offline() && matches!(reason, ErrorStateCause::IsOffline) {
@@ -145,6 +179,18 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
+                #[cfg(any(target_os = "linux", target_os = "macos"))]
+                Some(TunnelCommand::DnsBlocklists(dns_blocklists, complete_tx)) => {
+                    let _ = shared_values.set_dns_blocklists(dns_blocklists);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
+                #[cfg(target_os = "linux")]
+                Some(TunnelCommand::DnsManager(dns_manager, complete_tx)) => {
+                    let _ = shared_values.set_dns_manager(dns_manager);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 Some(TunnelCommand::BlockWhenDisconnected(
                     block_when_disconnected,
                     complete_tx,
@@ -153,6 +199,11 @@ impl DisconnectingState {
                     let _ = complete_tx.send(());
                     AfterDisconnect::Reconnect(retry_attempt)
                 }
+                Some(TunnelCommand::PskRekeyInterval(interval, complete_tx)) => {
+                    shared_values.set_psk_rekey_interval(interval);
+                    let _ = complete_tx.send(());
+                    AfterDisconnect::Reconnect(retry_attempt)
+                }
                 Some(TunnelCommand::Connectivity(connectivity)) => {
                     shared_values.connectivity = connectivity;
                     if connectivity.is_offline() {
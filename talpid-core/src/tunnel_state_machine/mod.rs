@@ -39,11 +39,16 @@ use std::{
     sync::{Arc, Mutex},
     time::Duration,
 };
+#[cfg(target_os = "linux")]
+use talpid_types::net::{
+    dns::{DnsConfigurationDrift, DnsManager},
+    Endpoint,
+};
 #[cfg(target_os = "android")]
 use talpid_types::{android::AndroidContext, ErrorExt};
 use talpid_types::{
     net::{AllowedEndpoint, Connectivity, TunnelParameters},
-    tunnel::{ErrorStateCause, ParameterGenerationError, TunnelStateTransition},
+    tunnel::{ErrorStateCause, ParameterGenerationError, TunnelFailure, TunnelStateTransition},
 };
 
 const TUNNEL_STATE_MACHINE_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
@@ -102,6 +107,12 @@ pub struct InitialTunnelState {
     /// Programs to exclude from the tunnel using the split tunnel driver.
     #[cfg(windows)]
     pub exclude_paths: Vec<OsString>,
+    /// User-supplied domain lists enforced by the filtering resolver.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub dns_blocklists: crate::resolver::BlocklistConfig,
+    /// The system component used to manage DNS.
+    #[cfg(target_os = "linux")]
+    pub dns_manager: DnsManager,
 }
 
 /// Identifiers for various network resources that should be unique to a given instance of a tunnel
@@ -123,6 +134,8 @@ pub async fn spawn(
     resource_dir: PathBuf,
     state_change_listener: impl Sender<TunnelStateTransition> + Send + 'static,
     offline_state_listener: mpsc::UnboundedSender<Connectivity>,
+    psk_renegotiation_failed_listener: mpsc::UnboundedSender<()>,
+    #[cfg(target_os = "linux")] dns_drift_listener: mpsc::UnboundedSender<DnsConfigurationDrift>,
     #[cfg(target_os = "windows")] volume_update_rx: mpsc::UnboundedReceiver<()>,
     #[cfg(target_os = "android")] android_context: AndroidContext,
     #[cfg(target_os = "linux")] linux_ids: LinuxNetworkingIdentifiers,
@@ -153,6 +166,9 @@ pub async fn spawn(
         settings: initial_settings,
         command_tx: weak_command_tx,
         offline_state_tx: offline_state_listener,
+        psk_renegotiation_failed_tx: psk_renegotiation_failed_listener,
+        #[cfg(target_os = "linux")]
+        dns_drift_tx: dns_drift_listener,
         tunnel_parameters_generator,
         tun_provider,
         log_dir,
@@ -170,6 +186,10 @@ pub async fn spawn(
 
     #[cfg(windows)]
     let split_tunnel = state_machine.shared_values.split_tunnel.handle();
+    #[cfg(target_os = "macos")]
+    let filtering_resolver = state_machine.shared_values.filtering_resolver.clone();
+    #[cfg(target_os = "linux")]
+    let dns_manager = state_machine.shared_values.dns_monitor.manager_handle();
 
     tokio::task::spawn_blocking(move || {
         state_machine.run(state_change_listener);
@@ -183,6 +203,10 @@ pub async fn spawn(
         shutdown_rx,
         #[cfg(windows)]
         split_tunnel,
+        #[cfg(target_os = "macos")]
+        filtering_resolver,
+        #[cfg(target_os = "linux")]
+        dns_manager,
     })
 }
 
@@ -196,8 +220,17 @@ pub enum TunnelCommand {
     AllowEndpoint(AllowedEndpoint, oneshot::Sender<()>),
     /// Set DNS servers to use.
     Dns(Option<Vec<IpAddr>>, oneshot::Sender<()>),
+    /// Set user-supplied domain lists to enforce using the filtering resolver.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    DnsBlocklists(crate::resolver::BlocklistConfig, oneshot::Sender<()>),
+    /// Set the system component used to manage DNS.
+    #[cfg(target_os = "linux")]
+    DnsManager(DnsManager, oneshot::Sender<()>),
     /// Enable or disable the block_when_disconnected feature.
     BlockWhenDisconnected(bool, oneshot::Sender<()>),
+    /// Set how often a new PSK is negotiated while a WireGuard tunnel is up. Applies to the
+    /// running tunnel without reconnecting.
+    PskRekeyInterval(Option<Duration>, oneshot::Sender<()>),
     /// Notify the state machine of the connectivity of the device.
     Connectivity(Connectivity),
     /// Open tunnel connection.
@@ -242,6 +275,9 @@ struct TunnelStateMachineInitArgs<G: TunnelParametersGenerator> {
     settings: InitialTunnelState,
     command_tx: std::sync::Weak<mpsc::UnboundedSender<TunnelCommand>>,
     offline_state_tx: mpsc::UnboundedSender<Connectivity>,
+    psk_renegotiation_failed_tx: mpsc::UnboundedSender<()>,
+    #[cfg(target_os = "linux")]
+    dns_drift_tx: mpsc::UnboundedSender<DnsConfigurationDrift>,
     tunnel_parameters_generator: G,
     tun_provider: TunProvider,
     log_dir: Option<PathBuf>,
@@ -268,6 +304,10 @@ impl TunnelStateMachine {
 
         #[cfg(target_os = "macos")]
         let filtering_resolver = crate::resolver::start_resolver().await?;
+        #[cfg(target_os = "macos")]
+        filtering_resolver
+            .set_blocklists(args.settings.dns_blocklists.clone())
+            .await;
 
         let route_manager = RouteManagerHandle::spawn(
             #[cfg(target_os = "linux")]
@@ -302,13 +342,20 @@ impl TunnelStateMachine {
 
         let firewall = Firewall::from_args(fw_args).map_err(Error::InitFirewallError)?;
 
-        let dns_monitor = DnsMonitor::new(
+        #[cfg_attr(not(target_os = "linux"), allow(unused_mut))]
+        let mut dns_monitor = DnsMonitor::new(
             #[cfg(target_os = "linux")]
             runtime.clone(),
             #[cfg(target_os = "linux")]
             route_manager.clone(),
+            #[cfg(target_os = "linux")]
+            args.dns_drift_tx,
         )
         .map_err(Error::InitDnsMonitorError)?;
+        #[cfg(target_os = "linux")]
+        dns_monitor.set_preferred_manager(args.settings.dns_manager);
+        #[cfg(target_os = "linux")]
+        dns_monitor.set_blocklists(args.settings.dns_blocklists.clone());
 
         let (offline_tx, mut offline_rx) = mpsc::unbounded();
         let initial_offline_state_tx = args.offline_state_tx.clone();
@@ -354,6 +401,9 @@ impl TunnelStateMachine {
             connectivity,
             dns_servers: args.settings.dns_servers,
             allowed_endpoint: args.settings.allowed_endpoint,
+            psk_rekey_interval_tx: tokio::sync::watch::channel(None).0,
+            psk_renegotiation_failed_tx: args.psk_renegotiation_failed_tx,
+            tunnel_failure: None,
             tunnel_parameters_generator: Box::new(args.tunnel_parameters_generator),
             tun_provider: Arc::new(Mutex::new(args.tun_provider)),
             log_dir: args.log_dir,
@@ -362,6 +412,10 @@ impl TunnelStateMachine {
             connectivity_check_was_enabled: None,
             #[cfg(target_os = "macos")]
             filtering_resolver,
+            #[cfg(target_os = "macos")]
+            dns_blocklists: args.settings.dns_blocklists,
+            #[cfg(target_os = "linux")]
+            dns_manager: args.settings.dns_manager,
         };
 
         tokio::task::spawn_blocking(move || {
@@ -419,6 +473,20 @@ pub trait TunnelParametersGenerator: Send + 'static {
         retry_attempt: u32,
         ipv6: bool,
     ) -> Pin<Box<dyn Future<Output = Result<TunnelParameters, ParameterGenerationError>>>>;
+
+    /// Returns relay endpoints to probe before a tunnel is created from the last generated
+    /// parameters. The firewall lets traffic marked with the tunnel fwmark reach these endpoints
+    /// while [`TunnelParametersGenerator::probe`] runs.
+    #[cfg(target_os = "linux")]
+    fn probe_endpoints(&mut self) -> Pin<Box<dyn Future<Output = Vec<Endpoint>>>>;
+
+    /// Probes the endpoints returned by [`TunnelParametersGenerator::probe_endpoints`]. Returns
+    /// parameters to use instead of the last generated ones if the probe shows that those would
+    /// not work on the current network.
+    #[cfg(target_os = "linux")]
+    fn probe(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<TunnelParameters>, ParameterGenerationError>>>>;
 }
 
 /// Values that are common to all tunnel states.
@@ -443,6 +511,12 @@ struct SharedTunnelStateValues {
     dns_servers: Option<Vec<IpAddr>>,
     /// Endpoint that should not be blocked by the firewall.
     allowed_endpoint: AllowedEndpoint,
+    /// Sender of changes to the PSK rekey interval, which are applied to the running tunnel.
+    psk_rekey_interval_tx: tokio::sync::watch::Sender<Option<Duration>>,
+    /// Notified when a new PSK could not be negotiated for an established tunnel.
+    psk_renegotiation_failed_tx: mpsc::UnboundedSender<()>,
+    /// Failure that closed the last tunnel. It is reported when reconnecting.
+    tunnel_failure: Option<TunnelFailure>,
     /// The generator of new `TunnelParameter`s
     tunnel_parameters_generator: Box<dyn TunnelParametersGenerator>,
     /// The provider of tunnel devices.
@@ -459,6 +533,13 @@ struct SharedTunnelStateValues {
     /// Filtering resolver handle
     #[cfg(target_os = "macos")]
     filtering_resolver: crate::resolver::ResolverHandle,
+    /// User-supplied domain lists enforced by the filtering resolver, or by the local resolver
+    /// DNS manager on Linux.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    dns_blocklists: crate::resolver::BlocklistConfig,
+    /// The system component used to manage DNS.
+    #[cfg(target_os = "linux")]
+    dns_manager: DnsManager,
 }
 
 impl SharedTunnelStateValues {
@@ -515,6 +596,62 @@ impl SharedTunnelStateValues {
         }
     }
 
+    /// Updates the domain lists enforced by the filtering resolver. Returns `true` if blocklists
+    /// were enabled or disabled, in which case DNS must be reconfigured while connected so that
+    /// queries do or do not pass through the filtering resolver.
+    #[cfg(target_os = "macos")]
+    pub fn set_dns_blocklists(&mut self, dns_blocklists: crate::resolver::BlocklistConfig) -> bool {
+        if self.dns_blocklists == dns_blocklists {
+            return false;
+        }
+
+        let was_active = self.dns_blocklists.is_active();
+        self.runtime.block_on(
+            self.filtering_resolver
+                .set_blocklists(dns_blocklists.clone()),
+        );
+        self.dns_blocklists = dns_blocklists;
+
+        was_active != self.dns_blocklists.is_active()
+    }
+
+    /// Updates the domain lists enforced by the local resolver DNS manager. These take effect
+    /// without reconfiguring DNS, so this always returns `false`.
+    #[cfg(target_os = "linux")]
+    pub fn set_dns_blocklists(&mut self, dns_blocklists: crate::resolver::BlocklistConfig) -> bool {
+        if self.dns_blocklists != dns_blocklists {
+            self.dns_monitor.set_blocklists(dns_blocklists.clone());
+            self.dns_blocklists = dns_blocklists;
+        }
+        false
+    }
+
+    /// Sets how often a new PSK is negotiated while a WireGuard tunnel is up.
+    pub fn set_psk_rekey_interval(&mut self, interval: Option<Duration>) {
+        self.psk_rekey_interval_tx.send_replace(interval);
+    }
+
+    /// Sets the system component used to manage DNS. Returns `true` if it changed, in which case
+    /// DNS must be reconfigured while connected.
+    #[cfg(target_os = "linux")]
+    pub fn set_dns_manager(&mut self, dns_manager: DnsManager) -> bool {
+        if self.dns_manager == dns_manager {
+            return false;
+        }
+        self.dns_manager = dns_manager;
+        self.dns_monitor.set_preferred_manager(dns_manager);
+        true
+    }
+
+    /// Returns the port that DNS should be redirected to while connected, if queries should pass
+    /// through the filtering resolver in order to enforce user-supplied blocklists.
+    #[cfg(target_os = "macos")]
+    pub fn connected_dns_redirect_port(&self) -> Option<u16> {
+        self.dns_blocklists
+            .is_active()
+            .then(|| self.filtering_resolver.listening_port())
+    }
+
     /// NetworkManager's connectivity check can get hung when DNS requests fail, thus the TSM
     /// should always disable it before applying firewall rules. The connectivity check should be
     /// reset whenever the firewall is cleared.
@@ -587,6 +724,10 @@ pub struct TunnelStateMachineHandle {
     shutdown_rx: oneshot::Receiver<()>,
     #[cfg(windows)]
     split_tunnel: split_tunnel::SplitTunnelHandle,
+    #[cfg(target_os = "macos")]
+    filtering_resolver: crate::resolver::ResolverHandle,
+    #[cfg(target_os = "linux")]
+    dns_manager: crate::dns::DnsManagerHandle,
 }
 
 impl TunnelStateMachineHandle {
@@ -611,4 +752,16 @@ impl TunnelStateMachineHandle {
     pub fn split_tunnel(&self) -> &split_tunnel::SplitTunnelHandle {
         &self.split_tunnel
     }
+
+    /// Returns filtering resolver handle.
+    #[cfg(target_os = "macos")]
+    pub fn filtering_resolver(&self) -> &crate::resolver::ResolverHandle {
+        &self.filtering_resolver
+    }
+
+    /// Returns a handle that reports which DNS manager is in use.
+    #[cfg(target_os = "linux")]
+    pub fn dns_manager(&self) -> &crate::dns::DnsManagerHandle {
+        &self.dns_manager
+    }
 }
@@ -56,7 +56,13 @@ impl EventProcessor {
     ) -> Result<(), Error> {
         log::debug!("Processing \"{:?}\" event", event);
 
-        let details = proto::EventDetails { env };
+        let details = proto::EventDetails {
+            pushed_options: pushed_options(&env),
+            signal: env.get("signal").cloned(),
+            server_certificate: env.get("tls_id_0").cloned(),
+            cipher: env.get("cipher").cloned(),
+            env,
+        };
 
         let response = match event {
             openvpn_plugin::EventType::AuthFailed => {
@@ -74,3 +80,32 @@ impl EventProcessor {
         response.map(|_| ()).map_err(Error::SendEvent)
     }
 }
+
+/// Returns the options pushed by the server, which OpenVPN exposes as `foreign_option_<n>`
+/// starting at 1.
+fn pushed_options(env: &HashMap<String, String>) -> Vec<String> {
+    (1..)
+        .map_while(|index| env.get(&format!("foreign_option_{index}")).cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pushed_options_order() {
+        let env: HashMap<String, String> = (1..=11)
+            .map(|index| {
+                (
+                    format!("foreign_option_{index}"),
+                    format!("dhcp-option DNS 10.0.0.{index}"),
+                )
+            })
+            .collect();
+        let options = pushed_options(&env);
+        assert_eq!(options.len(), 11);
+        assert_eq!(options[0], "dhcp-option DNS 10.0.0.1");
+        assert_eq!(options[10], "dhcp-option DNS 10.0.0.11");
+    }
+}
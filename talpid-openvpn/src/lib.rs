@@ -173,6 +173,9 @@ pub struct OpenVpnMonitor<C: OpenVpnBuilder = OpenVpnCommand> {
     _user_pass_file: mktemp::TempFile,
     /// Keep the 'TempFile' for the proxy user-pass file in the struct, so it's removed on drop.
     _proxy_auth_file: Option<mktemp::TempFile>,
+    /// Keep the certificates and keys of a custom server in the struct, so they're removed on
+    /// drop.
+    _custom_server_files: Option<CustomServerFiles>,
 
     event_server_abort_tx: triggered::Trigger,
     server_join_handle: task::JoinHandle<std::result::Result<(), event_server::Error>>,
@@ -262,6 +265,13 @@ impl OpenVpnMonitor<OpenVpnCommand> {
                 .map_err(Error::CredentialsWriteError)?;
         let proxy_auth_file =
             Self::create_proxy_auth_file(&params.proxy).map_err(Error::CredentialsWriteError)?;
+        let custom_server_files = params
+            .config
+            .custom_server
+            .as_ref()
+            .map(Self::create_custom_server_files)
+            .transpose()
+            .map_err(Error::CredentialsWriteError)?;
         let user_pass_file_path = user_pass_file.to_path_buf();
         let proxy_auth_file_path = proxy_auth_file.as_ref().map(|file| file.to_path_buf());
 
@@ -279,6 +289,7 @@ impl OpenVpnMonitor<OpenVpnCommand> {
             params,
             user_pass_file.as_ref(),
             proxy_auth_file.as_ref().map(AsRef::as_ref),
+            custom_server_files.as_ref(),
             resource_dir,
             &proxy_monitor,
             #[cfg(windows)]
@@ -299,6 +310,7 @@ impl OpenVpnMonitor<OpenVpnCommand> {
             log_path,
             user_pass_file,
             proxy_auth_file,
+            custom_server_files,
             proxy_monitor,
             #[cfg(target_os = "linux")]
             fwmark: params.fwmark,
@@ -364,11 +376,22 @@ struct OpenVpnTunnelInitArgs {
     log_path: Option<PathBuf>,
     user_pass_file: mktemp::TempFile,
     proxy_auth_file: Option<mktemp::TempFile>,
+    custom_server_files: Option<CustomServerFiles>,
     proxy_monitor: Option<Box<dyn ProxyMonitor>>,
     #[cfg(target_os = "linux")]
     fwmark: u32,
 }
 
+/// Temporary files containing the certificates and keys of a custom OpenVPN server.
+#[derive(Debug)]
+struct CustomServerFiles {
+    ca: mktemp::TempFile,
+    cert: Option<mktemp::TempFile>,
+    key: Option<mktemp::TempFile>,
+    tls_crypt: Option<mktemp::TempFile>,
+    tls_auth: Option<mktemp::TempFile>,
+}
+
 impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
     fn new_internal<L>(
         mut cmd: C,
@@ -385,6 +408,7 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
         let log_path = init_args.log_path;
         let user_pass_file = init_args.user_pass_file;
         let proxy_auth_file = init_args.proxy_auth_file;
+        let custom_server_files = init_args.custom_server_files;
         let proxy_monitor = init_args.proxy_monitor;
 
         let (server_join_handle, ipc_path) = event_server::start(on_event, event_server_abort_rx)
@@ -411,6 +435,7 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
             proxy_monitor,
             _user_pass_file: user_pass_file,
             _proxy_auth_file: proxy_auth_file,
+            _custom_server_files: custom_server_files,
 
             event_server_abort_tx,
             server_join_handle,
@@ -547,15 +572,19 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
     fn create_proxy_auth_file(
         proxy_settings: &Option<CustomProxy>,
     ) -> std::result::Result<Option<mktemp::TempFile>, io::Error> {
-        if let Some(CustomProxy::Socks5Remote(ref remote_proxy)) = proxy_settings {
-            if let Some(ref proxy_auth) = remote_proxy.auth {
-                return Ok(Some(Self::create_credentials_file(
-                    proxy_auth.username(),
-                    proxy_auth.password(),
-                )?));
-            }
+        match proxy_settings {
+            Some(CustomProxy::Socks5Remote(remote_proxy)) => remote_proxy
+                .auth
+                .as_ref()
+                .map(|auth| Self::create_credentials_file(auth.username(), auth.password()))
+                .transpose(),
+            Some(CustomProxy::HttpConnect(http_proxy)) => http_proxy
+                .auth
+                .as_ref()
+                .map(|auth| Self::create_credentials_file(auth.username(), auth.password()))
+                .transpose(),
+            _ => Ok(None),
         }
-        Ok(None)
     }
 
     /// Starts a proxy service, as applicable.
@@ -585,6 +614,32 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
         Ok(temp_file)
     }
 
+    fn create_custom_server_files(
+        config: &openvpn::CustomServerConfig,
+    ) -> io::Result<CustomServerFiles> {
+        let create_optional = |contents: &Option<String>| {
+            contents
+                .as_deref()
+                .map(Self::create_secret_file)
+                .transpose()
+        };
+        Ok(CustomServerFiles {
+            ca: Self::create_secret_file(&config.ca)?,
+            cert: create_optional(&config.cert)?,
+            key: create_optional(&config.key)?,
+            tls_crypt: create_optional(&config.tls_crypt)?,
+            tls_auth: create_optional(&config.tls_auth)?,
+        })
+    }
+
+    fn create_secret_file(contents: &str) -> io::Result<mktemp::TempFile> {
+        let temp_file = mktemp::TempFile::new();
+        let mut file = fs::File::create(&temp_file)?;
+        Self::set_user_pass_file_permissions(&file)?;
+        file.write_all(contents.as_bytes())?;
+        Ok(temp_file)
+    }
+
     #[cfg(unix)]
     fn set_user_pass_file_permissions(file: &fs::File) -> io::Result<()> {
         use std::os::unix::fs::PermissionsExt;
@@ -611,19 +666,50 @@ impl<C: OpenVpnBuilder + Send + 'static> OpenVpnMonitor<C> {
         params: &openvpn::TunnelParameters,
         user_pass_file: &Path,
         proxy_auth_file: Option<&Path>,
+        custom_server_files: Option<&CustomServerFiles>,
         resource_dir: &Path,
         proxy_monitor: &Option<Box<dyn ProxyMonitor>>,
         #[cfg(windows)] alias: OsString,
     ) -> Result<OpenVpnCommand> {
         let mut cmd = OpenVpnCommand::new(Self::get_openvpn_bin(resource_dir)?);
-        if let Some(config) = Self::get_config_path(resource_dir) {
-            cmd.config(config);
-        }
         cmd.remote(params.config.endpoint)
-            .user_pass(user_pass_file)
             .tunnel_options(&params.options)
-            .enable_ipv6(params.generic_options.enable_ipv6)
-            .ca(resource_dir.join("ca.crt"));
+            .enable_ipv6(params.generic_options.enable_ipv6);
+        match (&params.config.custom_server, custom_server_files) {
+            (Some(custom_server), Some(files)) => {
+                // The bundled config and CA only apply to the Mullvad relays
+                cmd.ca(&files.ca)
+                    .cipher(custom_server.cipher.clone())
+                    .data_ciphers(custom_server.data_ciphers.clone())
+                    .auth(custom_server.auth.clone())
+                    .key_direction(custom_server.key_direction)
+                    .verify_x509_name(custom_server.verify_x509_name.clone())
+                    .compress(custom_server.compress.clone())
+                    .restrict_tls(false);
+                if custom_server.auth_user_pass {
+                    cmd.user_pass(user_pass_file);
+                }
+                if let Some(cert) = &files.cert {
+                    cmd.cert(cert);
+                }
+                if let Some(key) = &files.key {
+                    cmd.key(key);
+                }
+                if let Some(tls_crypt) = &files.tls_crypt {
+                    cmd.tls_crypt(tls_crypt);
+                }
+                if let Some(tls_auth) = &files.tls_auth {
+                    cmd.tls_auth(tls_auth);
+                }
+            }
+            _ => {
+                if let Some(config) = Self::get_config_path(resource_dir) {
+                    cmd.config(config);
+                }
+                cmd.user_pass(user_pass_file)
+                    .ca(resource_dir.join("ca.crt"));
+            }
+        }
         #[cfg(windows)]
         cmd.tunnel_alias(Some(alias));
         if let Some(proxy_settings) = params.proxy.clone().take() {
@@ -772,7 +858,7 @@ mod event_server {
     use futures::stream::TryStreamExt;
     use parity_tokio_ipc::Endpoint as IpcEndpoint;
     use std::{
-        collections::{HashMap, HashSet},
+        collections::HashSet,
         pin::Pin,
         task::{Context, Poll},
     };
@@ -780,6 +866,7 @@ mod event_server {
     #[cfg(any(target_os = "macos", target_os = "windows"))]
     use talpid_types::net::proxy::CustomProxy;
     use talpid_types::ErrorExt;
+    use talpid_types::{net::openvpn::SessionInfo, tunnel::TunnelFailure};
     use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
     use tonic::{
         transport::{server::Connected, Server},
@@ -840,9 +927,9 @@ mod event_server {
             &self,
             request: Request<EventDetails>,
         ) -> std::result::Result<Response<()>, tonic::Status> {
-            let env = request.into_inner().env;
+            let details = request.into_inner();
             (self.on_event)(talpid_tunnel::TunnelEvent::InterfaceUp(
-                Self::get_tunnel_metadata(&env)?,
+                self.get_tunnel_metadata(&details)?,
                 talpid_types::net::AllowedTunnelTraffic::All,
             ))
             .await;
@@ -853,7 +940,8 @@ mod event_server {
             &self,
             request: Request<EventDetails>,
         ) -> std::result::Result<Response<()>, tonic::Status> {
-            let env = request.into_inner().env;
+            let details = request.into_inner();
+            let env = &details.env;
 
             let _ = tokio::fs::remove_file(&self.user_pass_file_path).await;
             if let Some(ref file_path) = &self.proxy_auth_file_path {
@@ -879,7 +967,7 @@ mod event_server {
                     return Err(tonic::Status::failed_precondition("Failed to add routes"));
                 }
 
-                let extracted_routes = super::extract_routes(&env)
+                let extracted_routes = super::extract_routes(env)
                     .map_err(|err| {
                         log::error!("{}", err.display_chain_with_msg("Failed to obtain routes"));
                         tonic::Status::failed_precondition("Failed to obtain routes")
@@ -889,7 +977,7 @@ mod event_server {
                 routes.extend(extracted_routes);
             }
 
-            let metadata = Self::get_tunnel_metadata(&env)?;
+            let metadata = self.get_tunnel_metadata(&details)?;
 
             #[cfg(windows)]
             {
@@ -921,8 +1009,10 @@ mod event_server {
         }
 
         fn get_tunnel_metadata(
-            env: &HashMap<String, String>,
+            &self,
+            details: &EventDetails,
         ) -> std::result::Result<TunnelMetadata, tonic::Status> {
+            let env = &details.env;
             let tunnel_alias = env
                 .get("dev")
                 .ok_or_else(|| tonic::Status::invalid_argument("missing tunnel alias"))?
@@ -964,6 +1054,11 @@ mod event_server {
                 ips,
                 ipv4_gateway,
                 ipv6_gateway,
+                openvpn_session: Some(SessionInfo {
+                    cipher: details.cipher.clone(),
+                    pushed_options: details.pushed_options.clone(),
+                    server_certificate: details.server_certificate.clone(),
+                }),
             })
         }
     }
@@ -1013,13 +1108,31 @@ mod event_server {
 
         async fn route_predown(
             &self,
-            _request: Request<EventDetails>,
+            request: Request<EventDetails>,
         ) -> std::result::Result<Response<()>, tonic::Status> {
+            if let Some(signal) = request.into_inner().signal {
+                log::info!("OpenVPN is closing the tunnel: {signal}");
+                if let Some(failure) = failure_from_signal(&signal) {
+                    (self.on_event)(talpid_tunnel::TunnelEvent::Failure(failure)).await;
+                }
+            }
             (self.on_event)(talpid_tunnel::TunnelEvent::Down).await;
             Ok(Response::new(()))
         }
     }
 
+    /// Maps a signal that OpenVPN reports when restarting or exiting to the failure that caused
+    /// it. Returns `None` if the tunnel was closed on purpose. Authentication failures are
+    /// reported separately by the plugin.
+    pub(super) fn failure_from_signal(signal: &str) -> Option<TunnelFailure> {
+        match signal {
+            "ping-restart" | "ping-exit" => Some(TunnelFailure::ServerUnresponsive),
+            "tls-error" => Some(TunnelFailure::TlsError),
+            "connection-reset" => Some(TunnelFailure::ConnectionReset),
+            _ => None,
+        }
+    }
+
     pub fn start<L>(
         event_proxy: L,
         abort_rx: triggered::Listener,
@@ -1205,6 +1318,7 @@ mod tests {
             log_path,
             user_pass_file: TempFile::new(),
             proxy_auth_file: None,
+            custom_server_files: None,
             proxy_monitor: None,
             #[cfg(target_os = "linux")]
             fwmark: 0,
@@ -1286,6 +1400,29 @@ mod tests {
         assert!(testee.wait().await.is_err());
     }
 
+    #[test]
+    fn failure_from_signal() {
+        use talpid_types::tunnel::TunnelFailure;
+
+        let failure = event_server::failure_from_signal;
+        assert_eq!(failure("tls-error"), Some(TunnelFailure::TlsError));
+        assert_eq!(
+            failure("ping-restart"),
+            Some(TunnelFailure::ServerUnresponsive)
+        );
+        assert_eq!(
+            failure("ping-exit"),
+            Some(TunnelFailure::ServerUnresponsive)
+        );
+        assert_eq!(
+            failure("connection-reset"),
+            Some(TunnelFailure::ConnectionReset)
+        );
+        assert_eq!(failure("auth-failure"), None);
+        assert_eq!(failure("sigterm"), None);
+        assert_eq!(failure("sigusr1"), None);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn wait_closed() {
         let builder = TestOpenVpnBuilder {
@@ -26,8 +26,6 @@ static BASE_ARGUMENTS: &[&[&str]] = &[
     &["--rcvbuf", "1048576"],
     &["--sndbuf", "1048576"],
     &["--fast-io"],
-    &["--data-ciphers-fallback", "AES-256-GCM"],
-    &["--tls-version-min", "1.3"],
     &["--verb", "3"],
     #[cfg(windows)]
     &[
@@ -48,6 +46,9 @@ static BASE_ARGUMENTS: &[&[&str]] = &[
     &["--windows-driver", "wintun"],
 ];
 
+/// Data channel cipher used unless another one is configured.
+static DEFAULT_DATA_CIPHER: &str = "AES-256-GCM";
+
 static ALLOWED_TLS1_3_CIPHERS: &[&str] =
     &["TLS_AES_256_GCM_SHA384", "TLS_CHACHA20_POLY1305_SHA256"];
 
@@ -62,6 +63,17 @@ pub struct OpenVpnCommand {
     proxy_auth_path: Option<PathBuf>,
     ca: Option<PathBuf>,
     crl: Option<PathBuf>,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+    tls_crypt: Option<PathBuf>,
+    tls_auth: Option<PathBuf>,
+    key_direction: Option<u8>,
+    cipher: Option<String>,
+    data_ciphers: Vec<String>,
+    auth: Option<String>,
+    verify_x509_name: Option<net::openvpn::VerifyX509Name>,
+    compress: Option<String>,
+    restrict_tls: bool,
     plugin: Option<(PathBuf, Vec<String>)>,
     log: Option<PathBuf>,
     tunnel_options: net::openvpn::TunnelOptions,
@@ -85,6 +97,17 @@ impl OpenVpnCommand {
             proxy_auth_path: None,
             ca: None,
             crl: None,
+            cert: None,
+            key: None,
+            tls_crypt: None,
+            tls_auth: None,
+            key_direction: None,
+            cipher: None,
+            data_ciphers: vec![],
+            auth: None,
+            verify_x509_name: None,
+            compress: None,
+            restrict_tls: true,
             plugin: None,
             log: None,
             tunnel_options: net::openvpn::TunnelOptions::default(),
@@ -142,6 +165,83 @@ impl OpenVpnCommand {
         self
     }
 
+    /// Sets the path to the client certificate file.
+    pub fn cert(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.cert = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the path to the file containing the private key of the client certificate.
+    pub fn key(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.key = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the path to the file containing the static key used to encrypt and authenticate the
+    /// control channel. See the `--tls-crypt` OpenVPN documentation for details.
+    pub fn tls_crypt(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.tls_crypt = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the path to the file containing the static key used to authenticate the control
+    /// channel. See the `--tls-auth` OpenVPN documentation for details.
+    pub fn tls_auth(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.tls_auth = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the direction in which the static key is used. See the `--key-direction` OpenVPN
+    /// documentation for details.
+    pub fn key_direction(&mut self, key_direction: Option<u8>) -> &mut Self {
+        self.key_direction = key_direction;
+        self
+    }
+
+    /// Sets the data channel cipher. The default cipher is used if this is `None`.
+    pub fn cipher(&mut self, cipher: Option<String>) -> &mut Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Sets the data channel ciphers that may be negotiated. If this is empty, only the cipher
+    /// set by [`Self::cipher`] is allowed, or OpenVPN's defaults if that is not set either.
+    pub fn data_ciphers(&mut self, data_ciphers: Vec<String>) -> &mut Self {
+        self.data_ciphers = data_ciphers;
+        self
+    }
+
+    /// Sets the digest used to authenticate packets. See the `--auth` OpenVPN documentation for
+    /// details.
+    pub fn auth(&mut self, auth: Option<String>) -> &mut Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Sets the name that the certificate of the server must have.
+    pub fn verify_x509_name(
+        &mut self,
+        verify_x509_name: Option<net::openvpn::VerifyX509Name>,
+    ) -> &mut Self {
+        self.verify_x509_name = verify_x509_name;
+        self
+    }
+
+    /// Sets the compression algorithm. Only decompression of packets from the server is
+    /// allowed, since compressing packets may leak their contents.
+    pub fn compress(&mut self, compress: Option<String>) -> &mut Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Sets whether only TLS 1.3 and the ciphersuites used by the Mullvad relays are allowed.
+    /// This is enabled by default, but other servers may not support it. TLS 1.2 is required
+    /// either way.
+    pub fn restrict_tls(&mut self, restrict_tls: bool) -> &mut Self {
+        self.restrict_tls = restrict_tls;
+        self
+    }
+
     /// Sets a plugin and its arguments that OpenVPN will be started with.
     pub fn plugin(&mut self, path: impl AsRef<Path>, args: Vec<String>) -> &mut Self {
         self.plugin = Some((path.as_ref().to_path_buf(), args));
@@ -214,6 +314,41 @@ impl OpenVpnCommand {
             args.push(OsString::from("--crl-verify"));
             args.push(OsString::from(crl.as_os_str()));
         }
+        if let Some(ref cert) = self.cert {
+            args.push(OsString::from("--cert"));
+            args.push(OsString::from(cert.as_os_str()));
+        }
+        if let Some(ref key) = self.key {
+            args.push(OsString::from("--key"));
+            args.push(OsString::from(key.as_os_str()));
+        }
+        if let Some(ref tls_crypt) = self.tls_crypt {
+            args.push(OsString::from("--tls-crypt"));
+            args.push(OsString::from(tls_crypt.as_os_str()));
+        }
+        if let Some(ref tls_auth) = self.tls_auth {
+            args.push(OsString::from("--tls-auth"));
+            args.push(OsString::from(tls_auth.as_os_str()));
+        }
+        if let Some(key_direction) = self.key_direction {
+            args.push(OsString::from("--key-direction"));
+            args.push(OsString::from(key_direction.to_string()));
+        }
+        if let Some(ref auth) = self.auth {
+            args.push(OsString::from("--auth"));
+            args.push(OsString::from(auth));
+        }
+        if let Some(ref verify_x509_name) = self.verify_x509_name {
+            args.push(OsString::from("--verify-x509-name"));
+            args.push(OsString::from(&verify_x509_name.name));
+            args.push(OsString::from(verify_x509_name.name_type.as_str()));
+        }
+        if let Some(ref compress) = self.compress {
+            args.push(OsString::from("--compress"));
+            args.push(OsString::from(compress));
+            args.push(OsString::from("--allow-compression"));
+            args.push(OsString::from("asym"));
+        }
 
         if let Some((ref path, ref plugin_args)) = self.plugin {
             args.push(OsString::from("--plugin"));
@@ -246,7 +381,8 @@ impl OpenVpnCommand {
             args.push(tunnel_device.clone());
         }
 
-        args.extend(Self::tls_cipher_arguments().iter().map(OsString::from));
+        args.extend(self.data_cipher_arguments().iter().map(OsString::from));
+        args.extend(self.tls_arguments().iter().map(OsString::from));
         args.extend(self.proxy_arguments().iter().map(OsString::from));
 
         #[cfg(target_os = "linux")]
@@ -267,8 +403,33 @@ impl OpenVpnCommand {
         args
     }
 
-    fn tls_cipher_arguments() -> Vec<String> {
+    fn data_cipher_arguments(&self) -> Vec<String> {
+        let mut args = vec![];
+        let data_ciphers = if self.data_ciphers.is_empty() {
+            self.cipher.clone()
+        } else {
+            Some(self.data_ciphers.join(":"))
+        };
+        if let Some(data_ciphers) = data_ciphers {
+            args.push("--data-ciphers".to_owned());
+            args.push(data_ciphers);
+        }
+        args.push("--data-ciphers-fallback".to_owned());
+        args.push(
+            self.cipher
+                .clone()
+                .unwrap_or_else(|| DEFAULT_DATA_CIPHER.to_owned()),
+        );
+        args
+    }
+
+    fn tls_arguments(&self) -> Vec<String> {
+        if !self.restrict_tls {
+            return vec!["--tls-version-min".to_owned(), "1.2".to_owned()];
+        }
         vec![
+            "--tls-version-min".to_owned(),
+            "1.3".to_owned(),
             "--tls-ciphersuites".to_owned(),
             ALLOWED_TLS1_3_CIPHERS.join(":"),
         ]
@@ -328,6 +489,25 @@ impl OpenVpnCommand {
                 args.push("255.255.255.255".to_owned());
                 args.push("net_gateway".to_owned());
             }
+            Some(CustomProxy::HttpConnect(ref http_proxy)) => {
+                args.push("--http-proxy".to_owned());
+                args.push(http_proxy.endpoint.ip().to_string());
+                args.push(http_proxy.endpoint.port().to_string());
+
+                if let Some(ref _auth) = http_proxy.auth {
+                    if let Some(ref auth_file) = self.proxy_auth_path {
+                        args.push(auth_file.to_string_lossy().to_string());
+                        args.push("basic".to_owned());
+                    } else {
+                        log::error!("Proxy credentials present but credentials file missing");
+                    }
+                }
+
+                args.push("--route".to_owned());
+                args.push(http_proxy.endpoint.ip().to_string());
+                args.push("255.255.255.255".to_owned());
+                args.push("net_gateway".to_owned());
+            }
             Some(CustomProxy::Shadowsocks(ref ss)) => {
                 args.push("--socks-proxy".to_owned());
                 args.push("127.0.0.1".to_owned());
@@ -457,7 +637,10 @@ impl OpenVpnProcHandle {
 mod tests {
     use super::OpenVpnCommand;
     use std::{ffi::OsString, net::Ipv4Addr};
-    use talpid_types::net::{Endpoint, TransportProtocol};
+    use talpid_types::net::{
+        openvpn::{VerifyX509Name, X509NameType},
+        Endpoint, TransportProtocol,
+    };
 
     #[test]
     fn passes_one_remote() {
@@ -484,4 +667,70 @@ mod tests {
         assert!(testee_args.contains(&OsString::from("123")));
         assert!(testee_args.contains(&OsString::from("cde")));
     }
+
+    #[test]
+    fn passes_data_cipher() {
+        let default_args = OpenVpnCommand::new("").get_arguments();
+        assert!(default_args.contains(&OsString::from("AES-256-GCM")));
+        assert!(!default_args.contains(&OsString::from("--data-ciphers")));
+
+        let testee_args = OpenVpnCommand::new("")
+            .cipher(Some("CHACHA20-POLY1305".to_owned()))
+            .get_arguments();
+        assert!(testee_args.contains(&OsString::from("--data-ciphers")));
+        assert!(testee_args.contains(&OsString::from("CHACHA20-POLY1305")));
+        assert!(!testee_args.contains(&OsString::from("AES-256-GCM")));
+
+        let testee_args = OpenVpnCommand::new("")
+            .data_ciphers(vec![
+                "AES-256-GCM".to_owned(),
+                "CHACHA20-POLY1305".to_owned(),
+            ])
+            .get_arguments();
+        assert!(testee_args.contains(&OsString::from("AES-256-GCM:CHACHA20-POLY1305")));
+    }
+
+    #[test]
+    fn passes_tls_restrictions() {
+        let default_args = OpenVpnCommand::new("").get_arguments();
+        assert!(default_args.contains(&OsString::from("--tls-version-min")));
+        assert!(default_args.contains(&OsString::from("--tls-ciphersuites")));
+
+        let testee_args = OpenVpnCommand::new("").restrict_tls(false).get_arguments();
+        let tls_version_min = testee_args
+            .iter()
+            .position(|arg| arg == "--tls-version-min")
+            .expect("TLS version must always be restricted");
+        assert_eq!(testee_args[tls_version_min + 1], "1.2");
+        assert!(!testee_args.contains(&OsString::from("--tls-ciphersuites")));
+    }
+
+    #[test]
+    fn passes_custom_server_options() {
+        let testee_args = OpenVpnCommand::new("")
+            .tls_auth("ta.key")
+            .key_direction(Some(1))
+            .auth(Some("SHA256".to_owned()))
+            .verify_x509_name(Some(VerifyX509Name {
+                name: "vpn.example.com".to_owned(),
+                name_type: X509NameType::Name,
+            }))
+            .compress(Some("lz4-v2".to_owned()))
+            .get_arguments();
+        let expected = [
+            ["--tls-auth", "ta.key"],
+            ["--key-direction", "1"],
+            ["--auth", "SHA256"],
+            ["--verify-x509-name", "vpn.example.com"],
+            ["--compress", "lz4-v2"],
+            ["--allow-compression", "asym"],
+        ];
+        for pair in expected {
+            let pair = pair.map(OsString::from);
+            assert!(
+                testee_args.windows(2).any(|window| window == pair),
+                "missing {pair:?}"
+            );
+        }
+    }
 }
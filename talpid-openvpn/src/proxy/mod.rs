@@ -56,6 +56,12 @@ pub async fn start_proxy(
                 remote_settings.endpoint.port(),
             )?))
         }
+        CustomProxy::HttpConnect(http_settings) => {
+            // These are generic proxy settings with the proxy client not managed by us.
+            Ok(Box::new(noop::NoopProxyMonitor::start(
+                http_settings.endpoint.port(),
+            )?))
+        }
         CustomProxy::Shadowsocks(ss_settings) => Ok(Box::new(
             ShadowsocksProxyMonitor::start(
                 ss_settings,
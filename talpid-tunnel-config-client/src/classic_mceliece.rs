@@ -44,3 +44,19 @@ pub fn decapsulate(
         secret,
     ))
 }
+
+/// Encapsulates a shared secret for `public_key`, like the relay does.
+#[cfg(any(test, feature = "mock-relay"))]
+pub fn encapsulate(
+    public_key: &[u8],
+    rng: &mut (impl rand::RngCore + rand::CryptoRng),
+) -> (Vec<u8>, [u8; 32]) {
+    use classic_mceliece_rust::CRYPTO_PUBLICKEYBYTES;
+
+    let public_key =
+        Box::<[u8; CRYPTO_PUBLICKEYBYTES]>::try_from(public_key.to_vec().into_boxed_slice())
+            .expect("invalid public key length");
+    let (ciphertext, shared_secret) =
+        classic_mceliece_rust::encapsulate_boxed(&PublicKey::from(public_key), rng);
+    (ciphertext.as_array().to_vec(), *shared_secret.as_array())
+}
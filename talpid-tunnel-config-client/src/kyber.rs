@@ -1,6 +1,6 @@
 use pqc_kyber::{SecretKey, KYBER_CIPHERTEXTBYTES};
 
-pub use pqc_kyber::{keypair, KyberError};
+pub use pqc_kyber::{keypair, Keypair, KyberError};
 
 /// Use the strongest variant of Kyber. It is fast and the keys are small, so there is no practical
 /// benefit of going with anything lower.
@@ -27,3 +27,14 @@ pub fn decapsulate(
         .map_err(super::Error::FailedDecapsulateKyber)?;
     Ok(shared_secret)
 }
+
+/// Encapsulates a shared secret for `public_key`, like the relay does.
+#[cfg(any(test, feature = "mock-relay"))]
+pub fn encapsulate(
+    public_key: &[u8],
+    rng: &mut (impl rand::RngCore + rand::CryptoRng),
+) -> (Vec<u8>, [u8; 32]) {
+    let (ciphertext, shared_secret) =
+        pqc_kyber::encapsulate(public_key, rng).expect("encapsulation failed");
+    (ciphertext.to_vec(), shared_secret)
+}
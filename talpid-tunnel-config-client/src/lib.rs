@@ -10,6 +10,11 @@ use zeroize::Zeroize;
 
 mod classic_mceliece;
 mod kyber;
+mod ml_kem;
+#[cfg(any(test, feature = "mock-relay"))]
+pub mod mock_relay;
+
+use crate::ml_kem::{MlKem1024, MlKem768, Variant};
 
 #[allow(clippy::derive_partial_eq_without_eq)]
 mod proto {
@@ -43,6 +48,13 @@ pub enum Error {
         actual: usize,
     },
     FailedDecapsulateKyber(kyber::KyberError),
+    FailedDecapsulateMlKem(&'static str),
+    UnexpectedKemAlgorithm {
+        algorithm: String,
+    },
+    InsufficientKems {
+        algorithms: Vec<String>,
+    },
 }
 
 impl std::fmt::Display for Error {
@@ -63,6 +75,18 @@ impl std::fmt::Display for Error {
                 write!(f, "Expected 2 ciphertext in the response, got {actual}")
             }
             FailedDecapsulateKyber(_) => "Failed to decapsulate Kyber1024 ciphertext".fmt(f),
+            FailedDecapsulateMlKem(algorithm) => {
+                write!(f, "Failed to decapsulate {algorithm} ciphertext")
+            }
+            UnexpectedKemAlgorithm { algorithm } => write!(
+                f,
+                "Relay used a KEM that was not offered, or used it more than once: {algorithm}"
+            ),
+            InsufficientKems { algorithms } => write!(
+                f,
+                "Relay must use Classic McEliece and at least one other KEM, but used: [{}]",
+                algorithms.join(", ")
+            ),
         }
     }
 }
@@ -93,33 +117,108 @@ pub const CONFIG_SERVICE_PORT: u16 = 1337;
 ///    handshake to work even if there is fragmentation.
 const CONFIG_CLIENT_MTU: u16 = 576;
 
+/// Version of the PSK exchange implemented by this client. Sent to the relay in `PskRequestV2`.
+const PROTOCOL_VERSION: u32 = 2;
+
 /// Generates a new WireGuard key pair and negotiates a PSK with the relay in a PQ-safe
 /// manner. This creates a peer on the relay with the new WireGuard pubkey and PSK,
 /// which can then be used to establish a PQ-safe tunnel to the relay.
+///
+/// The client offers all KEMs it supports and lets the relay pick which ones to use. Relays that
+/// do not support this fall back to the original exchange using Classic McEliece and Kyber.
 // TODO: consider binding to the tunnel interface here, on non-windows platforms
 pub async fn push_pq_key(
     service_address: IpAddr,
     wg_pubkey: PublicKey,
     wg_psk_pubkey: PublicKey,
 ) -> Result<PresharedKey, Error> {
-    let (cme_kem_pubkey, cme_kem_secret) = classic_mceliece::generate_keys().await;
-    let kyber_keypair = kyber::keypair(&mut rand::thread_rng());
+    let client = new_client(service_address).await?;
+    negotiate_psk(client, wg_pubkey, wg_psk_pubkey).await
+}
 
-    let mut client = new_client(service_address).await?;
+async fn negotiate_psk(
+    mut client: RelayConfigService,
+    wg_pubkey: PublicKey,
+    wg_psk_pubkey: PublicKey,
+) -> Result<PresharedKey, Error> {
+    let keys = KemKeys::generate().await;
+
+    match psk_exchange_v2(&mut client, &keys, &wg_pubkey, &wg_psk_pubkey).await {
+        Err(Error::GrpcError(status)) if status.code() == tonic::Code::Unimplemented => {
+            log::debug!("Relay does not support PSK exchange V2. Falling back to V1");
+            psk_exchange_v1(&mut client, &keys, &wg_pubkey, &wg_psk_pubkey).await
+        }
+        result => result,
+    }
+}
+
+/// Offers every supported KEM and mixes the shared secrets of the KEMs chosen by the relay into
+/// the PSK.
+async fn psk_exchange_v2(
+    client: &mut RelayConfigService,
+    keys: &KemKeys,
+    wg_pubkey: &PublicKey,
+    wg_psk_pubkey: &PublicKey,
+) -> Result<PresharedKey, Error> {
+    let response = client
+        .psk_exchange_v2(proto::PskRequestV2 {
+            protocol_version: PROTOCOL_VERSION,
+            wg_pubkey: wg_pubkey.as_bytes().to_vec(),
+            wg_psk_pubkey: wg_psk_pubkey.as_bytes().to_vec(),
+            kem_pubkeys: keys.pubkeys(),
+        })
+        .await
+        .map_err(Error::GrpcError)?;
+
+    let ciphertexts = response.into_inner().ciphertexts;
+
+    let mut used_algorithms: Vec<&str> = Vec::with_capacity(ciphertexts.len());
+    for ciphertext in &ciphertexts {
+        let algorithm = ciphertext.algorithm_name.as_str();
+        if !KemKeys::ALGORITHM_NAMES.contains(&algorithm) || used_algorithms.contains(&algorithm) {
+            return Err(Error::UnexpectedKemAlgorithm {
+                algorithm: algorithm.to_owned(),
+            });
+        }
+        used_algorithms.push(algorithm);
+    }
+    // Classic McEliece is never dropped, since it does not rely on lattices like the others.
+    if used_algorithms.len() < 2 || !used_algorithms.contains(&classic_mceliece::ALGORITHM_NAME) {
+        return Err(Error::InsufficientKems {
+            algorithms: used_algorithms
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        });
+    }
+    log::debug!("Relay chose KEMs: {}", used_algorithms.join(", "));
+
+    // Store the PSK data on the heap. So it can be passed around and then zeroized on drop without
+    // being stored in a bunch of places on the stack.
+    let mut psk_data = Box::new([0u8; 32]);
+    for ciphertext in &ciphertexts {
+        keys.decapsulate_into(
+            &ciphertext.algorithm_name,
+            &ciphertext.ciphertext,
+            &mut psk_data,
+        )?;
+    }
+
+    Ok(PresharedKey::from(psk_data))
+}
+
+/// Performs the original exchange, which always uses Classic McEliece and Kyber.
+async fn psk_exchange_v1(
+    client: &mut RelayConfigService,
+    keys: &KemKeys,
+    wg_pubkey: &PublicKey,
+    wg_psk_pubkey: &PublicKey,
+) -> Result<PresharedKey, Error> {
     let response = client
         .psk_exchange_v1(proto::PskRequestV1 {
             wg_pubkey: wg_pubkey.as_bytes().to_vec(),
             wg_psk_pubkey: wg_psk_pubkey.as_bytes().to_vec(),
-            kem_pubkeys: vec![
-                proto::KemPubkeyV1 {
-                    algorithm_name: classic_mceliece::ALGORITHM_NAME.to_owned(),
-                    key_data: cme_kem_pubkey.as_array().to_vec(),
-                },
-                proto::KemPubkeyV1 {
-                    algorithm_name: kyber::ALGORITHM_NAME.to_owned(),
-                    key_data: kyber_keypair.public.to_vec(),
-                },
-            ],
+            kem_pubkeys: vec![keys.cme_pubkey(), keys.kyber_pubkey()],
         })
         .await
         .map_err(Error::GrpcError)?;
@@ -135,30 +234,122 @@ pub async fn push_pq_key(
     // Store the PSK data on the heap. So it can be passed around and then zeroized on drop without
     // being stored in a bunch of places on the stack.
     let mut psk_data = Box::new([0u8; 32]);
+    keys.decapsulate_into(
+        classic_mceliece::ALGORITHM_NAME,
+        cme_ciphertext,
+        &mut psk_data,
+    )?;
+    keys.decapsulate_into(kyber::ALGORITHM_NAME, kyber_ciphertext, &mut psk_data)?;
 
-    // Decapsulate Classic McEliece and mix into PSK
-    {
-        let mut shared_secret = classic_mceliece::decapsulate(&cme_kem_secret, cme_ciphertext)?;
-        xor_assign(&mut psk_data, shared_secret.as_array());
+    Ok(PresharedKey::from(psk_data))
+}
+
+/// Key pairs for all KEMs supported by the client.
+struct KemKeys {
+    cme_pubkey: classic_mceliece_rust::PublicKey<'static>,
+    cme_secret: classic_mceliece_rust::SecretKey<'static>,
+    kyber: kyber::Keypair,
+    ml_kem_768: ml_kem::Keypair<MlKem768>,
+    ml_kem_1024: ml_kem::Keypair<MlKem1024>,
+}
+
+impl KemKeys {
+    /// Names of the supported KEMs, in the order that they are offered to the relay.
+    const ALGORITHM_NAMES: [&'static str; 4] = [
+        classic_mceliece::ALGORITHM_NAME,
+        MlKem1024::ALGORITHM_NAME,
+        MlKem768::ALGORITHM_NAME,
+        kyber::ALGORITHM_NAME,
+    ];
+
+    async fn generate() -> Self {
+        let (cme_pubkey, cme_secret) = classic_mceliece::generate_keys().await;
+        let mut rng = rand::thread_rng();
+        KemKeys {
+            cme_pubkey,
+            cme_secret,
+            kyber: kyber::keypair(&mut rng),
+            ml_kem_768: ml_kem::keypair(&mut rng),
+            ml_kem_1024: ml_kem::keypair(&mut rng),
+        }
+    }
 
-        // This should happen automatically due to `SharedSecret` implementing ZeroizeOnDrop. But
-        // doing it explicitly provides a stronger guarantee that it's not accidentally
-        // removed.
-        shared_secret.zeroize();
+    fn pubkeys(&self) -> Vec<proto::KemPubkeyV1> {
+        vec![
+            self.cme_pubkey(),
+            proto::KemPubkeyV1 {
+                algorithm_name: MlKem1024::ALGORITHM_NAME.to_owned(),
+                key_data: self.ml_kem_1024.encapsulation_key(),
+            },
+            proto::KemPubkeyV1 {
+                algorithm_name: MlKem768::ALGORITHM_NAME.to_owned(),
+                key_data: self.ml_kem_768.encapsulation_key(),
+            },
+            self.kyber_pubkey(),
+        ]
     }
-    // Decapsulate Kyber and mix into PSK
-    {
-        let mut shared_secret = kyber::decapsulate(kyber_keypair.secret, kyber_ciphertext)?;
-        xor_assign(&mut psk_data, &shared_secret);
 
-        // The shared secret is sadly stored in an array on the stack. So we can't get any
-        // guarantees that it's not copied around on the stack. The best we can do here
-        // is to zero out the version we have and hope the compiler optimizes out copies.
-        // https://github.com/Argyle-Software/kyber/issues/59
-        shared_secret.zeroize();
+    fn cme_pubkey(&self) -> proto::KemPubkeyV1 {
+        proto::KemPubkeyV1 {
+            algorithm_name: classic_mceliece::ALGORITHM_NAME.to_owned(),
+            key_data: self.cme_pubkey.as_array().to_vec(),
+        }
     }
 
-    Ok(PresharedKey::from(psk_data))
+    fn kyber_pubkey(&self) -> proto::KemPubkeyV1 {
+        proto::KemPubkeyV1 {
+            algorithm_name: kyber::ALGORITHM_NAME.to_owned(),
+            key_data: self.kyber.public.to_vec(),
+        }
+    }
+
+    /// Decapsulates `ciphertext` using the key for `algorithm` and mixes the shared secret into
+    /// `psk`.
+    fn decapsulate_into(
+        &self,
+        algorithm: &str,
+        ciphertext: &[u8],
+        psk: &mut [u8; 32],
+    ) -> Result<(), Error> {
+        match algorithm {
+            classic_mceliece::ALGORITHM_NAME => {
+                let mut shared_secret =
+                    classic_mceliece::decapsulate(&self.cme_secret, ciphertext)?;
+                xor_assign(psk, shared_secret.as_array());
+
+                // This should happen automatically due to `SharedSecret` implementing
+                // ZeroizeOnDrop. But doing it explicitly provides a stronger guarantee that it's
+                // not accidentally removed.
+                shared_secret.zeroize();
+            }
+            kyber::ALGORITHM_NAME => {
+                let mut shared_secret = kyber::decapsulate(self.kyber.secret, ciphertext)?;
+                xor_assign(psk, &shared_secret);
+
+                // The shared secret is sadly stored in an array on the stack. So we can't get any
+                // guarantees that it's not copied around on the stack. The best we can do here
+                // is to zero out the version we have and hope the compiler optimizes out copies.
+                // https://github.com/Argyle-Software/kyber/issues/59
+                shared_secret.zeroize();
+            }
+            MlKem768::ALGORITHM_NAME => {
+                let mut shared_secret = self.ml_kem_768.decapsulate(ciphertext)?;
+                xor_assign(psk, &shared_secret);
+                shared_secret.zeroize();
+            }
+            MlKem1024::ALGORITHM_NAME => {
+                let mut shared_secret = self.ml_kem_1024.decapsulate(ciphertext)?;
+                xor_assign(psk, &shared_secret);
+                shared_secret.zeroize();
+            }
+            _ => {
+                return Err(Error::UnexpectedKemAlgorithm {
+                    algorithm: algorithm.to_owned(),
+                })
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Performs `dst = dst ^ src`.
@@ -246,3 +437,93 @@ fn try_set_tcp_sock_mtu(dest: &IpAddr, sock: RawFd, mut mtu: u16) {
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mock_relay::{MockRelay, CLASSIC_MCELIECE, KYBER, ML_KEM_1024, ML_KEM_768};
+    use talpid_types::net::wireguard::PrivateKey;
+
+    /// Runs `relay` on an in-memory stream and returns a client connected to it.
+    async fn connect(relay: MockRelay) -> RelayConfigService {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(relay.serve_connection(server_io));
+
+        let mut client_io = Some(client_io);
+        let channel = Endpoint::from_static("http://[::]:50051")
+            .connect_with_connector(service_fn(move |_| {
+                let io = client_io.take();
+                async move {
+                    io.ok_or_else(|| std::io::Error::other("client stream already taken"))
+                }
+            }))
+            .await
+            .unwrap();
+        RelayConfigService::new(channel)
+    }
+
+    async fn exchange(relay: MockRelay) -> Result<PresharedKey, Error> {
+        let client = connect(relay).await;
+        negotiate_psk(
+            client,
+            PrivateKey::new_from_random().public_key(),
+            PrivateKey::new_from_random().public_key(),
+        )
+        .await
+    }
+
+    /// Checks that the client derived the same PSK as the relay.
+    async fn assert_same_psk(relay: MockRelay) {
+        let psk = exchange(relay.clone()).await.expect("PSK exchange failed");
+        assert_eq!(psk.as_bytes(), &relay.psk().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_v2_ml_kem() {
+        assert_same_psk(MockRelay::new()).await;
+    }
+
+    #[tokio::test]
+    async fn test_v2_all_kems() {
+        let relay = MockRelay::new().with_v2_algorithms(vec![
+            ML_KEM_768,
+            CLASSIC_MCELIECE,
+            KYBER,
+            ML_KEM_1024,
+        ]);
+        assert_same_psk(relay).await;
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_v1() {
+        assert_same_psk(MockRelay::v1_only()).await;
+    }
+
+    #[tokio::test]
+    async fn test_v2_requires_classic_mceliece() {
+        let relay = MockRelay::new().with_v2_algorithms(vec![ML_KEM_1024, KYBER]);
+        assert!(matches!(
+            exchange(relay).await,
+            Err(Error::InsufficientKems { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_v2_requires_two_kems() {
+        let relay = MockRelay::new().with_v2_algorithms(vec![CLASSIC_MCELIECE]);
+        assert!(matches!(
+            exchange(relay).await,
+            Err(Error::InsufficientKems { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_v2_rejects_duplicate_kem() {
+        let relay =
+            MockRelay::new().with_v2_algorithms(vec![CLASSIC_MCELIECE, ML_KEM_768, ML_KEM_768]);
+        assert!(matches!(
+            exchange(relay).await,
+            Err(Error::UnexpectedKemAlgorithm { .. })
+        ));
+    }
+}
@@ -0,0 +1,125 @@
+use ::ml_kem::{kem::Decapsulate, Ciphertext, EncodedSizeUser, KemCore};
+use rand::{CryptoRng, RngCore};
+use zeroize::{Zeroize, Zeroizing};
+
+pub use ::ml_kem::{MlKem1024, MlKem768};
+
+/// An ML-KEM (FIPS 203) parameter set that can be offered to the relay.
+pub trait Variant: KemCore {
+    /// Name of the parameter set, in the format that `liboqs` uses.
+    const ALGORITHM_NAME: &'static str;
+    /// Size of a ciphertext in bytes.
+    const CIPHERTEXT_LEN: usize;
+}
+
+impl Variant for MlKem768 {
+    const ALGORITHM_NAME: &'static str = "ML-KEM-768";
+    const CIPHERTEXT_LEN: usize = 1088;
+}
+
+impl Variant for MlKem1024 {
+    const ALGORITHM_NAME: &'static str = "ML-KEM-1024";
+    const CIPHERTEXT_LEN: usize = 1568;
+}
+
+pub struct Keypair<V: Variant> {
+    encapsulation_key: V::EncapsulationKey,
+    decapsulation_key: V::DecapsulationKey,
+}
+
+pub fn keypair<V: Variant>(rng: &mut (impl RngCore + CryptoRng)) -> Keypair<V> {
+    let (decapsulation_key, encapsulation_key) = V::generate(rng);
+    Keypair {
+        encapsulation_key,
+        decapsulation_key,
+    }
+}
+
+impl<V: Variant> Keypair<V> {
+    /// Returns the encoded public key, which the relay uses to encapsulate the shared secret.
+    pub fn encapsulation_key(&self) -> Vec<u8> {
+        self.encapsulation_key.as_bytes().to_vec()
+    }
+
+    // Always inline in order to try to avoid potential copies of `shared_secret` to multiple
+    // places on the stack.
+    #[inline(always)]
+    pub fn decapsulate(
+        &self,
+        ciphertext_slice: &[u8],
+    ) -> Result<Zeroizing<[u8; 32]>, super::Error> {
+        // Convert the slice into an array in order to report a length mismatch properly.
+        let ciphertext = Ciphertext::<V>::try_from(ciphertext_slice).map_err(|_| {
+            super::Error::InvalidCiphertextLength {
+                algorithm: V::ALGORITHM_NAME,
+                actual: ciphertext_slice.len(),
+                expected: V::CIPHERTEXT_LEN,
+            }
+        })?;
+        let mut shared_secret = self
+            .decapsulation_key
+            .decapsulate(&ciphertext)
+            .map_err(|_| super::Error::FailedDecapsulateMlKem(V::ALGORITHM_NAME))?;
+
+        let mut secret = Zeroizing::new([0u8; 32]);
+        secret.copy_from_slice(shared_secret.as_slice());
+        // The array returned by `ml-kem` does not implement `Zeroize`, so it is cleared by hand.
+        shared_secret.as_mut_slice().zeroize();
+        Ok(secret)
+    }
+}
+
+/// Encapsulates a shared secret for the encoded `encapsulation_key`, like the relay does.
+#[cfg(any(test, feature = "mock-relay"))]
+pub fn encapsulate<V: Variant>(
+    encapsulation_key: &[u8],
+    rng: &mut (impl RngCore + CryptoRng),
+) -> (Vec<u8>, [u8; 32]) {
+    use ::ml_kem::{kem::Encapsulate, Encoded};
+
+    let encoded = Encoded::<V::EncapsulationKey>::try_from(encapsulation_key)
+        .expect("invalid encapsulation key length");
+    let (ciphertext, shared_secret) = V::EncapsulationKey::from_bytes(&encoded)
+        .encapsulate(rng)
+        .expect("encapsulation failed");
+
+    let mut secret = [0u8; 32];
+    secret.copy_from_slice(shared_secret.as_slice());
+    (ciphertext.to_vec(), secret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn roundtrip<V: Variant>() {
+        let keypair = keypair::<V>(&mut rand::thread_rng());
+        let (ciphertext, server_secret) =
+            encapsulate::<V>(&keypair.encapsulation_key(), &mut rand::thread_rng());
+        assert_eq!(ciphertext.len(), V::CIPHERTEXT_LEN);
+        assert_eq!(*keypair.decapsulate(&ciphertext).unwrap(), server_secret);
+    }
+
+    #[test]
+    fn test_ml_kem_768_roundtrip() {
+        roundtrip::<MlKem768>();
+    }
+
+    #[test]
+    fn test_ml_kem_1024_roundtrip() {
+        roundtrip::<MlKem1024>();
+    }
+
+    #[test]
+    fn test_invalid_ciphertext_length() {
+        let keypair = keypair::<MlKem768>(&mut rand::thread_rng());
+        assert!(matches!(
+            keypair.decapsulate(&[0u8; 1568]),
+            Err(crate::Error::InvalidCiphertextLength {
+                actual: 1568,
+                expected: 1088,
+                ..
+            })
+        ));
+    }
+}
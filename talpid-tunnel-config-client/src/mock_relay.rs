@@ -0,0 +1,261 @@
+//! Stand-in for the relay config service, so that the PSK exchange can be tested without a real
+//! relay. The mock performs the relay side of the exchange and can be told to misbehave in various
+//! ways.
+
+use crate::{
+    classic_mceliece, kyber,
+    ml_kem::{self, MlKem1024, MlKem768, Variant},
+    proto::{
+        self,
+        post_quantum_secure_server::{PostQuantumSecure, PostQuantumSecureServer},
+    },
+    xor_assign, CONFIG_SERVICE_PORT, PROTOCOL_VERSION,
+};
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite},
+    net::TcpListener,
+};
+use tonic::{
+    transport::{server::Connected, Server},
+    Request, Response, Status,
+};
+
+/// Name of the Classic McEliece KEM, as sent over the wire.
+pub const CLASSIC_MCELIECE: &str = classic_mceliece::ALGORITHM_NAME;
+/// Name of the Kyber KEM, as sent over the wire.
+pub const KYBER: &str = kyber::ALGORITHM_NAME;
+/// Name of the ML-KEM-768 KEM, as sent over the wire.
+pub const ML_KEM_768: &str = MlKem768::ALGORITHM_NAME;
+/// Name of the ML-KEM-1024 KEM, as sent over the wire.
+pub const ML_KEM_1024: &str = MlKem1024::ALGORITHM_NAME;
+
+/// Ways in which the mock relay can misbehave.
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// Respond with an additional copy of the last ciphertext.
+    ExtraCiphertext,
+    /// Leave out the last ciphertext from the response.
+    MissingCiphertext,
+    /// Remove the last byte of the first ciphertext in the response.
+    TruncatedCiphertext,
+    /// Wait this long before responding.
+    Delay(Duration),
+    /// Close the connection as soon as the client has sent anything, without responding.
+    Disconnect,
+    /// Fail every request with this status code.
+    Status(tonic::Code),
+}
+
+/// Mock of the relay config service. Cloning it returns a handle to the same relay.
+#[derive(Debug, Clone)]
+pub struct MockRelay {
+    /// KEMs to use for `PskExchangeV2`, or `None` to behave like a relay without V2 support.
+    v2_algorithms: Option<Vec<&'static str>>,
+    fault: Option<Fault>,
+    psk: Arc<Mutex<Option<[u8; 32]>>>,
+}
+
+impl Default for MockRelay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockRelay {
+    /// Returns a relay that supports both versions of the exchange and that uses Classic McEliece
+    /// and ML-KEM-1024 for `PskExchangeV2`.
+    pub fn new() -> Self {
+        MockRelay {
+            v2_algorithms: Some(vec![CLASSIC_MCELIECE, ML_KEM_1024]),
+            fault: None,
+            psk: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns a relay that only supports `PskExchangeV1`.
+    pub fn v1_only() -> Self {
+        MockRelay {
+            v2_algorithms: None,
+            ..Self::new()
+        }
+    }
+
+    /// Use these KEMs for `PskExchangeV2`, in this order. Duplicates are kept, but every KEM must
+    /// have been offered by the client.
+    pub fn with_v2_algorithms(mut self, algorithms: Vec<&'static str>) -> Self {
+        self.v2_algorithms = Some(algorithms);
+        self
+    }
+
+    /// Inject `fault` into every exchange.
+    pub fn with_fault(mut self, fault: Fault) -> Self {
+        self.fault = Some(fault);
+        self
+    }
+
+    /// Returns the PSK derived by the relay in the most recent exchange, if any.
+    pub fn psk(&self) -> Option<[u8; 32]> {
+        *self.psk.lock().unwrap()
+    }
+
+    /// Serves the config service on `addr`, at the port used by real relays. The relay stops
+    /// when the returned handle is dropped.
+    pub async fn spawn(self, addr: IpAddr) -> io::Result<MockRelayHandle> {
+        let listener = TcpListener::bind(SocketAddr::new(addr, CONFIG_SERVICE_PORT)).await?;
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(self.clone().serve_connection(stream));
+                    }
+                    Err(error) => {
+                        log::error!("Mock relay failed to accept connection: {error}");
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(MockRelayHandle { task })
+    }
+
+    /// Serves the config service on a single connection, such as one half of a
+    /// [`tokio::io::duplex`] stream.
+    pub async fn serve_connection<IO>(self, mut io: IO)
+    where
+        IO: AsyncRead + AsyncWrite + Connected + Unpin + Send + 'static,
+    {
+        if let Some(Fault::Disconnect) = self.fault {
+            let _ = io.read(&mut [0u8; 1024]).await;
+            return;
+        }
+
+        let result = Server::builder()
+            .add_service(PostQuantumSecureServer::new(self))
+            .serve_with_incoming(tokio_stream::once(Ok::<_, io::Error>(io)))
+            .await;
+        if let Err(error) = result {
+            log::error!("Mock relay connection failed: {error}");
+        }
+    }
+
+    /// Applies faults that do not depend on the response.
+    async fn before_response(&self) -> Result<(), Status> {
+        match &self.fault {
+            Some(Fault::Delay(delay)) => tokio::time::sleep(*delay).await,
+            Some(Fault::Status(code)) => return Err(Status::new(*code, "injected fault")),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Applies faults that tamper with the ciphertexts in the response.
+    fn tamper<T: Clone>(&self, ciphertexts: &mut Vec<T>, truncate: impl FnOnce(&mut T)) {
+        match self.fault {
+            Some(Fault::ExtraCiphertext) => {
+                if let Some(last) = ciphertexts.last().cloned() {
+                    ciphertexts.push(last);
+                }
+            }
+            Some(Fault::MissingCiphertext) => {
+                ciphertexts.pop();
+            }
+            Some(Fault::TruncatedCiphertext) => {
+                if let Some(first) = ciphertexts.first_mut() {
+                    truncate(first);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn encapsulate(algorithm: &str, key_data: &[u8]) -> Result<(Vec<u8>, [u8; 32]), Status> {
+        let mut rng = rand::thread_rng();
+        match algorithm {
+            CLASSIC_MCELIECE => Ok(classic_mceliece::encapsulate(key_data, &mut rng)),
+            KYBER => Ok(kyber::encapsulate(key_data, &mut rng)),
+            ML_KEM_768 => Ok(ml_kem::encapsulate::<MlKem768>(key_data, &mut rng)),
+            ML_KEM_1024 => Ok(ml_kem::encapsulate::<MlKem1024>(key_data, &mut rng)),
+            _ => Err(Status::invalid_argument(format!(
+                "unsupported KEM: {algorithm}"
+            ))),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl PostQuantumSecure for MockRelay {
+    async fn psk_exchange_v1(
+        &self,
+        request: Request<proto::PskRequestV1>,
+    ) -> Result<Response<proto::PskResponseV1>, Status> {
+        self.before_response().await?;
+
+        let mut psk = [0u8; 32];
+        let mut ciphertexts = vec![];
+        for pubkey in &request.into_inner().kem_pubkeys {
+            let (ciphertext, secret) = Self::encapsulate(&pubkey.algorithm_name, &pubkey.key_data)?;
+            xor_assign(&mut psk, &secret);
+            ciphertexts.push(ciphertext);
+        }
+        *self.psk.lock().unwrap() = Some(psk);
+
+        self.tamper(&mut ciphertexts, |ciphertext| {
+            ciphertext.pop();
+        });
+        Ok(Response::new(proto::PskResponseV1 { ciphertexts }))
+    }
+
+    async fn psk_exchange_v2(
+        &self,
+        request: Request<proto::PskRequestV2>,
+    ) -> Result<Response<proto::PskResponseV2>, Status> {
+        let Some(algorithms) = &self.v2_algorithms else {
+            return Err(Status::unimplemented("PskExchangeV2"));
+        };
+        self.before_response().await?;
+
+        let request = request.into_inner();
+        if request.protocol_version != PROTOCOL_VERSION {
+            return Err(Status::invalid_argument("unsupported protocol version"));
+        }
+
+        let mut psk = [0u8; 32];
+        let mut ciphertexts = vec![];
+        for &algorithm in algorithms {
+            let pubkey = request
+                .kem_pubkeys
+                .iter()
+                .find(|pubkey| pubkey.algorithm_name == algorithm)
+                .ok_or_else(|| Status::invalid_argument(format!("missing {algorithm} key")))?;
+            let (ciphertext, secret) = Self::encapsulate(algorithm, &pubkey.key_data)?;
+            xor_assign(&mut psk, &secret);
+            ciphertexts.push(proto::KemCiphertextV2 {
+                algorithm_name: algorithm.to_owned(),
+                ciphertext,
+            });
+        }
+        *self.psk.lock().unwrap() = Some(psk);
+
+        self.tamper(&mut ciphertexts, |ciphertext| {
+            ciphertext.ciphertext.pop();
+        });
+        Ok(Response::new(proto::PskResponseV2 { ciphertexts }))
+    }
+}
+
+/// Handle to a relay started by [`MockRelay::spawn`]. Stops the relay when dropped.
+pub struct MockRelayHandle {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for MockRelayHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
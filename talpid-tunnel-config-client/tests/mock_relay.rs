@@ -0,0 +1,116 @@
+//! Runs the PSK exchange over TCP against a mock relay. Every test listens on its own loopback
+//! address, since the config service port is fixed. That requires all of 127.0.0.0/8 to be
+//! routed to the loopback interface, which is only the case on Linux.
+#![cfg(all(target_os = "linux", feature = "mock-relay"))]
+
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+use talpid_tunnel_config_client::{
+    mock_relay::{Fault, MockRelay, MockRelayHandle},
+    push_pq_key, Error,
+};
+use talpid_types::net::wireguard::{PresharedKey, PrivateKey};
+
+async fn spawn(relay: &MockRelay, last_octet: u8) -> (IpAddr, MockRelayHandle) {
+    let addr = IpAddr::V4(Ipv4Addr::new(127, 0, 13, last_octet));
+    let handle = relay.clone().spawn(addr).await.unwrap();
+    (addr, handle)
+}
+
+async fn exchange(addr: IpAddr) -> Result<PresharedKey, Error> {
+    push_pq_key(
+        addr,
+        PrivateKey::new_from_random().public_key(),
+        PrivateKey::new_from_random().public_key(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_exchange() {
+    let relay = MockRelay::new();
+    let (addr, _handle) = spawn(&relay, 1).await;
+    let psk = exchange(addr).await.unwrap();
+    assert_eq!(psk.as_bytes(), &relay.psk().unwrap());
+}
+
+#[tokio::test]
+async fn test_exchange_v1() {
+    let relay = MockRelay::v1_only();
+    let (addr, _handle) = spawn(&relay, 2).await;
+    let psk = exchange(addr).await.unwrap();
+    assert_eq!(psk.as_bytes(), &relay.psk().unwrap());
+}
+
+#[tokio::test]
+async fn test_truncated_ciphertext() {
+    let relay = MockRelay::v1_only().with_fault(Fault::TruncatedCiphertext);
+    let (addr, _handle) = spawn(&relay, 3).await;
+    assert!(matches!(
+        exchange(addr).await,
+        Err(Error::InvalidCiphertextLength { actual, expected, .. }) if actual + 1 == expected
+    ));
+}
+
+#[tokio::test]
+async fn test_truncated_ciphertext_v2() {
+    let relay = MockRelay::new().with_fault(Fault::TruncatedCiphertext);
+    let (addr, _handle) = spawn(&relay, 4).await;
+    assert!(matches!(
+        exchange(addr).await,
+        Err(Error::InvalidCiphertextLength { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_extra_ciphertext() {
+    let relay = MockRelay::v1_only().with_fault(Fault::ExtraCiphertext);
+    let (addr, _handle) = spawn(&relay, 5).await;
+    assert!(matches!(
+        exchange(addr).await,
+        Err(Error::InvalidCiphertextCount { actual: 3 })
+    ));
+}
+
+#[tokio::test]
+async fn test_missing_ciphertext() {
+    let relay = MockRelay::v1_only().with_fault(Fault::MissingCiphertext);
+    let (addr, _handle) = spawn(&relay, 6).await;
+    assert!(matches!(
+        exchange(addr).await,
+        Err(Error::InvalidCiphertextCount { actual: 1 })
+    ));
+}
+
+#[tokio::test]
+async fn test_error_status() {
+    let relay = MockRelay::new().with_fault(Fault::Status(tonic::Code::Unavailable));
+    let (addr, _handle) = spawn(&relay, 7).await;
+    assert!(matches!(
+        exchange(addr).await,
+        Err(Error::GrpcError(status)) if status.code() == tonic::Code::Unavailable
+    ));
+}
+
+#[tokio::test]
+async fn test_disconnect() {
+    let relay = MockRelay::new().with_fault(Fault::Disconnect);
+    let (addr, _handle) = spawn(&relay, 8).await;
+    assert!(matches!(
+        exchange(addr).await,
+        Err(Error::GrpcError(_) | Error::GrpcConnectError(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_delay() {
+    let relay = MockRelay::new().with_fault(Fault::Delay(Duration::from_secs(60)));
+    let (addr, _handle) = spawn(&relay, 9).await;
+    let result = tokio::time::timeout(Duration::from_secs(2), exchange(addr)).await;
+    assert!(
+        result.is_err(),
+        "exchange should not finish before the relay responds"
+    );
+}
@@ -2,6 +2,7 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     path::Path,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 #[cfg(windows)]
@@ -11,7 +12,10 @@ pub mod network_interface;
 pub mod tun_provider;
 use futures::{channel::oneshot, future::BoxFuture};
 use talpid_routing::RouteManagerHandle;
-use talpid_types::net::AllowedTunnelTraffic;
+use talpid_types::{
+    net::{openvpn, AllowedTunnelTraffic},
+    tunnel::TunnelFailure,
+};
 use tun_provider::TunProvider;
 
 /// Size of IPv4 header in bytes
@@ -46,6 +50,9 @@ where
     pub retry_attempt: u32,
     /// Route manager handle.
     pub route_manager: RouteManagerHandle,
+    /// Receiver of changes to the interval at which a new PSK is negotiated while the tunnel is
+    /// up. Only used by WireGuard tunnels.
+    pub psk_rekey_interval_rx: tokio::sync::watch::Receiver<Option<Duration>>,
 }
 
 /// Information about a VPN tunnel.
@@ -59,6 +66,8 @@ pub struct TunnelMetadata {
     pub ipv4_gateway: Ipv4Addr,
     /// The IP to the IPv6 default gateway on the tunnel interface.
     pub ipv6_gateway: Option<Ipv6Addr>,
+    /// Details about the OpenVPN session. This is `None` for other tunnel types.
+    pub openvpn_session: Option<openvpn::SessionInfo>,
 }
 
 /// Possible events from the VPN tunnel and the child process managing it.
@@ -72,4 +81,9 @@ pub enum TunnelEvent {
     Up(TunnelMetadata),
     /// Sent when the tunnel goes down, but before destroying the tunnel device.
     Down,
+    /// Sent before `Down` when the tunnel is closing because of a failure.
+    Failure(TunnelFailure),
+    /// Sent when a new PSK could not be negotiated for an established tunnel. The tunnel keeps
+    /// using its current PSK.
+    PskRenegotiationFailed,
 }
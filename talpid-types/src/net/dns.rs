@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, net::IpAddr, path::PathBuf, str::FromStr};
+
+/// The role of a user-supplied domain list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DomainListKind {
+    /// Queries for listed domains, and their subdomains, are refused.
+    Blocklist,
+    /// Queries for listed domains, and their subdomains, are never refused.
+    Allowlist,
+}
+
+impl fmt::Display for DomainListKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainListKind::Blocklist => f.write_str("blocklist"),
+            DomainListKind::Allowlist => f.write_str("allowlist"),
+        }
+    }
+}
+
+/// Statistics for a single domain list loaded by the local resolver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainListStats {
+    pub path: PathBuf,
+    pub kind: DomainListKind,
+    /// Number of domains that were successfully parsed from the list.
+    pub entries: u64,
+    /// Number of queries that matched an entry in this list.
+    pub hits: u64,
+    /// Set if the list could not be read the last time it was loaded.
+    pub error: Option<String>,
+}
+
+/// Statistics for all user-supplied domain lists loaded by the local resolver.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlocklistStats {
+    pub lists: Vec<DomainListStats>,
+}
+
+impl BlocklistStats {
+    /// Total number of queries refused by any blocklist.
+    pub fn blocked_queries(&self) -> u64 {
+        self.total_hits(DomainListKind::Blocklist)
+    }
+
+    /// Total number of queries let through because of an allowlist.
+    pub fn allowed_queries(&self) -> u64 {
+        self.total_hits(DomainListKind::Allowlist)
+    }
+
+    fn total_hits(&self, kind: DomainListKind) -> u64 {
+        self.lists
+            .iter()
+            .filter(|list| list.kind == kind)
+            .map(|list| list.hits)
+            .sum()
+    }
+}
+
+/// Reported when the system DNS configuration no longer matches what was applied while
+/// connected, for example because another program rewrote it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsConfigurationDrift {
+    /// The DNS manager that applied the configuration.
+    pub manager: DnsManager,
+    /// DNS servers that were applied.
+    pub expected_servers: Vec<IpAddr>,
+    /// DNS servers that were found to be in use.
+    pub actual_servers: Vec<IpAddr>,
+    /// Whether the expected configuration was successfully re-applied.
+    pub restored: bool,
+}
+
+/// The system component used to configure DNS on Linux.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsManager {
+    /// Use the first DNS manager that is detected to be in use.
+    #[default]
+    Auto,
+    SystemdResolved,
+    NetworkManager,
+    Resolvconf,
+    /// Edit /etc/resolv.conf directly.
+    StaticFile,
+    /// Point /etc/resolv.conf to a resolver run by the daemon, which forwards queries to the
+    /// tunnel DNS servers.
+    LocalResolver,
+}
+
+impl fmt::Display for DnsManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let manager = match self {
+            DnsManager::Auto => "auto",
+            DnsManager::SystemdResolved => "systemd-resolved",
+            DnsManager::NetworkManager => "network-manager",
+            DnsManager::Resolvconf => "resolvconf",
+            DnsManager::StaticFile => "static-file",
+            DnsManager::LocalResolver => "local-resolver",
+        };
+        f.write_str(manager)
+    }
+}
+
+impl FromStr for DnsManager {
+    type Err = DnsManagerParseError;
+
+    fn from_str(s: &str) -> Result<DnsManager, Self::Err> {
+        match s {
+            "auto" => Ok(DnsManager::Auto),
+            "systemd-resolved" | "systemd" => Ok(DnsManager::SystemdResolved),
+            "network-manager" => Ok(DnsManager::NetworkManager),
+            "resolvconf" => Ok(DnsManager::Resolvconf),
+            "static-file" => Ok(DnsManager::StaticFile),
+            "local-resolver" => Ok(DnsManager::LocalResolver),
+            _ => Err(DnsManagerParseError),
+        }
+    }
+}
+
+/// Returned when `DnsManager::from_str` fails to convert a string into a [`DnsManager`] object.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("Not a valid DNS manager")]
+pub struct DnsManagerParseError;
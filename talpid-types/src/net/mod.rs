@@ -12,6 +12,7 @@ use std::{
 
 use self::proxy::{CustomProxy, Socks5Local};
 
+pub mod dns;
 pub mod obfuscation;
 pub mod openvpn;
 pub mod proxy;
@@ -40,6 +41,8 @@ impl TunnelParameters {
                 obfuscation: None,
                 entry_endpoint: None,
                 tunnel_interface: None,
+                openvpn_session: None,
+                reconnect_reason: None,
             },
             TunnelParameters::Wireguard(params) => TunnelEndpoint {
                 tunnel_type: TunnelType::Wireguard,
@@ -55,6 +58,8 @@ impl TunnelParameters {
                     .get_exit_endpoint()
                     .map(|_| params.connection.get_endpoint()),
                 tunnel_interface: None,
+                openvpn_session: None,
+                reconnect_reason: None,
             },
         }
     }
@@ -81,6 +86,11 @@ impl TunnelParameters {
                 address: *endpoint,
                 protocol: TransportProtocol::Tcp,
             },
+            ObfuscatorConfig::Shadowsocks { endpoint, .. }
+            | ObfuscatorConfig::Quic { endpoint, .. } => Endpoint {
+                address: *endpoint,
+                protocol: TransportProtocol::Udp,
+            },
         }
     }
 
@@ -183,6 +193,10 @@ pub struct TunnelEndpoint {
     pub entry_endpoint: Option<Endpoint>,
     #[cfg_attr(target_os = "android", jnix(skip))]
     pub tunnel_interface: Option<String>,
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub openvpn_session: Option<openvpn::SessionInfo>,
+    #[cfg_attr(target_os = "android", jnix(skip))]
+    pub reconnect_reason: Option<crate::tunnel::TunnelFailure>,
 }
 
 impl fmt::Display for TunnelEndpoint {
@@ -218,12 +232,18 @@ impl fmt::Display for TunnelEndpoint {
 pub enum ObfuscationType {
     #[serde(rename = "udp2tcp")]
     Udp2Tcp,
+    #[serde(rename = "shadowsocks")]
+    Shadowsocks,
+    #[serde(rename = "quic")]
+    Quic,
 }
 
 impl fmt::Display for ObfuscationType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
             ObfuscationType::Udp2Tcp => "Udp2Tcp".fmt(f),
+            ObfuscationType::Shadowsocks => "Shadowsocks".fmt(f),
+            ObfuscationType::Quic => "QUIC".fmt(f),
         }
     }
 }
@@ -247,6 +267,20 @@ impl From<&ObfuscatorConfig> for ObfuscationEndpoint {
                 },
                 ObfuscationType::Udp2Tcp,
             ),
+            ObfuscatorConfig::Shadowsocks { endpoint, .. } => (
+                Endpoint {
+                    address: *endpoint,
+                    protocol: TransportProtocol::Udp,
+                },
+                ObfuscationType::Shadowsocks,
+            ),
+            ObfuscatorConfig::Quic { endpoint, .. } => (
+                Endpoint {
+                    address: *endpoint,
+                    protocol: TransportProtocol::Udp,
+                },
+                ObfuscationType::Quic,
+            ),
         };
 
         ObfuscationEndpoint {
@@ -1,7 +1,42 @@
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+use std::{fmt, net::SocketAddr};
 
 #[derive(Clone, Eq, PartialEq, Deserialize, Serialize, Debug)]
 pub enum ObfuscatorConfig {
-    Udp2Tcp { endpoint: SocketAddr },
+    Udp2Tcp {
+        endpoint: SocketAddr,
+    },
+    Shadowsocks {
+        endpoint: SocketAddr,
+        /// WireGuard endpoint that the Shadowsocks server should forward traffic to. `None` means
+        /// the WireGuard server on the same host as the Shadowsocks server.
+        #[serde(default)]
+        wireguard_endpoint: Option<SocketAddr>,
+        /// Credentials for a user-provided Shadowsocks server. `None` means that the credentials
+        /// of the Mullvad relays are used.
+        #[serde(default)]
+        credentials: Option<ShadowsocksCredentials>,
+    },
+    /// A QUIC connection that carries the WireGuard traffic in datagrams, which looks like HTTP/3.
+    Quic {
+        endpoint: SocketAddr,
+        /// Name that the certificate of the server must be valid for.
+        hostname: String,
+    },
+}
+
+#[derive(Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ShadowsocksCredentials {
+    pub password: String,
+    /// Name of the cipher. One of [`SHADOWSOCKS_CIPHERS`](super::proxy::SHADOWSOCKS_CIPHERS).
+    pub cipher: String,
+}
+
+impl fmt::Debug for ShadowsocksCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShadowsocksCredentials")
+            .field("password", &"<redacted>")
+            .field("cipher", &self.cipher)
+            .finish()
+    }
 }
@@ -1,5 +1,6 @@
 use crate::net::{Endpoint, GenericTunnelOptions};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 use super::proxy::CustomProxy;
 
@@ -21,6 +22,10 @@ pub struct ConnectionConfig {
     pub endpoint: Endpoint,
     pub username: String,
     pub password: String,
+    /// Configuration of a server that is not a Mullvad relay. `None` means that the CA and
+    /// options of the Mullvad relays are used.
+    #[serde(default)]
+    pub custom_server: Option<CustomServerConfig>,
 }
 
 impl ConnectionConfig {
@@ -29,10 +34,139 @@ impl ConnectionConfig {
             endpoint,
             username,
             password,
+            custom_server: None,
         }
     }
 }
 
+/// Data channel ciphers that a custom server may use.
+pub const ALLOWED_DATA_CIPHERS: &[&str] = &[
+    "AES-128-GCM",
+    "AES-192-GCM",
+    "AES-256-GCM",
+    "CHACHA20-POLY1305",
+    "AES-128-CBC",
+    "AES-192-CBC",
+    "AES-256-CBC",
+];
+
+/// Digests that a custom server may use to authenticate packets.
+pub const ALLOWED_AUTH_DIGESTS: &[&str] = &["SHA1", "SHA256", "SHA384", "SHA512"];
+
+/// Compression algorithms that a custom server may use. The stubs only enable the framing used
+/// for compression.
+pub const ALLOWED_COMPRESSION: &[&str] = &["stub", "stub-v2", "lz4", "lz4-v2", "lzo"];
+
+/// Configuration of an OpenVPN server that is not a Mullvad relay, such as one imported from an
+/// `.ovpn` file. Certificates and keys are PEM-encoded.
+#[derive(Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub struct CustomServerConfig {
+    /// CA certificate that the certificate of the server must be signed by
+    pub ca: String,
+    /// Client certificate, if the server requires one
+    pub cert: Option<String>,
+    /// Private key belonging to the client certificate
+    pub key: Option<String>,
+    /// Static key used to encrypt and authenticate the control channel
+    pub tls_crypt: Option<String>,
+    /// Static key used to authenticate the control channel
+    #[serde(default)]
+    pub tls_auth: Option<String>,
+    /// Direction in which `tls_auth` is used, 0 or 1
+    #[serde(default)]
+    pub key_direction: Option<u8>,
+    /// Data channel cipher, e.g. `AES-256-GCM`. `None` means that the default ciphers are used
+    pub cipher: Option<String>,
+    /// Data channel ciphers that may be negotiated. Empty means that OpenVPN's defaults are used
+    #[serde(default)]
+    pub data_ciphers: Vec<String>,
+    /// Digest used to authenticate packets, e.g. `SHA256`
+    #[serde(default)]
+    pub auth: Option<String>,
+    /// Name that the certificate of the server must have
+    #[serde(default)]
+    pub verify_x509_name: Option<VerifyX509Name>,
+    /// Compression algorithm, one of [`ALLOWED_COMPRESSION`]
+    #[serde(default)]
+    pub compress: Option<String>,
+    /// Whether the server requires authentication using a username and password
+    pub auth_user_pass: bool,
+}
+
+/// Name that the certificate of the server must have. See the `--verify-x509-name` OpenVPN
+/// documentation for details.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub struct VerifyX509Name {
+    pub name: String,
+    pub name_type: X509NameType,
+}
+
+/// Which part of the certificate [`VerifyX509Name::name`] is compared to.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum X509NameType {
+    /// The complete subject
+    Subject,
+    /// The common name
+    Name,
+    /// A prefix of the common name
+    NamePrefix,
+}
+
+impl X509NameType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            X509NameType::Subject => "subject",
+            X509NameType::Name => "name",
+            X509NameType::NamePrefix => "name-prefix",
+        }
+    }
+}
+
+impl std::str::FromStr for X509NameType {
+    type Err = ();
+
+    fn from_str(name_type: &str) -> Result<Self, Self::Err> {
+        match name_type {
+            "subject" => Ok(X509NameType::Subject),
+            "name" => Ok(X509NameType::Name),
+            "name-prefix" => Ok(X509NameType::NamePrefix),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Debug for CustomServerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redact = |secret: &Option<String>| secret.as_ref().map(|_| "<redacted>");
+        f.debug_struct("CustomServerConfig")
+            .field("ca", &self.ca)
+            .field("cert", &self.cert)
+            .field("key", &redact(&self.key))
+            .field("tls_crypt", &redact(&self.tls_crypt))
+            .field("tls_auth", &redact(&self.tls_auth))
+            .field("key_direction", &self.key_direction)
+            .field("cipher", &self.cipher)
+            .field("data_ciphers", &self.data_ciphers)
+            .field("auth", &self.auth)
+            .field("verify_x509_name", &self.verify_x509_name)
+            .field("compress", &self.compress)
+            .field("auth_user_pass", &self.auth_user_pass)
+            .finish()
+    }
+}
+
+/// Details about an established OpenVPN session, as reported by OpenVPN.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub struct SessionInfo {
+    /// Data channel cipher negotiated with the server
+    pub cipher: Option<String>,
+    /// Options pushed by the server that OpenVPN does not handle itself, such as DHCP options
+    pub pushed_options: Vec<String>,
+    /// Subject of the server certificate
+    pub server_certificate: Option<String>,
+}
+
 /// `TunnelOptions` contains options for an OpenVPN tunnel that should be applied
 /// irrespective of the relay parameters - i.e. have nothing to do with the particular
 /// OpenVPN server, but do affect the connection.
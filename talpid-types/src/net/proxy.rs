@@ -9,6 +9,10 @@ pub enum Error {
     /// Validation of SOCKS5 username or password failed.
     #[error("Invalid SOCKS5 authentication credentials: {0}")]
     InvalidSocksAuthValues(&'static str),
+
+    /// Validation of HTTP proxy username failed.
+    #[error("Invalid HTTP proxy authentication credentials: {0}")]
+    InvalidHttpAuthValues(&'static str),
 }
 
 /// Types of bridges that can be used to proxy a connection to a tunnel
@@ -44,6 +48,7 @@ pub enum CustomProxy {
     Shadowsocks(Shadowsocks),
     Socks5Local(Socks5Local),
     Socks5Remote(Socks5Remote),
+    HttpConnect(HttpConnect),
 }
 
 impl CustomProxy {
@@ -57,6 +62,10 @@ impl CustomProxy {
                 endpoint: Endpoint::from_socket_address(settings.endpoint, TransportProtocol::Tcp),
                 proxy_type: ProxyType::Custom,
             },
+            CustomProxy::HttpConnect(settings) => ProxyEndpoint {
+                endpoint: Endpoint::from_socket_address(settings.endpoint, TransportProtocol::Tcp),
+                proxy_type: ProxyType::Custom,
+            },
             CustomProxy::Shadowsocks(settings) => ProxyEndpoint {
                 endpoint: Endpoint::from_socket_address(settings.endpoint, TransportProtocol::Tcp),
                 proxy_type: ProxyType::Shadowsocks,
@@ -77,6 +86,12 @@ impl From<Socks5Local> for CustomProxy {
     }
 }
 
+impl From<HttpConnect> for CustomProxy {
+    fn from(value: HttpConnect) -> Self {
+        CustomProxy::HttpConnect(value)
+    }
+}
+
 impl From<Shadowsocks> for CustomProxy {
     fn from(value: Shadowsocks) -> Self {
         CustomProxy::Shadowsocks(value)
@@ -107,6 +122,60 @@ pub struct Socks5Remote {
     pub auth: Option<SocksAuth>,
 }
 
+/// An HTTP proxy which supports the `CONNECT` method. The connection to the proxy itself is
+/// unencrypted, so HTTPS proxies are not supported.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct HttpConnect {
+    pub endpoint: SocketAddr,
+    pub auth: Option<HttpAuth>,
+}
+
+/// Credentials for HTTP basic authentication against a proxy, according to
+/// RFC 7617: <https://datatracker.ietf.org/doc/html/rfc7617>.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct HttpAuth {
+    username: String,
+    password: String,
+}
+
+impl HttpAuth {
+    /// Validate an HTTP basic authentication username and password.
+    ///
+    /// # Examples
+    ///
+    /// The username must not be empty, and must not contain a colon.
+    ///
+    /// ```
+    /// use talpid_types::net::proxy::HttpAuth;
+    ///
+    /// assert!(HttpAuth::new("FooBar".to_string(), "hunter2".to_string()).is_ok());
+    /// assert!(HttpAuth::new("".to_string(), "hunter2".to_string()).is_err());
+    /// assert!(HttpAuth::new("Foo:Bar".to_string(), "hunter2".to_string()).is_err());
+    /// ```
+    pub fn new(username: String, password: String) -> Result<Self, Error> {
+        if username.is_empty() {
+            return Err(Error::InvalidHttpAuthValues("Username must not be empty"));
+        }
+        if username.contains(':') {
+            return Err(Error::InvalidHttpAuthValues(
+                "Username must not contain a colon",
+            ));
+        }
+
+        Ok(HttpAuth { username, password })
+    }
+
+    /// Read the username.
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Read the password.
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+}
+
 /// A valid SOCKS5 username/password authentication according to
 /// RFC 1929: <https://datatracker.ietf.org/doc/html/rfc1929>.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -235,6 +304,25 @@ impl Socks5Remote {
     }
 }
 
+impl HttpConnect {
+    pub fn new<I: Into<SocketAddr>>(endpoint: I) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            auth: None,
+        }
+    }
+
+    pub fn new_with_authentication<I: Into<SocketAddr>>(
+        endpoint: I,
+        authentication: HttpAuth,
+    ) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            auth: Some(authentication),
+        }
+    }
+}
+
 /// List of ciphers usable by a Shadowsocks proxy.
 /// Cf. [`ShadowsocksProxySettings::cipher`].
 pub const SHADOWSOCKS_CIPHERS: [&str; 19] = [
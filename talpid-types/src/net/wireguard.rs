@@ -5,6 +5,7 @@ use std::{
     cmp, fmt,
     hash::{Hash, Hasher},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
 };
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -45,6 +46,67 @@ impl ConnectionConfig {
             protocol: TransportProtocol::Udp,
         })
     }
+
+    /// Formats the configuration as `wg-quick` configuration files. A multihop configuration
+    /// results in two files: the first one for the entry relay, and the second one for the exit
+    /// relay, which is reached through the tunnel to the entry relay. Preshared keys are left out
+    /// unless `include_psk` is set.
+    pub fn to_wg_quick(&self, mtu: Option<u16>, include_psk: bool) -> Vec<String> {
+        let dns = IpAddr::from(self.ipv4_gateway);
+        match &self.exit_peer {
+            None => vec![self.wg_quick_config(&self.peer, mtu, Some(dns), include_psk)],
+            Some(exit_peer) => vec![
+                self.wg_quick_config(&self.peer, mtu, None, include_psk),
+                self.wg_quick_config(exit_peer, mtu, Some(dns), include_psk),
+            ],
+        }
+    }
+
+    fn wg_quick_config(
+        &self,
+        peer: &PeerConfig,
+        mtu: Option<u16>,
+        dns: Option<IpAddr>,
+        include_psk: bool,
+    ) -> String {
+        let join = |items: Vec<String>| items.join(", ");
+
+        let mut lines = vec![
+            "[Interface]".to_owned(),
+            format!("PrivateKey = {}", self.tunnel.private_key.to_base64()),
+            format!(
+                "Address = {}",
+                join(
+                    self.tunnel
+                        .addresses
+                        .iter()
+                        .map(|&address| IpNetwork::from(address).to_string())
+                        .collect()
+                )
+            ),
+        ];
+        if let Some(dns) = dns {
+            lines.push(format!("DNS = {dns}"));
+        }
+        if let Some(mtu) = mtu {
+            lines.push(format!("MTU = {mtu}"));
+        }
+
+        lines.push(String::new());
+        lines.push("[Peer]".to_owned());
+        lines.push(format!("PublicKey = {}", peer.public_key.to_base64()));
+        if let Some(psk) = peer.psk.as_ref().filter(|_| include_psk) {
+            lines.push(format!("PresharedKey = {}", base64::encode(psk.as_bytes())));
+        }
+        lines.push(format!(
+            "AllowedIPs = {}",
+            join(peer.allowed_ips.iter().map(ToString::to_string).collect())
+        ));
+        lines.push(format!("Endpoint = {}", peer.endpoint));
+        lines.push(String::new());
+
+        lines.join("\n")
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Deserialize, Serialize, Debug, Hash)]
@@ -76,6 +138,30 @@ pub struct TunnelOptions {
     pub mtu: Option<u16>,
     /// Perform PQ-safe PSK exchange when connecting
     pub quantum_resistant: bool,
+    /// Interval in seconds between keepalive packets sent to each peer. Disabled if `None`
+    pub persistent_keepalive: Option<u16>,
+    /// Parameters used to decide whether the tunnel is working
+    pub connectivity_check: ConnectivityCheckOptions,
+    /// How often to negotiate a new PSK with the relay while connected. Only used for
+    /// quantum-resistant tunnels. Disabled if `None`
+    pub psk_rekey_interval: Option<Duration>,
+}
+
+/// Advanced options for the connectivity check that runs for the lifetime of a WireGuard tunnel.
+/// Any value that is `None` uses the default for that parameter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectivityCheckOptions {
+    /// Time to wait for incoming traffic after sending traffic, before starting to ping
+    pub rx_timeout: Option<Duration>,
+    /// Time to wait for incoming traffic after the first ping, before the tunnel is considered
+    /// to be broken
+    pub ping_timeout: Option<Duration>,
+    /// Initial time to wait for incoming traffic when establishing the tunnel. This is doubled
+    /// on each retry, but never exceeds the ping timeout.
+    pub establish_timeout: Option<Duration>,
+    /// Address inside the tunnel to ping instead of the IPv4 gateway
+    pub ping_target: Option<Ipv4Addr>,
 }
 
 /// Wireguard x25519 private key
@@ -255,6 +341,10 @@ impl PresharedKey {
     pub fn as_bytes(&self) -> &[u8; 32] {
         &self.0
     }
+
+    pub fn from_base64(key: &str) -> Result<Self, InvalidKey> {
+        key_from_base64(key)
+    }
 }
 
 impl From<Box<[u8; 32]>> for PresharedKey {
@@ -263,6 +353,30 @@ impl From<Box<[u8; 32]>> for PresharedKey {
     }
 }
 
+impl From<[u8; 32]> for PresharedKey {
+    fn from(key: [u8; 32]) -> PresharedKey {
+        PresharedKey(Box::new(key))
+    }
+}
+
+impl Serialize for PresharedKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_key(self.as_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PresharedKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_key(deserializer)
+    }
+}
+
 impl fmt::Debug for PresharedKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", &base64::encode(self.as_bytes()))
@@ -296,3 +410,80 @@ fn key_from_base64<K: From<[u8; 32]>>(key: &str) -> Result<K, InvalidKey> {
     key.copy_from_slice(&bytes);
     Ok(From::from(key))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn peer(endpoint: &str, allowed_ips: &[&str]) -> PeerConfig {
+        PeerConfig {
+            public_key: PrivateKey::from([1; 32]).public_key(),
+            allowed_ips: allowed_ips.iter().map(|ip| ip.parse().unwrap()).collect(),
+            endpoint: endpoint.parse().unwrap(),
+            psk: Some(PresharedKey::from(Box::new([2; 32]))),
+        }
+    }
+
+    fn config(exit_peer: Option<PeerConfig>) -> ConnectionConfig {
+        ConnectionConfig {
+            tunnel: TunnelConfig {
+                private_key: PrivateKey::from([3; 32]),
+                addresses: vec!["10.64.0.2".parse().unwrap(), "fc00::2".parse().unwrap()],
+            },
+            peer: peer("1.2.3.4:51820", &["0.0.0.0/0", "::/0"]),
+            exit_peer,
+            ipv4_gateway: "10.64.0.1".parse().unwrap(),
+            ipv6_gateway: None,
+            #[cfg(target_os = "linux")]
+            fwmark: None,
+        }
+    }
+
+    #[test]
+    fn test_wg_quick_singlehop() {
+        let configs = config(None).to_wg_quick(Some(1280), false);
+        let public_key = PrivateKey::from([1; 32]).public_key();
+
+        assert_eq!(
+            configs,
+            vec![format!(
+                "[Interface]
+PrivateKey = {}
+Address = 10.64.0.2/32, fc00::2/128
+DNS = 10.64.0.1
+MTU = 1280
+
+[Peer]
+PublicKey = {public_key}
+AllowedIPs = 0.0.0.0/0, ::/0
+Endpoint = 1.2.3.4:51820
+",
+                PrivateKey::from([3; 32]).to_base64(),
+            )]
+        );
+    }
+
+    #[test]
+    fn test_wg_quick_multihop() {
+        let mut config = config(Some(peer("5.6.7.8:51820", &["0.0.0.0/0"])));
+        config.peer.allowed_ips = vec!["5.6.7.8/32".parse().unwrap()];
+
+        let configs = config.to_wg_quick(None, true);
+
+        assert_eq!(configs.len(), 2);
+        assert!(!configs[0].contains("DNS ="));
+        assert!(configs[0].contains("AllowedIPs = 5.6.7.8/32\nEndpoint = 1.2.3.4:51820"));
+        assert!(configs[1].contains("DNS = 10.64.0.1"));
+        assert!(configs[1].contains("Endpoint = 5.6.7.8:51820"));
+        for config in configs {
+            assert!(config.contains(&format!("PresharedKey = {}", base64::encode([2; 32]))));
+        }
+    }
+
+    #[test]
+    fn test_wg_quick_omits_psk() {
+        for config in config(None).to_wg_quick(None, false) {
+            assert!(!config.contains("PresharedKey"));
+        }
+    }
+}
@@ -139,6 +139,21 @@ pub enum ParameterGenerationError {
     CustomTunnelHostResultionError,
 }
 
+/// Failures that cause OpenVPN to close an established tunnel.
+#[derive(thiserror::Error, Debug, Serialize, Clone, Copy, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TunnelFailure {
+    /// The TLS handshake or renegotiation with the server failed
+    #[error("The TLS handshake with the server failed")]
+    TlsError,
+    /// The server stopped responding to pings
+    #[error("The server stopped responding")]
+    ServerUnresponsive,
+    /// The connection to the server was reset
+    #[error("The connection to the server was reset")]
+    ConnectionReset,
+}
+
 /// Application that prevents setting the firewall policy.
 #[cfg(windows)]
 #[derive(Debug, Serialize, Clone, Deserialize)]
@@ -19,9 +19,18 @@ fn main() {
         _ => panic!("Unsupported platform: {target_os}"),
     };
 
-    println!("cargo:rustc-link-lib{link_type}=wg");
+    // wireguard-go is optional on Linux, where boringtun or the kernel module can be used instead
+    let wireguard_go = match target_os.as_str() {
+        "linux" => env::var_os("CARGO_FEATURE_WIREGUARD_GO").is_some(),
+        "macos" | "android" => true,
+        _ => false,
+    };
+
+    if wireguard_go || target_os == "windows" {
+        println!("cargo:rustc-link-lib{link_type}=wg");
+    }
 
-    if let "linux" | "macos" | "android" = target_os.as_str() {
+    if wireguard_go {
         println!("cargo:rustc-cfg=wireguard_go");
     }
 }
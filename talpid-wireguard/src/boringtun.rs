@@ -0,0 +1,646 @@
+//! A userspace WireGuard implementation for Linux, built on top of [`boringtun`]. Unlike
+//! wireguard-go, this does not require anything but a Rust toolchain to build.
+
+use super::{
+    config::Config,
+    logging::{self, clean_up_logging, initialize_logging, LogLevel},
+    stats::{Stats, StatsMap},
+    Tunnel, TunnelError,
+};
+use ::boringtun::{
+    noise::{errors::WireGuardError, Tunn, TunnResult},
+    x25519,
+};
+use ipnetwork::IpNetwork;
+use std::{
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd},
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use talpid_tunnel::tun_provider::{Tun, TunConfig, TunProvider};
+use tokio::{io::unix::AsyncFd, net::UdpSocket, task::JoinHandle};
+
+type Result<T> = std::result::Result<T, TunnelError>;
+
+/// Interval at which the WireGuard timers of every peer are updated.
+const TIMER_INTERVAL: Duration = Duration::from_millis(250);
+/// Size of the buffers used for reading and writing packets. This fits the largest possible IP
+/// packet, the WireGuard overhead, and the packet information header of the tunnel device.
+const BUFFER_SIZE: usize = u16::MAX as usize + 64;
+/// Size of the packet information header that precedes every packet on the tunnel device.
+const PACKET_INFO_LEN: usize = 4;
+/// Tag used for entries in the WireGuard log file.
+const LOG_TAG: &str = "boringtun";
+
+pub struct BoringTun {
+    interface_name: String,
+    device: Arc<Device>,
+    tasks: Vec<JoinHandle<()>>,
+    // holding on to the tunnel device ensures that it is closed when the tunnel is stopped
+    _tunnel_device: Tun,
+}
+
+/// State shared between the tasks that move packets between the tunnel device and the sockets.
+struct Device {
+    tun: AsyncFd<OwnedFd>,
+    socket_v4: Arc<UdpSocket>,
+    socket_v6: Option<Arc<UdpSocket>>,
+    peers: Mutex<Arc<Vec<Peer>>>,
+    log_context: u32,
+}
+
+struct Peer {
+    public_key: [u8; 32],
+    endpoint: SocketAddr,
+    allowed_ips: Vec<IpNetwork>,
+    tunn: Mutex<Tunn>,
+    /// Traffic counted by a previous instance of this peer, before the config was replaced
+    base_stats: Stats,
+}
+
+impl BoringTun {
+    pub fn start_tunnel(
+        runtime: tokio::runtime::Handle,
+        config: &Config,
+        log_path: Option<&Path>,
+        tun_provider: Arc<Mutex<TunProvider>>,
+        routes: impl Iterator<Item = IpNetwork>,
+    ) -> Result<Self> {
+        let _guard = runtime.enter();
+
+        let tunnel_device = Self::get_tunnel(tun_provider, config, routes)?;
+        let interface_name = tunnel_device.interface_name().to_string();
+        crate::unix::set_mtu(&interface_name, config.mtu).map_err(TunnelError::SetMtuError)?;
+
+        let tun_fd =
+            nix::unistd::dup(tunnel_device.as_raw_fd()).map_err(TunnelError::FdDuplicationError)?;
+        // SAFETY: The file descriptor was just duplicated, so nothing else owns it.
+        let tun = AsyncFd::new(unsafe { OwnedFd::from_raw_fd(tun_fd) })
+            .map_err(TunnelError::BoringtunSetupError)?;
+
+        let socket_v4 = Self::create_socket(IpAddr::V4(Ipv4Addr::UNSPECIFIED), config.fwmark)
+            .map_err(TunnelError::BoringtunSetupError)?;
+        let socket_v6 = match Self::create_socket(IpAddr::V6(Ipv6Addr::UNSPECIFIED), config.fwmark)
+        {
+            Ok(socket) => Some(Arc::new(socket)),
+            Err(error) => {
+                log::debug!("Failed to create IPv6 socket for boringtun: {error}");
+                None
+            }
+        };
+
+        let log_context = initialize_logging(log_path).map_err(TunnelError::LoggingError)?;
+
+        let device = Arc::new(Device {
+            tun,
+            socket_v4: Arc::new(socket_v4),
+            socket_v6,
+            peers: Mutex::new(Arc::new(Peer::from_config(config, &[]))),
+            log_context,
+        });
+
+        let mut tasks = vec![
+            tokio::spawn(device.clone().route_outgoing()),
+            tokio::spawn(device.clone().update_timers()),
+            tokio::spawn(device.clone().route_incoming(device.socket_v4.clone())),
+        ];
+        if let Some(socket_v6) = device.socket_v6.clone() {
+            tasks.push(tokio::spawn(device.clone().route_incoming(socket_v6)));
+        }
+
+        Ok(BoringTun {
+            interface_name,
+            device,
+            tasks,
+            _tunnel_device: tunnel_device,
+        })
+    }
+
+    fn get_tunnel(
+        tun_provider: Arc<Mutex<TunProvider>>,
+        config: &Config,
+        routes: impl Iterator<Item = IpNetwork>,
+    ) -> Result<Tun> {
+        let mut dns_servers = vec![IpAddr::V4(config.ipv4_gateway)];
+        dns_servers.extend(config.ipv6_gateway.map(IpAddr::V6));
+
+        let tunnel_config = TunConfig {
+            addresses: config.tunnel.addresses.clone(),
+            dns_servers,
+            routes: routes.collect(),
+            mtu: config.mtu,
+        };
+
+        tun_provider
+            .lock()
+            .unwrap()
+            .get_tun(tunnel_config)
+            .map_err(TunnelError::SetupTunnelDevice)
+    }
+
+    fn create_socket(bind_addr: IpAddr, fwmark: Option<u32>) -> io::Result<UdpSocket> {
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        let socket = Socket::new(
+            Domain::for_address(SocketAddr::new(bind_addr, 0)),
+            Type::DGRAM,
+            Some(Protocol::UDP),
+        )?;
+        if bind_addr.is_ipv6() {
+            socket.set_only_v6(true)?;
+        }
+        if let Some(fwmark) = fwmark {
+            socket.set_mark(fwmark)?;
+        }
+        socket.set_nonblocking(true)?;
+        socket.bind(&SocketAddr::new(bind_addr, 0).into())?;
+        UdpSocket::from_std(socket.into())
+    }
+
+    fn stop_tunnel(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for BoringTun {
+    fn drop(&mut self) {
+        self.stop_tunnel();
+        clean_up_logging(self.device.log_context);
+    }
+}
+
+impl Tunnel for BoringTun {
+    fn get_interface_name(&self) -> String {
+        self.interface_name.clone()
+    }
+
+    fn get_tunnel_stats(&self) -> Result<StatsMap> {
+        Ok(self
+            .device
+            .peers()
+            .iter()
+            .map(|peer| (peer.public_key, peer.stats()))
+            .collect())
+    }
+
+    fn stop(mut self: Box<Self>) -> Result<()> {
+        self.stop_tunnel();
+        Ok(())
+    }
+
+    fn set_config(
+        &self,
+        config: Config,
+    ) -> Pin<Box<dyn Future<Output = std::result::Result<(), TunnelError>> + Send>> {
+        let device = self.device.clone();
+        Box::pin(async move {
+            device.set_config(&config);
+            Ok(())
+        })
+    }
+}
+
+impl Device {
+    fn peers(&self) -> Arc<Vec<Peer>> {
+        self.peers.lock().unwrap().clone()
+    }
+
+    /// Replaces the peers with those in `config`.
+    fn set_config(&self, config: &Config) {
+        let mut peers = self.peers.lock().unwrap();
+        *peers = Arc::new(Peer::from_config(config, &peers));
+    }
+
+    fn log(&self, level: LogLevel, msg: &str) {
+        logging::log(self.log_context, level, LOG_TAG, msg);
+    }
+
+    /// Encrypts packets read from the tunnel device and sends them to the peer that is
+    /// responsible for the destination address.
+    async fn route_outgoing(self: Arc<Self>) {
+        let mut packet_buf = vec![0u8; BUFFER_SIZE];
+        let mut out_buf = vec![0u8; BUFFER_SIZE];
+        loop {
+            let len = match self.read_tun(&mut packet_buf).await {
+                Ok(len) => len,
+                Err(error) => {
+                    self.log(
+                        LogLevel::Error,
+                        &format!("Failed to read from tunnel device: {error}"),
+                    );
+                    return;
+                }
+            };
+            let Some(packet) = packet_buf.get(PACKET_INFO_LEN..len) else {
+                continue;
+            };
+            let Some(destination) = destination_address(packet) else {
+                continue;
+            };
+
+            let peers = self.peers();
+            let Some(peer) = route_peer(&peers, destination) else {
+                continue;
+            };
+
+            let result = peer.tunn.lock().unwrap().encapsulate(packet, &mut out_buf);
+            match result {
+                TunnResult::WriteToNetwork(datagram) => {
+                    self.send_to_peer(peer, datagram).await;
+                }
+                TunnResult::Err(error) => {
+                    self.log(
+                        LogLevel::Verbose,
+                        &format!("Failed to encrypt packet: {error:?}"),
+                    );
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Decrypts datagrams received on `socket` and writes the resulting packets to the tunnel
+    /// device.
+    async fn route_incoming(self: Arc<Self>, socket: Arc<UdpSocket>) {
+        let mut datagram_buf = vec![0u8; BUFFER_SIZE];
+        let mut out_buf = vec![0u8; BUFFER_SIZE];
+        loop {
+            let (len, source) = match socket.recv_from(&mut datagram_buf).await {
+                Ok(result) => result,
+                Err(error) => {
+                    self.log(
+                        LogLevel::Error,
+                        &format!("Failed to receive from UDP socket: {error}"),
+                    );
+                    return;
+                }
+            };
+
+            let peers = self.peers();
+            let Some(peer) = peers.iter().find(|peer| peer.endpoint == source) else {
+                continue;
+            };
+
+            let mut datagram = &datagram_buf[..len];
+            loop {
+                let result = peer.tunn.lock().unwrap().decapsulate(
+                    Some(source.ip()),
+                    datagram,
+                    &mut out_buf[PACKET_INFO_LEN..],
+                );
+                match result {
+                    TunnResult::WriteToNetwork(response) => {
+                        self.send_to_peer(peer, response).await;
+                        // Flush any packets that were queued while waiting for the handshake.
+                        datagram = &[];
+                        continue;
+                    }
+                    TunnResult::WriteToTunnelV4(packet, address) => {
+                        let len = packet.len();
+                        self.write_tun_if_allowed(peer, IpAddr::V4(address), &mut out_buf, len)
+                            .await;
+                    }
+                    TunnResult::WriteToTunnelV6(packet, address) => {
+                        let len = packet.len();
+                        self.write_tun_if_allowed(peer, IpAddr::V6(address), &mut out_buf, len)
+                            .await;
+                    }
+                    TunnResult::Err(error) => {
+                        self.log(
+                            LogLevel::Verbose,
+                            &format!("Failed to decrypt datagram: {error:?}"),
+                        );
+                    }
+                    TunnResult::Done => (),
+                }
+                break;
+            }
+        }
+    }
+
+    /// Drives handshakes and keepalives for all peers.
+    async fn update_timers(self: Arc<Self>) {
+        let mut out_buf = vec![0u8; BUFFER_SIZE];
+        let mut interval = tokio::time::interval(TIMER_INTERVAL);
+        loop {
+            interval.tick().await;
+            for peer in self.peers().iter() {
+                let result = peer.tunn.lock().unwrap().update_timers(&mut out_buf);
+                match result {
+                    TunnResult::WriteToNetwork(datagram) => {
+                        self.send_to_peer(peer, datagram).await;
+                    }
+                    TunnResult::Err(WireGuardError::ConnectionExpired) => {
+                        self.log(LogLevel::Info, "Connection expired");
+                    }
+                    TunnResult::Err(error) => {
+                        self.log(LogLevel::Warning, &format!("Timer error: {error:?}"));
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    async fn send_to_peer(&self, peer: &Peer, datagram: &[u8]) {
+        let socket = match peer.endpoint {
+            SocketAddr::V4(_) => Some(&self.socket_v4),
+            SocketAddr::V6(_) => self.socket_v6.as_ref(),
+        };
+        let Some(socket) = socket else {
+            self.log(LogLevel::Error, "No socket available for IPv6 endpoint");
+            return;
+        };
+        if let Err(error) = socket.send_to(datagram, peer.endpoint).await {
+            self.log(
+                LogLevel::Verbose,
+                &format!("Failed to send datagram to {}: {error}", peer.endpoint),
+            );
+        }
+    }
+
+    /// Writes a decrypted packet to the tunnel device, unless its source address is outside the
+    /// allowed IPs of `peer`. `buf` contains the packet at offset [`PACKET_INFO_LEN`].
+    async fn write_tun_if_allowed(&self, peer: &Peer, source: IpAddr, buf: &mut [u8], len: usize) {
+        if peer.route_prefix(source).is_none() {
+            self.log(
+                LogLevel::Verbose,
+                &format!("Dropping packet from {source}, which is not an allowed IP"),
+            );
+            return;
+        }
+        let protocol: u16 = match source {
+            IpAddr::V4(_) => libc::ETH_P_IP as u16,
+            IpAddr::V6(_) => libc::ETH_P_IPV6 as u16,
+        };
+        buf[..2].fill(0);
+        buf[2..PACKET_INFO_LEN].copy_from_slice(&protocol.to_be_bytes());
+
+        if let Err(error) = self.write_tun(&buf[..PACKET_INFO_LEN + len]).await {
+            self.log(
+                LogLevel::Error,
+                &format!("Failed to write to tunnel device: {error}"),
+            );
+        }
+    }
+
+    async fn read_tun(&self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let mut guard = self.tun.readable().await?;
+            match guard.try_io(|fd| nix::unistd::read(fd.as_raw_fd(), buf).map_err(io::Error::from))
+            {
+                Ok(result) => return result,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    async fn write_tun(&self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            let mut guard = self.tun.writable().await?;
+            match guard
+                .try_io(|fd| nix::unistd::write(fd.as_raw_fd(), buf).map_err(io::Error::from))
+            {
+                Ok(result) => return result,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl Peer {
+    /// Creates peers from `config`. Traffic counters are carried over from `previous` peers with
+    /// the same public key, so that the stats keep increasing across config changes.
+    fn from_config(config: &Config, previous: &[Peer]) -> Vec<Peer> {
+        let private_key = x25519::StaticSecret::from(config.tunnel.private_key.to_bytes());
+        config
+            .peers()
+            .enumerate()
+            .map(|(index, peer)| {
+                let public_key = *peer.public_key.as_bytes();
+                let base_stats = previous
+                    .iter()
+                    .find(|previous| previous.public_key == public_key)
+                    .map(Peer::stats)
+                    .unwrap_or_default();
+                let tunn = Tunn::new(
+                    private_key.clone(),
+                    x25519::PublicKey::from(public_key),
+                    peer.psk.as_ref().map(|psk| *psk.as_bytes()),
+                    config.persistent_keepalive,
+                    index as u32,
+                    None,
+                );
+                Peer {
+                    public_key,
+                    endpoint: peer.endpoint,
+                    allowed_ips: peer.allowed_ips.clone(),
+                    tunn: Mutex::new(tunn),
+                    base_stats,
+                }
+            })
+            .collect()
+    }
+
+    fn stats(&self) -> Stats {
+        let (_, tx_bytes, rx_bytes, ..) = self.tunn.lock().unwrap().stats();
+        Stats {
+            tx_bytes: self.base_stats.tx_bytes + tx_bytes as u64,
+            rx_bytes: self.base_stats.rx_bytes + rx_bytes as u64,
+        }
+    }
+
+    /// Returns the length of the longest allowed IP prefix that contains `address`, if any.
+    fn route_prefix(&self, address: IpAddr) -> Option<u8> {
+        self.allowed_ips
+            .iter()
+            .filter(|network| network.contains(address))
+            .map(|network| network.prefix())
+            .max()
+    }
+}
+
+/// Returns the peer with the most specific allowed IP that contains `destination`, if any.
+fn route_peer(peers: &[Peer], destination: IpAddr) -> Option<&Peer> {
+    peers
+        .iter()
+        .filter_map(|peer| Some((peer.route_prefix(destination)?, peer)))
+        .max_by_key(|(prefix, _)| *prefix)
+        .map(|(_, peer)| peer)
+}
+
+/// Returns the destination address of an IPv4 or IPv6 packet.
+fn destination_address(packet: &[u8]) -> Option<IpAddr> {
+    match packet.first()? >> 4 {
+        4 => {
+            let octets: [u8; 4] = packet.get(16..20)?.try_into().ok()?;
+            Some(IpAddr::from(octets))
+        }
+        6 => {
+            let octets: [u8; 16] = packet.get(24..40)?.try_into().ok()?;
+            Some(IpAddr::from(octets))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use talpid_types::net::wireguard::{PeerConfig, PrivateKey, PublicKey, TunnelConfig};
+
+    const ENTRY_ENDPOINT: SocketAddr =
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 33, 1)), 51820);
+    const EXIT_ENDPOINT: SocketAddr =
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 33, 2)), 51820);
+
+    fn peer_config(endpoint: SocketAddr, allowed_ips: &[&str]) -> PeerConfig {
+        PeerConfig {
+            public_key: PrivateKey::new_from_random().public_key(),
+            allowed_ips: allowed_ips.iter().map(|ip| ip.parse().unwrap()).collect(),
+            endpoint,
+            psk: None,
+        }
+    }
+
+    /// Returns a multihop config, where only traffic to the exit relay goes to the entry peer.
+    fn multihop_config() -> Config {
+        Config {
+            tunnel: TunnelConfig {
+                private_key: PrivateKey::new_from_random(),
+                addresses: vec![IpAddr::V4(Ipv4Addr::new(10, 64, 0, 2))],
+            },
+            entry_peer: peer_config(ENTRY_ENDPOINT, &["127.0.33.2/32"]),
+            exit_peer: Some(peer_config(EXIT_ENDPOINT, &["0.0.0.0/0", "10.64.0.1/32"])),
+            ipv4_gateway: Ipv4Addr::new(10, 64, 0, 1),
+            ipv6_gateway: None,
+            mtu: 1380,
+            persistent_keepalive: None,
+            psk_rekey_interval: None,
+            fwmark: None,
+            enable_ipv6: false,
+            obfuscator_config: None,
+        }
+    }
+
+    fn public_keys(peers: &[Peer]) -> Vec<PublicKey> {
+        peers
+            .iter()
+            .map(|peer| PublicKey::from(peer.public_key))
+            .collect()
+    }
+
+    /// Returns a device without a tunnel device, which is enough to manage peers.
+    async fn test_device(config: &Config) -> Device {
+        let (tun, _) = std::os::unix::net::UnixDatagram::pair().unwrap();
+        tun.set_nonblocking(true).unwrap();
+        Device {
+            tun: AsyncFd::new(OwnedFd::from(tun)).unwrap(),
+            socket_v4: Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap()),
+            socket_v6: None,
+            peers: Mutex::new(Arc::new(Peer::from_config(config, &[]))),
+            log_context: u32::MAX,
+        }
+    }
+
+    #[test]
+    fn test_route_peer() {
+        let config = multihop_config();
+        let peers = Peer::from_config(&config, &[]);
+        let route = |destination: &str| {
+            route_peer(&peers, destination.parse().unwrap()).map(|peer| peer.endpoint)
+        };
+
+        // The most specific allowed IP wins
+        assert_eq!(route("127.0.33.2"), Some(ENTRY_ENDPOINT));
+        assert_eq!(route("10.64.0.1"), Some(EXIT_ENDPOINT));
+        assert_eq!(route("1.1.1.1"), Some(EXIT_ENDPOINT));
+        assert_eq!(route("::1"), None);
+    }
+
+    #[test]
+    fn test_peers_from_config() {
+        let config = multihop_config();
+        let peers = Peer::from_config(&config, &[]);
+
+        // The exit peer comes first
+        assert_eq!(
+            public_keys(&peers),
+            vec![
+                config.exit_peer.unwrap().public_key,
+                config.entry_peer.public_key.clone()
+            ]
+        );
+        assert_eq!(peers[1].allowed_ips, config.entry_peer.allowed_ips);
+        assert!(peers.iter().all(|peer| peer.stats() == Stats::default()));
+    }
+
+    #[tokio::test]
+    async fn test_set_config() {
+        let config = multihop_config();
+        let device = test_device(&config).await;
+
+        // Pretend that some traffic went through the entry peer
+        let mut peers = Peer::from_config(&config, &[]);
+        peers[1].base_stats = Stats {
+            tx_bytes: 100,
+            rx_bytes: 200,
+        };
+        *device.peers.lock().unwrap() = Arc::new(peers);
+
+        // Replace the exit peer and move the entry peer to a new endpoint
+        let mut new_config = config.clone();
+        let new_endpoint = SocketAddr::new(ENTRY_ENDPOINT.ip(), 443);
+        new_config.entry_peer.endpoint = new_endpoint;
+        new_config.exit_peer = Some(peer_config(EXIT_ENDPOINT, &["0.0.0.0/0"]));
+        device.set_config(&new_config);
+
+        let peers = device.peers();
+        assert_eq!(
+            public_keys(&peers),
+            vec![
+                new_config.exit_peer.unwrap().public_key,
+                new_config.entry_peer.public_key.clone()
+            ]
+        );
+        assert_eq!(peers[1].endpoint, new_endpoint);
+        // Traffic counters of peers that are kept must not be reset
+        assert_eq!(
+            peers[1].stats(),
+            Stats {
+                tx_bytes: 100,
+                rx_bytes: 200,
+            }
+        );
+        assert_eq!(peers[0].stats(), Stats::default());
+    }
+
+    #[test]
+    fn test_destination_address() {
+        let mut ipv4_packet = [0u8; 20];
+        ipv4_packet[0] = 0x45;
+        ipv4_packet[16..20].copy_from_slice(&[10, 64, 0, 1]);
+        assert_eq!(
+            destination_address(&ipv4_packet),
+            Some(IpAddr::V4(Ipv4Addr::new(10, 64, 0, 1)))
+        );
+
+        let mut ipv6_packet = [0u8; 40];
+        ipv6_packet[0] = 0x60;
+        ipv6_packet[24..40].copy_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        assert_eq!(
+            destination_address(&ipv6_packet),
+            Some(IpAddr::V6(Ipv6Addr::LOCALHOST))
+        );
+
+        assert_eq!(destination_address(&ipv4_packet[..10]), None);
+        assert_eq!(destination_address(&[]), None);
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     borrow::Cow,
     ffi::CString,
     net::{Ipv4Addr, Ipv6Addr},
+    time::Duration,
 };
 use talpid_types::net::{obfuscation::ObfuscatorConfig, wireguard, GenericTunnelOptions};
 
@@ -20,6 +21,11 @@ pub struct Config {
     pub ipv6_gateway: Option<Ipv6Addr>,
     /// Maximum transmission unit for the tunnel
     pub mtu: u16,
+    /// Interval in seconds between keepalive packets sent to each peer
+    pub persistent_keepalive: Option<u16>,
+    /// Interval at which to negotiate a new PSK with the exit relay, for quantum-resistant
+    /// tunnels
+    pub psk_rekey_interval: Option<Duration>,
     /// Firewall mark
     #[cfg(target_os = "linux")]
     pub fwmark: Option<u32>,
@@ -87,6 +93,8 @@ impl Config {
             ipv4_gateway: connection.ipv4_gateway,
             ipv6_gateway,
             mtu,
+            persistent_keepalive: wg_options.persistent_keepalive,
+            psk_rekey_interval: wg_options.psk_rekey_interval,
             #[cfg(target_os = "linux")]
             fwmark: connection.fwmark,
             #[cfg(target_os = "linux")]
@@ -111,7 +119,7 @@ impl Config {
         // the order of insertion matters, public key entry denotes a new peer entry
         let mut wg_conf = WgConfigBuffer::new();
         wg_conf
-            .add("private_key", self.tunnel.private_key.to_bytes().as_ref())
+            .add("private_key", &self.tunnel.private_key.to_bytes()[..])
             .add("listen_port", "0");
 
         #[cfg(target_os = "linux")]
@@ -123,11 +131,17 @@ impl Config {
 
         for peer in self.peers() {
             wg_conf
-                .add("public_key", peer.public_key.as_bytes().as_ref())
+                .add("public_key", &peer.public_key.as_bytes()[..])
                 .add("endpoint", peer.endpoint.to_string().as_str())
                 .add("replace_allowed_ips", "true");
             if let Some(ref psk) = peer.psk {
-                wg_conf.add("preshared_key", psk.as_bytes().as_ref());
+                wg_conf.add("preshared_key", &psk.as_bytes()[..]);
+            }
+            if let Some(interval) = self.persistent_keepalive {
+                wg_conf.add(
+                    "persistent_keepalive_interval",
+                    interval.to_string().as_str(),
+                );
             }
             for addr in &peer.allowed_ips {
                 wg_conf.add("allowed_ip", addr.to_string().as_str());
@@ -143,6 +157,11 @@ impl Config {
         self.exit_peer.is_some()
     }
 
+    /// Return the exit peer. `exit_peer` if it is set, otherwise `entry_peer`.
+    pub fn exit_peer(&self) -> &wireguard::PeerConfig {
+        self.exit_peer.as_ref().unwrap_or(&self.entry_peer)
+    }
+
     /// Return the exit peer. `exit_peer` if it is set, otherwise `entry_peer`.
     pub fn exit_peer_mut(&mut self) -> &mut wireguard::PeerConfig {
         if let Some(ref mut peer) = self.exit_peer {
@@ -3,12 +3,14 @@ use crate::{
     stats::StatsMap,
 };
 use std::{
-    cmp,
+    cmp, fmt,
     net::Ipv4Addr,
     sync::{mpsc, Mutex, Weak},
     time::{Duration, Instant},
 };
 
+use talpid_types::net::wireguard::ConnectivityCheckOptions;
+
 use super::{Tunnel, TunnelError};
 
 /// Sleep time used when initially establishing connectivity
@@ -16,25 +18,180 @@ const DELAY_ON_INITIAL_SETUP: Duration = Duration::from_millis(50);
 /// Sleep time used when checking if an established connection is still working.
 const REGULAR_LOOP_SLEEP: Duration = Duration::from_secs(1);
 
-/// Timeout for waiting on receiving traffic after sending outgoing traffic.  Once this timeout is
-/// hit, a ping will be sent every `SECONDS_PER_PING` until `PING_TIMEOUT` is reached, or traffic
-/// is received.
+/// Default timeout for waiting on receiving traffic after sending outgoing traffic.  Once this
+/// timeout is hit, a ping will be sent every `SECONDS_PER_PING` until the ping timeout is reached,
+/// or traffic is received.
 const BYTES_RX_TIMEOUT: Duration = Duration::from_secs(5);
 /// Timeout for waiting on receiving or sending any traffic.  Once this timeout is hit, a ping will
-/// be sent every `SECONDS_PER_PING` until `PING_TIMEOUT` is reached or traffic is received.
+/// be sent every `SECONDS_PER_PING` until the ping timeout is reached or traffic is received.
 const TRAFFIC_TIMEOUT: Duration = Duration::from_secs(120);
-/// Timeout for waiting on receiving traffic after sending the first ICMP packet.  Once this
-/// timeout is reached, it is assumed that the connection is lost.
+/// Default timeout for waiting on receiving traffic after sending the first ICMP packet.  Once
+/// this timeout is reached, it is assumed that the connection is lost.
 const PING_TIMEOUT: Duration = Duration::from_secs(15);
-/// Timeout for receiving traffic when establishing a connection.
+/// Default timeout for receiving traffic when establishing a connection.
 const ESTABLISH_TIMEOUT: Duration = Duration::from_secs(4);
-/// `ESTABLISH_TIMEOUT` is multiplied by this after each failed connection attempt.
+/// The establish timeout is multiplied by this after each failed connection attempt. The result
+/// is capped by the ping timeout.
 const ESTABLISH_TIMEOUT_MULTIPLIER: u32 = 2;
-/// Maximum timeout for establishing a connection.
-const MAX_ESTABLISH_TIMEOUT: Duration = PING_TIMEOUT;
 /// Number of seconds to wait between sending ICMP packets
 const SECONDS_PER_PING: Duration = Duration::from_secs(3);
 
+/// Timeouts used by the connectivity monitor. Values that aren't set in
+/// [`ConnectivityCheckOptions`] fall back to the defaults defined in this module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Time to wait for incoming traffic after sending traffic, before starting to ping
+    pub rx_timeout: Duration,
+    /// Time to wait for incoming traffic after the first ping before the connection is lost. This
+    /// is also the maximum timeout for establishing a connection.
+    pub ping_timeout: Duration,
+    /// Initial timeout for receiving traffic when establishing a connection
+    pub establish_timeout: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            rx_timeout: BYTES_RX_TIMEOUT,
+            ping_timeout: PING_TIMEOUT,
+            establish_timeout: ESTABLISH_TIMEOUT,
+        }
+    }
+}
+
+/// Returns how long to wait for traffic when establishing a connection on `retry_attempt`. The
+/// timeout grows exponentially with each attempt, but never exceeds `max_timeout`.
+fn establish_timeout(
+    retry_attempt: u32,
+    timeout_initial: Duration,
+    timeout_multiplier: u32,
+    max_timeout: Duration,
+) -> Duration {
+    cmp::min(
+        max_timeout,
+        timeout_initial.saturating_mul(timeout_multiplier.saturating_pow(retry_attempt)),
+    )
+}
+
+impl From<&ConnectivityCheckOptions> for Timeouts {
+    fn from(options: &ConnectivityCheckOptions) -> Self {
+        let default = Timeouts::default();
+        Timeouts {
+            rx_timeout: options.rx_timeout.unwrap_or(default.rx_timeout),
+            ping_timeout: options.ping_timeout.unwrap_or(default.ping_timeout),
+            establish_timeout: options
+                .establish_timeout
+                .unwrap_or(default.establish_timeout),
+        }
+    }
+}
+
+/// Decisions made by the connectivity monitor. These are logged as `key=value` pairs behind a
+/// common prefix, so that they are easy to extract from a log file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckEvent {
+    Started {
+        target: Ipv4Addr,
+        timeouts: Timeouts,
+    },
+    Established {
+        retry_attempt: u32,
+    },
+    EstablishTimedOut {
+        retry_attempt: u32,
+        timeout: Duration,
+    },
+    PingSent {
+        reason: PingReason,
+        num_pings_sent: u32,
+    },
+    TrafficResumed {
+        num_pings_sent: u32,
+    },
+    ConnectionLost {
+        timeout: Duration,
+    },
+    Suspended {
+        duration: Duration,
+    },
+}
+
+/// The reason for sending a ping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PingReason {
+    /// Traffic was sent but nothing was received within the rx timeout
+    RxTimeout,
+    /// No traffic has been sent or received in a while
+    TrafficTimeout,
+}
+
+impl CheckEvent {
+    fn log(&self) {
+        match self {
+            CheckEvent::Established { .. }
+            | CheckEvent::Started { .. }
+            | CheckEvent::Suspended { .. } => log::debug!("{self}"),
+            CheckEvent::PingSent { .. } | CheckEvent::TrafficResumed { .. } => {
+                log::trace!("{self}")
+            }
+            CheckEvent::EstablishTimedOut { .. } | CheckEvent::ConnectionLost { .. } => {
+                log::warn!("{self}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for CheckEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "connectivity_check ")?;
+        match self {
+            CheckEvent::Started { target, timeouts } => write!(
+                f,
+                "event=started target={target} rx_timeout_ms={} ping_timeout_ms={} \
+                 establish_timeout_ms={}",
+                timeouts.rx_timeout.as_millis(),
+                timeouts.ping_timeout.as_millis(),
+                timeouts.establish_timeout.as_millis(),
+            ),
+            CheckEvent::Established { retry_attempt } => {
+                write!(f, "event=established retry_attempt={retry_attempt}")
+            }
+            CheckEvent::EstablishTimedOut {
+                retry_attempt,
+                timeout,
+            } => write!(
+                f,
+                "event=establish_timed_out retry_attempt={retry_attempt} timeout_ms={}",
+                timeout.as_millis()
+            ),
+            CheckEvent::PingSent {
+                reason,
+                num_pings_sent,
+            } => {
+                let reason = match reason {
+                    PingReason::RxTimeout => "rx_timeout",
+                    PingReason::TrafficTimeout => "traffic_timeout",
+                };
+                write!(
+                    f,
+                    "event=ping_sent reason={reason} num_pings_sent={num_pings_sent}"
+                )
+            }
+            CheckEvent::TrafficResumed { num_pings_sent } => {
+                write!(f, "event=traffic_resumed num_pings_sent={num_pings_sent}")
+            }
+            CheckEvent::ConnectionLost { timeout } => write!(
+                f,
+                "event=connection_lost ping_timeout_ms={}",
+                timeout.as_millis()
+            ),
+            CheckEvent::Suspended { duration } => {
+                write!(f, "event=suspended duration_ms={}", duration.as_millis())
+            }
+        }
+    }
+}
+
 /// Connectivity monitor errors
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -60,17 +217,20 @@ pub enum Error {
 /// timeout. A connection is considered to be established the first time an increase in incoming
 /// traffic is observed.
 ///
-/// The connectivity monitor will start sending pings and start the countdown to `PING_TIMEOUT` in
-/// the following cases:
+/// The connectivity monitor will start sending pings and start the countdown to the ping timeout
+/// in the following cases:
 /// - In case that we have observed a bump in the outgoing traffic but no corresponding incoming
-/// traffic for longer than `BYTES_RX_TIMEOUT`, then the monitor will start pinging.
+/// traffic for longer than the rx timeout, then the monitor will start pinging.
 /// - In case that no increase in outgoing or incoming traffic has been observed for longer than
 /// `TRAFFIC_TIMEOUT`, then the monitor will start pinging as well.
 ///
 /// Once a connection established, a connection is only considered broken once the connectivity
-/// monitor has started pinging and no traffic has been received for a duration of `PING_TIMEOUT`.
+/// monitor has started pinging and no traffic has been received for a duration of the ping timeout.
+///
+/// The timeouts and the address that is pinged can be changed using [`ConnectivityCheckOptions`].
 pub struct ConnectivityMonitor {
     tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
+    timeouts: Timeouts,
     conn_state: ConnState,
     initial_ping_timestamp: Option<Instant>,
     num_pings_sent: u32,
@@ -79,23 +239,30 @@ pub struct ConnectivityMonitor {
 }
 
 impl ConnectivityMonitor {
+    /// Creates a monitor that pings `gateway`, unless a different target is set in `options`.
     pub(super) fn new(
-        addr: Ipv4Addr,
+        gateway: Ipv4Addr,
+        options: &ConnectivityCheckOptions,
         #[cfg(any(target_os = "macos", target_os = "linux"))] interface: String,
         tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
         close_receiver: mpsc::Receiver<()>,
     ) -> Result<Self, Error> {
+        let target = options.ping_target.unwrap_or(gateway);
+        let timeouts = Timeouts::from(options);
         let pinger = new_pinger(
-            addr,
+            target,
             #[cfg(any(target_os = "macos", target_os = "linux"))]
             interface,
         )
         .map_err(Error::PingError)?;
 
+        CheckEvent::Started { target, timeouts }.log();
+
         let now = Instant::now();
 
         Ok(Self {
             tunnel_handle,
+            timeouts,
             conn_state: ConnState::new(now, Default::default()),
             initial_ping_timestamp: None,
             num_pings_sent: 0,
@@ -111,9 +278,9 @@ impl ConnectivityMonitor {
         self.pinger.send_icmp().map_err(Error::PingError)?;
         self.establish_connectivity_inner(
             retry_attempt,
-            ESTABLISH_TIMEOUT,
+            self.timeouts.establish_timeout,
             ESTABLISH_TIMEOUT_MULTIPLIER,
-            MAX_ESTABLISH_TIMEOUT,
+            self.timeouts.ping_timeout,
         )
     }
 
@@ -128,20 +295,28 @@ impl ConnectivityMonitor {
             return Ok(true);
         }
 
-        let check_timeout = cmp::min(
+        let check_timeout = establish_timeout(
+            retry_attempt,
+            timeout_initial,
+            timeout_multiplier,
             max_timeout,
-            timeout_initial.saturating_mul(timeout_multiplier.saturating_pow(retry_attempt)),
         );
 
         let start = Instant::now();
         while start.elapsed() < check_timeout {
             if self.check_connectivity_interval(Instant::now(), check_timeout)? {
+                CheckEvent::Established { retry_attempt }.log();
                 return Ok(true);
             }
             if self.should_shut_down(DELAY_ON_INITIAL_SETUP) {
                 return Ok(false);
             }
         }
+        CheckEvent::EstablishTimedOut {
+            retry_attempt,
+            timeout: check_timeout,
+        }
+        .log();
         Ok(false)
     }
 
@@ -164,6 +339,12 @@ impl ConnectivityMonitor {
             let time_slept = current_iteration - last_iteration;
             if time_slept < (iter_delay * 2) {
                 if !self.check_connectivity(Instant::now())? {
+                    if self.ping_timed_out(self.timeouts.ping_timeout) {
+                        CheckEvent::ConnectionLost {
+                            timeout: self.timeouts.ping_timeout,
+                        }
+                        .log();
+                    }
                     return Ok(());
                 }
 
@@ -174,6 +355,10 @@ impl ConnectivityMonitor {
             } else {
                 // Loop was suspended for too long, so it's safer to assume that the host still has
                 // connectivity.
+                CheckEvent::Suspended {
+                    duration: time_slept,
+                }
+                .log();
                 self.reset_pinger();
                 self.conn_state.reset_after_suspension(current_iteration);
             }
@@ -184,7 +369,7 @@ impl ConnectivityMonitor {
 
     /// Returns true if connection is established
     fn check_connectivity(&mut self, now: Instant) -> Result<bool, Error> {
-        self.check_connectivity_interval(now, PING_TIMEOUT)
+        self.check_connectivity_interval(now, self.timeouts.ping_timeout)
     }
 
     /// Returns true if connection is established
@@ -199,6 +384,12 @@ impl ConnectivityMonitor {
                 let new_stats = new_stats?;
 
                 if self.conn_state.update(now, new_stats) {
+                    if self.num_pings_sent > 0 {
+                        CheckEvent::TrafficResumed {
+                            num_pings_sent: self.num_pings_sent,
+                        }
+                        .log();
+                    }
                     self.reset_pinger();
                     return Ok(true);
                 }
@@ -231,19 +422,30 @@ impl ConnectivityMonitor {
         // Only send out a ping if we haven't received a byte in a while or no traffic has flowed
         // in the last 2 minutes, but if a ping already has been sent out, only send one out every
         // 3 seconds.
-        if (self.conn_state.rx_timed_out() || self.conn_state.traffic_timed_out())
-            && self
-                .initial_ping_timestamp
-                .map(|initial_ping_timestamp| {
-                    initial_ping_timestamp.elapsed() / self.num_pings_sent < SECONDS_PER_PING
-                })
-                .unwrap_or(true)
+        let reason = if self.conn_state.rx_timed_out(&self.timeouts) {
+            PingReason::RxTimeout
+        } else if self.conn_state.traffic_timed_out(&self.timeouts) {
+            PingReason::TrafficTimeout
+        } else {
+            return Ok(());
+        };
+        if self
+            .initial_ping_timestamp
+            .map(|initial_ping_timestamp| {
+                initial_ping_timestamp.elapsed() / self.num_pings_sent < SECONDS_PER_PING
+            })
+            .unwrap_or(true)
         {
             self.pinger.send_icmp().map_err(Error::PingError)?;
             if self.initial_ping_timestamp.is_none() {
                 self.initial_ping_timestamp = Some(now);
             }
             self.num_pings_sent += 1;
+            CheckEvent::PingSent {
+                reason,
+                num_pings_sent: self.num_pings_sent,
+            }
+            .log();
         }
         Ok(())
     }
@@ -357,9 +559,9 @@ impl ConnState {
     }
 
     // check if last time data was received is too long ago
-    pub fn rx_timed_out(&self) -> bool {
+    pub fn rx_timed_out(&self, timeouts: &Timeouts) -> bool {
         match self {
-            ConnState::Connecting { start, .. } => start.elapsed() >= BYTES_RX_TIMEOUT,
+            ConnState::Connecting { start, .. } => start.elapsed() >= timeouts.rx_timeout,
             ConnState::Connected {
                 rx_timestamp,
                 tx_timestamp,
@@ -367,16 +569,16 @@ impl ConnState {
             } => {
                 // if last sent bytes were sent after or at the same time as last received bytes
                 tx_timestamp >= rx_timestamp &&
-                    // and the response hasn't been seen for BYTES_RX_TIMEOUT
-                    rx_timestamp.elapsed() >= BYTES_RX_TIMEOUT
+                    // and the response hasn't been seen for the rx timeout
+                    rx_timestamp.elapsed() >= timeouts.rx_timeout
             }
         }
     }
 
     // check if no bytes have been sent or received in a while
-    pub fn traffic_timed_out(&self) -> bool {
+    pub fn traffic_timed_out(&self, timeouts: &Timeouts) -> bool {
         match self {
-            ConnState::Connecting { .. } => self.rx_timed_out(),
+            ConnState::Connecting { .. } => self.rx_timed_out(timeouts),
             ConnState::Connected {
                 rx_timestamp,
                 tx_timestamp,
@@ -417,8 +619,8 @@ mod test {
         let conn_state = ConnState::new(now, Default::default());
 
         assert!(!conn_state.connected());
-        assert!(!conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(!conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(!conn_state.traffic_timed_out(&Timeouts::default()));
     }
 
     /// Test if ConnState::Connecting will timeout after not receiving any traffic after
@@ -429,8 +631,8 @@ mod test {
         let conn_state = ConnState::new(now, Default::default());
 
         assert!(!conn_state.connected());
-        assert!(conn_state.rx_timed_out());
-        assert!(conn_state.traffic_timed_out());
+        assert!(conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(conn_state.traffic_timed_out(&Timeouts::default()));
     }
 
     /// Test if ConnState::Connecting correctly transitions into ConnState::Connected if traffic is
@@ -450,8 +652,8 @@ mod test {
         conn_state.update(Instant::now(), stats);
 
         assert!(conn_state.connected());
-        assert!(!conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(!conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(!conn_state.traffic_timed_out(&Timeouts::default()));
     }
 
     /// Test if ConnState::Connected correctly times out after TRAFFIC_TIMEOUT when no traffic is
@@ -475,8 +677,8 @@ mod test {
         conn_state.update(connect_time, stats);
 
         assert!(conn_state.connected());
-        assert!(!conn_state.rx_timed_out());
-        assert!(conn_state.traffic_timed_out());
+        assert!(!conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(conn_state.traffic_timed_out(&Timeouts::default()));
     }
 
     /// Test if ConnState::Connected correctly times out after BYTES_RX_TIMEOUT when no incoming
@@ -510,8 +712,46 @@ mod test {
         conn_state.update(update_time, stats);
 
         assert!(conn_state.connected());
-        assert!(conn_state.rx_timed_out());
-        assert!(!conn_state.traffic_timed_out());
+        assert!(conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(!conn_state.traffic_timed_out(&Timeouts::default()));
+    }
+
+    /// Test that unset options fall back to the default timeouts
+    #[test]
+    fn test_timeouts_from_options() {
+        assert_eq!(
+            Timeouts::from(&ConnectivityCheckOptions::default()),
+            Timeouts::default()
+        );
+
+        let options = ConnectivityCheckOptions {
+            ping_timeout: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert_eq!(
+            Timeouts::from(&options),
+            Timeouts {
+                ping_timeout: Duration::from_secs(60),
+                ..Timeouts::default()
+            }
+        );
+    }
+
+    /// Test that ConnState respects a custom rx timeout
+    #[test]
+    fn test_conn_state_custom_rx_timeout() {
+        let timeouts = Timeouts {
+            rx_timeout: BYTES_RX_TIMEOUT * 4,
+            ..Timeouts::default()
+        };
+        let start = Instant::now()
+            .checked_sub(BYTES_RX_TIMEOUT + Duration::from_secs(1))
+            .unwrap();
+        let conn_state = ConnState::new(start, Default::default());
+
+        assert!(conn_state.rx_timed_out(&Timeouts::default()));
+        assert!(!conn_state.rx_timed_out(&timeouts));
+        assert!(!conn_state.traffic_timed_out(&timeouts));
     }
 
     #[derive(Default)]
@@ -619,8 +859,25 @@ mod test {
         pinger: Box<dyn Pinger>,
         tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
         close_receiver: mpsc::Receiver<()>,
+    ) -> ConnectivityMonitor {
+        mock_monitor_with_timeouts(
+            now,
+            Timeouts::default(),
+            pinger,
+            tunnel_handle,
+            close_receiver,
+        )
+    }
+
+    fn mock_monitor_with_timeouts(
+        now: Instant,
+        timeouts: Timeouts,
+        pinger: Box<dyn Pinger>,
+        tunnel_handle: Weak<Mutex<Option<Box<dyn Tunnel>>>>,
+        close_receiver: mpsc::Receiver<()>,
     ) -> ConnectivityMonitor {
         ConnectivityMonitor {
+            timeouts,
             conn_state: ConnState::new(now, Default::default()),
             initial_ping_timestamp: None,
             num_pings_sent: 0,
@@ -667,6 +924,30 @@ mod test {
         assert!(!monitor.check_connectivity(now).unwrap())
     }
 
+    #[test]
+    /// Verify that `check_connectivity()` keeps returning `true` while pinging for longer than the
+    /// default `PING_TIMEOUT` if a longer ping timeout is configured.
+    fn test_custom_ping_timeout() {
+        let (_tunnel_anchor, tunnel) = MockTunnel::never_incrementing().into_locked();
+        let (_tx, rx) = mpsc::channel();
+        let pinger = MockPinger::default();
+        let now = Instant::now();
+        let start = now
+            .checked_sub(BYTES_RX_TIMEOUT + PING_TIMEOUT + Duration::from_secs(10))
+            .unwrap();
+        let timeouts = Timeouts {
+            ping_timeout: PING_TIMEOUT * 4,
+            ..Timeouts::default()
+        };
+        let mut monitor = mock_monitor_with_timeouts(start, timeouts, Box::new(pinger), tunnel, rx);
+
+        // Mock the state - connectivity has been established
+        monitor.conn_state = connected_state(start);
+        // A ping was sent to verify connectivity
+        monitor.maybe_send_ping(start).unwrap();
+        assert!(monitor.check_connectivity(now).unwrap())
+    }
+
     #[test]
     /// Verify that `check_connectivity()` returns `true` if the tunnel is connected and traffic is
     /// flowing constantly.
@@ -828,4 +1109,51 @@ mod test {
         assert_rx(Duration::from_secs(2));
         assert_rx(Duration::from_secs(2));
     }
+
+    #[test]
+    /// Verify that a custom establish timeout is used when setting up a tunnel, and that it is
+    /// capped by the ping timeout.
+    fn test_custom_establish_timeout() {
+        let mut tunnel_stats = stats::StatsMap::new();
+        tunnel_stats.insert(
+            [0u8; 32],
+            stats::Stats {
+                tx_bytes: 0,
+                rx_bytes: 0,
+            },
+        );
+
+        let pinger = MockPinger::default();
+        let (_tunnel_anchor, tunnel) =
+            MockTunnel::new(move || Ok(tunnel_stats.clone())).into_locked();
+
+        let (result_tx, result_rx) = mpsc::channel();
+
+        let (_stop_tx, stop_rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let now = Instant::now();
+            let start = now.checked_sub(Duration::from_secs(1)).unwrap();
+            let timeouts = Timeouts {
+                establish_timeout: Duration::from_millis(250),
+                ping_timeout: Duration::from_secs(1),
+                ..Timeouts::default()
+            };
+            let mut monitor =
+                mock_monitor_with_timeouts(start, timeouts, Box::new(pinger), tunnel, stop_rx);
+
+            for attempt in 0..4 {
+                result_tx
+                    .send(monitor.establish_connectivity(attempt))
+                    .unwrap();
+            }
+        });
+        let err = DELAY_ON_INITIAL_SETUP + Duration::from_millis(350);
+        let assert_rx = |recv_timeout: Duration| {
+            assert!(!result_rx.recv_timeout(recv_timeout + err).unwrap().unwrap());
+        };
+        assert_rx(Duration::from_millis(250));
+        assert_rx(Duration::from_millis(500));
+        assert_rx(Duration::from_secs(1));
+        assert_rx(Duration::from_secs(1));
+    }
 }
@@ -5,18 +5,20 @@
 use self::config::Config;
 #[cfg(windows)]
 use futures::channel::mpsc;
-use futures::future::{abortable, AbortHandle as FutureAbortHandle, BoxFuture, Future};
+use futures::future::{abortable, AbortHandle as FutureAbortHandle, BoxFuture, Either, Future};
 #[cfg(target_os = "linux")]
 use once_cell::sync::Lazy;
 #[cfg(target_os = "android")]
 use std::borrow::Cow;
 #[cfg(target_os = "linux")]
 use std::env;
-#[cfg(windows)]
+#[cfg(target_os = "linux")]
+use std::fmt;
+#[cfg(any(windows, all(target_os = "linux", feature = "boringtun")))]
 use std::io;
 use std::{
     convert::Infallible,
-    net::IpAddr,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     path::Path,
     pin::Pin,
     sync::{mpsc as sync_mpsc, Arc, Mutex},
@@ -32,16 +34,21 @@ use ipnetwork::IpNetwork;
 use talpid_types::{
     net::{
         obfuscation::ObfuscatorConfig,
-        wireguard::{PresharedKey, PrivateKey, PublicKey},
+        wireguard::{ConnectivityCheckOptions, PresharedKey, PrivateKey, PublicKey},
         AllowedTunnelTraffic, Endpoint, TransportProtocol,
     },
     BoxedError, ErrorExt,
 };
-use tokio::sync::Mutex as AsyncMutex;
+use tokio::sync::{watch, Mutex as AsyncMutex};
+#[cfg(feature = "quic")]
+use tunnel_obfuscation::QuicSettings;
 use tunnel_obfuscation::{
-    create_obfuscator, Error as ObfuscationError, Settings as ObfuscationSettings, Udp2TcpSettings,
+    create_obfuscator, Error as ObfuscationError, Settings as ObfuscationSettings,
+    ShadowsocksSettings, Udp2TcpSettings,
 };
 
+#[cfg(all(target_os = "linux", feature = "boringtun"))]
+mod boringtun;
 /// WireGuard config data-types
 pub mod config;
 mod connectivity_check;
@@ -89,6 +96,11 @@ pub enum Error {
     #[error("Tunnel obfuscator failed")]
     ObfuscatorError(#[source] ObfuscationError),
 
+    /// QUIC obfuscation was requested, but is not part of this build
+    #[cfg(not(feature = "quic"))]
+    #[error("QUIC obfuscation is not supported in this build")]
+    QuicObfuscationUnsupported,
+
     /// Failed to set up connectivity monitor
     #[error("Connectivity monitor failed")]
     ConnectivityMonitorError(#[source] connectivity_check::Error),
@@ -97,6 +109,10 @@ pub enum Error {
     #[error("Failed to negotiate PQ PSK")]
     PskNegotiationError(#[source] talpid_tunnel_config_client::Error),
 
+    /// The connectivity check target cannot be reached through the tunnel
+    #[error("Connectivity check target {0} is not in the allowed IPs of the exit peer")]
+    PingTargetNotAllowed(Ipv4Addr),
+
     /// Failed to set up IP interfaces.
     #[cfg(windows)]
     #[error("Failed to set up IP interfaces")]
@@ -156,6 +172,10 @@ const INITIAL_PSK_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(8);
 const MAX_PSK_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(48);
 const PSK_EXCHANGE_TIMEOUT_MULTIPLIER: u32 = 2;
 
+/// Address of the WireGuard server as seen by the Shadowsocks server on the relay.
+const SHADOWSOCKS_WIREGUARD_ENDPOINT: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 51820);
+
 /// Simple wrapper that automatically cancels the future which runs an obfuscator.
 struct ObfuscatorHandle {
     abort_handle: FutureAbortHandle,
@@ -199,55 +219,122 @@ static FORCE_USERSPACE_WIREGUARD: Lazy<bool> = Lazy::new(|| {
         .unwrap_or(false)
 });
 
+/// Userspace implementations of WireGuard that can be used on Linux
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserspaceImplementation {
+    /// wireguard-go, linked as a static library
+    WireguardGo,
+    /// boringtun, which is written in Rust
+    Boringtun,
+}
+
+#[cfg(target_os = "linux")]
+impl fmt::Display for UserspaceImplementation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserspaceImplementation::WireguardGo => f.write_str("wireguard-go"),
+            UserspaceImplementation::Boringtun => f.write_str("boringtun"),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+/// Selects the userspace implementation of WireGuard to use when the kernel module is not used.
+/// Defaults to wireguard-go if it is part of the build.
+static USERSPACE_WIREGUARD_IMPLEMENTATION: Lazy<UserspaceImplementation> = Lazy::new(|| {
+    let default = if cfg!(wireguard_go) {
+        UserspaceImplementation::WireguardGo
+    } else {
+        UserspaceImplementation::Boringtun
+    };
+    match env::var("TALPID_USERSPACE_WIREGUARD_IMPLEMENTATION").as_deref() {
+        Ok("wireguard-go") => UserspaceImplementation::WireguardGo,
+        Ok("boringtun") => UserspaceImplementation::Boringtun,
+        Ok(other) => {
+            log::warn!("Unknown userspace WireGuard implementation \"{other}\", using {default}");
+            default
+        }
+        Err(_) => default,
+    }
+});
+
 async fn maybe_create_obfuscator(
     config: &mut Config,
     close_msg_sender: sync_mpsc::Sender<CloseMsg>,
 ) -> Result<Option<ObfuscatorHandle>> {
-    if let Some(ref obfuscator_config) = config.obfuscator_config {
-        match obfuscator_config {
-            ObfuscatorConfig::Udp2Tcp { endpoint } => {
-                log::trace!("Connecting to Udp2Tcp endpoint {:?}", *endpoint);
-                let settings = Udp2TcpSettings {
-                    peer: *endpoint,
-                    #[cfg(target_os = "linux")]
-                    fwmark: config.fwmark,
-                };
-                let obfuscator = create_obfuscator(&ObfuscationSettings::Udp2Tcp(settings))
-                    .await
-                    .map_err(Error::CreateObfuscatorError)?;
-                let endpoint = obfuscator.endpoint();
+    let Some(ref obfuscator_config) = config.obfuscator_config else {
+        return Ok(None);
+    };
+    let settings = match obfuscator_config {
+        ObfuscatorConfig::Udp2Tcp { endpoint } => {
+            log::trace!("Connecting to Udp2Tcp endpoint {:?}", *endpoint);
+            ObfuscationSettings::Udp2Tcp(Udp2TcpSettings {
+                peer: *endpoint,
+                #[cfg(target_os = "linux")]
+                fwmark: config.fwmark,
+            })
+        }
+        ObfuscatorConfig::Shadowsocks {
+            endpoint,
+            wireguard_endpoint,
+            credentials,
+        } => {
+            log::trace!("Connecting to Shadowsocks endpoint {:?}", *endpoint);
+            ObfuscationSettings::Shadowsocks(ShadowsocksSettings {
+                shadowsocks_endpoint: *endpoint,
+                wireguard_endpoint: wireguard_endpoint.unwrap_or(SHADOWSOCKS_WIREGUARD_ENDPOINT),
+                credentials: credentials.clone(),
+                #[cfg(target_os = "linux")]
+                fwmark: config.fwmark,
+            })
+        }
+        #[cfg(feature = "quic")]
+        ObfuscatorConfig::Quic { endpoint, hostname } => {
+            log::trace!("Connecting to QUIC endpoint {:?}", *endpoint);
+            ObfuscationSettings::Quic(QuicSettings {
+                quic_endpoint: *endpoint,
+                hostname: hostname.clone(),
+                trusted_certificates: vec![],
+                #[cfg(target_os = "linux")]
+                fwmark: config.fwmark,
+            })
+        }
+        #[cfg(not(feature = "quic"))]
+        ObfuscatorConfig::Quic { .. } => return Err(Error::QuicObfuscationUnsupported),
+    };
+    let obfuscator = create_obfuscator(&settings)
+        .await
+        .map_err(Error::CreateObfuscatorError)?;
+    let endpoint = obfuscator.endpoint();
 
-                log::trace!("Patching first WireGuard peer to become {:?}", endpoint);
-                config.entry_peer.endpoint = endpoint;
+    log::trace!("Patching first WireGuard peer to become {:?}", endpoint);
+    config.entry_peer.endpoint = endpoint;
 
-                #[cfg(target_os = "android")]
-                let remote_socket_fd = obfuscator.remote_socket_fd();
+    #[cfg(target_os = "android")]
+    let remote_socket_fd = obfuscator.remote_socket_fd();
 
-                let (runner, abort_handle) = abortable(async move {
-                    match obfuscator.run().await {
-                        Ok(_) => {
-                            let _ = close_msg_sender.send(CloseMsg::ObfuscatorExpired);
-                        }
-                        Err(error) => {
-                            log::error!(
-                                "{}",
-                                error.display_chain_with_msg("Obfuscation controller failed")
-                            );
-                            let _ = close_msg_sender
-                                .send(CloseMsg::ObfuscatorFailed(Error::ObfuscatorError(error)));
-                        }
-                    }
-                });
-                tokio::spawn(runner);
-                return Ok(Some(ObfuscatorHandle::new(
-                    abort_handle,
-                    #[cfg(target_os = "android")]
-                    remote_socket_fd,
-                )));
+    let (runner, abort_handle) = abortable(async move {
+        match obfuscator.run().await {
+            Ok(_) => {
+                let _ = close_msg_sender.send(CloseMsg::ObfuscatorExpired);
+            }
+            Err(error) => {
+                log::error!(
+                    "{}",
+                    error.display_chain_with_msg("Obfuscation controller failed")
+                );
+                let _ = close_msg_sender
+                    .send(CloseMsg::ObfuscatorFailed(Error::ObfuscatorError(error)));
             }
         }
-    }
-    Ok(None)
+    });
+    tokio::spawn(runner);
+    Ok(Some(ObfuscatorHandle::new(
+        abort_handle,
+        #[cfg(target_os = "android")]
+        remote_socket_fd,
+    )))
 }
 
 impl WireguardMonitor {
@@ -261,12 +348,18 @@ impl WireguardMonitor {
     >(
         mut config: Config,
         psk_negotiation: bool,
+        connectivity_check_options: &ConnectivityCheckOptions,
         #[cfg(not(target_os = "android"))] detect_mtu: bool,
         log_path: Option<&Path>,
         args: TunnelArgs<'_, F>,
     ) -> Result<WireguardMonitor> {
         let on_event = args.on_event.clone();
 
+        let ping_target = connectivity_check_options.ping_target;
+        if let Some(target) = ping_target {
+            Self::validate_ping_target(&config, target)?;
+        }
+
         let endpoint_addrs: Vec<IpAddr> = config.peers().map(|peer| peer.endpoint.ip()).collect();
 
         let (close_obfs_sender, close_obfs_listener) = sync_mpsc::channel();
@@ -317,6 +410,7 @@ impl WireguardMonitor {
         let gateway = config.ipv4_gateway;
         let mut connectivity_monitor = connectivity_check::ConnectivityMonitor::new(
             gateway,
+            connectivity_check_options,
             #[cfg(any(target_os = "macos", target_os = "linux"))]
             iface_name.clone(),
             Arc::downgrade(&monitor.tunnel),
@@ -355,7 +449,7 @@ impl WireguardMonitor {
                 .map_err(Error::SetupRoutingError)
                 .map_err(CloseMsg::SetupError)?;
 
-            let routes = Self::get_pre_tunnel_routes(&iface_name, &config)
+            let routes = Self::get_pre_tunnel_routes(&iface_name, &config, ping_target)
                 .chain(Self::get_endpoint_routes(&endpoint_addrs))
                 .collect();
 
@@ -433,16 +527,29 @@ impl WireguardMonitor {
             let metadata = Self::tunnel_metadata(&iface_name, &config);
             (on_event)(TunnelEvent::Up(metadata)).await;
 
-            tokio::task::spawn_blocking(move || {
+            let connectivity_monitor = tokio::task::spawn_blocking(move || {
                 if let Err(error) = connectivity_monitor.run() {
                     log::error!(
                         "{}",
                         error.display_chain_with_msg("Connectivity monitor failed")
                     );
                 }
-            })
-            .await
-            .unwrap();
+            });
+
+            if !psk_negotiation {
+                connectivity_monitor.await.unwrap();
+            } else {
+                let psk_rekeying = Box::pin(Self::psk_rekeying(
+                    &tunnel,
+                    config,
+                    args.psk_rekey_interval_rx,
+                    on_event,
+                ));
+                match futures::future::select(connectivity_monitor, psk_rekeying).await {
+                    Either::Left((result, _)) => result.unwrap(),
+                    Either::Right((never, _)) => match never {},
+                }
+            }
 
             Err::<Infallible, CloseMsg>(CloseMsg::PingErr)
         };
@@ -567,6 +674,127 @@ impl WireguardMonitor {
         Ok(())
     }
 
+    /// Negotiates a new PSK with the relay at the interval given by the config, over the
+    /// established tunnel, and applies it without reconnecting. Changes to the interval received on
+    /// `interval_rx` take effect immediately. Failed attempts are reported as events, and the
+    /// tunnel keeps using the current PSK until the next attempt succeeds.
+    async fn psk_rekeying<F>(
+        tunnel: &Arc<Mutex<Option<Box<dyn Tunnel>>>>,
+        mut config: Config,
+        mut interval_rx: watch::Receiver<Option<Duration>>,
+        on_event: F,
+    ) -> Infallible
+    where
+        F: (Fn(TunnelEvent) -> Pin<Box<dyn std::future::Future<Output = ()> + Send>>)
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+    {
+        let mut interval = config.psk_rekey_interval;
+        let mut interval_updates = true;
+        loop {
+            let rekey_timer = async {
+                match interval {
+                    Some(interval) => tokio::time::sleep(interval).await,
+                    None => futures::future::pending().await,
+                }
+            };
+            tokio::select! {
+                () = rekey_timer => (),
+                result = interval_rx.changed(), if interval_updates => {
+                    match result {
+                        Ok(()) => {
+                            interval = *interval_rx.borrow_and_update();
+                            log::debug!("PSK rekey interval changed to {interval:?}");
+                        }
+                        // The sender is gone, so keep the current interval
+                        Err(_) => interval_updates = false,
+                    }
+                    continue;
+                }
+            }
+
+            match Self::rekey_psk(tunnel, &config).await {
+                Ok(new_config) => {
+                    log::debug!("Successfully renegotiated PSK");
+                    config = new_config;
+                }
+                Err(close_msg) => {
+                    if let CloseMsg::SetupError(error) = close_msg {
+                        log::error!(
+                            "{}",
+                            error.display_chain_with_msg("Failed to renegotiate PSK")
+                        );
+                    }
+                    (on_event)(TunnelEvent::PskRenegotiationFailed).await;
+                }
+            }
+        }
+    }
+
+    /// Negotiates new PSKs with the relays and applies them, along with a new ephemeral private
+    /// key. Returns the new config used by the tunnel. As during setup, the entry relay of a
+    /// multihop tunnel is reached by temporarily routing the gateway IP to the entry peer. The
+    /// tunnel is restored to `config` if that fails.
+    async fn rekey_psk(
+        tunnel: &Arc<Mutex<Option<Box<dyn Tunnel>>>>,
+        config: &Config,
+    ) -> std::result::Result<Config, CloseMsg> {
+        let wg_psk_privkey = PrivateKey::new_from_random();
+        let exit_psk =
+            Self::perform_psk_negotiation(0, config, wg_psk_privkey.public_key()).await?;
+
+        let mut new_config = config.clone();
+        if config.is_multihop() {
+            let mut entry_tun_config = config.clone();
+            entry_tun_config
+                .entry_peer
+                .allowed_ips
+                .push(IpNetwork::new(IpAddr::V4(config.ipv4_gateway), 32).unwrap());
+            Self::set_tunnel_config(tunnel, entry_tun_config.clone()).await?;
+
+            let entry_psk = match Self::perform_psk_negotiation(
+                0,
+                &entry_tun_config,
+                wg_psk_privkey.public_key(),
+            )
+            .await
+            {
+                Ok(entry_psk) => entry_psk,
+                Err(close_msg) => {
+                    Self::set_tunnel_config(tunnel, config.clone()).await?;
+                    return Err(close_msg);
+                }
+            };
+            new_config.entry_peer.psk = Some(entry_psk);
+        }
+        new_config.exit_peer_mut().psk = Some(exit_psk);
+        new_config.tunnel.private_key = wg_psk_privkey;
+
+        Self::set_tunnel_config(tunnel, new_config.clone()).await?;
+
+        Ok(new_config)
+    }
+
+    /// Applies `config` to the tunnel, if it is still running.
+    async fn set_tunnel_config(
+        tunnel: &Arc<Mutex<Option<Box<dyn Tunnel>>>>,
+        config: Config,
+    ) -> std::result::Result<(), CloseMsg> {
+        let set_config_future = tunnel
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|tunnel| tunnel.set_config(config));
+        if let Some(f) = set_config_future {
+            f.await
+                .map_err(Error::TunnelError)
+                .map_err(CloseMsg::SetupError)?;
+        }
+        Ok(())
+    }
+
     /// Reconfigures the tunnel to use the provided config while potentially modifying the config
     /// and restarting the obfuscation provider. Returns the new config used by the new tunnel.
     async fn reconfigure_tunnel(
@@ -594,16 +822,7 @@ impl WireguardMonitor {
             }
         }
 
-        let set_config_future = tunnel
-            .lock()
-            .unwrap()
-            .as_ref()
-            .map(|tunnel| tunnel.set_config(config.clone()));
-        if let Some(f) = set_config_future {
-            f.await
-                .map_err(Error::TunnelError)
-                .map_err(CloseMsg::SetupError)?;
-        }
+        Self::set_tunnel_config(tunnel, config.clone()).await?;
 
         Ok(config)
     }
@@ -683,13 +902,20 @@ impl WireguardMonitor {
         config: &Config,
         wg_psk_pubkey: PublicKey,
     ) -> std::result::Result<PresharedKey, CloseMsg> {
-        log::debug!("Performing PQ-safe PSK exchange");
-
         let timeout = std::cmp::min(
             MAX_PSK_EXCHANGE_TIMEOUT,
             INITIAL_PSK_EXCHANGE_TIMEOUT
                 .saturating_mul(PSK_EXCHANGE_TIMEOUT_MULTIPLIER.saturating_pow(retry_attempt)),
         );
+        Self::perform_psk_negotiation_with_timeout(timeout, config, wg_psk_pubkey).await
+    }
+
+    async fn perform_psk_negotiation_with_timeout(
+        timeout: Duration,
+        config: &Config,
+        wg_psk_pubkey: PublicKey,
+    ) -> std::result::Result<PresharedKey, CloseMsg> {
+        log::debug!("Performing PQ-safe PSK exchange");
 
         let psk = tokio::time::timeout(
             timeout,
@@ -726,7 +952,7 @@ impl WireguardMonitor {
         #[cfg(target_os = "linux")]
         if !*FORCE_USERSPACE_WIREGUARD {
             if will_nm_manage_dns() {
-                match wireguard_kernel::NetworkManagerTunnel::new(runtime, config) {
+                match wireguard_kernel::NetworkManagerTunnel::new(runtime.clone(), config) {
                     Ok(tunnel) => {
                         log::debug!("Using NetworkManager to use kernel WireGuard implementation");
                         return Ok(Box::new(tunnel));
@@ -741,7 +967,7 @@ impl WireguardMonitor {
                     }
                 };
             } else {
-                match wireguard_kernel::NetlinkTunnel::new(runtime, config) {
+                match wireguard_kernel::NetlinkTunnel::new(runtime.clone(), config) {
                     Ok(tunnel) => {
                         log::debug!("Using kernel WireGuard implementation");
                         return Ok(Box::new(tunnel));
@@ -765,7 +991,12 @@ impl WireguardMonitor {
                 .map_err(Error::TunnelError)
         }
 
-        #[cfg(wireguard_go)]
+        #[cfg(target_os = "linux")]
+        {
+            Self::open_userspace_tunnel(runtime, config, log_path, tun_provider)
+        }
+
+        #[cfg(all(wireguard_go, not(target_os = "linux")))]
         {
             let routes =
                 Self::get_tunnel_destinations(config).flat_map(Self::replace_default_prefixes);
@@ -773,8 +1004,6 @@ impl WireguardMonitor {
             #[cfg(target_os = "android")]
             let config = Self::patch_allowed_ips(config, psk_negotiation);
 
-            #[cfg(target_os = "linux")]
-            log::debug!("Using userspace WireGuard implementation");
             Ok(Box::new(
                 WgGoTunnel::start_tunnel(
                     #[allow(clippy::needless_borrow)]
@@ -788,6 +1017,46 @@ impl WireguardMonitor {
         }
     }
 
+    #[cfg(target_os = "linux")]
+    #[allow(unused_variables)]
+    fn open_userspace_tunnel(
+        runtime: tokio::runtime::Handle,
+        config: &Config,
+        log_path: Option<&Path>,
+        tun_provider: Arc<Mutex<TunProvider>>,
+    ) -> Result<Box<dyn Tunnel>> {
+        let routes = Self::get_tunnel_destinations(config).flat_map(Self::replace_default_prefixes);
+
+        match *USERSPACE_WIREGUARD_IMPLEMENTATION {
+            #[cfg(wireguard_go)]
+            UserspaceImplementation::WireguardGo => {
+                log::debug!("Using userspace WireGuard implementation");
+                Ok(Box::new(
+                    WgGoTunnel::start_tunnel(config, log_path, tun_provider, routes)
+                        .map_err(Error::TunnelError)?,
+                ))
+            }
+            #[cfg(feature = "boringtun")]
+            UserspaceImplementation::Boringtun => {
+                log::debug!("Using boringtun userspace WireGuard implementation");
+                Ok(Box::new(
+                    boringtun::BoringTun::start_tunnel(
+                        runtime,
+                        config,
+                        log_path,
+                        tun_provider,
+                        routes,
+                    )
+                    .map_err(Error::TunnelError)?,
+                ))
+            }
+            #[allow(unreachable_patterns)]
+            implementation => Err(Error::TunnelError(
+                TunnelError::UserspaceImplementationUnavailable(implementation),
+            )),
+        }
+    }
+
     /// Blocks the current thread until tunnel disconnects
     pub fn wait(mut self) -> Result<()> {
         let wait_result = match self.close_msg_receiver.recv() {
@@ -858,10 +1127,26 @@ impl WireguardMonitor {
         }
     }
 
-    /// Return routes for all allowed IPs, as well as the gateway, except 0.0.0.0/0.
+    /// Returns an error unless `target` is covered by the allowed IPs of the exit peer, since it
+    /// would otherwise never respond to the connectivity check.
+    fn validate_ping_target(config: &Config, target: Ipv4Addr) -> Result<()> {
+        let allowed = config
+            .exit_peer()
+            .allowed_ips
+            .iter()
+            .any(|allowed_ip| allowed_ip.contains(IpAddr::V4(target)));
+        if !allowed {
+            return Err(Error::PingTargetNotAllowed(target));
+        }
+        Ok(())
+    }
+
+    /// Return routes for all allowed IPs, as well as the gateway and the connectivity check
+    /// target, except 0.0.0.0/0.
     fn get_pre_tunnel_routes<'a>(
         iface_name: &str,
         config: &'a Config,
+        ping_target: Option<Ipv4Addr>,
     ) -> impl Iterator<Item = RequiredRoute> + 'a {
         let gateway_node = routing::Node::device(iface_name.to_string());
         let gateway_routes = std::iter::once(RequiredRoute::new(
@@ -878,7 +1163,14 @@ impl WireguardMonitor {
         let gateway_routes =
             gateway_routes.map(|route| Self::apply_route_mtu_for_multihop(route, config));
 
-        let routes = gateway_routes.chain(
+        // The default route is not added until the connectivity check has succeeded
+        let ping_target_route = ping_target
+            .filter(|&target| target != config.ipv4_gateway)
+            .map(|target| {
+                RequiredRoute::new(ipnetwork::Ipv4Network::from(target).into(), node_v4.clone())
+            });
+
+        let routes = gateway_routes.chain(ping_target_route).chain(
             Self::get_tunnel_destinations(config)
                 .filter(|allowed_ip| allowed_ip.prefix() != 0)
                 .map(move |allowed_ip| {
@@ -972,6 +1264,7 @@ impl WireguardMonitor {
             ips: config.tunnel.addresses.clone(),
             ipv4_gateway: config.ipv4_gateway,
             ipv6_gateway: config.ipv6_gateway,
+            openvpn_session: None,
         }
     }
 }
@@ -1070,6 +1363,21 @@ pub enum TunnelError {
     /// Failure to set up logging
     #[error("Failed to set up logging")]
     LoggingError(#[source] logging::Error),
+
+    /// The selected userspace implementation is not part of this build
+    #[cfg(target_os = "linux")]
+    #[error("The {0} userspace WireGuard implementation is not available in this build")]
+    UserspaceImplementationUnavailable(UserspaceImplementation),
+
+    /// Failed to set the MTU of the tunnel device
+    #[cfg(all(target_os = "linux", feature = "boringtun"))]
+    #[error("Failed to set tunnel device MTU")]
+    SetMtuError(#[source] io::Error),
+
+    /// Failed to set up sockets or the tunnel device for boringtun
+    #[cfg(all(target_os = "linux", feature = "boringtun"))]
+    #[error("Failed to set up boringtun")]
+    BoringtunSetupError(#[source] io::Error),
 }
 
 #[cfg(target_os = "linux")]
@@ -1087,3 +1395,267 @@ fn will_nm_manage_dns() -> bool {
         })
         .unwrap_or(false)
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+    use futures::{channel::mpsc, StreamExt};
+    use talpid_tunnel_config_client::mock_relay::{Fault, MockRelay, MockRelayHandle};
+    use talpid_types::net::wireguard::{PeerConfig, TunnelConfig};
+
+    /// Spawns `relay` on its own loopback address and returns a config with that address as the
+    /// gateway.
+    async fn spawn_relay(relay: &MockRelay, last_octet: u8) -> (Config, MockRelayHandle) {
+        let gateway = Ipv4Addr::new(127, 0, 14, last_octet);
+        let handle = relay.clone().spawn(IpAddr::V4(gateway)).await.unwrap();
+        (test_config(gateway), handle)
+    }
+
+    fn test_config(gateway: Ipv4Addr) -> Config {
+        Config {
+            tunnel: TunnelConfig {
+                private_key: PrivateKey::new_from_random(),
+                addresses: vec![IpAddr::V4(Ipv4Addr::new(10, 64, 0, 2))],
+            },
+            entry_peer: PeerConfig {
+                public_key: PrivateKey::new_from_random().public_key(),
+                allowed_ips: vec!["0.0.0.0/0".parse().unwrap()],
+                endpoint: SocketAddr::new(IpAddr::V4(gateway), 51820),
+                psk: None,
+            },
+            exit_peer: None,
+            ipv4_gateway: gateway,
+            ipv6_gateway: None,
+            mtu: 1380,
+            persistent_keepalive: None,
+            psk_rekey_interval: None,
+            fwmark: None,
+            enable_ipv6: false,
+            obfuscator_config: None,
+        }
+    }
+
+    #[test]
+    fn test_ping_target_must_be_allowed() {
+        let gateway = Ipv4Addr::new(10, 64, 0, 1);
+        let mut config = test_config(gateway);
+        config.entry_peer.allowed_ips = vec!["10.64.0.0/16".parse().unwrap()];
+
+        assert!(WireguardMonitor::validate_ping_target(&config, gateway).is_ok());
+        assert!(matches!(
+            WireguardMonitor::validate_ping_target(&config, Ipv4Addr::new(1, 1, 1, 1)),
+            Err(Error::PingTargetNotAllowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_pre_tunnel_route_for_ping_target() {
+        let config = test_config(Ipv4Addr::new(10, 64, 0, 1));
+        let target = Ipv4Addr::new(10, 64, 0, 53);
+        let target_prefix = IpNetwork::from(IpAddr::V4(target));
+
+        let routes: Vec<_> =
+            WireguardMonitor::get_pre_tunnel_routes("wg0-mullvad", &config, Some(target))
+                .map(|route| route.prefix)
+                .collect();
+        assert!(routes.contains(&target_prefix));
+        // The default route must wait for the connectivity check
+        assert!(routes.iter().all(|prefix| prefix.prefix() != 0));
+
+        let routes: Vec<_> = WireguardMonitor::get_pre_tunnel_routes("wg0-mullvad", &config, None)
+            .map(|route| route.prefix)
+            .collect();
+        assert!(!routes.contains(&target_prefix));
+    }
+
+    #[tokio::test]
+    async fn test_psk_negotiation() {
+        let relay = MockRelay::new();
+        let (config, _handle) = spawn_relay(&relay, 1).await;
+        let psk = WireguardMonitor::perform_psk_negotiation(
+            0,
+            &config,
+            PrivateKey::new_from_random().public_key(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(psk.as_bytes(), &relay.psk().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_psk_negotiation_invalid_response() {
+        let relay = MockRelay::v1_only().with_fault(Fault::ExtraCiphertext);
+        let (config, _handle) = spawn_relay(&relay, 2).await;
+        let result = WireguardMonitor::perform_psk_negotiation(
+            0,
+            &config,
+            PrivateKey::new_from_random().public_key(),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(CloseMsg::SetupError(Error::PskNegotiationError(
+                talpid_tunnel_config_client::Error::InvalidCiphertextCount { actual: 3 }
+            )))
+        ));
+    }
+
+    /// Tunnel that records the configs that are applied to it.
+    struct RecordingTunnel(Arc<Mutex<Vec<Config>>>);
+
+    impl Tunnel for RecordingTunnel {
+        fn get_interface_name(&self) -> String {
+            "wg-test".to_owned()
+        }
+
+        fn stop(self: Box<Self>) -> std::result::Result<(), TunnelError> {
+            Ok(())
+        }
+
+        fn get_tunnel_stats(&self) -> std::result::Result<stats::StatsMap, TunnelError> {
+            Ok(stats::StatsMap::new())
+        }
+
+        fn set_config(
+            &self,
+            config: Config,
+        ) -> Pin<Box<dyn Future<Output = std::result::Result<(), TunnelError>> + Send>> {
+            self.0.lock().unwrap().push(config);
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_psk_rekey() {
+        let relay = MockRelay::new();
+        let (config, _handle) = spawn_relay(&relay, 4).await;
+        let applied_configs = Arc::new(Mutex::new(vec![]));
+        let tunnel: Arc<Mutex<Option<Box<dyn Tunnel>>>> = Arc::new(Mutex::new(Some(Box::new(
+            RecordingTunnel(applied_configs.clone()),
+        ))));
+
+        let new_config = WireguardMonitor::rekey_psk(&tunnel, &config).await.unwrap();
+
+        let new_psk = new_config.entry_peer.psk.as_ref().unwrap();
+        assert_eq!(new_psk.as_bytes(), &relay.psk().unwrap());
+        assert_ne!(
+            new_config.tunnel.private_key.public_key().as_bytes(),
+            config.tunnel.private_key.public_key().as_bytes()
+        );
+
+        let applied_configs = applied_configs.lock().unwrap();
+        assert_eq!(applied_configs.len(), 1);
+        assert_eq!(applied_configs[0].entry_peer.psk.as_ref(), Some(new_psk));
+    }
+
+    #[tokio::test]
+    async fn test_psk_rekey_multihop() {
+        let relay = MockRelay::new();
+        let (mut config, _handle) = spawn_relay(&relay, 7).await;
+        let mut exit_peer = config.entry_peer.clone();
+        exit_peer.public_key = PrivateKey::new_from_random().public_key();
+        config.entry_peer.allowed_ips = vec![];
+        config.exit_peer = Some(exit_peer);
+        let applied_configs = Arc::new(Mutex::new(vec![]));
+        let tunnel: Arc<Mutex<Option<Box<dyn Tunnel>>>> = Arc::new(Mutex::new(Some(Box::new(
+            RecordingTunnel(applied_configs.clone()),
+        ))));
+
+        let new_config = WireguardMonitor::rekey_psk(&tunnel, &config).await.unwrap();
+
+        assert!(new_config.entry_peer.psk.is_some());
+        assert!(new_config.exit_peer().psk.is_some());
+        assert!(new_config.entry_peer.allowed_ips.is_empty());
+
+        // The entry relay is reached through the gateway IP while negotiating with it
+        let applied_configs = applied_configs.lock().unwrap();
+        assert_eq!(applied_configs.len(), 2);
+        assert_eq!(
+            applied_configs[0].entry_peer.allowed_ips,
+            vec![IpNetwork::new(IpAddr::V4(config.ipv4_gateway), 32).unwrap()]
+        );
+        assert!(applied_configs[1].entry_peer.allowed_ips.is_empty());
+        assert_eq!(applied_configs[1].entry_peer.psk, new_config.entry_peer.psk);
+        assert_eq!(
+            applied_configs[1].exit_peer().psk,
+            new_config.exit_peer().psk
+        );
+    }
+
+    #[tokio::test]
+    async fn test_psk_rekey_failure_keeps_config() {
+        let relay = MockRelay::new().with_fault(Fault::TruncatedCiphertext);
+        let (config, _handle) = spawn_relay(&relay, 5).await;
+        let applied_configs = Arc::new(Mutex::new(vec![]));
+        let tunnel: Arc<Mutex<Option<Box<dyn Tunnel>>>> = Arc::new(Mutex::new(Some(Box::new(
+            RecordingTunnel(applied_configs.clone()),
+        ))));
+
+        assert!(WireguardMonitor::rekey_psk(&tunnel, &config).await.is_err());
+        assert!(applied_configs.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_psk_rekey_interval_change_and_failure_event() {
+        let relay = MockRelay::new().with_fault(Fault::TruncatedCiphertext);
+        let (config, _handle) = spawn_relay(&relay, 6).await;
+        let applied_configs = Arc::new(Mutex::new(vec![]));
+        let tunnel: Arc<Mutex<Option<Box<dyn Tunnel>>>> = Arc::new(Mutex::new(Some(Box::new(
+            RecordingTunnel(applied_configs.clone()),
+        ))));
+
+        let (event_tx, mut event_rx) = mpsc::unbounded();
+        let on_event = move |event| -> Pin<Box<dyn std::future::Future<Output = ()> + Send>> {
+            let _ = event_tx.unbounded_send(event);
+            Box::pin(async {})
+        };
+        let (interval_tx, interval_rx) = watch::channel(None);
+        let rekeying = tokio::spawn(async move {
+            WireguardMonitor::psk_rekeying(&tunnel, config, interval_rx, on_event).await
+        });
+
+        // Rekeying is disabled by the initial config, so it only happens after the interval is set
+        interval_tx.send_replace(Some(Duration::from_millis(10)));
+        let event = tokio::time::timeout(Duration::from_secs(5), event_rx.next())
+            .await
+            .expect("PSK renegotiation was not attempted");
+        assert_eq!(event, Some(TunnelEvent::PskRenegotiationFailed));
+        assert!(applied_configs.lock().unwrap().is_empty());
+
+        rekeying.abort();
+    }
+
+    /// Accepts connections to the config service on `gateway` but never responds to them.
+    async fn spawn_unresponsive_relay(gateway: Ipv4Addr) -> tokio::task::JoinHandle<()> {
+        let listener = tokio::net::TcpListener::bind((
+            gateway,
+            talpid_tunnel_config_client::CONFIG_SERVICE_PORT,
+        ))
+        .await
+        .unwrap();
+        tokio::spawn(async move {
+            // Keep the connections open, so that the client keeps waiting for a response
+            let mut connections = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_psk_negotiation_timeout() {
+        let gateway = Ipv4Addr::new(127, 0, 14, 3);
+        let relay = spawn_unresponsive_relay(gateway).await;
+        let config = test_config(gateway);
+
+        let result = WireguardMonitor::perform_psk_negotiation_with_timeout(
+            Duration::from_millis(500),
+            &config,
+            PrivateKey::new_from_random().public_key(),
+        )
+        .await;
+        assert!(matches!(result, Err(CloseMsg::PskNegotiationTimeout)));
+
+        relay.abort();
+    }
+}
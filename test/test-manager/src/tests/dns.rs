@@ -81,6 +81,10 @@ pub async fn test_dns_leak_custom_public_ip(
                 addresses: vec![CONFIG_IP],
             },
             state: settings::DnsState::Custom,
+            #[cfg(target_os = "macos")]
+            blocklists: Default::default(),
+            #[cfg(target_os = "linux")]
+            manager: Default::default(),
         })
         .await
         .expect("failed to configure DNS server");
@@ -113,6 +117,10 @@ pub async fn test_dns_leak_custom_private_ip(
                 addresses: vec![CONFIG_IP],
             },
             state: settings::DnsState::Custom,
+            #[cfg(target_os = "macos")]
+            blocklists: Default::default(),
+            #[cfg(target_os = "linux")]
+            manager: Default::default(),
         })
         .await
         .expect("failed to configure DNS server");
@@ -367,6 +375,10 @@ pub async fn test_dns_config_custom_private(
                 addresses: vec![IpAddr::V4(NON_TUN_GATEWAY)],
             },
             state: settings::DnsState::Custom,
+            #[cfg(target_os = "macos")]
+            blocklists: Default::default(),
+            #[cfg(target_os = "linux")]
+            manager: Default::default(),
         })
         .await
         .expect("failed to configure DNS server");
@@ -396,6 +408,10 @@ pub async fn test_dns_config_custom_public(
                 addresses: vec![custom_ip],
             },
             state: settings::DnsState::Custom,
+            #[cfg(target_os = "macos")]
+            blocklists: Default::default(),
+            #[cfg(target_os = "linux")]
+            manager: Default::default(),
         })
         .await
         .expect("failed to configure DNS server");
@@ -500,6 +516,10 @@ pub async fn test_content_blockers(
                 default_options: test_opts,
                 custom_options: settings::CustomDnsOptions::default(),
                 state: settings::DnsState::Default,
+                #[cfg(target_os = "macos")]
+                blocklists: Default::default(),
+                #[cfg(target_os = "linux")]
+                manager: Default::default(),
             })
             .await
             .expect("failed to configure DNS server");
@@ -654,6 +674,7 @@ async fn connect_local_wg_relay(mullvad_client: &mut MullvadProxyClient) -> Resu
             fwmark: None,
             ipv6_gateway: None,
         }),
+        wireguard_options: Default::default(),
     });
 
     set_relay_settings(mullvad_client, relay_settings)
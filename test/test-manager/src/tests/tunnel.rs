@@ -146,6 +146,7 @@ pub async fn test_udp2tcp_tunnel(
             udp2tcp: Udp2TcpObfuscationSettings {
                 port: Constraint::Any,
             },
+            ..Default::default()
         })
         .await
         .expect("failed to enable udp2tcp");
@@ -554,6 +555,7 @@ pub async fn test_quantum_resistant_multihop_udp2tcp_tunnel(
             udp2tcp: Udp2TcpObfuscationSettings {
                 port: Constraint::Any,
             },
+            ..Default::default()
         })
         .await
         .expect("Failed to enable obfuscation");
@@ -191,6 +191,7 @@ pub async fn test_connecting_state(
     let relay_settings = RelaySettings::CustomTunnelEndpoint(CustomTunnelEndpoint {
         host: "1.3.3.7".to_owned(),
         config: mullvad_types::ConnectionConfig::Wireguard(unreachable_wireguard_tunnel()),
+        wireguard_options: Default::default(),
     });
 
     set_relay_settings(&mut mullvad_client, relay_settings)
@@ -390,6 +391,8 @@ pub async fn test_connected_state(
                     obfuscation: None,
                     entry_endpoint: None,
                     tunnel_interface: _,
+                    openvpn_session: None,
+                    reconnect_reason: _,
                 },
             ..
         } => {
@@ -1,7 +1,15 @@
 use async_trait::async_trait;
 use std::net::SocketAddr;
 
+#[cfg(feature = "quic")]
+mod quic;
+#[cfg(feature = "quic-server")]
+pub mod quic_server;
+mod shadowsocks;
 mod udp2tcp;
+#[cfg(feature = "quic")]
+pub use quic::QuicSettings;
+pub use shadowsocks::{ShadowsocksCredentials, ShadowsocksSettings};
 pub use udp2tcp::Udp2TcpSettings;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -13,6 +21,20 @@ pub enum Error {
 
     #[error("Failed to run Udp2Tcp obfuscator")]
     RunUdp2TcpObfuscator(#[source] udp2tcp::Error),
+
+    #[error("Failed to create Shadowsocks obfuscator")]
+    CreateShadowsocksObfuscator(#[source] shadowsocks::Error),
+
+    #[error("Failed to run Shadowsocks obfuscator")]
+    RunShadowsocksObfuscator(#[source] shadowsocks::Error),
+
+    #[cfg(feature = "quic")]
+    #[error("Failed to create QUIC obfuscator")]
+    CreateQuicObfuscator(#[source] quic::Error),
+
+    #[cfg(feature = "quic")]
+    #[error("Failed to run QUIC obfuscator")]
+    RunQuicObfuscator(#[source] quic::Error),
 }
 
 #[async_trait]
@@ -29,6 +51,9 @@ pub trait Obfuscator: Send {
 
 pub enum Settings {
     Udp2Tcp(Udp2TcpSettings),
+    Shadowsocks(ShadowsocksSettings),
+    #[cfg(feature = "quic")]
+    Quic(QuicSettings),
 }
 
 pub async fn create_obfuscator(settings: &Settings) -> Result<Box<dyn Obfuscator>> {
@@ -36,5 +61,12 @@ pub async fn create_obfuscator(settings: &Settings) -> Result<Box<dyn Obfuscator
         Settings::Udp2Tcp(s) => udp2tcp::create_obfuscator(s)
             .await
             .map_err(Error::CreateUdp2TcpObfuscator),
+        Settings::Shadowsocks(s) => shadowsocks::create_obfuscator(s)
+            .await
+            .map_err(Error::CreateShadowsocksObfuscator),
+        #[cfg(feature = "quic")]
+        Settings::Quic(s) => quic::create_obfuscator(s)
+            .await
+            .map_err(Error::CreateQuicObfuscator),
     }
 }
@@ -1,5 +1,9 @@
 use std::{env::args, net::SocketAddr};
-use tunnel_obfuscation::{create_obfuscator, Obfuscator, Settings, Udp2TcpSettings};
+#[cfg(feature = "quic")]
+use tunnel_obfuscation::QuicSettings;
+use tunnel_obfuscation::{
+    create_obfuscator, Obfuscator, Settings, ShadowsocksSettings, Udp2TcpSettings,
+};
 
 #[tokio::main]
 async fn main() {
@@ -29,6 +33,35 @@ async fn instantiate_requested(obfuscator_type: &str) -> Box<dyn Obfuscator> {
                 .await
                 .expect("Creating obfuscator failed")
         }
+        "shadowsocks" => {
+            let settings = ShadowsocksSettings {
+                shadowsocks_endpoint: SocketAddr::new("127.0.0.1".parse().unwrap(), 3030),
+                wireguard_endpoint: SocketAddr::new("127.0.0.1".parse().unwrap(), 51820),
+                credentials: None,
+                #[cfg(target_os = "linux")]
+                fwmark: Some(1337),
+            };
+
+            create_obfuscator(&Settings::Shadowsocks(settings))
+                .await
+                .expect("Creating obfuscator failed")
+        }
+        #[cfg(feature = "quic")]
+        "quic" => {
+            // Certificate written by `mullvad-obfuscation-server quic`
+            let certificate = std::fs::read("quic-server.der").expect("Failed to read certificate");
+            let settings = QuicSettings {
+                quic_endpoint: SocketAddr::new("127.0.0.1".parse().unwrap(), 3030),
+                hostname: "localhost".to_owned(),
+                trusted_certificates: vec![certificate],
+                #[cfg(target_os = "linux")]
+                fwmark: Some(1337),
+            };
+
+            create_obfuscator(&Settings::Quic(settings))
+                .await
+                .expect("Creating obfuscator failed")
+        }
         _ => {
             unimplemented!()
         }
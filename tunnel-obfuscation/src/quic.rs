@@ -0,0 +1,289 @@
+use crate::Obfuscator;
+use async_trait::async_trait;
+use bytes::Bytes;
+use quinn::{ClientConfig, Connection, Endpoint, EndpointConfig, SendDatagramError, TokioRuntime};
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::UdpSocket;
+
+/// ALPN protocol advertised during the handshake. Makes the connection look like HTTP/3.
+pub(crate) const ALPN_PROTOCOL: &[u8] = b"h3";
+
+/// How often to send keep-alive packets, so that the connection does not time out while the
+/// tunnel is idle.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Largest possible UDP payload.
+const MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
+
+pub struct QuicSettings {
+    /// Address of the QUIC server
+    pub quic_endpoint: SocketAddr,
+    /// Hostname used for SNI and to verify the certificate of the server
+    pub hostname: String,
+    /// DER-encoded root certificates that the certificate of the server must be signed by. If
+    /// empty, it must be signed by a publicly trusted root instead
+    pub trusted_certificates: Vec<Vec<u8>>,
+    #[cfg(target_os = "linux")]
+    pub fwmark: Option<u32>,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Failed to bind the local UDP socket
+    #[error("Failed to bind local UDP socket")]
+    BindLocalSocket(#[source] io::Error),
+
+    /// Failed to determine UDP socket details
+    #[error("Failed to determine UDP socket details")]
+    GetUdpSocketDetails(#[source] io::Error),
+
+    /// Failed to create the socket used to talk to the QUIC server
+    #[error("Failed to create remote UDP socket")]
+    BindRemoteSocket(#[source] io::Error),
+
+    /// A trusted certificate could not be parsed
+    #[error("Invalid trusted certificate")]
+    InvalidCertificate,
+
+    /// Failed to create the QUIC endpoint
+    #[error("Failed to create QUIC endpoint")]
+    CreateEndpoint(#[source] io::Error),
+
+    /// Failed to initiate the QUIC connection
+    #[error("Failed to connect to QUIC server")]
+    Connect(#[source] quinn::ConnectError),
+
+    /// The QUIC connection failed or was closed
+    #[error("QUIC connection failed")]
+    Connection(#[source] quinn::ConnectionError),
+
+    /// The QUIC server does not accept datagrams
+    #[error("QUIC server does not support datagrams")]
+    DatagramsUnsupported,
+
+    /// Failed to receive a packet from the local WireGuard client
+    #[error("Failed to receive packet from WireGuard")]
+    ReceiveLocal(#[source] io::Error),
+
+    /// Failed to send a packet to the local WireGuard client
+    #[error("Failed to send packet to WireGuard")]
+    SendLocal(#[source] io::Error),
+
+    /// Failed to connect the local socket to the WireGuard client
+    #[error("Failed to connect local UDP socket")]
+    ConnectLocal(#[source] io::Error),
+
+    /// Failed to send a datagram to the QUIC server
+    #[error("Failed to send datagram to QUIC server")]
+    SendDatagram(#[source] SendDatagramError),
+}
+
+struct Quic {
+    local_addr: SocketAddr,
+    local_socket: UdpSocket,
+    // The endpoint drives the connection, so it must be kept alive along with it.
+    _endpoint: Endpoint,
+    connection: Connection,
+    #[cfg(target_os = "android")]
+    remote_socket_fd: std::os::unix::io::RawFd,
+}
+
+impl Quic {
+    pub async fn new(settings: &QuicSettings) -> Result<Self> {
+        let (listen_addr, remote_bind_addr) = if settings.quic_endpoint.is_ipv4() {
+            (
+                SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+                SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            )
+        } else {
+            (
+                SocketAddr::from((Ipv6Addr::LOCALHOST, 0)),
+                SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+            )
+        };
+        let local_socket = UdpSocket::bind(listen_addr)
+            .await
+            .map_err(Error::BindLocalSocket)?;
+        let local_addr = local_socket
+            .local_addr()
+            .map_err(Error::GetUdpSocketDetails)?;
+
+        let remote_socket = Self::bind_remote_socket(
+            remote_bind_addr,
+            #[cfg(target_os = "linux")]
+            settings.fwmark,
+        )
+        .map_err(Error::BindRemoteSocket)?;
+        #[cfg(target_os = "android")]
+        let remote_socket_fd = {
+            use std::os::unix::io::AsRawFd;
+            remote_socket.as_raw_fd()
+        };
+
+        let endpoint = Endpoint::new(
+            EndpointConfig::default(),
+            None,
+            remote_socket,
+            Arc::new(TokioRuntime),
+        )
+        .map_err(Error::CreateEndpoint)?;
+        let connection = endpoint
+            .connect_with(
+                Self::client_config(&settings.trusted_certificates)?,
+                settings.quic_endpoint,
+                &settings.hostname,
+            )
+            .map_err(Error::Connect)?
+            .await
+            .map_err(Error::Connection)?;
+        if connection.max_datagram_size().is_none() {
+            return Err(Error::DatagramsUnsupported);
+        }
+
+        Ok(Self {
+            local_addr,
+            local_socket,
+            _endpoint: endpoint,
+            connection,
+            #[cfg(target_os = "android")]
+            remote_socket_fd,
+        })
+    }
+
+    fn bind_remote_socket(
+        addr: SocketAddr,
+        #[cfg(target_os = "linux")] fwmark: Option<u32>,
+    ) -> io::Result<std::net::UdpSocket> {
+        let socket = socket2::Socket::new(
+            socket2::Domain::for_address(addr),
+            socket2::Type::DGRAM,
+            Some(socket2::Protocol::UDP),
+        )?;
+        #[cfg(target_os = "linux")]
+        if let Some(fwmark) = fwmark {
+            socket.set_mark(fwmark)?;
+        }
+        socket.bind(&addr.into())?;
+        Ok(socket.into())
+    }
+
+    fn client_config(trusted_certificates: &[Vec<u8>]) -> Result<ClientConfig> {
+        let mut roots = rustls::RootCertStore::empty();
+        if trusted_certificates.is_empty() {
+            roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+                rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    anchor.subject,
+                    anchor.spki,
+                    anchor.name_constraints,
+                )
+            }));
+        } else {
+            let (_, invalid) = roots.add_parsable_certificates(trusted_certificates);
+            if invalid > 0 {
+                return Err(Error::InvalidCertificate);
+            }
+        }
+        let mut crypto = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        crypto.alpn_protocols = vec![ALPN_PROTOCOL.to_vec()];
+
+        let mut transport = quinn::TransportConfig::default();
+        transport.keep_alive_interval(Some(KEEP_ALIVE_INTERVAL));
+
+        let mut config = ClientConfig::new(Arc::new(crypto));
+        config.transport_config(Arc::new(transport));
+        Ok(config)
+    }
+
+    async fn relay(self) -> Result<()> {
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+
+        // Only accept traffic from whoever sends the first packet, i.e. the WireGuard client
+        let (len, client_addr) = self
+            .local_socket
+            .recv_from(&mut buf)
+            .await
+            .map_err(Error::ReceiveLocal)?;
+        self.local_socket
+            .connect(client_addr)
+            .await
+            .map_err(Error::ConnectLocal)?;
+        self.send_datagram(&buf[..len])?;
+
+        tokio::select! {
+            result = self.forward_outgoing(buf) => result,
+            result = self.forward_incoming() => result,
+        }
+    }
+
+    /// Forwards packets from the WireGuard client to the QUIC server.
+    async fn forward_outgoing(&self, mut buf: Vec<u8>) -> Result<()> {
+        loop {
+            let len = self
+                .local_socket
+                .recv(&mut buf)
+                .await
+                .map_err(Error::ReceiveLocal)?;
+            self.send_datagram(&buf[..len])?;
+        }
+    }
+
+    /// Forwards packets from the QUIC server to the WireGuard client.
+    async fn forward_incoming(&self) -> Result<()> {
+        loop {
+            let datagram = self
+                .connection
+                .read_datagram()
+                .await
+                .map_err(Error::Connection)?;
+            self.local_socket
+                .send(&datagram)
+                .await
+                .map_err(Error::SendLocal)?;
+        }
+    }
+
+    fn send_datagram(&self, packet: &[u8]) -> Result<()> {
+        match self
+            .connection
+            .send_datagram(Bytes::copy_from_slice(packet))
+        {
+            // Packets that do not fit in a datagram are dropped, like on any other link with a
+            // too small MTU.
+            Err(SendDatagramError::TooLarge) => {
+                log::trace!("Dropping packet of {} bytes", packet.len());
+                Ok(())
+            }
+            result => result.map_err(Error::SendDatagram),
+        }
+    }
+}
+
+#[async_trait]
+impl Obfuscator for Quic {
+    fn endpoint(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    async fn run(self: Box<Self>) -> crate::Result<()> {
+        self.relay().await.map_err(crate::Error::RunQuicObfuscator)
+    }
+
+    #[cfg(target_os = "android")]
+    fn remote_socket_fd(&self) -> std::os::unix::io::RawFd {
+        self.remote_socket_fd
+    }
+}
+
+pub async fn create_obfuscator(settings: &QuicSettings) -> Result<Box<dyn Obfuscator>> {
+    Ok(Box::new(Quic::new(settings).await?))
+}
@@ -0,0 +1,148 @@
+//! Server side of the QUIC obfuscation protocol. Stands in for the relay when testing the QUIC
+//! obfuscator locally: every datagram received on a QUIC connection is forwarded to a fixed UDP
+//! endpoint, such as a WireGuard server, and every reply is sent back as a datagram on the same
+//! connection.
+
+use crate::quic::ALPN_PROTOCOL;
+use bytes::Bytes;
+use quinn::{Connection, Endpoint, SendDatagramError, ServerConfig};
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::net::UdpSocket;
+
+/// Largest possible UDP payload.
+const MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// Failed to generate a self-signed certificate
+    #[error("Failed to generate certificate")]
+    GenerateCertificate(#[source] rcgen::RcgenError),
+
+    /// The certificate or private key was rejected
+    #[error("Invalid certificate or private key")]
+    InvalidCertificate(#[source] rustls::Error),
+
+    /// Failed to bind the QUIC endpoint
+    #[error("Failed to bind QUIC endpoint")]
+    Bind(#[source] io::Error),
+}
+
+/// A DER-encoded certificate along with its DER-encoded private key.
+pub struct Certificate {
+    pub certificate: Vec<u8>,
+    pub private_key: Vec<u8>,
+}
+
+impl Certificate {
+    /// Generates a self-signed certificate for `hostname`. Clients have to trust the certificate
+    /// itself, since it is not signed by any CA.
+    pub fn self_signed(hostname: &str) -> Result<Self, Error> {
+        let certificate = rcgen::generate_simple_self_signed(vec![hostname.to_owned()])
+            .map_err(Error::GenerateCertificate)?;
+        Ok(Self {
+            certificate: certificate
+                .serialize_der()
+                .map_err(Error::GenerateCertificate)?,
+            private_key: certificate.serialize_private_key_der(),
+        })
+    }
+}
+
+pub struct QuicServer {
+    endpoint: Endpoint,
+    forward_addr: SocketAddr,
+}
+
+impl QuicServer {
+    /// Listens for QUIC connections on `listen_addr` and forwards their datagrams to
+    /// `forward_addr`.
+    pub fn bind(
+        listen_addr: SocketAddr,
+        forward_addr: SocketAddr,
+        certificate: &Certificate,
+    ) -> Result<Self, Error> {
+        let mut crypto = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![rustls::Certificate(certificate.certificate.clone())],
+                rustls::PrivateKey(certificate.private_key.clone()),
+            )
+            .map_err(Error::InvalidCertificate)?;
+        crypto.alpn_protocols = vec![ALPN_PROTOCOL.to_vec()];
+
+        let endpoint = Endpoint::server(ServerConfig::with_crypto(Arc::new(crypto)), listen_addr)
+            .map_err(Error::Bind)?;
+        Ok(Self {
+            endpoint,
+            forward_addr,
+        })
+    }
+
+    /// Returns the address that the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.endpoint.local_addr()
+    }
+
+    /// Accepts connections until the endpoint is closed.
+    pub async fn run(self) {
+        while let Some(connecting) = self.endpoint.accept().await {
+            let forward_addr = self.forward_addr;
+            tokio::spawn(async move {
+                let connection = match connecting.await {
+                    Ok(connection) => connection,
+                    Err(error) => {
+                        log::debug!("QUIC handshake failed: {error}");
+                        return;
+                    }
+                };
+                let remote_addr = connection.remote_address();
+                log::debug!("Accepted QUIC connection from {remote_addr}");
+                if let Err(error) = forward(connection, forward_addr).await {
+                    log::debug!("QUIC connection from {remote_addr} closed: {error}");
+                }
+            });
+        }
+    }
+}
+
+/// Forwards datagrams between `connection` and `forward_addr` until either side fails.
+async fn forward(connection: Connection, forward_addr: SocketAddr) -> io::Result<()> {
+    let bind_addr = if forward_addr.is_ipv4() {
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+    } else {
+        SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(forward_addr).await?;
+
+    tokio::select! {
+        result = forward_outgoing(&connection, &socket) => result,
+        result = forward_incoming(&connection, &socket) => result,
+    }
+}
+
+/// Forwards datagrams from the QUIC connection to the UDP socket.
+async fn forward_outgoing(connection: &Connection, socket: &UdpSocket) -> io::Result<()> {
+    loop {
+        let datagram = connection.read_datagram().await?;
+        socket.send(&datagram).await?;
+    }
+}
+
+/// Forwards packets from the UDP socket to the QUIC connection.
+async fn forward_incoming(connection: &Connection, socket: &UdpSocket) -> io::Result<()> {
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    loop {
+        let len = socket.recv(&mut buf).await?;
+        match connection.send_datagram(Bytes::copy_from_slice(&buf[..len])) {
+            Ok(()) => (),
+            Err(SendDatagramError::TooLarge) => log::trace!("Dropping packet of {len} bytes"),
+            Err(error) => return Err(io::Error::new(io::ErrorKind::Other, error)),
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use crate::Obfuscator;
+use async_trait::async_trait;
+use shadowsocks::{
+    config::{ServerConfig, ServerType},
+    context::Context,
+    crypto::CipherKind,
+    net::ConnectOpts,
+    relay::{socks5::Address, udprelay::ProxySocket},
+};
+use std::{io, net::SocketAddr, str::FromStr};
+pub use talpid_types::net::obfuscation::ShadowsocksCredentials;
+use tokio::net::UdpSocket;
+
+/// Cipher used by the Shadowsocks servers on the relays.
+const DEFAULT_SHADOWSOCKS_CIPHER: CipherKind = CipherKind::AES_256_GCM;
+/// Password used by the Shadowsocks servers on the relays.
+const DEFAULT_SHADOWSOCKS_PASSWORD: &str = "mullvad";
+
+/// Largest possible UDP payload.
+const MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
+
+pub struct ShadowsocksSettings {
+    /// Address of the Shadowsocks server
+    pub shadowsocks_endpoint: SocketAddr,
+    /// WireGuard endpoint that the Shadowsocks server should relay traffic to
+    pub wireguard_endpoint: SocketAddr,
+    /// Credentials for the Shadowsocks server, or `None` to use those of the Mullvad relays
+    pub credentials: Option<ShadowsocksCredentials>,
+    #[cfg(target_os = "linux")]
+    pub fwmark: Option<u32>,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    /// The cipher is not supported
+    #[error("Unsupported Shadowsocks cipher: {0}")]
+    InvalidCipher(String),
+
+    /// Failed to bind the local UDP socket
+    #[error("Failed to bind local UDP socket")]
+    BindLocalSocket(#[source] io::Error),
+
+    /// Failed to determine UDP socket details
+    #[error("Failed to determine UDP socket details")]
+    GetUdpSocketDetails(#[source] io::Error),
+
+    /// Failed to create the socket used to talk to the Shadowsocks server
+    #[error("Failed to connect to Shadowsocks server")]
+    CreateProxySocket(#[source] io::Error),
+
+    /// Failed to receive a packet from the local WireGuard client
+    #[error("Failed to receive packet from WireGuard")]
+    ReceiveLocal(#[source] io::Error),
+
+    /// Failed to send a packet to the local WireGuard client
+    #[error("Failed to send packet to WireGuard")]
+    SendLocal(#[source] io::Error),
+
+    /// Failed to connect the local socket to the WireGuard client
+    #[error("Failed to connect local UDP socket")]
+    ConnectLocal(#[source] io::Error),
+
+    /// Failed to receive a packet from the Shadowsocks server
+    #[error("Failed to receive packet from Shadowsocks server")]
+    ReceiveRemote(#[source] io::Error),
+
+    /// Failed to send a packet to the Shadowsocks server
+    #[error("Failed to send packet to Shadowsocks server")]
+    SendRemote(#[source] io::Error),
+}
+
+struct Shadowsocks {
+    local_addr: SocketAddr,
+    local_socket: UdpSocket,
+    server: ProxySocket,
+    wireguard_endpoint: Address,
+}
+
+impl Shadowsocks {
+    pub async fn new(settings: &ShadowsocksSettings) -> Result<Self> {
+        let listen_addr = if settings.shadowsocks_endpoint.is_ipv4() {
+            SocketAddr::new("127.0.0.1".parse().unwrap(), 0)
+        } else {
+            SocketAddr::new("::1".parse().unwrap(), 0)
+        };
+        let local_socket = UdpSocket::bind(listen_addr)
+            .await
+            .map_err(Error::BindLocalSocket)?;
+        let local_addr = local_socket
+            .local_addr()
+            .map_err(Error::GetUdpSocketDetails)?;
+
+        let (password, cipher) = match &settings.credentials {
+            Some(credentials) => (
+                credentials.password.as_str(),
+                CipherKind::from_str(&credentials.cipher)
+                    .map_err(|_| Error::InvalidCipher(credentials.cipher.clone()))?,
+            ),
+            None => (DEFAULT_SHADOWSOCKS_PASSWORD, DEFAULT_SHADOWSOCKS_CIPHER),
+        };
+        let server_config = ServerConfig::new(settings.shadowsocks_endpoint, password, cipher);
+        #[allow(unused_mut)]
+        let mut connect_opts = ConnectOpts::default();
+        #[cfg(target_os = "linux")]
+        {
+            connect_opts.fwmark = settings.fwmark;
+        }
+        let server = ProxySocket::connect_with_opts(
+            Context::new_shared(ServerType::Local),
+            &server_config,
+            &connect_opts,
+        )
+        .await
+        .map_err(|error| Error::CreateProxySocket(io::Error::from(error)))?;
+
+        Ok(Self {
+            local_addr,
+            local_socket,
+            server,
+            wireguard_endpoint: Address::SocketAddress(settings.wireguard_endpoint),
+        })
+    }
+
+    async fn relay(self) -> Result<()> {
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+
+        // Only accept traffic from whoever sends the first packet, i.e. the WireGuard client
+        let (len, client_addr) = self
+            .local_socket
+            .recv_from(&mut buf)
+            .await
+            .map_err(Error::ReceiveLocal)?;
+        self.local_socket
+            .connect(client_addr)
+            .await
+            .map_err(Error::ConnectLocal)?;
+        self.send_remote(&buf[..len]).await?;
+
+        tokio::select! {
+            result = self.forward_outgoing(buf) => result,
+            result = self.forward_incoming() => result,
+        }
+    }
+
+    /// Forwards packets from the WireGuard client to the Shadowsocks server.
+    async fn forward_outgoing(&self, mut buf: Vec<u8>) -> Result<()> {
+        loop {
+            let len = self
+                .local_socket
+                .recv(&mut buf)
+                .await
+                .map_err(Error::ReceiveLocal)?;
+            self.send_remote(&buf[..len]).await?;
+        }
+    }
+
+    /// Forwards packets from the Shadowsocks server to the WireGuard client.
+    async fn forward_incoming(&self) -> Result<()> {
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        loop {
+            let (len, _target, _) = self
+                .server
+                .recv(&mut buf)
+                .await
+                .map_err(|error| Error::ReceiveRemote(io::Error::from(error)))?;
+            self.local_socket
+                .send(&buf[..len])
+                .await
+                .map_err(Error::SendLocal)?;
+        }
+    }
+
+    async fn send_remote(&self, packet: &[u8]) -> Result<()> {
+        self.server
+            .send(&self.wireguard_endpoint, packet)
+            .await
+            .map_err(|error| Error::SendRemote(io::Error::from(error)))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Obfuscator for Shadowsocks {
+    fn endpoint(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    async fn run(self: Box<Self>) -> crate::Result<()> {
+        self.relay()
+            .await
+            .map_err(crate::Error::RunShadowsocksObfuscator)
+    }
+
+    #[cfg(target_os = "android")]
+    fn remote_socket_fd(&self) -> std::os::unix::io::RawFd {
+        use std::os::unix::io::AsRawFd;
+        self.server.as_raw_fd()
+    }
+}
+
+pub async fn create_obfuscator(settings: &ShadowsocksSettings) -> Result<Box<dyn Obfuscator>> {
+    Ok(Box::new(Shadowsocks::new(settings).await?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    /// Spawns a Shadowsocks server that sends every packet back to its sender, rather than
+    /// relaying it. The requested target addresses are sent on the returned channel.
+    async fn spawn_echo_server(
+        password: &str,
+        cipher: CipherKind,
+    ) -> (SocketAddr, mpsc::UnboundedReceiver<Address>) {
+        let config = ServerConfig::new(SocketAddr::from(([127, 0, 0, 1], 0)), password, cipher);
+        let server = ProxySocket::bind(Context::new_shared(ServerType::Server), &config)
+            .await
+            .unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        let (target_tx, target_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+            while let Ok((len, peer, target, _)) = server.recv_from(&mut buf).await {
+                let _ = target_tx.send(target.clone());
+                if server.send_to(peer, &target, &buf[..len]).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        (server_addr, target_rx)
+    }
+
+    /// Sends a packet through an obfuscator with the given settings and returns the response.
+    async fn round_trip(settings: ShadowsocksSettings) -> Vec<u8> {
+        let obfuscator = create_obfuscator(&settings).await.unwrap();
+        let endpoint = obfuscator.endpoint();
+        tokio::spawn(obfuscator.run());
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(endpoint).await.unwrap();
+        client.send(b"handshake initiation").await.unwrap();
+
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        let len = tokio::time::timeout(std::time::Duration::from_secs(5), client.recv(&mut buf))
+            .await
+            .expect("no response through the obfuscator")
+            .unwrap();
+        buf.truncate(len);
+        buf
+    }
+
+    fn settings(
+        shadowsocks_endpoint: SocketAddr,
+        credentials: Option<ShadowsocksCredentials>,
+    ) -> ShadowsocksSettings {
+        ShadowsocksSettings {
+            shadowsocks_endpoint,
+            wireguard_endpoint: SocketAddr::from(([10, 0, 0, 1], 51820)),
+            credentials,
+            #[cfg(target_os = "linux")]
+            fwmark: None,
+        }
+    }
+
+    /// The obfuscator must relay packets to the WireGuard endpoint through a server that uses the
+    /// password and cipher of the Shadowsocks servers on the relays.
+    #[tokio::test]
+    async fn test_round_trip() {
+        let (server_addr, mut targets) =
+            spawn_echo_server("mullvad", CipherKind::AES_256_GCM).await;
+
+        let response = round_trip(settings(server_addr, None)).await;
+
+        assert_eq!(response, b"handshake initiation");
+        assert_eq!(
+            targets.recv().await,
+            Some(Address::SocketAddress(SocketAddr::from((
+                [10, 0, 0, 1],
+                51820
+            ))))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_custom_credentials() {
+        let (server_addr, _targets) =
+            spawn_echo_server("secret", CipherKind::CHACHA20_POLY1305).await;
+
+        let response = round_trip(settings(
+            server_addr,
+            Some(ShadowsocksCredentials {
+                password: "secret".to_owned(),
+                cipher: "chacha20-ietf-poly1305".to_owned(),
+            }),
+        ))
+        .await;
+
+        assert_eq!(response, b"handshake initiation");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_cipher() {
+        let result = create_obfuscator(&settings(
+            SocketAddr::from(([127, 0, 0, 1], 1)),
+            Some(ShadowsocksCredentials {
+                password: "secret".to_owned(),
+                cipher: "rot13".to_owned(),
+            }),
+        ))
+        .await;
+
+        assert!(matches!(result, Err(Error::InvalidCipher(_))));
+    }
+}
@@ -0,0 +1,79 @@
+//! Runs the QUIC obfuscator against the server stand-in, with a UDP echo server in place of the
+//! WireGuard server.
+#![cfg(feature = "quic-server")]
+
+use std::net::{Ipv4Addr, SocketAddr};
+use tokio::net::UdpSocket;
+use tunnel_obfuscation::{
+    create_obfuscator,
+    quic_server::{Certificate, QuicServer},
+    Error, QuicSettings, Settings,
+};
+
+const HOSTNAME: &str = "localhost";
+
+async fn spawn_echo_server() -> SocketAddr {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; 2048];
+        loop {
+            let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+            socket.send_to(&buf[..len], peer).await.unwrap();
+        }
+    });
+    addr
+}
+
+fn spawn_quic_server(certificate: &Certificate, forward_addr: SocketAddr) -> SocketAddr {
+    let server = QuicServer::bind(
+        SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        forward_addr,
+        certificate,
+    )
+    .unwrap();
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(server.run());
+    addr
+}
+
+fn settings(quic_endpoint: SocketAddr, trusted_certificate: Vec<u8>) -> Settings {
+    Settings::Quic(QuicSettings {
+        quic_endpoint,
+        hostname: HOSTNAME.to_owned(),
+        trusted_certificates: vec![trusted_certificate],
+        #[cfg(target_os = "linux")]
+        fwmark: None,
+    })
+}
+
+#[tokio::test]
+async fn test_forward_datagrams() {
+    let certificate = Certificate::self_signed(HOSTNAME).unwrap();
+    let quic_endpoint = spawn_quic_server(&certificate, spawn_echo_server().await);
+
+    let obfuscator = create_obfuscator(&settings(quic_endpoint, certificate.certificate))
+        .await
+        .unwrap();
+    let endpoint = obfuscator.endpoint();
+    tokio::spawn(obfuscator.run());
+
+    let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    client.connect(endpoint).await.unwrap();
+    let mut buf = vec![0u8; 2048];
+    for packet in [&b"first packet"[..], &[0xab; 1000]] {
+        client.send(packet).await.unwrap();
+        let len = client.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], packet);
+    }
+}
+
+#[tokio::test]
+async fn test_untrusted_certificate() {
+    let certificate = Certificate::self_signed(HOSTNAME).unwrap();
+    let quic_endpoint = spawn_quic_server(&certificate, spawn_echo_server().await);
+
+    let other_certificate = Certificate::self_signed(HOSTNAME).unwrap();
+    let result = create_obfuscator(&settings(quic_endpoint, other_certificate.certificate)).await;
+    assert!(matches!(result, Err(Error::CreateQuicObfuscator(_))));
+}